@@ -12,10 +12,11 @@ use std::io::Write;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
-use dirs::config_dir;
+use crate::config::xa_dir;
 use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc;
 use tokio_stream::StreamExt;
+use tracing::Instrument;
 
 /// A single configurable chat provider.
 #[derive(Clone, Serialize, Deserialize)]
@@ -28,6 +29,13 @@ pub struct Provider {
     /// Reserved for future provider kinds (`openai`, `anthropic`, ...).
     #[serde(default = "default_kind")]
     pub kind: String,
+    /// This provider's own `requests_per_minute`, used over config.toml's
+    /// when a project picks it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_minute: Option<u32>,
+    /// Likewise for `tokens_per_minute`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens_per_minute: Option<u32>,
 }
 
 fn default_kind() -> String {
@@ -42,6 +50,8 @@ impl Default for Provider {
             api_key: String::new(),
             model: "gpt-4o-mini".into(),
             kind: "openai".into(),
+            requests_per_minute: None,
+            tokens_per_minute: None,
         }
     }
 }
@@ -54,7 +64,7 @@ impl Provider {
     }
 }
 
-/// On-disk collection of providers, kept in `config_dir()/xa/providers.toml`.
+/// On-disk collection of providers, kept in `xa_dir()/providers.toml`.
 #[derive(Clone, Serialize, Deserialize, Default)]
 pub struct ProvidersConfig {
     #[serde(default)]
@@ -65,7 +75,7 @@ pub struct ProvidersConfig {
 
 impl ProvidersConfig {
     pub fn path() -> Option<std::path::PathBuf> {
-        config_dir().map(|d| d.join("xa").join("providers.toml"))
+        xa_dir().map(|d| d.join("providers.toml"))
     }
 
     pub fn load() -> ProvidersConfig {
@@ -131,6 +141,9 @@ pub struct ChatMessage {
 #[derive(Clone, Debug)]
 pub enum StreamEvent {
     Delta(String),
+    /// Reasoning streamed by a thinking model (`reasoning_content`); not
+    /// part of the answer.
+    Reasoning(String),
     /// A tool call is about to be executed (for rendering in the TUI).
     ToolCall { id: String, name: String, arguments: String },
     /// A tool call finished (for rendering in the TUI).
@@ -158,6 +171,24 @@ pub enum StreamEvent {
 /// do we fall back to the legacy `config.toml` single-provider setup.
 pub async fn load_active_provider() -> Provider {
     let pc = ProvidersConfig::load();
+    if let Ok(Some(project)) = crate::project::load() {
+        // A project's `.xa.toml` may pick one of the configured providers
+        // and/or the model to use with it.
+        let chosen = match &project.provider {
+            Some(name) => pc.providers.get(name).cloned(),
+            None if project.model.is_some() => pc.active_provider(),
+            None => None,
+        };
+        if let Some(mut provider) = chosen {
+            if let Some(model) = &project.model {
+                provider.model = match crate::config::load_config_sync() {
+                    Ok(config) => config.resolve_model(model),
+                    Err(_) => model.clone(),
+                };
+            }
+            return provider;
+        }
+    }
     if !pc.providers.is_empty() {
         if let Some(p) = pc.active_provider() {
             return p;
@@ -167,10 +198,12 @@ pub async fn load_active_provider() -> Provider {
         if !c.api_key.is_empty() {
             return Provider {
                 name: "default".into(),
+                model: c.default_model.as_ref().map(|_| c.model()).unwrap_or_default(),
                 endpoint: c.base_url,
                 api_key: c.api_key,
-                model: c.default_model.unwrap_or_default(),
                 kind: "openai".into(),
+                requests_per_minute: c.requests_per_minute,
+                tokens_per_minute: c.tokens_per_minute,
             };
         }
     }
@@ -183,6 +216,9 @@ pub async fn load_active_provider() -> Provider {
 pub struct ProviderPreset {
     pub name: &'static str,
     pub base_url: &'static str,
+    /// Suggested model for a fresh setup; empty when the endpoint has no
+    /// sensible default (self-hosted gateways).
+    pub default_model: &'static str,
     pub note: Option<&'static str>,
 }
 
@@ -192,51 +228,73 @@ pub fn builtin_presets() -> Vec<ProviderPreset> {
         ProviderPreset {
             name: "agnes",
             base_url: "https://apihub.agnes-ai.com/v1",
+            default_model: "",
             note: None,
         },
         ProviderPreset {
             name: "zenmux",
             base_url: "https://zenmux.ai/api/v1",
+            default_model: "",
             note: None,
         },
         ProviderPreset {
             name: "vllm",
             base_url: "http://vllm.manaai.cn:8080/v1",
+            default_model: "",
             note: Some("local"),
         },
         ProviderPreset {
             name: "openai",
             base_url: "https://api.openai.com/v1",
+            default_model: "gpt-4o-mini",
             note: None,
         },
         ProviderPreset {
             name: "anthropic",
             base_url: "https://api.anthropic.com/v1",
+            default_model: "claude-3-5-haiku-latest",
             note: Some("needs an OpenAI-compatible gateway for chat"),
         },
         ProviderPreset {
             name: "alibaba",
             base_url: "https://dashscope.aliyuncs.com/compatible-mode/v1",
+            default_model: "qwen-plus",
             note: None,
         },
         ProviderPreset {
             name: "openrouter",
             base_url: "https://openrouter.ai/api/v1",
+            default_model: "openai/gpt-4o-mini",
             note: None,
         },
         ProviderPreset {
             name: "deepseek",
             base_url: "https://api.deepseek.com/v1",
+            default_model: "deepseek-chat",
             note: None,
         },
         ProviderPreset {
             name: "groq",
             base_url: "https://api.groq.com/openai/v1",
+            default_model: "llama-3.3-70b-versatile",
+            note: None,
+        },
+        ProviderPreset {
+            name: "gemini",
+            base_url: "https://generativelanguage.googleapis.com/v1beta/openai",
+            default_model: "gemini-2.0-flash",
+            note: None,
+        },
+        ProviderPreset {
+            name: "moonshot",
+            base_url: "https://api.moonshot.ai/v1",
+            default_model: "moonshot-v1-8k",
             note: None,
         },
         ProviderPreset {
             name: "ollama",
             base_url: "http://localhost:11434/v1",
+            default_model: "llama3.2",
             note: Some("local, no key needed"),
         },
     ]
@@ -247,7 +305,7 @@ pub fn builtin_presets() -> Vec<ProviderPreset> {
 pub async fn fetch_models(endpoint: &str, api_key: &str) -> Result<Vec<String>, String> {
     let base = endpoint.trim_end_matches('/');
     let models_url = format!("{base}/models");
-    let client = reqwest::Client::new();
+    let client = crate::http::client()?;
     let mut req = client.get(&models_url);
     if !api_key.is_empty() {
         req = req.bearer_auth(api_key);
@@ -344,6 +402,8 @@ pub async fn interactive_configure(mut ask: impl FnMut(&str) -> String) -> Provi
         api_key,
         model,
         kind: "openai".into(),
+        requests_per_minute: None,
+        tokens_per_minute: None,
     }
 }
 
@@ -557,7 +617,8 @@ async fn stream_completion_with_retry(
         if cancel.load(Ordering::SeqCst) {
             return Err("cancelled".into());
         }
-        match stream_completion(provider, messages, tools, tx, cancel.clone()).await {
+        let span = tracing::info_span!("agent_request", provider = %provider.name, model = %provider.model, attempt = attempt + 1);
+        match stream_completion(provider, messages, tools, tx, cancel.clone()).instrument(span).await {
             Ok(result) => return Ok(result),
             Err(e) => {
                 let is_retryable = e.contains("HTTP 429")
@@ -573,6 +634,7 @@ async fn stream_completion_with_retry(
                 if !is_retryable || attempt + 1 >= MAX_RETRIES {
                     return Err(e);
                 }
+                tracing::warn!(attempt = attempt + 1, max = MAX_RETRIES, error = %e, "retrying request");
                 last_err = e.clone();
                 let _ = tx
                     .send(StreamEvent::Retrying {
@@ -599,7 +661,7 @@ async fn stream_completion(
     tx: &mpsc::Sender<StreamEvent>,
     cancel: Arc<AtomicBool>,
 ) -> Result<(String, Vec<ToolCallRepr>), String> {
-    let client = reqwest::Client::new();
+    let client = crate::http::client()?;
 
     let mut body = serde_json::json!({
         "model": provider.model,
@@ -625,6 +687,7 @@ async fn stream_completion(
         body["tool_choice"] = "auto".into();
     }
 
+    tracing::debug!(url = %provider.chat_url(), messages = messages.len(), tools = tools.len(), "POST");
     let res = client
         .post(provider.chat_url())
         .bearer_auth(&provider.api_key)
@@ -632,6 +695,7 @@ async fn stream_completion(
         .send()
         .await
         .map_err(|e| format!("request failed: {e}"))?;
+    tracing::info!(status = %res.status(), "response");
 
     if !res.status().is_success() {
         let status = res.status();
@@ -673,11 +737,20 @@ async fn stream_completion(
                         let completion = u.get("completion_tokens").and_then(|x| x.as_u64()).unwrap_or(0) as u32;
                         let total = u.get("total_tokens").and_then(|x| x.as_u64()).unwrap_or(0) as u32;
                         if total > 0 {
+                            crate::budget::record(&provider.model, crate::llm::Usage { prompt_tokens: prompt, completion_tokens: completion });
                             let _ = tx.send(StreamEvent::Usage { prompt, completion, total }).await;
                         }
                     }
                     if let Some(choices) = v.get("choices").and_then(|c| c.as_array()) {
                         if let Some(c) = choices.first() {
+                            // Reasoning delta from a thinking model.
+                            let delta = c.get("delta");
+                            let reasoning = delta.and_then(|d| d.get("reasoning_content").or_else(|| d.get("reasoning"))).and_then(|x| x.as_str());
+                            if let Some(r) = reasoning.filter(|r| !r.is_empty()) {
+                                if tx.send(StreamEvent::Reasoning(r.to_string())).await.is_err() {
+                                    return Ok((text, prune_calls(calls)));
+                                }
+                            }
                             // Text delta.
                             if let Some(d) = c.get("delta").and_then(|d| d.get("content")).and_then(|x| x.as_str()) {
                                 if !d.is_empty() {
@@ -0,0 +1,443 @@
+//! `xa serve --http <addr>`: the prompt library as a small REST API, so
+//! scripts, editors and launcher workflows (Raycast, Alfred) can reuse the
+//! user's config without starting `xa` for every call.
+//!
+//! - `GET /commands` lists the commands and their arguments
+//! - `POST /run/<command>` with `{"input": "...", "args": [...]}` runs one
+//! - `POST /ask` with `{"input": "...", "persona": "..."}` asks a question
+//!
+//! Answers are `{"command", "output", "model", "usage"}`, or Server-Sent
+//! Events (`data: {"delta": "..."}` frames, then `data: {"done": true, ...}`)
+//! when the body has `"stream": true` or the client accepts
+//! `text/event-stream`. Pipelines are left out, as in `xa batch`.
+//!
+//! A plain HTTP/1.1 server is enough for this: one request per connection,
+//! bodies sized by `Content-Length`, read within [`READ_TIMEOUT`]. POSTs
+//! must be `application/json`, which browsers cannot send cross-site
+//! without a CORS preflight this server never answers. Without `--token`
+//! the `Host` must be an IP address or `localhost`, so a page whose domain
+//! was rebound to 127.0.0.1 is refused; `--token` requires a bearer token
+//! instead, compared in constant time.
+
+use crate::config::Config;
+use crate::error::XaError;
+use crate::llm::Usage;
+use crate::prompt::PromptEntry;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Largest request head (request line and headers) accepted.
+const MAX_HEAD_BYTES: usize = 16 * 1024;
+/// Largest request body accepted.
+const MAX_BODY_BYTES: usize = 4 * 1024 * 1024;
+/// How long a client may take to send its whole request.
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The request settings for a command: its name, entry and the persona
+/// asked for (`ask` only).
+pub type Configure = dyn Fn(&str, &PromptEntry, Option<&str>) -> Result<Config, XaError> + Send + Sync;
+
+struct Server {
+    prompts: HashMap<String, PromptEntry>,
+    token: Option<String>,
+    configure: Box<Configure>,
+}
+
+/// Serve `prompts` on `addr` until interrupted.
+pub async fn serve(
+    addr: &str,
+    prompts: HashMap<String, PromptEntry>,
+    token: Option<String>,
+    configure: Box<Configure>,
+) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    let local = listener.local_addr()?;
+    let commands = prompts.values().filter(|entry| entry.pipeline.is_none()).count();
+    eprintln!("xa HTTP API: {} commands on http://{}", commands, local);
+    if !local.ip().is_loopback() && token.is_none() {
+        eprintln!(
+            "\x1b[33mWarning: {} is reachable from other machines and anyone there can spend your API key; \
+             consider --token\x1b[0m",
+            local
+        );
+    }
+
+    let server = Arc::new(Server { prompts, token, configure });
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let server = server.clone();
+        tokio::spawn(async move {
+            if let Err(e) = server.handle(stream).await {
+                tracing::debug!(%peer, error = %e, "HTTP connection failed");
+            }
+        });
+    }
+}
+
+/// A parsed request.
+#[derive(Debug)]
+struct Request {
+    method: String,
+    path: String,
+    /// Header names are lowercased.
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+}
+
+/// An error answer: the status and its message.
+struct Failure(u16, String);
+
+impl Failure {
+    /// The provider's request failed: 429 when the client should wait (the
+    /// provider's rate limit or xa's budget), 502 otherwise.
+    fn upstream(error: XaError) -> Failure {
+        let status = match error {
+            XaError::RateLimit(_) | XaError::Budget(_) => 429,
+            _ => 502,
+        };
+        Failure(status, error.to_string())
+    }
+}
+
+impl Server {
+    async fn handle(&self, mut stream: TcpStream) -> std::io::Result<()> {
+        let request = match tokio::time::timeout(READ_TIMEOUT, read_request(&mut stream)).await {
+            Ok(request) => request?,
+            Err(_) => Err(Failure(408, "request not received in time".into())),
+        };
+        let request = match request {
+            Ok(request) => request,
+            Err(Failure(status, message)) => return respond(&mut stream, status, &json!({ "error": message })).await,
+        };
+        tracing::info!(method = %request.method, path = %request.path, "HTTP request");
+        if let Err(Failure(status, message)) = self.route(&mut stream, &request).await {
+            return respond(&mut stream, status, &json!({ "error": message })).await;
+        }
+        Ok(())
+    }
+
+    async fn route(&self, stream: &mut TcpStream, request: &Request) -> Result<(), Failure> {
+        match &self.token {
+            Some(token) => {
+                let given = request.header("authorization").and_then(|value| value.strip_prefix("Bearer "));
+                if !given.is_some_and(|given| same_secret(given.as_bytes(), token.as_bytes())) {
+                    return Err(Failure(401, "missing or wrong bearer token".into()));
+                }
+            }
+            None => {
+                if !request.header("host").is_none_or(is_direct_host) {
+                    return Err(Failure(421, "requests must address this server by IP or localhost; use --token for host names".into()));
+                }
+            }
+        }
+        let path = request.path.split('?').next().unwrap_or_default();
+        match (request.method.as_str(), path) {
+            ("GET", "/commands") => {
+                let _ = respond(stream, 200, &json!({ "commands": self.commands() })).await;
+                Ok(())
+            }
+            ("POST", "/ask") => self.run(stream, request, "ask").await,
+            ("POST", _) if path.starts_with("/run/") => self.run(stream, request, &path["/run/".len()..]).await,
+            (_, "/commands" | "/ask") => Err(Failure(405, format!("{} is not allowed here", request.method))),
+            _ => Err(Failure(404, format!("no such endpoint: {}", path))),
+        }
+    }
+
+    /// The `GET /commands` listing.
+    fn commands(&self) -> Vec<Value> {
+        let mut names: Vec<&String> =
+            self.prompts.iter().filter(|(_, entry)| entry.pipeline.is_none()).map(|(name, _)| name).collect();
+        names.sort();
+        names
+            .into_iter()
+            .map(|name| {
+                let entry = &self.prompts[name];
+                let args: Vec<Value> = entry
+                    .args
+                    .iter()
+                    .flatten()
+                    .map(|arg| json!({ "name": arg.name, "default": arg.default_value, "description": arg.description }))
+                    .collect();
+                json!({ "name": name, "description": entry.description, "args": args })
+            })
+            .collect()
+    }
+
+    /// Run command `name` with the request's JSON body.
+    async fn run(&self, stream: &mut TcpStream, request: &Request, name: &str) -> Result<(), Failure> {
+        let json_body = request.header("content-type").is_some_and(|value| value.starts_with("application/json"));
+        if !json_body {
+            return Err(Failure(415, "send the body as Content-Type: application/json".into()));
+        }
+        let body: Value =
+            serde_json::from_slice(&request.body).map_err(|e| Failure(400, format!("invalid JSON body: {}", e)))?;
+        let Some(entry) = self.prompts.get(name) else {
+            return Err(Failure(404, format!("unknown command: {} (see GET /commands)", name)));
+        };
+        if entry.pipeline.is_some() {
+            return Err(Failure(400, format!("'{}' is a pipeline; pipelines are not served", name)));
+        }
+        let Some(input) = body["input"].as_str().or(body["question"].as_str()) else {
+            return Err(Failure(400, "the body needs an \"input\" string".into()));
+        };
+        let args = command_args(entry, &body["args"]).map_err(|message| Failure(400, message))?;
+        let config = (self.configure)(name, entry, body["persona"].as_str()).map_err(|e| Failure(400, e.to_string()))?;
+        let input = entry.prepare_input(input).await.map_err(|e| Failure(400, e.to_string()))?;
+        let prompt = entry.fill_prompt(&config, &input, &args).await.map_err(|e| Failure(400, e.to_string()))?;
+
+        let streaming = body["stream"].as_bool().unwrap_or(false)
+            || request.header("accept").is_some_and(|value| value.contains("text/event-stream"));
+        if streaming {
+            let _ = stream_answer(stream, &config, name, entry, &prompt).await;
+            return Ok(());
+        }
+        let done = crate::llm::complete(&config, &prompt).await.map_err(Failure::upstream)?;
+        let output = entry.finish_output(&done.text).await.map_err(|e| Failure(500, e.to_string()))?;
+        let _ = respond(stream, 200, &answer(&config, name, &output, done.usage)).await;
+        Ok(())
+    }
+}
+
+/// The arguments for `entry`, in its order, from a JSON array of positional
+/// values or an object keyed by argument name; missing ones take their
+/// defaults later.
+fn command_args(entry: &PromptEntry, args: &Value) -> Result<Vec<String>, String> {
+    let text = |value: &Value| match value {
+        Value::String(text) => Some(text.clone()),
+        Value::Number(_) | Value::Bool(_) => Some(value.to_string()),
+        _ => None,
+    };
+    match args {
+        Value::Null => Ok(Vec::new()),
+        Value::Array(values) => values.iter().map(|value| text(value).ok_or("\"args\" must hold strings".to_string())).collect(),
+        Value::Object(values) => {
+            let declared: Vec<_> = entry.args.iter().flatten().collect();
+            if let Some(unknown) = values.keys().find(|key| !declared.iter().any(|arg| &arg.name == *key)) {
+                return Err(format!("unknown argument: {}", unknown));
+            }
+            Ok(declared
+                .iter()
+                .map(|arg| values.get(&arg.name).and_then(text).unwrap_or_else(|| arg.default_value.clone()))
+                .collect())
+        }
+        _ => Err("\"args\" must be an array or an object".into()),
+    }
+}
+
+/// The JSON answer for a finished run.
+fn answer(config: &Config, name: &str, output: &str, usage: Option<Usage>) -> Value {
+    json!({ "command": name, "output": output, "model": config.model(), "usage": usage })
+}
+
+/// Stream the run as Server-Sent Events. The final `done` frame carries the
+/// output after `postprocess` and `post_hook`, which may differ from the
+/// streamed deltas.
+async fn stream_answer(
+    stream: &mut TcpStream,
+    config: &Config,
+    name: &str,
+    entry: &PromptEntry,
+    prompt: &str,
+) -> std::io::Result<()> {
+    stream
+        .write_all(b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n")
+        .await?;
+    let (sender, mut deltas) = tokio::sync::mpsc::unbounded_channel::<String>();
+    let completion = async move {
+        let done = crate::llm::stream_to(config, prompt, |delta| {
+            let _ = sender.send(delta.to_string());
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+        let output = entry.finish_output(&done.text).await.map_err(|e| e.to_string())?;
+        Ok::<_, String>((output, done.usage))
+    };
+    let forward = async {
+        // Keep draining after a write error so the completion still finishes
+        let mut open = true;
+        while let Some(delta) = deltas.recv().await {
+            if open {
+                open = stream.write_all(event(None, &json!({ "delta": delta })).as_bytes()).await.is_ok();
+            }
+        }
+        open
+    };
+    let (result, open) = tokio::join!(completion, forward);
+    if !open {
+        return Ok(());
+    }
+    let last = match result {
+        Ok((output, usage)) => {
+            let mut answer = answer(config, name, &output, usage);
+            answer["done"] = true.into();
+            event(None, &answer)
+        }
+        Err(error) => event(Some("error"), &json!({ "error": error })),
+    };
+    stream.write_all(last.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// One Server-Sent Events frame.
+fn event(name: Option<&str>, data: &Value) -> String {
+    match name {
+        Some(name) => format!("event: {}\ndata: {}\n\n", name, data),
+        None => format!("data: {}\n\n", data),
+    }
+}
+
+/// A JSON response with `status`, closing the connection after it.
+async fn respond(stream: &mut TcpStream, status: u16, body: &Value) -> std::io::Result<()> {
+    let body = body.to_string();
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        reason(status),
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        413 => "Payload Too Large",
+        415 => "Unsupported Media Type",
+        421 => "Misdirected Request",
+        429 => "Too Many Requests",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        _ => "Bad Gateway",
+    }
+}
+
+/// Read one request. The outer error is a broken connection; the inner
+/// one a request to refuse.
+async fn read_request(stream: &mut TcpStream) -> std::io::Result<Result<Request, Failure>> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 8192];
+    let head_end = loop {
+        if let Some(end) = buf.windows(4).position(|window| window == b"\r\n\r\n") {
+            break end;
+        }
+        if buf.len() > MAX_HEAD_BYTES {
+            return Ok(Err(Failure(431, "request headers too large".into())));
+        }
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+    let Some(mut request) = parse_head(&String::from_utf8_lossy(&buf[..head_end])) else {
+        return Ok(Err(Failure(400, "malformed request".into())));
+    };
+    let length = match request.header("content-length").map(str::parse::<usize>) {
+        None => 0,
+        Some(Ok(length)) => length,
+        Some(Err(_)) => return Ok(Err(Failure(400, "invalid Content-Length".into()))),
+    };
+    if length > MAX_BODY_BYTES {
+        return Ok(Err(Failure(413, format!("request body over {} MB", MAX_BODY_BYTES / 1024 / 1024))));
+    }
+    let mut body = buf.split_off(head_end + 4);
+    while body.len() < length {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    body.truncate(length);
+    request.body = body;
+    Ok(Ok(request))
+}
+
+/// Whether a `Host` header names the server by IP address or as
+/// `localhost`: a browser only sends another name for a domain, which may
+/// have been rebound to this machine.
+fn is_direct_host(host: &str) -> bool {
+    let name = match host.strip_prefix('[') {
+        Some(bracketed) => bracketed.split_once(']').map_or("", |(address, _)| address),
+        None => host.rsplit_once(':').map_or(host, |(name, _)| name),
+    };
+    let name = name.trim_end_matches('.').to_ascii_lowercase();
+    name.parse::<IpAddr>().is_ok() || name == "localhost" || name.ends_with(".localhost")
+}
+
+/// `given == expected`, taking as long for a near miss as for a wild guess.
+fn same_secret(given: &[u8], expected: &[u8]) -> bool {
+    given.len() == expected.len() && given.iter().zip(expected).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// The request line and headers, without the blank line that ends them.
+fn parse_head(head: &str) -> Option<Request> {
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next()?.split(' ');
+    let method = request_line.next().filter(|method| !method.is_empty())?.to_string();
+    let path = request_line.next().filter(|path| path.starts_with('/'))?.to_string();
+    request_line.next().filter(|version| version.starts_with("HTTP/1."))?;
+    let headers = lines
+        .map(|line| line.split_once(':').map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string())))
+        .collect::<Option<Vec<_>>>()?;
+    Some(Request { method, path, headers, body: Vec::new() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prompt::PromptArg;
+
+    #[test]
+    fn parses_requests_and_arguments() {
+        let request = parse_head("POST /run/translate HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json").unwrap();
+        assert_eq!((request.method.as_str(), request.path.as_str()), ("POST", "/run/translate"));
+        assert_eq!(request.header("content-type"), Some("application/json"));
+        assert!(parse_head("GET localhost HTTP/1.1").is_none());
+        assert!(parse_head("GET / HTTP/1.1\r\nno colon").is_none());
+
+        let entry = PromptEntry {
+            template: "Translate into {target_lang} ({tone}): {input}".into(),
+            args: Some(vec![
+                PromptArg { name: "target_lang".into(), default_value: "zh".into(), description: None },
+                PromptArg { name: "tone".into(), default_value: "neutral".into(), description: None },
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(command_args(&entry, &json!(["fr"])).unwrap(), ["fr"]);
+        assert_eq!(command_args(&entry, &json!({ "tone": "formal" })).unwrap(), ["zh", "formal"]);
+        assert!(command_args(&entry, &json!({ "style": "x" })).is_err());
+        assert!(command_args(&entry, &json!("fr")).is_err());
+    }
+
+    #[test]
+    fn refuses_rebound_host_names() {
+        for host in ["127.0.0.1:7777", "localhost:7777", "LOCALHOST", "[::1]:7777", "192.168.1.20", "app.localhost:80"] {
+            assert!(is_direct_host(host), "{host}");
+        }
+        for host in ["evil.example:7777", "attacker.com", "127.0.0.1.nip.io:7777", "[::1"] {
+            assert!(!is_direct_host(host), "{host}");
+        }
+        assert!(same_secret(b"s3cret", b"s3cret"));
+        assert!(!same_secret(b"s3cres", b"s3cret"));
+        assert!(!same_secret(b"s3cret-", b"s3cret"));
+    }
+}
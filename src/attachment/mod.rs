@@ -0,0 +1,123 @@
+//! Files given as context to `xa ask -f <path>` and `/file <path>` in the
+//! conversation mode, and command output attached with `!!<command>`,
+//! labeled with their source so the model (and the user reading `--debug`)
+//! can tell them apart from the question.
+//!
+//! Each one is cut at [`MAX_FILE_BYTES`] with a note saying so, and a
+//! conversation refuses files past [`MAX_TOTAL_BYTES`] in all: every
+//! attached file is resent with each message.
+
+use crate::error::XaError;
+use std::fs;
+use std::path::Path;
+
+pub const MAX_FILE_BYTES: usize = 64 * 1024;
+pub const MAX_TOTAL_BYTES: usize = 256 * 1024;
+
+pub struct Attachment {
+    /// The path as given, or the command line for command output.
+    pub path: String,
+    pub command: bool,
+    pub text: String,
+    /// Original size in bytes when `text` was cut short.
+    pub truncated_from: Option<usize>,
+}
+
+impl Attachment {
+    /// Read a UTF-8 text file, cut to [`MAX_FILE_BYTES`].
+    pub fn read(path: &str) -> Result<Attachment, XaError> {
+        let bytes = fs::read(Path::new(path)).map_err(|e| XaError::Usage(format!("cannot read {}: {}", path, e)))?;
+        let text = String::from_utf8(bytes)
+            .map_err(|_| XaError::Usage(format!("{} is not a text file", path)))?;
+        Ok(Attachment::from_text(path, text))
+    }
+
+    /// What `command` printed, cut to [`MAX_FILE_BYTES`].
+    pub fn command_output(command: &str, text: String) -> Attachment {
+        Attachment { command: true, ..Attachment::from_text(command, text) }
+    }
+
+    fn from_text(path: &str, mut text: String) -> Attachment {
+        let mut truncated_from = None;
+        if text.len() > MAX_FILE_BYTES {
+            truncated_from = Some(text.len());
+            let mut end = MAX_FILE_BYTES;
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            text.truncate(end);
+        }
+        Attachment { path: path.to_string(), command: false, text, truncated_from }
+    }
+
+    /// "notes.md (12.3 KB)", with "cut from ..." when truncated.
+    pub fn summary(&self) -> String {
+        match self.truncated_from {
+            Some(size) => format!("{} ({}, cut from {})", self.path, human_size(self.text.len()), human_size(size)),
+            None => format!("{} ({})", self.path, human_size(self.text.len())),
+        }
+    }
+}
+
+/// Refuse `attachment` when `attached` plus it would pass [`MAX_TOTAL_BYTES`].
+pub fn check_total(attached: &[Attachment], attachment: &Attachment) -> Result<(), XaError> {
+    let total: usize = attached.iter().chain([attachment]).map(|a| a.text.len()).sum();
+    if total > MAX_TOTAL_BYTES {
+        return Err(XaError::Usage(format!(
+            "{} would bring the attached files to {}, over the {} limit",
+            attachment.path,
+            human_size(total),
+            human_size(MAX_TOTAL_BYTES)
+        )));
+    }
+    Ok(())
+}
+
+/// The attachments as one block to put ahead of the question; empty when
+/// there are none.
+pub fn context(attachments: &[Attachment]) -> String {
+    let mut context = String::new();
+    for attachment in attachments {
+        let tag = if attachment.command { "command_output" } else { "file" };
+        let label = if attachment.command { "command" } else { "path" };
+        context.push_str(&format!("<{} {}=\"{}\">\n{}", tag, label, attachment.path, attachment.text));
+        if !attachment.text.ends_with('\n') {
+            context.push('\n');
+        }
+        if let Some(size) = attachment.truncated_from {
+            context.push_str(&format!("[truncated: first {} of {} bytes]\n", attachment.text.len(), size));
+        }
+        context.push_str(&format!("</{}>\n\n", tag));
+    }
+    context
+}
+
+fn human_size(bytes: usize) -> String {
+    match bytes {
+        0..1024 => format!("{} B", bytes),
+        _ => format!("{:.1} KB", bytes as f64 / 1024.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_truncates_and_limits_files() {
+        let small = Attachment::from_text("notes.md", "# Notes".to_string());
+        assert_eq!(context(&[small]), "<file path=\"notes.md\">\n# Notes\n</file>\n\n");
+        let output = Attachment::command_output("git diff", "+added\n".to_string());
+        assert_eq!(context(&[output]), "<command_output command=\"git diff\">\n+added\n</command_output>\n\n");
+
+        let big = Attachment::from_text("big.txt", "é".repeat(MAX_FILE_BYTES));
+        assert_eq!(big.truncated_from, Some(2 * MAX_FILE_BYTES));
+        assert_eq!(big.text.len(), MAX_FILE_BYTES);
+        assert!(context(&[big]).contains("[truncated: first 65536 of 131072 bytes]"));
+
+        let full: Vec<_> = (0..4).map(|i| Attachment::from_text(&i.to_string(), "x".repeat(MAX_FILE_BYTES))).collect();
+        assert!(check_total(&full[..3], &full[3]).is_ok());
+        let extra = Attachment::from_text("extra", "x".to_string());
+        assert!(check_total(&full, &extra).is_err());
+    }
+}
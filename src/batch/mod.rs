@@ -0,0 +1,293 @@
+//! `xa batch`: one command over many inputs, several requests at a time.
+//!
+//! Each non-empty input line becomes the template's `{input}`; results are
+//! printed one per line in input order as soon as every earlier line is
+//! done, so the output lines up with the input file.
+//!
+//! `xa <command> <files...> --each` runs the same way over whole files,
+//! writing each result next to its file or all of them into one report.
+
+use crate::config::Config;
+use crate::output::{paint, OutputFormat, ResultRecord};
+use crate::prompt::PromptEntry;
+use crate::error::XaError;
+use crate::redact::DebugMode;
+use std::collections::BTreeMap;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::{mpsc, Semaphore};
+
+/// Outcome of a batch run.
+pub struct BatchSummary {
+    pub total: usize,
+    pub failed: usize,
+    /// Why the last failed request failed.
+    pub cause: Option<XaError>,
+}
+
+impl BatchSummary {
+    /// `Ok` when nothing failed. When every one of the `what` failed on a
+    /// request, the request's error, so a wrong key still exits as `auth`.
+    pub fn check(self, what: &str) -> Result<(), XaError> {
+        match self.cause {
+            _ if self.failed == 0 => Ok(()),
+            Some(cause) if self.failed == self.total => Err(cause),
+            _ => Err(XaError::Other(format!("{} of {} {} failed.", self.failed, self.total, what))),
+        }
+    }
+}
+
+/// How `xa batch` runs and where it writes its results.
+pub struct BatchOptions<'a> {
+    /// Requests in flight at once.
+    pub jobs: usize,
+    /// Print each filled prompt on stderr.
+    pub debug: Option<DebugMode>,
+    pub writer: &'a mut dyn Write,
+    pub format: OutputFormat,
+    /// Whether `writer` is the terminal (a progress counter is shown only
+    /// when it is not).
+    pub to_terminal: bool,
+}
+
+/// Start a request for each input, at most `jobs` at a time, and return
+/// where the results arrive, tagged with the input's index. Blank inputs
+/// get an empty result without a request. `debug` names an input (`line`,
+/// `file`) and how to show it when each filled prompt should be printed
+/// on stderr.
+fn spawn(
+    config: &Config,
+    entry: &PromptEntry,
+    args: &[String],
+    inputs: &[String],
+    jobs: usize,
+    debug: Option<(&'static str, DebugMode)>,
+) -> mpsc::UnboundedReceiver<(usize, Result<String, XaError>)> {
+    let semaphore = Arc::new(Semaphore::new(jobs.max(1)));
+    let (tx, rx) = mpsc::unbounded_channel::<(usize, Result<String, XaError>)>();
+
+    for (index, input) in inputs.iter().enumerate() {
+        if input.trim().is_empty() {
+            let _ = tx.send((index, Ok(String::new())));
+            continue;
+        }
+        let (config, entry, args, input, tx, semaphore) =
+            (config.clone(), entry.clone(), args.to_vec(), input.clone(), tx.clone(), semaphore.clone());
+        tokio::spawn(async move {
+            let Ok(_permit) = semaphore.acquire_owned().await else { return };
+            let result = async {
+                let input = entry.prepare_input(&input).await?;
+                let prompt = entry.fill_prompt(&config, &input, &args).await?;
+                if let Some((what, mode)) = debug {
+                    let shown = mode.show(&config.redact, &prompt)?;
+                    eprintln!("[DEBUG] {} {}: {}", what, index + 1, shown);
+                }
+                let done = crate::llm::complete(&config, &prompt).await?;
+                entry.finish_output(&done.text).await
+            };
+            let _ = tx.send((index, result.await));
+        });
+    }
+    rx
+}
+
+/// Run `command` (`entry`) over `inputs` with at most `options.jobs` requests
+/// in flight. Blank inputs pass through as blank lines without an API
+/// call; a failed item writes an empty line and prints its error on stderr.
+pub async fn run(
+    config: &Config,
+    command: &str,
+    entry: &PromptEntry,
+    args: &[String],
+    inputs: Vec<String>,
+    options: BatchOptions<'_>,
+) -> Result<BatchSummary, XaError> {
+    let total = inputs.len();
+    let mut rx = spawn(config, entry, args, &inputs, options.jobs, options.debug.map(|mode| ("line", mode)));
+
+    let show_progress = io::stderr().is_terminal() && !options.to_terminal;
+    let mut pending = BTreeMap::new();
+    let (mut next, mut done, mut failed) = (0, 0, 0);
+    let mut cause = None;
+    while let Some((index, result)) = rx.recv().await {
+        done += 1;
+        pending.insert(index, result);
+        while let Some(result) = pending.remove(&next) {
+            let text = match result {
+                Ok(text) => text,
+                Err(error) => {
+                    failed += 1;
+                    if show_progress {
+                        eprint!("\r\x1b[2K");
+                    }
+                    eprintln!("line {}: {}", next + 1, error);
+                    cause = Some(error);
+                    String::new()
+                }
+            };
+            let line = match options.format {
+                OutputFormat::Json => {
+                    let record = ResultRecord { command, input: &inputs[next], output: &text };
+                    record.format(OutputFormat::Json)
+                }
+                OutputFormat::Md | OutputFormat::Txt => format!("{}\n", one_line(&text)),
+            };
+            options.writer.write_all(line.as_bytes())?;
+            next += 1;
+        }
+        options.writer.flush()?;
+        if show_progress {
+            eprint!("\r\x1b[2K\x1b[90m[{}/{}]\x1b[0m", done, total);
+        }
+    }
+    if show_progress {
+        eprint!("\r\x1b[2K");
+    }
+    Ok(BatchSummary { total, failed, cause })
+}
+
+/// How `--each` runs and where the results go.
+pub struct EachOptions<'a> {
+    /// Requests in flight at once.
+    pub jobs: usize,
+    /// Print each filled prompt on stderr.
+    pub debug: Option<DebugMode>,
+    /// One report of every result (`-o`), in file order; otherwise each
+    /// result is written next to its file.
+    pub report: Option<(&'a mut dyn Write, OutputFormat)>,
+}
+
+/// Where `--each` writes the result for `file`: `notes.md` →
+/// `notes.md.summarize.md`.
+pub fn result_path(file: &Path, command: &str) -> PathBuf {
+    let mut name = file.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}.md", command));
+    file.with_file_name(name)
+}
+
+/// Run `command` over the contents of each of `files`. A file that cannot
+/// be read or whose request fails is reported on stderr and counted as
+/// failed; the others still get their results.
+pub async fn run_each(
+    config: &Config,
+    command: &str,
+    entry: &PromptEntry,
+    args: &[String],
+    files: &[PathBuf],
+    mut options: EachOptions<'_>,
+) -> Result<BatchSummary, XaError> {
+    let total = files.len();
+    let mut failed = 0;
+    let inputs: Vec<String> = files
+        .iter()
+        .map(|file| {
+            std::fs::read_to_string(file).unwrap_or_else(|e| {
+                eprintln!("{}: {}", file.display(), e);
+                failed += 1;
+                String::new()
+            })
+        })
+        .collect();
+    let mut rx = spawn(config, entry, args, &inputs, options.jobs, options.debug.map(|mode| ("file", mode)));
+
+    let show_progress = io::stderr().is_terminal();
+    let mut pending = BTreeMap::new();
+    let (mut next, mut done, mut written) = (0, 0, 0);
+    let mut cause = None;
+    while let Some((index, result)) = rx.recv().await {
+        done += 1;
+        let file = &files[index];
+        if show_progress {
+            eprint!("\r\x1b[2K");
+        }
+        let text = match result {
+            Ok(text) if text.trim().is_empty() => {
+                if !inputs[index].is_empty() {
+                    eprintln!("{}: empty result", file.display());
+                }
+                None
+            }
+            Ok(text) => Some(text),
+            Err(error) => {
+                failed += 1;
+                eprintln!("{}: {}", file.display(), error);
+                cause = Some(error);
+                None
+            }
+        };
+        match &mut options.report {
+            Some((writer, format)) => {
+                pending.insert(index, text);
+                while let Some(text) = pending.remove(&next) {
+                    if let Some(text) = text {
+                        let path = files[next].display().to_string();
+                        let section = match format {
+                            OutputFormat::Json => ResultRecord { command, input: &path, output: &text }.format(OutputFormat::Json),
+                            OutputFormat::Md | OutputFormat::Txt => format!("## {}\n\n{}\n\n", path, text.trim()),
+                        };
+                        writer.write_all(section.as_bytes())?;
+                        written += 1;
+                    }
+                    next += 1;
+                }
+                writer.flush()?;
+            }
+            None => {
+                if let Some(text) = text {
+                    let path = result_path(file, command);
+                    match std::fs::write(&path, format!("{}\n", text.trim_end())) {
+                        Ok(()) => written += 1,
+                        Err(e) => {
+                            failed += 1;
+                            eprintln!("{}: {}", path.display(), e);
+                        }
+                    }
+                }
+            }
+        }
+        if show_progress {
+            eprint!("\x1b[90m[{}/{}]\x1b[0m", done, total);
+        }
+    }
+    if show_progress {
+        eprint!("\r\x1b[2K");
+    }
+    if options.report.is_none() && written > 0 {
+        let plural = if written == 1 { "" } else { "s" };
+        eprintln!("{}", paint("90", &format!("✓ {} result{} written next to the files as <file>.{}.md", written, plural, command)));
+    }
+    Ok(BatchSummary { total, failed, cause })
+}
+
+/// Results are printed one per line; a multi-line answer is joined with
+/// `\n` escapes so line N of the output always belongs to input line N.
+fn one_line(text: &str) -> String {
+    text.trim().replace("\r\n", "\n").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_batch_that_failed_throughout_exits_as_its_requests() {
+        let summary = |failed, cause| BatchSummary { total: 3, failed, cause };
+        let auth = || Some(XaError::Auth { status: 401, message: "bad key".into() });
+        assert!(summary(0, None).check("inputs").is_ok());
+        assert_eq!(summary(3, auth()).check("inputs").unwrap_err().kind(), "auth");
+        assert_eq!(summary(1, auth()).check("inputs").unwrap_err().to_string(), "1 of 3 inputs failed.");
+    }
+
+    #[test]
+    fn multi_line_results_stay_on_one_line() {
+        assert_eq!(one_line("  Bonjour\n"), "Bonjour");
+        assert_eq!(one_line("a\r\nb\nc"), "a\\nb\\nc");
+    }
+
+    #[test]
+    fn each_result_goes_next_to_its_file() {
+        assert_eq!(result_path(Path::new("src/main.rs"), "summarize"), Path::new("src/main.rs.summarize.md"));
+        assert_eq!(result_path(Path::new("notes"), "polish"), Path::new("notes.polish.md"));
+    }
+}
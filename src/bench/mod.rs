@@ -0,0 +1,118 @@
+//! `xa bench`: one command's prompt sent to several models at once, for
+//! choosing between them. Each model gets its own request, run in
+//! parallel; the report is a table of latency, tokens and estimated cost,
+//! then the answers side by side.
+
+use crate::config::Config;
+use crate::error::XaError;
+use crate::llm::Usage;
+use crate::output::paint;
+use crate::prompt::PromptEntry;
+use std::time::{Duration, Instant};
+
+/// One model's run.
+pub struct Run {
+    pub model: String,
+    pub elapsed: Duration,
+    pub usage: Option<Usage>,
+    /// The answer after the command's `postprocess` and `post_hook`, or
+    /// why there is none.
+    pub result: Result<String, XaError>,
+}
+
+/// Send `prompt` to each model in `models` (names or aliases), all at once.
+pub async fn run(config: &Config, entry: &PromptEntry, prompt: &str, models: &[String]) -> Vec<Run> {
+    let tasks: Vec<_> = models
+        .iter()
+        .map(|model| {
+            let mut config = config.clone();
+            config.default_model = Some(model.clone());
+            let prompt = prompt.to_string();
+            tokio::spawn(async move {
+                let started = Instant::now();
+                let result = crate::llm::complete(&config, &prompt).await;
+                (config.model(), started.elapsed(), result)
+            })
+        })
+        .collect();
+    let mut runs = Vec::with_capacity(tasks.len());
+    for (task, model) in tasks.into_iter().zip(models) {
+        let run = match task.await {
+            Ok((model, elapsed, Ok(completion))) => Run {
+                model,
+                elapsed,
+                usage: completion.usage,
+                result: entry.finish_output(&completion.text).await,
+            },
+            Ok((model, elapsed, Err(error))) => Run { model, elapsed, usage: None, result: Err(error) },
+            Err(error) => Run { model: model.clone(), elapsed: Duration::ZERO, usage: None, result: Err(XaError::Other(error.to_string())) },
+        };
+        runs.push(run);
+    }
+    runs
+}
+
+/// The comparison table: a row per run, costs priced from `config`'s
+/// `[prices.<model>]` ("-" for a model without one).
+pub fn table(config: &Config, runs: &[Run]) -> String {
+    let rows: Vec<[String; 5]> = runs
+        .iter()
+        .map(|run| {
+            let (tokens, cost) = match run.usage {
+                Some(usage) => (
+                    format!("{} + {}", usage.prompt_tokens, usage.completion_tokens),
+                    config.prices.get(&run.model).map_or("-".to_string(), |price| format!("${:.5}", price.cost(usage))),
+                ),
+                None => ("-".to_string(), "-".to_string()),
+            };
+            let status = if run.result.is_ok() { "ok" } else { "failed" };
+            [run.model.clone(), format!("{:.2}s", run.elapsed.as_secs_f64()), tokens, cost, status.to_string()]
+        })
+        .collect();
+    let header = ["Model", "Time", "Tokens (in + out)", "Cost", "Status"];
+    let widths: Vec<usize> = (0..header.len())
+        .map(|col| rows.iter().map(|row| row[col].chars().count()).chain([header[col].len()]).max().unwrap_or(0))
+        .collect();
+    let line = |cells: &[String]| {
+        let cells: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .enumerate()
+            // The model name reads best left-aligned, the figures right-aligned
+            .map(|(col, (cell, &width))| if col == 0 { format!("{:<width$}", cell) } else { format!("{:>width$}", cell) })
+            .collect();
+        cells.join("  ").trim_end().to_string()
+    };
+    let mut out = paint("1", &line(&header.map(String::from))) + "\n";
+    for row in &rows {
+        out.push_str(&line(row));
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Price;
+
+    #[test]
+    fn tabulates_runs_with_costs() {
+        let mut config = Config::default();
+        config.prices.insert("gpt-4o-mini".into(), Price { input: 0.15, output: 0.6 });
+        let runs = [
+            Run {
+                model: "gpt-4o-mini".into(),
+                elapsed: Duration::from_millis(1234),
+                usage: Some(Usage { prompt_tokens: 1000, completion_tokens: 500 }),
+                result: Ok("Bonjour".into()),
+            },
+            Run { model: "llama3".into(), elapsed: Duration::from_millis(80), usage: None, result: Err(XaError::Network("connection refused".into())) },
+        ];
+        let table = table(&config, &runs);
+        let lines: Vec<&str> = table.lines().collect();
+        assert!(lines[0].contains("Model") && lines[0].contains("Cost"));
+        assert_eq!(lines[1], "gpt-4o-mini  1.23s         1000 + 500  $0.00045      ok");
+        assert_eq!(lines[2], "llama3       0.08s                  -         -  failed");
+    }
+}
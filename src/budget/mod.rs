@@ -0,0 +1,169 @@
+//! Spending guardrails for shared keys: `daily_token_limit` and
+//! `monthly_budget_usd` in config.toml, checked before a command sends
+//! anything against a usage ledger that [`crate::llm`] appends to after
+//! every response that reports its usage, whatever the command and whether
+//! or not `history` is on.
+//!
+//! Past 80% of a limit each run warns on stderr; once it is used up, runs
+//! are refused with a `budget` error unless `--force` is given. Responses
+//! whose model has no `[prices.<model>]` entry count towards the token limit
+//! but cost nothing towards the dollar one.
+
+use crate::config::{xa_dir, Config};
+use crate::error::XaError;
+use crate::llm::Usage;
+use chrono::{DateTime, Datelike, Local};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Share of a limit past which each run warns.
+const WARN_AT: f64 = 0.8;
+
+/// One response's usage in the ledger.
+#[derive(Debug, Serialize, Deserialize)]
+struct Spend {
+    time: i64,
+    model: String,
+    #[serde(flatten)]
+    usage: Usage,
+}
+
+/// What the ledger holds: tokens today and dollars this month.
+#[derive(Debug, Default, PartialEq)]
+struct Spent {
+    tokens_today: u64,
+    usd_this_month: f64,
+}
+
+/// The ledger for `now`'s month, `<xa dir>/usage/YYYY-MM.jsonl`; both
+/// limits fall within a month, so older ones are never read.
+fn ledger_path(now: DateTime<Local>) -> Option<PathBuf> {
+    xa_dir().map(|dir| dir.join("usage").join(format!("{}.jsonl", now.format("%Y-%m"))))
+}
+
+/// Add one response's usage to the ledger. A failure is reported but never
+/// fails the request that used the tokens.
+pub fn record(model: &str, usage: Usage) {
+    let now = Local::now();
+    let spend = Spend { time: now.timestamp(), model: model.to_string(), usage };
+    let result = ledger_path(now)
+        .ok_or_else(|| io::Error::other("Could not determine config directory"))
+        .and_then(|path| append(&path, &spend));
+    if let Err(e) = result {
+        eprintln!("Warning: could not record usage for budgets: {}", e);
+    }
+}
+
+fn append(path: &Path, spend: &Spend) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut options = OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    writeln!(options.open(path)?, "{}", serde_json::to_string(spend)?)
+}
+
+/// The ledger's entries; a missing ledger or a torn line counts as nothing.
+fn load(path: &Path) -> Vec<Spend> {
+    fs::read_to_string(path)
+        .map(|text| text.lines().filter_map(|line| serde_json::from_str(line).ok()).collect())
+        .unwrap_or_default()
+}
+
+fn spent(config: &Config, entries: &[Spend], now: DateTime<Local>) -> Spent {
+    let mut spent = Spent::default();
+    for entry in entries {
+        let Some(time) = DateTime::from_timestamp(entry.time, 0) else { continue };
+        let time = time.with_timezone(&Local);
+        if (time.year(), time.month()) != (now.year(), now.month()) {
+            continue;
+        }
+        if time.day() == now.day() {
+            spent.tokens_today += u64::from(entry.usage.prompt_tokens) + u64::from(entry.usage.completion_tokens);
+        }
+        if let Some(price) = config.prices.get(&entry.model) {
+            spent.usd_this_month += price.cost(entry.usage);
+        }
+    }
+    spent
+}
+
+/// Warn about a limit that is nearly used up, and refuse (unless `force`)
+/// one that is used up.
+pub fn check(config: &Config, force: bool) -> Result<(), XaError> {
+    if config.daily_token_limit.is_none() && config.monthly_budget_usd.is_none() {
+        return Ok(());
+    }
+    let now = Local::now();
+    let entries = ledger_path(now).map(|path| load(&path)).unwrap_or_default();
+    enforce(config, &spent(config, &entries, now), force)
+}
+
+fn enforce(config: &Config, spent: &Spent, force: bool) -> Result<(), XaError> {
+    let limits = [
+        config.daily_token_limit.map(|limit| {
+            (spent.tokens_today as f64 / limit as f64, format!("{} of today's {} tokens", spent.tokens_today, limit))
+        }),
+        config.monthly_budget_usd.map(|limit| {
+            (spent.usd_this_month / limit, format!("${:.2} of this month's ${:.2} budget", spent.usd_this_month, limit))
+        }),
+    ];
+    for (used, what) in limits.into_iter().flatten() {
+        if used < WARN_AT {
+            continue;
+        }
+        if used < 1.0 {
+            eprintln!("Warning: {} used", what);
+        } else if force {
+            eprintln!("Warning: {} used; running anyway (--force)", what);
+        } else {
+            return Err(XaError::Budget(format!("{} used; pass --force to run anyway", what)));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Price;
+    use chrono::TimeZone;
+
+    #[test]
+    fn counts_today_and_this_month() {
+        let now = Local.with_ymd_and_hms(2026, 3, 15, 12, 0, 0).unwrap();
+        let run = |day: u32, model: &str, prompt_tokens: u32| Spend {
+            time: Local.with_ymd_and_hms(2026, 3, day, 9, 0, 0).unwrap().timestamp(),
+            model: model.into(),
+            usage: Usage { prompt_tokens, completion_tokens: 500_000 },
+        };
+        let mut config = Config::default();
+        config.prices.insert("cheap".into(), Price { input: 1.0, output: 2.0 });
+        let mut earlier = run(15, "cheap", 1);
+        earlier.time -= 40 * 86_400;
+        let entries = [run(15, "cheap", 1_000_000), run(3, "cheap", 0), run(15, "unpriced", 0), earlier];
+        assert_eq!(spent(&config, &entries, now), Spent { tokens_today: 2_000_000, usd_this_month: 3.0 });
+    }
+
+    #[test]
+    fn usage_counts_with_history_off() {
+        let dir = std::env::temp_dir().join(format!("xa-budget-ledger-{}", std::process::id()));
+        let path = dir.join("usage").join("ledger.jsonl");
+        let now = Local::now();
+        for _ in 0..2 {
+            let spend = Spend { time: now.timestamp(), model: "m".into(), usage: Usage { prompt_tokens: 400, completion_tokens: 200 } };
+            append(&path, &spend).unwrap();
+        }
+        let mut config = Config { history: false, daily_token_limit: Some(1_000), ..Config::default() };
+        let spent = spent(&config, &load(&path), now);
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(spent.tokens_today, 1_200);
+        assert!(matches!(enforce(&config, &spent, false), Err(XaError::Budget(_))));
+        config.daily_token_limit = Some(10_000);
+        assert!(enforce(&config, &spent, false).is_ok());
+    }
+}
@@ -0,0 +1,180 @@
+//! `xa cards "<topic or text>" --deck <file>`: flashcards for spaced
+//! repetition. The model answers in JSON mode with question/answer pairs,
+//! and the deck is written as Anki's tab-separated import format (with
+//! the header lines that tell Anki the deck, note type and columns) or as
+//! JSON for genanki scripts and other tools.
+
+use crate::config::Config;
+use crate::error::XaError;
+use crate::output::paint;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Card {
+    pub front: String,
+    pub back: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct Deck<'a> {
+    deck: &'a str,
+    cards: &'a [Card],
+}
+
+#[derive(Deserialize)]
+struct Answer {
+    #[serde(default)]
+    cards: Vec<Card>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DeckFormat {
+    Tsv,
+    Json,
+}
+
+impl DeckFormat {
+    /// By extension: `.tsv`/`.txt` for Anki, `.json` for scripts. `.apkg`
+    /// needs Anki's database format, which is not written here.
+    pub fn from_path(path: &Path) -> Result<DeckFormat, XaError> {
+        match path.extension().and_then(|ext| ext.to_str()).map(str::to_lowercase).as_deref() {
+            Some("tsv" | "txt") => Ok(DeckFormat::Tsv),
+            Some("json") => Ok(DeckFormat::Json),
+            Some("apkg") => Err(XaError::Usage(
+                "xa cannot write .apkg packages; write a .tsv deck and import it in Anki with File > Import, or a .json deck for genanki".into(),
+            )),
+            _ => Err(XaError::Usage(format!("{}: the deck must be a .tsv (Anki import) or .json file", path.display()))),
+        }
+    }
+}
+
+/// Inputs up to this long on one line are a topic to make cards about;
+/// anything else is text to make cards from.
+const MAX_TOPIC: usize = 200;
+
+fn prompt(source: &str, count: u32, language: Option<&str>) -> String {
+    let what = if source.len() <= MAX_TOPIC && !source.trim().contains('\n') {
+        format!("about {}", source.trim())
+    } else {
+        "from the text below, covering its key facts and ideas".to_string()
+    };
+    let mut prompt = format!(
+        "Write {} flashcards for spaced repetition {}. Each card asks one thing: a short question, term or phrase on \
+         the front and a short, exact answer on the back that a learner can check themselves against. No duplicates, \
+         no trick questions, and no answer given away by its question.",
+        count, what
+    );
+    if let Some(language) = language {
+        prompt.push_str(&format!(
+            " For vocabulary or phrases, put the word or phrase in {} on the front and its meaning, with the \
+             pronunciation if it is not written in Latin script, on the back; otherwise write the cards in {}.",
+            language, language
+        ));
+    }
+    prompt.push_str(
+        "\n\nReply with JSON only, in this form:\n{\"cards\": [{\"front\": \"...\", \"back\": \"...\", \"tags\": [\"...\"]}]}\n\
+         with one or two lowercase tags per card.",
+    );
+    if !what.starts_with("about") {
+        prompt.push_str(&format!("\n\n{}", source));
+    }
+    prompt
+}
+
+/// The cards in a model's answer, without empty ones and repeated fronts.
+fn parse(answer: &str) -> Result<Vec<Card>, String> {
+    let text = crate::postprocess::extract_code_block(answer).unwrap_or_else(|| answer.to_string());
+    let json = match (text.find('{'), text.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &text[start..=end],
+        _ => return Err("no JSON in the answer".into()),
+    };
+    let mut cards = serde_json::from_str::<Answer>(json).map_err(|e| e.to_string())?.cards;
+    let mut seen = std::collections::HashSet::new();
+    cards.retain(|card| !card.front.trim().is_empty() && !card.back.trim().is_empty() && seen.insert(card.front.trim().to_lowercase()));
+    if cards.is_empty() {
+        return Err("the answer has no cards".into());
+    }
+    Ok(cards)
+}
+
+/// `count` cards for `source` (a topic or a text), with the fronts in
+/// `language` for vocabulary. An answer that is not card JSON is asked
+/// for again up to `retries` times.
+pub async fn generate(config: &Config, source: &str, count: u32, language: Option<&str>, retries: u32) -> Result<Vec<Card>, XaError> {
+    let mut config = config.clone();
+    config.extra_body.insert("response_format".to_string(), serde_json::json!({ "type": "json_object" }));
+    let request = prompt(source, count, language);
+    let mut prompt = request.clone();
+    for attempt in 0..=retries {
+        let spinner = crate::spinner::Spinner::start(if attempt == 0 { "Writing cards" } else { "Retrying" });
+        let answer = crate::llm::complete(&config, &prompt).await;
+        spinner.stop();
+        let error = match parse(&answer?.text) {
+            Ok(cards) => return Ok(cards),
+            Err(error) => error,
+        };
+        if attempt == retries {
+            return Err(XaError::Other(format!("no usable cards after {} attempts: {}", retries + 1, error)));
+        }
+        eprintln!("{}", paint("33", &format!("The answer has no usable cards ({}); asking again.", error)));
+        prompt = format!("{}\n\nYour previous answer could not be read: {}. Reply with the JSON only.", request, error);
+    }
+    unreachable!("the last attempt returns")
+}
+
+/// One field for Anki's HTML-enabled import: markup escaped, line breaks
+/// as `<br>`, no tabs.
+fn field(text: &str) -> String {
+    text.trim().replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('\t', " ").replace("\r\n", "\n").replace('\n', "<br>")
+}
+
+/// The deck in Anki's text import format: header lines naming the
+/// separator, deck, note type and columns, then `front\tback\ttags`.
+pub fn tsv(deck: &str, cards: &[Card]) -> String {
+    let mut out = format!("#separator:tab\n#html:true\n#deck:{}\n#notetype:Basic\n#columns:Front\tBack\tTags\n#tags column:3\n", deck);
+    for card in cards {
+        let tags: Vec<String> = card.tags.iter().map(|tag| tag.trim().replace(char::is_whitespace, "_")).filter(|tag| !tag.is_empty()).collect();
+        out.push_str(&format!("{}\t{}\t{}\n", field(&card.front), field(&card.back), tags.join(" ")));
+    }
+    out
+}
+
+/// The deck as JSON: `{"deck": name, "cards": [{"front", "back", "tags"}]}`.
+pub fn json(deck: &str, cards: &[Card]) -> String {
+    format!("{}\n", serde_json::to_string_pretty(&Deck { deck, cards }).expect("cards serialize"))
+}
+
+/// The cards on the terminal: each front in bold with its back below.
+pub fn render(cards: &[Card]) -> String {
+    cards.iter().map(|card| format!("{}\n  {}", paint("1", card.front.trim()), card.back.trim().replace('\n', "\n  "))).collect::<Vec<_>>().join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_anki_and_json_decks() {
+        let answer = "{\"cards\": [{\"front\": \"Hund\", \"back\": \"dog\", \"tags\": [\"animals\", \"a1 nouns\"]}, \
+                      {\"front\": \"hund \", \"back\": \"dog again\"}, {\"front\": \"x < y?\", \"back\": \"line\\none\\tend\"}, {\"front\": \"\", \"back\": \"?\"}]}";
+        let cards = parse(answer).unwrap();
+        assert_eq!(cards.len(), 2);
+        assert_eq!(
+            tsv("German", &cards),
+            "#separator:tab\n#html:true\n#deck:German\n#notetype:Basic\n#columns:Front\tBack\tTags\n#tags column:3\n\
+             Hund\tdog\tanimals a1_nouns\nx &lt; y?\tline<br>one end\t\n"
+        );
+        let parsed: serde_json::Value = serde_json::from_str(&json("German", &cards)).unwrap();
+        assert_eq!(parsed["cards"][0]["tags"][1], "a1 nouns");
+        assert!(parsed["cards"][1].get("tags").is_none());
+        assert!(parse("{\"cards\": []}").is_err());
+
+        assert!(prompt("the French Revolution", 10, None).contains("10 flashcards for spaced repetition about the French Revolution."));
+        assert!(prompt(&"Long text. ".repeat(30), 5, Some("Japanese")).ends_with("Long text. "));
+        assert_eq!(DeckFormat::from_path(Path::new("deck.TSV")).unwrap(), DeckFormat::Tsv);
+        assert!(DeckFormat::from_path(Path::new("deck.apkg")).unwrap_err().to_string().contains(".tsv"));
+    }
+}
@@ -0,0 +1,125 @@
+//! `xa changelog <range>`: release notes for the commits in a range. The
+//! commits are grouped by their conventional-commit type (`feat:`, `fix:`,
+//! …) into the Keep a Changelog sections, and the model writes the entry
+//! from those groups with the `changelog` command's template.
+
+use crate::git::LogEntry;
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// The Keep a Changelog sections, in the order they are written.
+const SECTIONS: [&str; 6] = ["Added", "Changed", "Deprecated", "Removed", "Fixed", "Security"];
+
+/// Commits that are not a user-facing change of their own (docs, tests,
+/// CI, chores) or do not follow the convention.
+const OTHER: &str = "Other";
+
+/// `type(scope)!: description`.
+static CONVENTIONAL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?<kind>[A-Za-z]+)(?:\((?<scope>[^)]*)\))?(?<breaking>!)?:\s*(?<description>.+)$").expect("valid regex"));
+
+/// A commit subject split by the conventional-commit convention.
+#[derive(Debug, PartialEq)]
+struct Conventional<'a> {
+    kind: &'a str,
+    scope: Option<&'a str>,
+    breaking: bool,
+    description: &'a str,
+}
+
+fn parse(subject: &str) -> Option<Conventional<'_>> {
+    let captures = CONVENTIONAL.captures(subject)?;
+    Some(Conventional {
+        kind: captures.name("kind")?.as_str(),
+        scope: captures.name("scope").map(|scope| scope.as_str()).filter(|scope| !scope.is_empty()),
+        breaking: captures.name("breaking").is_some(),
+        description: captures.name("description")?.as_str(),
+    })
+}
+
+/// The section a commit type goes under.
+fn section(kind: &str) -> &'static str {
+    match kind.to_lowercase().as_str() {
+        "feat" | "feature" | "add" => "Added",
+        "fix" | "bugfix" | "hotfix" => "Fixed",
+        "perf" | "refactor" | "change" | "update" => "Changed",
+        "deprecate" => "Deprecated",
+        "remove" | "revert" => "Removed",
+        "security" | "sec" => "Security",
+        _ => OTHER,
+    }
+}
+
+/// The commits as the model sees them: one list per section, each line
+/// the description with its scope, a breaking-change mark and the body.
+pub fn grouped(commits: &[LogEntry]) -> String {
+    let mut groups: Vec<(&str, Vec<String>)> = SECTIONS.iter().chain([&OTHER]).map(|section| (*section, Vec::new())).collect();
+    for commit in commits {
+        let parsed = parse(&commit.subject);
+        let name = parsed.as_ref().map_or(OTHER, |parsed| section(parsed.kind));
+        let mut line = match &parsed {
+            Some(parsed) => match parsed.scope {
+                Some(scope) => format!("- {}: {}", scope, parsed.description),
+                None => format!("- {}", parsed.description),
+            },
+            None => format!("- {}", commit.subject),
+        };
+        if parsed.is_some_and(|parsed| parsed.breaking) || commit.body.contains("BREAKING CHANGE") {
+            line.push_str(" (BREAKING)");
+        }
+        for body_line in commit.body.lines().filter(|body_line| !body_line.trim().is_empty()) {
+            line.push_str(&format!("\n  {}", body_line.trim()));
+        }
+        if let Some((_, lines)) = groups.iter_mut().find(|(section, _)| *section == name) {
+            lines.push(line);
+        }
+    }
+    groups
+        .into_iter()
+        .filter(|(_, lines)| !lines.is_empty())
+        .map(|(section, lines)| format!("{}:\n{}", section, lines.join("\n")))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// The entry's heading: `## [1.3.0] - 2026-10-16`, or `## [Unreleased]`
+/// without a version. A leading `v` is dropped, as Keep a Changelog does.
+pub fn heading(version: Option<&str>, date: &str) -> String {
+    match version {
+        Some(version) => format!("## [{}] - {}", version.strip_prefix('v').unwrap_or(version), date),
+        None => "## [Unreleased]".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(subject: &str, body: &str) -> LogEntry {
+        LogEntry { subject: subject.to_string(), body: body.to_string() }
+    }
+
+    #[test]
+    fn groups_commits_by_conventional_type() {
+        assert_eq!(
+            parse("feat(api)!: drop the v1 routes"),
+            Some(Conventional { kind: "feat", scope: Some("api"), breaking: true, description: "drop the v1 routes" })
+        );
+        assert_eq!(parse("Update README"), None);
+
+        let commits = [
+            commit("fix: crash on empty input", ""),
+            commit("feat(tui): /export command", "Writes Markdown or HTML.\n\nCloses #12"),
+            commit("chore: bump deps", ""),
+            commit("Tidy up", ""),
+            commit("feat: new config format", "BREAKING CHANGE: the old keys are gone"),
+        ];
+        let text = grouped(&commits);
+        assert!(text.starts_with("Added:\n- tui: /export command\n  Writes Markdown or HTML.\n  Closes #12\n- new config format (BREAKING)"));
+        assert!(text.contains("\n\nFixed:\n- crash on empty input\n\nOther:\n- bump deps\n- Tidy up"));
+        assert!(!text.contains("Changed:"));
+
+        assert_eq!(heading(Some("v1.3.0"), "2026-10-16"), "## [1.3.0] - 2026-10-16");
+        assert_eq!(heading(None, "2026-10-16"), "## [Unreleased]");
+    }
+}
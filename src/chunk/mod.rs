@@ -0,0 +1,194 @@
+//! Inputs too long for one request. A prompt entry's `chunk_strategy` says
+//! what happens when its filled prompt would not fit the model's context
+//! window (see [`Config::context_window`]):
+//!
+//! - `error` (the default): stop before sending, saying how far over it is.
+//!   Unless `context_window` is configured, the window is only guessed
+//!   from the model's name, so an entry without a `chunk_strategy` warns
+//!   and sends the prompt anyway;
+//! - `truncate`: keep as much of the start of the input as fits;
+//! - `map_reduce`: run the command on each part of the input, then once
+//!   more on the parts' results joined together, which is the prompt
+//!   returned. A summary of summaries, for example.
+//!
+//! Token counts are estimates: four ASCII characters or one other
+//! character to a token.
+
+use crate::config::Config;
+use crate::error::XaError;
+use crate::prompt::{fill_template, PromptEntry};
+use serde::{Deserialize, Serialize};
+
+/// Rounds of `map_reduce` before giving up on results that still do not fit.
+const MAX_ROUNDS: usize = 3;
+/// Smallest part `map_reduce` will split into, however large the template.
+const MIN_PART_TOKENS: usize = 500;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkStrategy {
+    #[default]
+    Error,
+    Truncate,
+    MapReduce,
+}
+
+/// Rough token count of `text`.
+pub fn estimate_tokens(text: &str) -> usize {
+    quarters(text).div_ceil(4)
+}
+
+/// `text`'s size in quarter tokens.
+fn quarters(text: &str) -> usize {
+    text.chars().map(|c| if c.is_ascii() { 1 } else { 4 }).sum()
+}
+
+/// Tokens a prompt may use, leaving room for the answer.
+fn budget(config: &Config) -> usize {
+    let window = config.context_window();
+    window - (window / 4).min(8_192)
+}
+
+/// `entry`'s template filled with `input`, `args` and the previous result
+/// `last`, made to fit the model by the entry's `chunk_strategy`.
+pub async fn fill(config: &Config, entry: &PromptEntry, input: &str, args: &[String], last: Option<&str>) -> Result<String, XaError> {
+    let fill = |text: &str| fill_template(&entry.template, text, args, entry.args.as_ref(), last);
+    let prompt = fill(input);
+    let budget = budget(config);
+    let tokens = estimate_tokens(&prompt);
+    if tokens <= budget {
+        return Ok(prompt);
+    }
+    if entry.chunk_strategy.is_none() && config.context_window.is_none() {
+        eprintln!(
+            "The prompt is about {} tokens, more than the {} that likely fit {}'s context window; sending it anyway. \
+             Set context_window in config.toml to have it stopped or split by the command's chunk_strategy.",
+            tokens,
+            budget,
+            config.model()
+        );
+        return Ok(prompt);
+    }
+    let room = budget.saturating_sub(estimate_tokens(&fill(""))).max(MIN_PART_TOKENS);
+    match entry.chunk_strategy.unwrap_or_default() {
+        ChunkStrategy::Error => Err(XaError::Usage(format!(
+            "The prompt is about {} tokens, over the {} that fit {}'s context window. Set chunk_strategy = \"map_reduce\" \
+             or \"truncate\" on the command, or context_window in config.toml if the model takes more.",
+            tokens,
+            budget,
+            config.model()
+        ))),
+        ChunkStrategy::Truncate => {
+            let kept = split(input, room).into_iter().next().unwrap_or_default();
+            eprintln!("Input cut to its first ~{} of ~{} tokens to fit the context window", room, estimate_tokens(input));
+            Ok(fill(kept))
+        }
+        ChunkStrategy::MapReduce => {
+            let mut text = input.to_string();
+            for _ in 0..MAX_ROUNDS {
+                let parts = split(&text, room);
+                let count = parts.len();
+                let mut results = Vec::with_capacity(parts.len());
+                for (index, part) in parts.iter().enumerate() {
+                    let spinner = crate::spinner::Spinner::start(&format!("Part {}/{}", index + 1, count));
+                    let done = crate::llm::complete(config, &fill(part)).await;
+                    spinner.stop();
+                    results.push(done?.text);
+                }
+                text = results.join("\n\n");
+                let prompt = fill(&text);
+                if estimate_tokens(&prompt) <= budget {
+                    eprintln!("Input processed in {} parts; combining the results", count);
+                    return Ok(prompt);
+                }
+            }
+            Err(XaError::Usage(format!("The input is still too long for {} after {} rounds of map_reduce.", config.model(), MAX_ROUNDS)))
+        }
+    }
+}
+
+/// `text` in consecutive parts of at most `max` tokens each, split between
+/// paragraphs where possible, then between lines, then anywhere.
+pub fn split(text: &str, max: usize) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut start, mut end, mut size) = (0, 0, 0);
+    for piece in pieces(text, max) {
+        let cost = quarters(piece);
+        if end > start && size + cost > max * 4 {
+            parts.push(&text[start..end]);
+            (start, size) = (end, 0);
+        }
+        end += piece.len();
+        size += cost;
+    }
+    if end > start {
+        parts.push(&text[start..end]);
+    }
+    parts
+}
+
+/// `text` cut into paragraphs, and any paragraph over `max` tokens into
+/// lines, and any such line into runs of characters; in order, covering
+/// all of `text`.
+fn pieces(text: &str, max: usize) -> Vec<&str> {
+    let mut out = Vec::new();
+    for paragraph in text.split_inclusive("\n\n") {
+        if estimate_tokens(paragraph) <= max {
+            out.push(paragraph);
+            continue;
+        }
+        for line in paragraph.split_inclusive('\n') {
+            if estimate_tokens(line) <= max {
+                out.push(line);
+                continue;
+            }
+            let (mut start, mut size) = (0, 0);
+            for (at, c) in line.char_indices() {
+                let cost = if c.is_ascii() { 1 } else { 4 };
+                if size + cost > max * 4 && at > start {
+                    out.push(&line[start..at]);
+                    (start, size) = (at, 0);
+                }
+                size += cost;
+            }
+            out.push(&line[start..]);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_between_paragraphs_then_lines() {
+        assert_eq!(estimate_tokens("abcdefgh"), 2);
+        assert_eq!(estimate_tokens("你好"), 2);
+
+        let text = "first paragraph here\n\nsecond one\n\nthird";
+        let parts = split(text, 6);
+        assert_eq!(parts, ["first paragraph here\n\n", "second one\n\nthird"]);
+        assert_eq!(parts.concat(), text);
+
+        let long_line = "x".repeat(30);
+        let parts = split(&long_line, 3);
+        assert!(parts.iter().all(|part| estimate_tokens(part) <= 3));
+        assert_eq!(parts.concat(), long_line);
+    }
+
+    #[tokio::test]
+    async fn a_guessed_window_warns_and_a_configured_one_refuses() {
+        let entry = PromptEntry { template: "{input}".into(), ..PromptEntry::default() };
+        let input = "word ".repeat(40_000);
+        let mut config = Config { default_model: Some("llama3.2".into()), ..Config::default() };
+        assert_eq!(config.context_window(), 128_000);
+        let small = Config { default_model: Some("small".into()), context_window: Some(8_192), ..config.clone() };
+        assert!(matches!(fill(&small, &entry, &input, &[], None).await, Err(XaError::Usage(_))));
+
+        config.default_model = Some("gpt-4".into());
+        assert_eq!(fill(&config, &entry, &input, &[], None).await.unwrap(), input);
+        let strict = PromptEntry { chunk_strategy: Some(ChunkStrategy::Error), ..entry };
+        assert!(fill(&config, &strict, &input, &[], None).await.is_err());
+    }
+}
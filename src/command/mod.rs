@@ -0,0 +1,109 @@
+//! What running a prompt command involves before and after the model is
+//! asked, for `xa <command>` and anything else that runs commands: the
+//! config a run uses, and the input and arguments the command gets.
+
+use crate::config::{load_config, Config};
+use crate::error::XaError;
+use crate::language;
+use crate::prompt::PromptEntry;
+
+/// What one run changes about config.toml (the `--model`, `--force`,
+/// sampling and similar flags of the CLI).
+#[derive(Debug, Default, Clone)]
+pub struct RunOptions {
+    pub model: Option<String>,
+    /// Run even when a usage budget is used up.
+    pub force: bool,
+    pub hide_thinking: bool,
+    pub redact: bool,
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    pub max_tokens: Option<u32>,
+}
+
+/// [`load_config`] with `options` applied, once the usage budgets allow
+/// another run.
+pub async fn load_run_config(options: &RunOptions) -> Result<Config, XaError> {
+    let mut config = load_config().await?;
+    crate::budget::check(&config, options.force)?;
+    if let Some(model) = &options.model {
+        config.default_model = Some(model.clone());
+    }
+    config.hide_thinking |= options.hide_thinking;
+    config.redact.enabled |= options.redact;
+    let overrides = [
+        ("temperature", options.temperature.map(serde_json::Value::from)),
+        ("top_p", options.top_p.map(serde_json::Value::from)),
+        ("max_tokens", options.max_tokens.map(serde_json::Value::from)),
+    ];
+    config.body_overrides.extend(overrides.into_iter().filter_map(|(key, value)| Some((key.to_string(), value?))));
+    Ok(config)
+}
+
+/// The input and arguments `cmd` runs with. `xa translate fr "text"` puts
+/// the language first, so for `translate` a short code given as the input
+/// (`positional`: from the command line; piped text is never a language
+/// code) trades places with the text after it.
+pub fn command_input(cmd: &str, input: &str, args: &[String], positional: bool) -> (String, Vec<String>) {
+    let language_first = cmd == "translate"
+        && positional
+        && !args.is_empty()
+        && (2..=3).contains(&input.len())
+        && input.chars().all(|c| c.is_ascii_alphabetic());
+    if language_first {
+        (args[0].clone(), vec![input.to_string()])
+    } else {
+        (input.to_string(), args.to_vec())
+    }
+}
+
+/// `args` with `language` as the command's `target_lang` argument (for
+/// `--to`); arguments before it keep their defaults.
+pub fn with_target_lang(cmd: &str, entry: &PromptEntry, mut args: Vec<String>, language: &str) -> Result<Vec<String>, XaError> {
+    let prompt_args = entry.args.as_deref().unwrap_or_default();
+    let Some(index) = prompt_args.iter().position(|arg| arg.name == "target_lang") else {
+        return Err(XaError::Usage(format!("--to sets a target_lang argument, and '{}' has none", cmd)));
+    };
+    while args.len() < index {
+        args.push(prompt_args[args.len()].default_value.clone());
+    }
+    if args.len() == index {
+        args.push(language.to_string());
+    } else {
+        args[index] = language.to_string();
+    }
+    Ok(args)
+}
+
+/// The command's target language and the one to use instead, when `input`
+/// is already written in the target language.
+pub fn flipped_target(config: &Config, entry: &PromptEntry, input: &str, args: &[String]) -> Option<(String, String)> {
+    let prompt_args = entry.args.as_deref()?;
+    let index = prompt_args.iter().position(|arg| arg.name == "target_lang")?;
+    let target = args.get(index).unwrap_or(&prompt_args[index].default_value);
+    let secondary = language::secondary(config, target)?;
+    language::flip(input, target, &secondary).map(|secondary| (target.clone(), secondary))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prompt::PromptArg;
+
+    #[test]
+    fn a_leading_language_code_trades_places_with_the_text() {
+        let args = vec!["Good morning".to_string()];
+        assert_eq!(command_input("translate", "fr", &args, true), ("Good morning".to_string(), vec!["fr".to_string()]));
+        assert_eq!(command_input("translate", "fr", &args, false), ("fr".to_string(), args.clone()));
+        assert_eq!(command_input("summarize", "fr", &args, true), ("fr".to_string(), args.clone()));
+    }
+
+    #[test]
+    fn target_lang_fills_the_arguments_before_it() {
+        let arg = |name: &str, default_value: &str| PromptArg { name: name.into(), default_value: default_value.into(), description: None };
+        let entry = PromptEntry { args: Some(vec![arg("tone", "neutral"), arg("target_lang", "en")]), ..Default::default() };
+        assert_eq!(with_target_lang("translate", &entry, Vec::new(), "de").unwrap(), ["neutral", "de"]);
+        assert_eq!(with_target_lang("translate", &entry, vec!["formal".into(), "fr".into()], "de").unwrap(), ["formal", "de"]);
+        assert!(matches!(with_target_lang("summarize", &PromptEntry::default(), Vec::new(), "de"), Err(XaError::Usage(_))));
+    }
+}
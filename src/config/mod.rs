@@ -1,16 +1,465 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::io::{self, Write};
-use dirs::config_dir;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use crate::error::XaError;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Config {
     pub base_url: String,
     pub api_key: String,
     pub default_model: Option<String>,
+    /// Appearance: `theme = "light"` for the mode alone, or a `[theme]`
+    /// table that also sets the one-shot Markdown colors.
+    #[serde(
+        default,
+        skip_serializing_if = "ThemeConfig::is_empty",
+        deserialize_with = "ThemeConfig::deserialize_compact",
+        serialize_with = "ThemeConfig::serialize_compact"
+    )]
+    pub theme: ThemeConfig,
+    /// Send `xa <words...>` that match no command to the `ask` prompt as a
+    /// question instead of failing. Off by default so typos still error.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub fallback_to_ask: bool,
+    /// Ask before running a command that was only fuzzy-matched
+    /// (`xa smrz` → `summarize`); exact and unique-prefix names never ask.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub confirm_fuzzy: bool,
+    /// Don't copy one-shot results to the clipboard (headless machines, CI).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub no_clipboard: bool,
+    /// Leave out the reasoning that thinking models stream before their
+    /// answer (`reasoning_content`, or a leading `<think>` block).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub hide_thinking: bool,
+    /// Language `translate` (and other commands' `target_lang = "auto"`)
+    /// translates into. Omitted → the language of `LC_ALL`/`LANG`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_target_lang: Option<String>,
+    /// Language `translate` switches to when the input is already in the
+    /// target language. Omitted → English; `off` never switches.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secondary_target_lang: Option<String>,
+    /// Signature `reply` puts under its answers (`\n` for line breaks when
+    /// set with `xa config set`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email_signature: Option<String>,
+    /// Git remote `xa store sync` keeps `stores.toml` in, e.g.
+    /// `git@github.com:me/xa-store.git`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub store_remote: Option<String>,
+    /// Let `xa store sync` push `stores.toml` as it is, secrets in plain
+    /// text; without it the sync refuses to run.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub store_sync_plaintext: bool,
+    /// Shell command whose output is the API key (`pass show openai/key`,
+    /// `op read op://...`). Run only when `api_key` is empty, and only by
+    /// commands that call the API.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_cmd: Option<String>,
+    /// Model for `xa index` and `xa ask --with-index`. Omitted →
+    /// `text-embedding-3-small`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding_model: Option<String>,
+    /// Model for `xa transcribe` (default: whisper-1).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transcription_model: Option<String>,
+    /// How `xa ocr` reads images: `vision` (the default) or `tesseract`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ocr_backend: Option<String>,
+    /// Vision model for `xa ocr`. Omitted → the default model.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ocr_model: Option<String>,
+    /// Tokens the model accepts per request, for commands' `chunk_strategy`.
+    /// Omitted → guessed from the model name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_window: Option<usize>,
+    /// Where embeddings are computed when not at `base_url`, e.g. a local
+    /// Ollama (`http://localhost:11434`), which gets no API key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding_url: Option<String>,
+    /// Short names for models, e.g. `fast = "gpt-4o-mini"`, accepted
+    /// wherever a model is given (`default_model`, `--model`, a prompt
+    /// entry's `model`) so prompts.toml works across providers.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub model_aliases: BTreeMap<String, String>,
+    /// Proxy for every API request: `http://host:port`, `socks5://host:port`
+    /// (`socks5h://` resolves names through the proxy). Unset → the
+    /// system's `HTTPS_PROXY`/`ALL_PROXY` variables are used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    /// Comma-separated hosts that bypass `proxy`, e.g. `localhost,.corp`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_proxy: Option<String>,
+    /// PEM file with extra root certificates (corporate MITM proxies,
+    /// self-signed gateways); trusted alongside the built-in roots.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_bundle: Option<String>,
+    /// Skip TLS certificate verification entirely. Last resort for
+    /// debugging; prefer `ca_bundle`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub insecure_tls: bool,
+    /// Headers sent with every one-shot request, e.g. `X-Org-Id` for a
+    /// gateway. A prompt entry's own `extra_headers` win over these.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extra_headers: BTreeMap<String, String>,
+    /// Top-level fields merged into every one-shot request body, e.g.
+    /// `temperature` or OpenRouter's `provider` routing object.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extra_body: BTreeMap<String, serde_json::Value>,
+    /// Append every one-shot request and its full response to the
+    /// transcript log (see `xa transcript`). Off by default.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub log_transcripts: bool,
+    /// Log every one-shot run, input included, to the run history (see
+    /// `xa history`). On by default; the usage budgets count from it.
+    #[serde(default = "on", skip_serializing_if = "is_on")]
+    pub history: bool,
+    /// Tokens (prompt and completion) the logged runs may use per local
+    /// day before xa refuses to send more without `--force`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daily_token_limit: Option<u64>,
+    /// Dollars the logged runs may cost per calendar month, priced from
+    /// `prices`, before xa refuses to send more without `--force`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monthly_budget_usd: Option<f64>,
+    /// Requests per minute xa sends to one endpoint and key; more wait for
+    /// their turn (`xa batch`, the clipboard daemon, `xa serve`). A
+    /// provider in providers.toml may set its own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_minute: Option<u32>,
+    /// Tokens per minute, likewise: the prompt's estimated size before the
+    /// request, the completion once it is known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens_per_minute: Option<u32>,
+    /// What each model costs, as `[prices.<model>]` tables in dollars per
+    /// million tokens, for `monthly_budget_usd`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub prices: BTreeMap<String, Price>,
+    /// Named system prompts for `xa ask --persona <name>` and `/persona` in
+    /// the conversation mode, each as a `[personas.<name>]` table.
+    /// `default` is used when none is chosen and may be overridden here.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub personas: BTreeMap<String, Persona>,
+    /// Web search backend for `xa ask --search`.
+    #[serde(default, skip_serializing_if = "SearchConfig::is_empty")]
+    pub search: SearchConfig,
+    /// Masking of secrets in prompts before they are sent (see
+    /// [`crate::redact`]).
+    #[serde(default, skip_serializing_if = "crate::redact::RedactConfig::is_empty")]
+    pub redact: crate::redact::RedactConfig,
+    /// Model Context Protocol servers whose tools `xa ask --tools` can
+    /// use, each as an `[mcp_servers.<name>]` table.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub mcp_servers: BTreeMap<String, McpServer>,
+    /// External commands run as `xa <name>`, each as a `[plugins.<name>]`
+    /// table; `xa-<name>` executables on PATH need no entry.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub plugins: BTreeMap<String, Plugin>,
+    /// System message sent ahead of the prompt, set per run from a persona.
+    #[serde(skip)]
+    pub system_prompt: Option<String>,
+    /// Image URLs (`https:` or `data:`) sent with the prompt, set per run
+    /// from `xa ask -i`.
+    #[serde(skip)]
+    pub images: Vec<String>,
+    /// Request body fields set per run from `--temperature`, `--top-p` and
+    /// `--max-tokens`. Merged last, so they beat `extra_body`, a prompt
+    /// entry's and a persona's.
+    #[serde(skip)]
+    pub body_overrides: BTreeMap<String, serde_json::Value>,
+    /// Start of the answer for the model to continue, set per run from a
+    /// prompt entry's `assistant_prefix`.
+    #[serde(skip)]
+    pub assistant_prefix: Option<String>,
+}
+
+/// `[search]` in config.toml.
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+pub struct SearchConfig {
+    /// `duckduckgo` (the default, no key), `searxng` or `brave`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backend: Option<String>,
+    /// Base URL of the SearxNG instance (with JSON output enabled).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Brave Search API key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    /// How many results go into the prompt. Omitted → 5.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub results: Option<usize>,
+}
+
+/// Fields of `[search]` settable as `search.<field>`.
+pub const SEARCH_FIELDS: &[&str] = &["backend", "url", "api_key", "results"];
+
+/// Backends accepted by `search.backend`.
+pub const SEARCH_BACKENDS: &[&str] = &["duckduckgo", "searxng", "brave"];
+
+impl SearchConfig {
+    fn is_empty(&self) -> bool {
+        *self == SearchConfig::default()
+    }
+
+    fn get(&self, field: &str) -> Option<Option<String>> {
+        Some(match field {
+            "backend" => self.backend.clone(),
+            "url" => self.url.clone(),
+            "api_key" => self.api_key.clone(),
+            "results" => self.results.map(|results| results.to_string()),
+            _ => return None,
+        })
+    }
+
+    fn set(&mut self, field: &str, value: Option<String>) -> Result<(), XaError> {
+        match field {
+            "backend" => {
+                if let Some(backend) = value.as_deref().filter(|backend| !SEARCH_BACKENDS.contains(backend)) {
+                    return Err(XaError::Usage(format!("Invalid search backend '{}': expected {}", backend, SEARCH_BACKENDS.join(", "))));
+                }
+                self.backend = value;
+            }
+            "url" => self.url = value.map(|url| url.trim_end_matches('/').to_string()),
+            "api_key" => self.api_key = value,
+            "results" => {
+                self.results = match value {
+                    Some(value) => match value.parse() {
+                        Ok(results) if results > 0 => Some(results),
+                        _ => return Err(XaError::Usage(format!("Invalid value '{}' for search.results: expected a positive number", value))),
+                    },
+                    None => None,
+                };
+            }
+            _ => return Err(unknown_key(&format!("search.{}", field))),
+        }
+        Ok(())
+    }
+}
+
+/// One `[prices.<model>]` table: dollars per million tokens.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub struct Price {
+    pub input: f64,
+    pub output: f64,
+}
+
+impl Price {
+    /// Dollars for the tokens in `usage`.
+    pub fn cost(&self, usage: crate::llm::Usage) -> f64 {
+        (usage.prompt_tokens as f64 * self.input + usage.completion_tokens as f64 * self.output) / 1e6
+    }
+}
+
+/// One `[personas.<name>]` table.
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+pub struct Persona {
+    pub system: String,
+    /// Model (or alias) for this persona; `--model` still wins.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+}
+
+/// One `[mcp_servers.<name>]` table: a local server started as `command`
+/// (spoken to over stdin/stdout), or a remote one at `url`.
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+pub struct McpServer {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    /// Extra environment for `command`, e.g. a token the server needs.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Streamable HTTP endpoint, e.g. `https://mcp.example.com/mcp`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Headers sent to `url`, e.g. `Authorization`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+}
+
+/// One `[plugins.<name>]` table: the program `xa <name>` runs.
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+pub struct Plugin {
+    pub command: String,
+    /// Arguments passed ahead of the ones given on the command line.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    /// Extra environment for `command`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Shown by `xa ls plugins`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// System prompt of the built-in `default` persona.
+pub const DEFAULT_PERSONA_PROMPT: &str = "You are a helpful assistant called xa, execute anything by your side.";
+
+/// `[theme]` in config.toml. Colors are names (`blue`, `bright_cyan`),
+/// 256-color numbers (`208`) or `#rrggbb`; unset ones come from the preset.
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+pub struct ThemeConfig {
     /// TUI appearance: `auto` | `dark` | `light`. Omitted → auto.
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub theme: Option<String>,
+    pub mode: Option<String>,
+    /// Markdown colors for one-shot output: `dark`, `light` or `plain`.
+    /// Omitted → matches the resolved mode.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub headers: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bold: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub italic: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote: Option<String>,
+}
+
+/// Fields of `[theme]` settable as `theme.<field>`.
+pub const THEME_FIELDS: &[&str] = &["preset", "headers", "bold", "italic", "code", "quote"];
+
+/// Markdown presets accepted by `theme.preset`.
+pub const THEME_PRESETS: &[&str] = &["dark", "light", "plain"];
+
+impl ThemeConfig {
+    fn is_empty(&self) -> bool {
+        *self == ThemeConfig::default()
+    }
+
+    fn field(&self, name: &str) -> Option<&Option<String>> {
+        Some(match name {
+            "preset" => &self.preset,
+            "headers" => &self.headers,
+            "bold" => &self.bold,
+            "italic" => &self.italic,
+            "code" => &self.code,
+            "quote" => &self.quote,
+            _ => return None,
+        })
+    }
+
+    fn field_mut(&mut self, name: &str) -> Option<&mut Option<String>> {
+        Some(match name {
+            "preset" => &mut self.preset,
+            "headers" => &mut self.headers,
+            "bold" => &mut self.bold,
+            "italic" => &mut self.italic,
+            "code" => &mut self.code,
+            "quote" => &mut self.quote,
+            _ => return None,
+        })
+    }
+
+    /// Accept the older `theme = "light"` as well as the `[theme]` table.
+    fn deserialize_compact<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<ThemeConfig, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Compact {
+            Mode(String),
+            Table(ThemeConfig),
+        }
+        Ok(match Compact::deserialize(deserializer)? {
+            Compact::Mode(mode) => ThemeConfig { mode: Some(mode), ..ThemeConfig::default() },
+            Compact::Table(theme) => theme,
+        })
+    }
+
+    /// Write a theme that only sets the mode back as `theme = "<mode>"`.
+    fn serialize_compact<S: serde::Serializer>(theme: &ThemeConfig, serializer: S) -> Result<S::Ok, S::Error> {
+        match &theme.mode {
+            Some(mode) if *theme == (ThemeConfig { mode: Some(mode.clone()), ..ThemeConfig::default() }) => {
+                serializer.serialize_str(mode)
+            }
+            _ => theme.serialize(serializer),
+        }
+    }
+}
+
+impl Config {
+    /// `name` with a `[model_aliases]` entry applied, if there is one.
+    pub fn resolve_model(&self, name: &str) -> String {
+        self.model_aliases.get(name).cloned().unwrap_or_else(|| name.to_string())
+    }
+
+    /// The model to request: `default_model` (alias resolved), or
+    /// `gpt-4o-mini` when none is configured.
+    pub fn model(&self) -> String {
+        self.resolve_model(self.default_model.as_deref().unwrap_or("gpt-4o-mini"))
+    }
+
+    /// Whether runs need an API key that is not set: every model needs one
+    /// except a local `file:` model.
+    pub fn missing_api_key(&self) -> bool {
+        self.api_key.is_empty() && !self.model().starts_with("file:")
+    }
+
+    /// The embedding model to request (alias resolved).
+    pub fn embedding_model(&self) -> String {
+        self.resolve_model(self.embedding_model.as_deref().unwrap_or("text-embedding-3-small"))
+    }
+
+    /// The speech-to-text model to request (alias resolved).
+    pub fn transcription_model(&self) -> String {
+        self.resolve_model(self.transcription_model.as_deref().unwrap_or("whisper-1"))
+    }
+
+    /// Tokens the model accepts per request: `context_window`, or a guess
+    /// from the model's name (128k for names it does not know).
+    pub fn context_window(&self) -> usize {
+        if let Some(tokens) = self.context_window {
+            return tokens;
+        }
+        let model = self.model().to_lowercase();
+        CONTEXT_WINDOWS
+            .iter()
+            .find(|(name, _)| model.contains(name))
+            .map_or(128_000, |(_, tokens)| *tokens)
+    }
+
+    /// The `[personas]` entry called `name`; `default` falls back to the
+    /// built-in assistant prompt.
+    pub fn persona(&self, name: &str) -> Option<Persona> {
+        match self.personas.get(name) {
+            Some(persona) => Some(persona.clone()),
+            None if name == "default" => Some(Persona { system: DEFAULT_PERSONA_PROMPT.to_string(), ..Persona::default() }),
+            None => None,
+        }
+    }
+
+    /// Names accepted by [`Config::persona`], `default` included.
+    pub fn persona_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.personas.keys().cloned().collect();
+        if !self.personas.contains_key("default") {
+            names.insert(0, "default".to_string());
+        }
+        names
+    }
+
+    /// These settings as prompt command `entry` runs with them: its
+    /// `model`, `extra_headers` and `extra_body` applied.
+    pub fn for_entry(&self, entry: &crate::prompt::PromptEntry) -> Config {
+        let mut config = self.clone();
+        if let Some(model) = &entry.model {
+            config.default_model = Some(model.clone());
+        }
+        config.extra_headers.extend(entry.extra_headers.clone());
+        config.extra_body.extend(entry.extra_body.clone());
+        if !entry.stop.is_empty() {
+            config.extra_body.insert("stop".to_string(), serde_json::json!(entry.stop));
+        }
+        config.assistant_prefix = entry.assistant_prefix.clone().filter(|prefix| !prefix.is_empty());
+        config
+    }
 }
 
 impl Default for Config {
@@ -19,12 +468,183 @@ impl Default for Config {
             base_url: "https://api.openai.com/v1".to_string(),
             api_key: "".to_string(),
             default_model: Some("gpt-4o-mini".to_string()),
-            theme: None,
+            theme: ThemeConfig::default(),
+            fallback_to_ask: false,
+            confirm_fuzzy: false,
+            no_clipboard: false,
+            hide_thinking: false,
+            default_target_lang: None,
+            secondary_target_lang: None,
+            email_signature: None,
+            store_remote: None,
+            store_sync_plaintext: false,
+            api_key_cmd: None,
+            embedding_model: None,
+            embedding_url: None,
+            transcription_model: None,
+            ocr_backend: None,
+            ocr_model: None,
+            context_window: None,
+            model_aliases: BTreeMap::new(),
+            proxy: None,
+            no_proxy: None,
+            ca_bundle: None,
+            insecure_tls: false,
+            extra_headers: BTreeMap::new(),
+            extra_body: BTreeMap::new(),
+            log_transcripts: false,
+            history: true,
+            daily_token_limit: None,
+            monthly_budget_usd: None,
+            requests_per_minute: None,
+            tokens_per_minute: None,
+            prices: BTreeMap::new(),
+            search: SearchConfig::default(),
+            redact: crate::redact::RedactConfig::default(),
+            personas: BTreeMap::new(),
+            mcp_servers: BTreeMap::new(),
+            plugins: BTreeMap::new(),
+            system_prompt: None,
+            images: Vec::new(),
+            body_overrides: BTreeMap::new(),
+            assistant_prefix: None,
+        }
+    }
+}
+
+/// Environment variables that override config.toml, with the key each one
+/// replaces. `XA_CONFIG_DIR` (see [`xa_dir`]) moves the whole directory.
+pub const ENV_OVERRIDES: &[(&str, &str)] = &[
+    ("XA_API_KEY", "api_key"),
+    ("XA_BASE_URL", "base_url"),
+    ("XA_MODEL", "model"),
+    ("XA_NO_CLIPBOARD", "no_clipboard"),
+];
+
+/// Directory holding config.toml, prompts.toml, sessions and the rest of
+/// xa's files: `$XA_CONFIG_DIR` when set, otherwise `<config dir>/xa`.
+pub fn xa_dir() -> Option<PathBuf> {
+    match std::env::var_os("XA_CONFIG_DIR") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => dirs::config_dir().map(|dir| dir.join("xa")),
+    }
+}
+
+/// Previous versions kept next to a file rewritten by [`safe_write`]:
+/// `prompts.toml.bak.1` is the newest, `.bak.3` the oldest.
+const BACKUP_GENERATIONS: usize = 3;
+
+/// How long a writer waits for another xa process to let go of a file.
+const LOCK_WAIT: Duration = Duration::from_secs(10);
+
+/// An exclusive advisory lock on one of xa's files, released on drop.
+pub struct FileLock {
+    _file: fs::File,
+}
+
+/// Lock `path` against other xa processes for a read-modify-write: take
+/// the lock, read the file afresh, change it, [`safe_write`] it, drop the
+/// lock. The lock is on a `.<name>.lock` file beside it, since `safe_write`
+/// replaces the file itself. Not reentrant: never lock the same file twice
+/// in one process.
+pub fn lock(path: &Path) -> io::Result<FileLock> {
+    lock_within(path, LOCK_WAIT)
+}
+
+fn lock_within(path: &Path, wait: Duration) -> io::Result<FileLock> {
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    fs::create_dir_all(dir)?;
+    let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let file = fs::OpenOptions::new().create(true).truncate(false).write(true).open(dir.join(format!(".{}.lock", name)))?;
+    let start = Instant::now();
+    loop {
+        match file.try_lock() {
+            Ok(()) => return Ok(FileLock { _file: file }),
+            Err(fs::TryLockError::WouldBlock) if start.elapsed() < wait => std::thread::sleep(Duration::from_millis(50)),
+            Err(fs::TryLockError::WouldBlock) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("{} is being changed by another xa process; try again", path.display()),
+                ))
+            }
+            Err(fs::TryLockError::Error(e)) => return Err(e),
         }
     }
 }
 
-use reqwest;
+/// Replace `path` with `contents` without ever leaving it half-written: the
+/// data goes to a temporary file in the same directory, is flushed to disk
+/// and renamed over the original. The version being replaced is rotated
+/// into `<file>.bak.1`. Unchanged contents are not rewritten.
+pub fn safe_write(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let contents = contents.as_ref();
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    fs::create_dir_all(dir)?;
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?
+        .to_string_lossy()
+        .into_owned();
+
+    let existing = fs::metadata(path).ok();
+    if existing.is_some() {
+        if fs::read(path)? == contents {
+            return Ok(());
+        }
+        let backup = |n: usize| dir.join(format!("{}.bak.{}", name, n));
+        for n in (1..BACKUP_GENERATIONS).rev() {
+            if backup(n).exists() {
+                fs::rename(backup(n), backup(n + 1))?;
+            }
+        }
+        fs::copy(path, backup(1))?;
+    }
+
+    let temp = dir.join(format!(".{}.tmp-{}", name, std::process::id()));
+    // Left over by a crashed process that had the same id
+    let _ = fs::remove_file(&temp);
+    let result = (|| {
+        let mut file = private_file(&temp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        if let Some(metadata) = &existing {
+            keep_stricter_permissions(&temp, metadata)?;
+        }
+        fs::rename(&temp, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// A new file at `path` that only its owner can read (these files hold API
+/// keys and secrets), refusing to reuse one that is already there.
+pub fn private_file(path: &Path) -> io::Result<fs::File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)
+}
+
+/// Give `temp`, created 0600, the permissions of the file it replaces
+/// where those are stricter still (read-only, say), never looser ones.
+fn keep_stricter_permissions(temp: &Path, original: &fs::Metadata) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = original.permissions().mode() & 0o600;
+        if mode != 0o600 {
+            fs::set_permissions(temp, fs::Permissions::from_mode(mode))?;
+        }
+    }
+    #[cfg(not(unix))]
+    if original.permissions().readonly() {
+        fs::set_permissions(temp, original.permissions())?;
+    }
+    Ok(())
+}
 
 #[derive(serde::Deserialize)]
 struct ModelsResponse {
@@ -36,13 +656,25 @@ struct ModelData {
     id: String,
 }
 
-pub async fn configure_openai() -> Result<(), Box<dyn std::error::Error>> {
+/// Values given on the command line to `xa set openai`. Each one that is
+/// present skips its question; with `yes` nothing is asked at all.
+pub struct SetupOptions {
+    pub base_url: Option<String>,
+    pub api_key: Option<String>,
+    pub model: Option<String>,
+    pub yes: bool,
+}
+
+pub async fn configure_openai(options: SetupOptions) -> Result<(), XaError> {
+    if options.yes {
+        return configure_openai_unattended(options);
+    }
+
     println!("Setting up OpenAI-compatible configuration...");
 
     // Get config directory
-    let config_dir = config_dir()
-        .ok_or("Could not determine config directory")?
-        .join("xa");
+    let config_dir = xa_dir()
+        .ok_or_else(|| XaError::Config("Could not determine config directory".into()))?;
 
     // Create config directory if it doesn't exist
     fs::create_dir_all(&config_dir)?;
@@ -63,20 +695,34 @@ pub async fn configure_openai() -> Result<(), Box<dyn std::error::Error>> {
     };
 
     // Prompt user for configuration values
-    print!("Base URL [{}]: ", config.base_url);
-    io::stdout().flush()?;
-    let mut base_url = String::new();
-    io::stdin().read_line(&mut base_url)?;
-    base_url = base_url.trim().to_string();
-    if base_url.is_empty() {
-        base_url = config.base_url;
-    }
+    let (base_url, preset_model) = match options.base_url {
+        Some(base_url) => (base_url, None),
+        None => choose_endpoint(&config.base_url)?,
+    };
+    // Model offered as the default below: the preset's suggestion when a
+    // provider was picked from the menu, otherwise the current one
+    let suggested_model = preset_model.or_else(|| config.default_model.clone());
 
-    print!("API Key: ");
-    io::stdout().flush()?;
-    let mut api_key = String::new();
-    io::stdin().read_line(&mut api_key)?;
-    api_key = api_key.trim().to_string();
+    let api_key = match options.api_key {
+        Some(api_key) => api_key,
+        None => {
+            print!("API Key: ");
+            io::stdout().flush()?;
+            let mut api_key = String::new();
+            io::stdin().read_line(&mut api_key)?;
+            api_key.trim().to_string()
+        }
+    };
+
+    if let Some(model) = options.model {
+        let new_config = Config {
+            base_url,
+            api_key,
+            default_model: Some(model).filter(|model| !model.is_empty()),
+            ..config
+        };
+        return save_setup(&config_file, &new_config);
+    }
 
     // Validate the API key and base URL by testing the models endpoint
     if !api_key.is_empty() {
@@ -94,14 +740,14 @@ pub async fn configure_openai() -> Result<(), Box<dyn std::error::Error>> {
                 println!("  {}. Custom model", models.len() + 1);
 
                 print!("Select model by number (or press Enter for default '{}'): ",
-                       config.default_model.as_deref().unwrap_or("gpt-4o-mini"));
+                       suggested_model.as_deref().unwrap_or("gpt-4o-mini"));
                 io::stdout().flush()?;
                 let mut selection = String::new();
                 io::stdin().read_line(&mut selection)?;
                 let selection = selection.trim();
 
                 let selected_model = if selection.is_empty() {
-                    config.default_model.unwrap_or_default()
+                    suggested_model.unwrap_or_default()
                 } else if let Ok(num) = selection.parse::<usize>() {
                     if num > 0 && num <= models.len() {
                         models[num - 1].clone()
@@ -113,11 +759,11 @@ pub async fn configure_openai() -> Result<(), Box<dyn std::error::Error>> {
                         custom_model.trim().to_string()
                     } else {
                         eprintln!("Invalid selection. Using default model.");
-                        config.default_model.unwrap_or_default()
+                        suggested_model.unwrap_or_default()
                     }
                 } else {
                     eprintln!("Invalid selection. Using default model.");
-                    config.default_model.unwrap_or_default()
+                    suggested_model.unwrap_or_default()
                 };
 
                 // Create new config
@@ -125,17 +771,10 @@ pub async fn configure_openai() -> Result<(), Box<dyn std::error::Error>> {
                     base_url,
                     api_key,
                     default_model: if selected_model.is_empty() { None } else { Some(selected_model) },
-                    theme: config.theme.clone(),
+                    ..config
                 };
 
-                // Serialize and write to file
-                let config_content = toml::to_string(&new_config)?;
-                fs::write(&config_file, config_content)?;
-
-                println!("Configuration saved to: {:?}", config_file);
-                println!("Setup complete! You can now use xa with your commands.");
-
-                return Ok(());
+                return save_setup(&config_file, &new_config);
             }
             Err(e) => {
                 eprintln!("⚠ Warning: Could not validate API key and base URL: {}", e);
@@ -145,13 +784,13 @@ pub async fn configure_openai() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     // If validation failed or no API key provided, ask for model directly
-    print!("Default model [{}]: ", config.default_model.as_deref().unwrap_or(""));
+    print!("Default model [{}]: ", suggested_model.as_deref().unwrap_or(""));
     io::stdout().flush()?;
     let mut default_model = String::new();
     io::stdin().read_line(&mut default_model)?;
     default_model = default_model.trim().to_string();
     if default_model.is_empty() {
-        default_model = config.default_model.unwrap_or_default();
+        default_model = suggested_model.unwrap_or_default();
     }
 
     // Create new config
@@ -159,12 +798,88 @@ pub async fn configure_openai() -> Result<(), Box<dyn std::error::Error>> {
         base_url,
         api_key,
         default_model: if default_model.is_empty() { None } else { Some(default_model) },
-        theme: config.theme.clone(),
+        ..config
     };
 
+    save_setup(&config_file, &new_config)
+}
+
+/// Provider menu for `xa set openai`: a numbered list of the built-in
+/// presets plus a custom endpoint. Returns the base URL and, for presets,
+/// their suggested model. Enter keeps `current`.
+fn choose_endpoint(current: &str) -> Result<(String, Option<String>), XaError> {
+    let presets = crate::agent::builtin_presets();
+    let width = presets.iter().map(|preset| preset.name.len()).max().unwrap_or(0);
+    println!("Choose a provider:");
+    for (i, preset) in presets.iter().enumerate() {
+        let note = preset.note.map(|note| format!("  ({})", note)).unwrap_or_default();
+        println!("  {:>2}. {:<width$}  {}{}", i + 1, preset.name, preset.base_url, note, width = width);
+    }
+    println!("  {:>2}. Custom endpoint", presets.len() + 1);
+
+    loop {
+        print!("Provider [Enter keeps {}]: ", current);
+        io::stdout().flush()?;
+        let mut selection = String::new();
+        io::stdin().read_line(&mut selection)?;
+        let selection = selection.trim();
+
+        if selection.is_empty() {
+            return Ok((current.to_string(), None));
+        }
+        let index = match selection.parse::<usize>() {
+            Ok(num) if (1..=presets.len() + 1).contains(&num) => num - 1,
+            // Accept the preset's name as well as its number
+            _ => match presets.iter().position(|preset| preset.name.eq_ignore_ascii_case(selection)) {
+                Some(index) => index,
+                None => {
+                    eprintln!("Invalid selection '{}'.", selection);
+                    continue;
+                }
+            },
+        };
+        if let Some(preset) = presets.get(index) {
+            let model = Some(preset.default_model.to_string()).filter(|model| !model.is_empty());
+            return Ok((preset.base_url.to_string(), model));
+        }
+
+        print!("Base URL [{}]: ", current);
+        io::stdout().flush()?;
+        let mut base_url = String::new();
+        io::stdin().read_line(&mut base_url)?;
+        let base_url = base_url.trim();
+        let base_url = if base_url.is_empty() { current } else { base_url };
+        return Ok((base_url.to_string(), None));
+    }
+}
+
+/// `xa set openai --yes`: no questions and no network calls. Values not
+/// given as flags come from `XA_BASE_URL` / `XA_API_KEY` / `XA_MODEL`, then
+/// from the existing config.
+fn configure_openai_unattended(options: SetupOptions) -> Result<(), XaError> {
+    let mut config = read_config_file()?;
+    let from_env = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+    let values = [
+        ("base_url", options.base_url.or_else(|| from_env("XA_BASE_URL"))),
+        ("api_key", options.api_key.or_else(|| from_env("XA_API_KEY"))),
+        ("model", options.model.or_else(|| from_env("XA_MODEL"))),
+    ];
+    for (key, value) in values {
+        if let Some(value) = value {
+            set_value(&mut config, key, &value)?;
+        }
+    }
+    if config.api_key.is_empty() && config.api_key_cmd.is_none() {
+        eprintln!("Warning: no API key set; pass --api-key or XA_API_KEY.");
+    }
+    save_setup(&config_file_path()?, &config)
+}
+
+fn save_setup(config_file: &Path, config: &Config) -> Result<(), XaError> {
     // Serialize and write to file
-    let config_content = toml::to_string(&new_config)?;
-    fs::write(&config_file, config_content)?;
+    let config_content = toml::to_string(config)?;
+    let _lock = lock(config_file)?;
+    safe_write(config_file, config_content)?;
 
     println!("Configuration saved to: {:?}", config_file);
     println!("Setup complete! You can now use xa with your commands.");
@@ -172,8 +887,8 @@ pub async fn configure_openai() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-async fn fetch_models(base_url: &str, api_key: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-    let client = reqwest::Client::new();
+async fn fetch_models(base_url: &str, api_key: &str) -> Result<Vec<String>, XaError> {
+    let client = crate::http::client().map_err(XaError::Config)?;
 
     // Adjust the URL to ensure it has the correct format
     let models_url = if base_url.ends_with("/v1") {
@@ -191,9 +906,10 @@ async fn fetch_models(base_url: &str, api_key: &str) -> Result<Vec<String>, Box<
         .send()
         .await?;
 
-    if !response.status().is_success() {
+    let status = response.status();
+    if !status.is_success() {
         let error_text = response.text().await?;
-        return Err(format!("API request failed: {}", error_text).into());
+        return Err(XaError::from_status(status, error_text));
     }
 
     let models_response: ModelsResponse = response.json().await?;
@@ -202,27 +918,584 @@ async fn fetch_models(base_url: &str, api_key: &str) -> Result<Vec<String>, Box<
     Ok(models)
 }
 
-pub async fn load_config() -> Result<Config, Box<dyn std::error::Error>> {
-    Ok(load_config_sync()?)
+/// Config for talking to the API: like [`load_config_sync`], plus the key
+/// fetched with `api_key_cmd` when none is set directly.
+pub async fn load_config() -> Result<Config, XaError> {
+    let mut config = load_config_sync()?;
+    if config.api_key.is_empty() {
+        if let Some(cmd) = config.api_key_cmd.clone() {
+            config.api_key = tokio::task::spawn_blocking(move || run_key_command(&cmd))
+                .await
+                .map_err(|e| XaError::Other(e.to_string()))??;
+        }
+    }
+    Ok(config)
+}
+
+/// Run `api_key_cmd` through the shell and return its trimmed stdout.
+fn run_key_command(cmd: &str) -> Result<String, XaError> {
+    tracing::debug!("running api_key_cmd");
+    #[cfg(windows)]
+    let output = std::process::Command::new("cmd").args(["/C", cmd]).output();
+    #[cfg(not(windows))]
+    let output = std::process::Command::new("sh").args(["-c", cmd]).output();
+
+    let output = output.map_err(|e| XaError::Config(format!("api_key_cmd `{}` could not be run: {}", cmd, e)))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(XaError::Config(format!("api_key_cmd `{}` failed ({}): {}", cmd, output.status, stderr.trim())));
+    }
+    let key = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if key.is_empty() {
+        return Err(XaError::Config(format!("api_key_cmd `{}` printed no key", cmd)));
+    }
+    Ok(key)
 }
 
 /// Synchronous config load (used for early theme resolution before the TUI).
-pub fn load_config_sync() -> Result<Config, Box<dyn std::error::Error>> {
-    let config_dir = config_dir()
-        .ok_or("Could not determine config directory")?
-        .join("xa");
+/// The project's `.xa.toml` overrides the file, and `XA_*` environment
+/// variables override both.
+pub fn load_config_sync() -> Result<Config, XaError> {
+    let mut config = read_config_file()?;
+    if let Some(project) = crate::project::load()? {
+        tracing::debug!(path = %project.path.display(), "applying project overlay");
+        project.apply(&mut config)?;
+    }
+    apply_env_overrides(&mut config, |name| std::env::var(name).ok())?;
+    Ok(config)
+}
 
-    let config_file = config_dir.join("config.toml");
+/// config.toml exactly as saved, without environment overrides.
+fn read_config_file() -> Result<Config, XaError> {
+    let config_file = config_file_path()?;
 
     if !config_file.exists() {
+        tracing::debug!(path = %config_file.display(), "no config file; using defaults");
         return Ok(Config::default());
     }
 
+    tracing::debug!(path = %config_file.display(), "reading config");
     let content = fs::read_to_string(&config_file)?;
-    Ok(toml::from_str(&content)?)
+    toml::from_str(&content)
+        .map_err(|e| XaError::Config(format!("{}: {}", config_file.display(), e)))
+}
+
+fn apply_env_overrides(
+    config: &mut Config,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<(), XaError> {
+    for (name, key) in ENV_OVERRIDES {
+        if let Some(value) = lookup(name).filter(|value| !value.is_empty()) {
+            tracing::debug!(variable = name, key, "environment override");
+            set_value(config, key, &value).map_err(|e| XaError::Config(format!("{}: {}", name, e)))?;
+        }
+    }
+    Ok(())
+}
+
+/// `[theme]` from config.toml; defaults when the file cannot be read.
+pub fn load_theme_config() -> ThemeConfig {
+    load_config_sync().map(|c| c.theme).unwrap_or_default()
+}
+
+/// Context windows by model family, the more specific names first.
+const CONTEXT_WINDOWS: &[(&str, usize)] = &[
+    ("gpt-4.1", 1_000_000),
+    ("gpt-4o", 128_000),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4", 8_192),
+    ("gpt-3.5", 16_385),
+    ("gpt-5", 400_000),
+    ("claude", 200_000),
+    ("gemini", 1_000_000),
+    ("deepseek", 128_000),
+    ("qwen", 128_000),
+    ("llama", 128_000),
+    ("mistral", 32_000),
+];
+
+/// Keys accepted by `xa config get|set`, in `xa config show` order.
+pub const CONFIG_KEYS: &[&str] = &[
+    "base_url",
+    "api_key",
+    "model",
+    "theme",
+    "fallback_to_ask",
+    "confirm_fuzzy",
+    "no_clipboard",
+    "hide_thinking",
+    "default_target_lang",
+    "secondary_target_lang",
+    "email_signature",
+    "store_remote",
+    "store_sync_plaintext",
+    "api_key_cmd",
+    "embedding_model",
+    "embedding_url",
+    "transcription_model",
+    "ocr_backend",
+    "ocr_model",
+    "context_window",
+    "proxy",
+    "no_proxy",
+    "ca_bundle",
+    "insecure_tls",
+    "log_transcripts",
+    "history",
+    "daily_token_limit",
+    "monthly_budget_usd",
+    "requests_per_minute",
+    "tokens_per_minute",
+];
+
+fn config_file_path() -> Result<PathBuf, XaError> {
+    Ok(xa_dir()
+        .ok_or_else(|| XaError::Config("Could not determine config directory".into()))?
+        .join("config.toml"))
+}
+
+/// Current value of `key`, or `None` when it is unset.
+pub fn get_value(config: &Config, key: &str) -> Result<Option<String>, XaError> {
+    Ok(match key {
+        "base_url" => Some(config.base_url.clone()),
+        "api_key" => Some(config.api_key.clone()).filter(|key| !key.is_empty()),
+        "model" | "default_model" => config.default_model.clone(),
+        "theme" => config.theme.mode.clone(),
+        "fallback_to_ask" => Some(config.fallback_to_ask.to_string()),
+        "confirm_fuzzy" => Some(config.confirm_fuzzy.to_string()),
+        "no_clipboard" => Some(config.no_clipboard.to_string()),
+        "hide_thinking" => Some(config.hide_thinking.to_string()),
+        "default_target_lang" => config.default_target_lang.clone(),
+        "secondary_target_lang" => config.secondary_target_lang.clone(),
+        "email_signature" => config.email_signature.clone(),
+        "store_remote" => config.store_remote.clone(),
+        "store_sync_plaintext" => Some(config.store_sync_plaintext.to_string()),
+        "api_key_cmd" => config.api_key_cmd.clone(),
+        "embedding_model" => config.embedding_model.clone(),
+        "embedding_url" => config.embedding_url.clone(),
+        "transcription_model" => config.transcription_model.clone(),
+        "ocr_backend" => config.ocr_backend.clone(),
+        "ocr_model" => config.ocr_model.clone(),
+        "context_window" => config.context_window.map(|tokens| tokens.to_string()),
+        "proxy" => config.proxy.clone(),
+        "no_proxy" => config.no_proxy.clone(),
+        "ca_bundle" => config.ca_bundle.clone(),
+        "insecure_tls" => Some(config.insecure_tls.to_string()),
+        "log_transcripts" => Some(config.log_transcripts.to_string()),
+        "history" => Some(config.history.to_string()),
+        "daily_token_limit" => config.daily_token_limit.map(|tokens| tokens.to_string()),
+        "monthly_budget_usd" => config.monthly_budget_usd.map(|usd| usd.to_string()),
+        "requests_per_minute" => config.requests_per_minute.map(|requests| requests.to_string()),
+        "tokens_per_minute" => config.tokens_per_minute.map(|tokens| tokens.to_string()),
+        _ => match key.strip_prefix("alias.") {
+            Some(alias) => config.model_aliases.get(alias).cloned(),
+            None => match key.strip_prefix("theme.") {
+                Some(field) => config.theme.field(field).ok_or_else(|| unknown_key(key))?.clone(),
+                None => match key.strip_prefix("search.") {
+                    Some(field) => config.search.get(field).ok_or_else(|| unknown_key(key))?,
+                    None => return Err(unknown_key(key)),
+                },
+            },
+        },
+    })
+}
+
+/// Set `key` from its command-line form. An empty value clears optional keys.
+pub fn set_value(config: &mut Config, key: &str, value: &str) -> Result<(), XaError> {
+    let optional = |value: &str| Some(value.to_string()).filter(|v| !v.is_empty());
+    match key {
+        "base_url" => {
+            if value.is_empty() {
+                return Err(XaError::Usage("base_url cannot be empty".into()));
+            }
+            config.base_url = value.trim_end_matches('/').to_string();
+        }
+        "api_key" => config.api_key = value.to_string(),
+        "model" | "default_model" => config.default_model = optional(value),
+        "theme" => {
+            if !value.is_empty() && !["auto", "dark", "light"].contains(&value) {
+                return Err(XaError::Usage(format!("Invalid theme '{}': expected auto, dark or light", value)));
+            }
+            config.theme.mode = optional(value);
+        }
+        "fallback_to_ask" => config.fallback_to_ask = parse_flag(key, value)?,
+        "confirm_fuzzy" => config.confirm_fuzzy = parse_flag(key, value)?,
+        "no_clipboard" => config.no_clipboard = parse_flag(key, value)?,
+        "hide_thinking" => config.hide_thinking = parse_flag(key, value)?,
+        "default_target_lang" => config.default_target_lang = optional(value),
+        "secondary_target_lang" => config.secondary_target_lang = optional(value),
+        "email_signature" => config.email_signature = optional(&value.replace("\\n", "\n")),
+        "store_remote" => config.store_remote = optional(value.trim()),
+        "store_sync_plaintext" => config.store_sync_plaintext = parse_flag(key, value)?,
+        "api_key_cmd" => config.api_key_cmd = optional(value),
+        "embedding_model" => config.embedding_model = optional(value),
+        "embedding_url" => config.embedding_url = optional(value.trim_end_matches('/')),
+        "transcription_model" => config.transcription_model = optional(value),
+        "ocr_backend" => {
+            if !value.is_empty() && !["vision", "tesseract"].contains(&value) {
+                return Err(XaError::Usage(format!("Invalid ocr_backend '{}': expected vision or tesseract", value)));
+            }
+            config.ocr_backend = optional(value);
+        }
+        "ocr_model" => config.ocr_model = optional(value),
+        "context_window" => {
+            config.context_window = match value {
+                "" => None,
+                _ => Some(value.parse().ok().filter(|&tokens: &usize| tokens > 0).ok_or_else(|| {
+                    XaError::Usage(format!("Invalid context_window '{}': expected a number of tokens", value))
+                })?),
+            }
+        }
+        "proxy" => config.proxy = optional(value),
+        "no_proxy" => config.no_proxy = optional(value),
+        "ca_bundle" => config.ca_bundle = optional(value),
+        "insecure_tls" => config.insecure_tls = parse_flag(key, value)?,
+        "log_transcripts" => config.log_transcripts = parse_flag(key, value)?,
+        "history" => config.history = parse_flag(key, value)?,
+        "daily_token_limit" => {
+            config.daily_token_limit = match value {
+                "" => None,
+                _ => Some(value.parse().ok().filter(|&tokens: &u64| tokens > 0).ok_or_else(|| {
+                    XaError::Usage(format!("Invalid daily_token_limit '{}': expected a number of tokens", value))
+                })?),
+            }
+        }
+        "monthly_budget_usd" => {
+            config.monthly_budget_usd = match value {
+                "" => None,
+                _ => Some(value.trim_start_matches('$').parse().ok().filter(|&usd: &f64| usd > 0.0 && usd.is_finite()).ok_or_else(|| {
+                    XaError::Usage(format!("Invalid monthly_budget_usd '{}': expected an amount in dollars", value))
+                })?),
+            }
+        }
+        "requests_per_minute" | "tokens_per_minute" => {
+            let limit = match value {
+                "" => None,
+                _ => Some(value.parse().ok().filter(|&limit: &u32| limit > 0).ok_or_else(|| {
+                    XaError::Usage(format!("Invalid {} '{}': expected a positive number", key, value))
+                })?),
+            };
+            match key {
+                "requests_per_minute" => config.requests_per_minute = limit,
+                _ => config.tokens_per_minute = limit,
+            }
+        }
+        _ => match key.strip_prefix("alias.") {
+            Some("") => return Err(XaError::Usage("alias.<name> needs a name, e.g. alias.fast".into())),
+            Some(alias) if value.is_empty() => {
+                config.model_aliases.remove(alias);
+            }
+            Some(alias) => {
+                config.model_aliases.insert(alias.to_string(), value.to_string());
+            }
+            None if key.starts_with("search.") => config.search.set(&key["search.".len()..], optional(value))?,
+            None => {
+                let field = key.strip_prefix("theme.").ok_or_else(|| unknown_key(key))?;
+                if field == "preset" && !value.is_empty() && !THEME_PRESETS.contains(&value) {
+                    return Err(XaError::Usage(format!("Invalid preset '{}': expected {}", value, THEME_PRESETS.join(", "))));
+                }
+                if field != "preset" && !value.is_empty() && crate::output::parse_color(value).is_none() {
+                    return Err(XaError::Usage(format!(
+                        "Invalid color '{}' for {}: expected a name (blue, bright_cyan), 0-255 or #rrggbb",
+                        value, key
+                    )));
+                }
+                *config.theme.field_mut(field).ok_or_else(|| unknown_key(key))? = optional(value);
+            }
+        },
+    }
+    Ok(())
+}
+
+fn on() -> bool {
+    true
+}
+
+fn is_on(flag: &bool) -> bool {
+    *flag
+}
+
+fn parse_flag(key: &str, value: &str) -> Result<bool, XaError> {
+    match value.to_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Ok(true),
+        "false" | "no" | "off" | "0" | "" => Ok(false),
+        _ => Err(XaError::Usage(format!("Invalid value '{}' for {}: expected true or false", value, key))),
+    }
+}
+
+fn unknown_key(key: &str) -> XaError {
+    XaError::Usage(format!(
+        "Unknown config key '{}'. Available keys: {}, alias.<name>, theme.<{}>, search.<{}>",
+        key,
+        CONFIG_KEYS.join(", "),
+        THEME_FIELDS.join("|"),
+        SEARCH_FIELDS.join("|")
+    ))
+}
+
+/// `sk-abc…wxyz` style preview that is safe to print.
+pub fn mask_secret(secret: &str) -> String {
+    let chars: Vec<char> = secret.chars().collect();
+    if chars.len() <= 8 {
+        return "*".repeat(chars.len());
+    }
+    let head: String = chars[..3].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}…{}", head, tail)
+}
+
+/// `xa config get <key>`: print the raw value (nothing when unset).
+pub fn print_value(key: &str) -> Result<(), XaError> {
+    if let Some(value) = get_value(&load_config_sync()?, key)? {
+        println!("{}", value);
+    }
+    Ok(())
+}
+
+/// `xa config set <key> <value>`: update one key in config.toml.
+pub fn update_value(key: &str, value: &str) -> Result<(), XaError> {
+    // Edit the file's own values so environment overrides are never persisted
+    let config_file = config_file_path()?;
+    let lock = lock(&config_file)?;
+    let mut config = read_config_file()?;
+    set_value(&mut config, key, value)?;
+
+    safe_write(&config_file, toml::to_string(&config)?)?;
+    drop(lock);
+
+    match get_value(&config, key)? {
+        Some(_) if key == "api_key" => println!("api_key = {}", mask_secret(&config.api_key)),
+        Some(value) => println!("{} = {}", key, value),
+        None => println!("{} unset", key),
+    }
+    Ok(())
+}
+
+/// `xa config show`: every key with the API key masked.
+pub fn show_config() -> Result<(), XaError> {
+    let config = load_config_sync()?;
+    println!("Config file: {:?}", config_file_path()?);
+    if let Some(project) = crate::project::load()? {
+        println!("Project overlay: {:?}", project.path);
+    }
+    let width = CONFIG_KEYS.iter().map(|key| key.len()).max().unwrap_or(0);
+    for key in CONFIG_KEYS {
+        let value = match get_value(&config, key)? {
+            Some(value) if *key == "api_key" => mask_secret(&value),
+            Some(value) => value,
+            None => "(unset)".to_string(),
+        };
+        let source = ENV_OVERRIDES
+            .iter()
+            .find(|(name, k)| k == key && std::env::var(name).is_ok_and(|v| !v.is_empty()))
+            .map(|(name, _)| format!("  (from {})", name))
+            .unwrap_or_default();
+        println!("{:<width$} = {}{}", key, value, source, width = width);
+    }
+    for (alias, model) in &config.model_aliases {
+        println!("alias.{} = {}", alias, model);
+    }
+    for field in THEME_FIELDS {
+        if let Some(Some(value)) = config.theme.field(field) {
+            println!("theme.{} = {}", field, value);
+        }
+    }
+    for field in SEARCH_FIELDS {
+        match config.search.get(field) {
+            Some(Some(value)) if *field == "api_key" => println!("search.{} = {}", field, mask_secret(&value)),
+            Some(Some(value)) => println!("search.{} = {}", field, value),
+            _ => {}
+        }
+    }
+    for (name, value) in &config.extra_headers {
+        println!("extra_headers.{} = {}", name, mask_secret(value));
+    }
+    for (field, value) in &config.extra_body {
+        println!("extra_body.{} = {}", field, value);
+    }
+    for (name, persona) in &config.personas {
+        let model = persona.model.as_deref().unwrap_or("(default model)");
+        println!("personas.{} = {}", name, model);
+    }
+    for (name, server) in &config.mcp_servers {
+        let target = match (&server.command, &server.url) {
+            (Some(command), _) => std::iter::once(command).chain(&server.args).cloned().collect::<Vec<_>>().join(" "),
+            (None, Some(url)) => url.clone(),
+            (None, None) => "(no command or url)".to_string(),
+        };
+        println!("mcp_servers.{} = {}", name, target);
+    }
+    for (name, plugin) in &config.plugins {
+        println!("plugins.{} = {}", name, std::iter::once(&plugin.command).chain(&plugin.args).cloned().collect::<Vec<_>>().join(" "));
+    }
+    Ok(())
 }
 
-/// Optional `theme` string from config.toml (`auto` / `dark` / `light`).
-pub fn load_theme_setting() -> Option<String> {
-    load_config_sync().ok().and_then(|c| c.theme)
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_and_get_round_trip() {
+        let mut config = Config::default();
+        set_value(&mut config, "model", "gpt-4o").unwrap();
+        set_value(&mut config, "confirm_fuzzy", "yes").unwrap();
+        set_value(&mut config, "base_url", "http://localhost:11434/v1/").unwrap();
+        assert_eq!(get_value(&config, "model").unwrap().as_deref(), Some("gpt-4o"));
+        assert_eq!(get_value(&config, "confirm_fuzzy").unwrap().as_deref(), Some("true"));
+        assert_eq!(config.base_url, "http://localhost:11434/v1");
+
+        set_value(&mut config, "model", "").unwrap();
+        assert_eq!(get_value(&config, "model").unwrap(), None);
+        assert!(set_value(&mut config, "theme", "blue").is_err());
+        assert!(set_value(&mut config, "fallback_to_ask", "maybe").is_err());
+        assert!(get_value(&config, "nope").is_err());
+    }
+
+    #[test]
+    fn theme_accepts_mode_string_or_table() {
+        let config: Config = toml::from_str("base_url = \"x\"\napi_key = \"\"\ntheme = \"light\"\n").unwrap();
+        assert_eq!(config.theme.mode.as_deref(), Some("light"));
+        assert!(toml::to_string(&config).unwrap().contains("theme = \"light\""));
+
+        let mut config: Config =
+            toml::from_str("base_url = \"x\"\napi_key = \"\"\n[theme]\nmode = \"dark\"\nbold = \"red\"\n").unwrap();
+        assert_eq!(config.theme.bold.as_deref(), Some("red"));
+        set_value(&mut config, "theme.headers", "#336699").unwrap();
+        assert!(set_value(&mut config, "theme.quote", "notacolor").is_err());
+        assert!(set_value(&mut config, "theme.preset", "neon").is_err());
+        let written = toml::to_string(&config).unwrap();
+        assert!(written.contains("[theme]") && written.contains("headers = \"#336699\""));
+        assert_eq!(get_value(&config, "theme.bold").unwrap().as_deref(), Some("red"));
+    }
+
+    #[test]
+    fn search_settings_are_validated() {
+        let mut config = Config::default();
+        set_value(&mut config, "search.backend", "searxng").unwrap();
+        set_value(&mut config, "search.url", "https://searx.example.org/").unwrap();
+        set_value(&mut config, "search.results", "3").unwrap();
+        assert_eq!(config.search.url.as_deref(), Some("https://searx.example.org"));
+        assert_eq!(get_value(&config, "search.results").unwrap().as_deref(), Some("3"));
+        assert!(set_value(&mut config, "search.backend", "altavista").is_err());
+        assert!(set_value(&mut config, "search.results", "0").is_err());
+        assert!(get_value(&config, "search.nope").is_err());
+        assert!(toml::to_string(&config).unwrap().contains("[search]"));
+    }
+
+    #[test]
+    fn personas_fall_back_to_the_builtin_default() {
+        let config: Config = toml::from_str(
+            "base_url = \"x\"\napi_key = \"\"\n[personas.reviewer]\nsystem = \"Review code.\"\nmodel = \"fast\"\ntemperature = 0.2\n",
+        )
+        .unwrap();
+        let reviewer = config.persona("reviewer").unwrap();
+        assert_eq!((reviewer.model.as_deref(), reviewer.temperature), (Some("fast"), Some(0.2)));
+        assert_eq!(config.persona("default").unwrap().system, DEFAULT_PERSONA_PROMPT);
+        assert!(config.persona("nope").is_none());
+        assert_eq!(config.persona_names(), ["default", "reviewer"]);
+        assert!(toml::to_string(&config).unwrap().contains("[personas.reviewer]"));
+    }
+
+    #[test]
+    fn safe_write_replaces_file_and_rotates_backups() {
+        let dir = std::env::temp_dir().join(format!("xa-safe-write-{}", std::process::id()));
+        let path = dir.join("prompts.toml");
+        for version in ["one", "two", "three", "four", "five"] {
+            safe_write(&path, version).unwrap();
+        }
+        safe_write(&path, "five").unwrap();
+        let read = |name: &str| fs::read_to_string(dir.join(name)).ok();
+        let files = fs::read_dir(&dir).unwrap().count();
+        let (current, newest, oldest) = (read("prompts.toml"), read("prompts.toml.bak.1"), read("prompts.toml.bak.3"));
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(current.as_deref(), Some("five"));
+        assert_eq!(newest.as_deref(), Some("four"));
+        assert_eq!(oldest.as_deref(), Some("two"));
+        // Current file plus three backups; no temp file left behind.
+        assert_eq!(files, 4);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn safe_write_never_leaves_a_file_readable_by_others() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("xa-safe-write-mode-{}", std::process::id()));
+        let mode = |name: &str| fs::metadata(dir.join(name)).unwrap().permissions().mode() & 0o777;
+        let (new, loose, strict) = (dir.join("stores.toml"), dir.join("config.toml"), dir.join("prompts.toml"));
+        safe_write(&new, "secret").unwrap();
+        safe_write(&loose, "one").unwrap();
+        fs::set_permissions(&loose, fs::Permissions::from_mode(0o644)).unwrap();
+        safe_write(&loose, "two").unwrap();
+        safe_write(&strict, "one").unwrap();
+        fs::set_permissions(&strict, fs::Permissions::from_mode(0o400)).unwrap();
+        safe_write(&strict, "two").unwrap();
+        let modes = (mode("stores.toml"), mode("config.toml"), mode("prompts.toml"));
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(modes, (0o600, 0o600, 0o400));
+    }
+
+    #[test]
+    fn lock_excludes_other_holders_until_dropped() {
+        let dir = std::env::temp_dir().join(format!("xa-lock-{}", std::process::id()));
+        let path = dir.join("stores.toml");
+        let held = lock(&path).unwrap();
+        let busy = lock_within(&path, Duration::ZERO);
+        drop(held);
+        let free = lock_within(&path, Duration::ZERO);
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(busy.err().map(|e| e.kind()), Some(io::ErrorKind::TimedOut));
+        assert!(free.is_ok());
+    }
+
+    #[test]
+    fn env_overrides_replace_file_values() {
+        let mut config = Config::default();
+        let env = |name: &str| match name {
+            "XA_MODEL" => Some("llama3".to_string()),
+            "XA_NO_CLIPBOARD" => Some("1".to_string()),
+            "XA_API_KEY" => Some(String::new()),
+            _ => None,
+        };
+        apply_env_overrides(&mut config, env).unwrap();
+        assert_eq!(config.default_model.as_deref(), Some("llama3"));
+        assert!(config.no_clipboard);
+        assert_eq!(config.api_key, "");
+
+        let bad = |name: &str| (name == "XA_NO_CLIPBOARD").then(|| "sometimes".to_string());
+        let error = apply_env_overrides(&mut config, bad).unwrap_err().to_string();
+        assert!(error.starts_with("XA_NO_CLIPBOARD:"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn api_key_cmd_output_is_trimmed() {
+        assert_eq!(run_key_command("echo '  sk-from-cmd  '").unwrap(), "sk-from-cmd");
+        assert!(run_key_command("exit 3").unwrap_err().to_string().contains("failed"));
+        assert!(run_key_command("true").unwrap_err().to_string().contains("no key"));
+    }
+
+    #[test]
+    fn model_aliases_resolve_one_level() {
+        let mut config = Config::default();
+        set_value(&mut config, "alias.fast", "gpt-4o-mini").unwrap();
+        set_value(&mut config, "alias.smart", "o3").unwrap();
+        set_value(&mut config, "model", "smart").unwrap();
+        assert_eq!(config.model(), "o3");
+        assert_eq!(config.resolve_model("fast"), "gpt-4o-mini");
+        assert_eq!(config.resolve_model("qwen2.5:14b"), "qwen2.5:14b");
+
+        set_value(&mut config, "alias.smart", "").unwrap();
+        assert_eq!(config.model(), "smart");
+        assert!(set_value(&mut config, "alias.", "x").is_err());
+    }
+
+    #[test]
+    fn masks_api_keys() {
+        assert_eq!(mask_secret("sk-1234567890abcd"), "sk-…abcd");
+        assert_eq!(mask_secret("short"), "*****");
+    }
+}
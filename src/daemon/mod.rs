@@ -0,0 +1,198 @@
+//! `xa daemon --on-copy <command>`: watch the clipboard and run a command
+//! on each newly copied text, putting the result back on the clipboard or
+//! showing it as a desktop notification.
+//!
+//! The clipboard is polled, since no portable change notification exists.
+//! Text already there at startup is left alone, and so is the daemon's own
+//! result when it lands on the clipboard. Copies over the size limit,
+//! matching none of the `--only` patterns, or holding what `--debug` would
+//! mask (a `[redact]` pattern or one of the store's secrets: a password
+//! copied from a password manager, say) are skipped without a request.
+//! Edits to the command in prompts.toml apply from the next copy.
+
+use crate::config::Config;
+use crate::error::XaError;
+use crate::prompt::PromptEntry;
+use crate::redact::Redactor;
+use chrono::Local;
+use regex::Regex;
+use std::time::{Duration, SystemTime};
+
+/// How often the clipboard is read.
+const POLL: Duration = Duration::from_millis(700);
+
+/// What the daemon runs and which copies it acts on.
+pub struct DaemonOptions<'a> {
+    pub command: &'a str,
+    pub entry: &'a PromptEntry,
+    pub args: &'a [String],
+    /// Copies longer than this (in characters) are skipped.
+    pub max_chars: usize,
+    /// When not empty, only copies matching one of these are handled.
+    pub only: Vec<Regex>,
+    /// Show the result as a notification instead of copying it.
+    pub notify: bool,
+}
+
+/// Compile the `--only` patterns.
+pub fn allowlist(patterns: &[String]) -> Result<Vec<Regex>, XaError> {
+    patterns
+        .iter()
+        .map(|pattern| Regex::new(pattern).map_err(|e| XaError::Usage(format!("invalid --only pattern '{}': {}", pattern, e))))
+        .collect()
+}
+
+/// Why a copy is not handled, or `None` when it is.
+fn skip_reason(text: &str, max_chars: usize, only: &[Regex], secrets: &Redactor) -> Option<String> {
+    if text.trim().is_empty() {
+        return Some("empty".into());
+    }
+    let chars = text.chars().count();
+    if chars > max_chars {
+        return Some(format!("{} characters, over the {} limit", chars, max_chars));
+    }
+    if !only.is_empty() && !only.iter().any(|re| re.is_match(text)) {
+        return Some("not matched by --only".into());
+    }
+    if let Some(rule) = secrets.detect(text) {
+        return Some(format!("looks like a secret ({})", rule));
+    }
+    None
+}
+
+/// Poll the clipboard until interrupted, handling each new copy in turn.
+pub async fn run(config: &Config, options: DaemonOptions<'_>) -> Result<(), XaError> {
+    let read = || crate::utils::read_clipboard().map(|text| text.trim_end_matches(['\n', '\r']).to_string());
+    // Fail at once when there is no clipboard tool, rather than poll in silence
+    let mut last = read()?;
+    let mut unreadable = false;
+    let mut entry = options.entry.clone();
+    let mut modified = prompts_modified();
+    loop {
+        tokio::time::sleep(POLL).await;
+        let text = match read() {
+            Ok(text) => {
+                unreadable = false;
+                text
+            }
+            Err(e) => {
+                // Reported once per outage, not on every poll
+                if !std::mem::replace(&mut unreadable, true) {
+                    eprintln!("[{}] cannot read the clipboard: {}", Local::now().format("%H:%M:%S"), e);
+                }
+                continue;
+            }
+        };
+        if text == last {
+            continue;
+        }
+        last = text.clone();
+        let stamp = Local::now().format("%H:%M:%S");
+        // The store's secrets as they are now
+        let secrets = Redactor::for_debug(&config.redact, crate::store::secret_values())?;
+        if let Some(reason) = skip_reason(&text, options.max_chars, &options.only, &secrets) {
+            tracing::debug!(reason = %reason, "skipping copied text");
+            if reason.starts_with("looks like a secret") {
+                eprintln!("[{}] skipped a copy that {}", stamp, reason);
+            }
+            continue;
+        }
+        if prompts_modified() != modified {
+            modified = prompts_modified();
+            match reload(options.command).await {
+                Ok(reloaded) => entry = reloaded,
+                Err(e) => eprintln!("[{}] keeping the previous {}: {}", stamp, options.command, e),
+            }
+        }
+        eprintln!("[{}] copied {} characters; running {}", stamp, text.chars().count(), options.command);
+        let result = match run_command(config, &entry, options.args, &text).await {
+            Ok(result) => result,
+            Err(e) => {
+                eprintln!("[{}] {} failed: {}", stamp, options.command, e);
+                continue;
+            }
+        };
+        println!("{}\n", result);
+        if options.notify {
+            if let Err(e) = notify(&format!("xa {}", options.command), &result) {
+                eprintln!("[{}] cannot show a notification: {}", stamp, e);
+            }
+        } else {
+            match crate::utils::copy_to_clipboard(&result) {
+                // Our own result must not count as a new copy
+                Ok(()) => last = result.trim_end_matches(['\n', '\r']).to_string(),
+                Err(e) => eprintln!("[{}] cannot copy the result: {}", stamp, e),
+            }
+        }
+    }
+}
+
+async fn run_command(config: &Config, entry: &PromptEntry, args: &[String], text: &str) -> Result<String, XaError> {
+    let input = entry.prepare_input(text).await?;
+    let prompt = entry.fill_prompt(config, &input, args).await?;
+    let done = crate::llm::complete(config, &prompt).await?;
+    entry.finish_output(&done.text).await
+}
+
+/// When prompts.toml was last changed, by this or another xa process.
+fn prompts_modified() -> Option<SystemTime> {
+    let path = crate::config::xa_dir()?.join("prompts.toml");
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// `command` as prompts.toml now has it.
+async fn reload(command: &str) -> Result<PromptEntry, XaError> {
+    let prompts = crate::prompt::load_prompt_config().await?.prompts;
+    match prompts.get(command) {
+        Some(entry) if entry.pipeline.is_none() => Ok(entry.clone()),
+        Some(_) => Err(XaError::Usage(format!("'{}' is now a pipeline", command))),
+        None => Err(XaError::CommandNotFound(command.to_string())),
+    }
+}
+
+/// A desktop notification through the platform's own tool.
+fn notify(title: &str, body: &str) -> Result<(), XaError> {
+    use std::process::Command;
+
+    #[cfg(target_os = "macos")]
+    let status = {
+        let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+        let script = format!("display notification {} with title {}", quote(body), quote(title));
+        Command::new("osascript").args(["-e", &script]).status()
+    };
+    #[cfg(all(unix, not(target_os = "macos")))]
+    let status = Command::new("notify-send").args(["--", title, body]).status();
+    #[cfg(windows)]
+    let status = Command::new("msg").args(["*", &format!("{}: {}", title, body)]).status();
+
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(XaError::Other(format!("notifier exited with {}", status))),
+        Err(e) => Err(XaError::Other(format!("no notifier available ({})", e))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_copies_outside_the_limits() {
+        let only = allowlist(&["[\\p{Han}]".to_string()]).unwrap();
+        let none = Redactor::default();
+        assert_eq!(skip_reason("你好，世界", 100, &only, &none), None);
+        assert_eq!(skip_reason("hello", 100, &[], &none), None);
+        assert_eq!(skip_reason("hello", 100, &only, &none).as_deref(), Some("not matched by --only"));
+        assert_eq!(skip_reason("  \n", 100, &[], &none).as_deref(), Some("empty"));
+        assert_eq!(skip_reason("你好，世界", 4, &only, &none).as_deref(), Some("5 characters, over the 4 limit"));
+        assert!(allowlist(&["(".to_string()]).is_err());
+    }
+
+    #[test]
+    fn skips_copies_holding_secrets() {
+        let secrets = Redactor::for_debug(&Default::default(), vec!["hunter2-correct-horse".into()]).unwrap();
+        assert_eq!(skip_reason("Bonjour tout le monde", 100, &[], &secrets), None);
+        assert_eq!(skip_reason("hunter2-correct-horse", 100, &[], &secrets).as_deref(), Some("looks like a secret (STORED_SECRET)"));
+        assert_eq!(skip_reason("sk-abcdefghijklmnopqrstuvwx", 100, &[], &secrets).as_deref(), Some("looks like a secret (API_KEY)"));
+    }
+}
@@ -0,0 +1,164 @@
+//! `xa data "<instruction>"`: structured data transformed by the model,
+//! e.g. filtered, reshaped or converted between JSON, YAML, TOML and CSV.
+//! The answer must parse in the target format; when it does not, the
+//! request is repeated with the parse error, up to `--retries` times.
+
+use crate::config::Config;
+use crate::error::XaError;
+use crate::output::paint;
+use std::path::Path;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, clap::ValueEnum)]
+pub enum DataFormat {
+    Json,
+    Yaml,
+    Toml,
+    Csv,
+}
+
+impl DataFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            DataFormat::Json => "JSON",
+            DataFormat::Yaml => "YAML",
+            DataFormat::Toml => "TOML",
+            DataFormat::Csv => "CSV",
+        }
+    }
+
+    /// By extension: `.json`, `.yaml`/`.yml`, `.toml`, `.csv`.
+    pub fn from_path(path: &Path) -> Option<DataFormat> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "json" => Some(DataFormat::Json),
+            "yaml" | "yml" => Some(DataFormat::Yaml),
+            "toml" => Some(DataFormat::Toml),
+            "csv" => Some(DataFormat::Csv),
+            _ => None,
+        }
+    }
+
+    /// The first format `text` is valid in, for input without an extension.
+    pub fn guess(text: &str) -> Option<DataFormat> {
+        [DataFormat::Json, DataFormat::Yaml, DataFormat::Toml, DataFormat::Csv]
+            .into_iter()
+            .find(|format| format.check(text).is_ok())
+    }
+
+    /// Why `text` is not a document in this format, if it is not. YAML must
+    /// be a mapping or a list (any line of prose is a valid YAML string),
+    /// and CSV needs a header with at least two columns and rows that match
+    /// it.
+    pub fn check(self, text: &str) -> Result<(), String> {
+        if text.trim().is_empty() {
+            return Err("the answer is empty".into());
+        }
+        match self {
+            DataFormat::Json => serde_json::from_str::<serde_json::Value>(text).map(drop).map_err(|e| e.to_string()),
+            DataFormat::Yaml => match serde_yaml::from_str::<serde_yaml::Value>(text) {
+                Ok(serde_yaml::Value::Mapping(_) | serde_yaml::Value::Sequence(_)) => Ok(()),
+                Ok(_) => Err("not a YAML mapping or list".into()),
+                Err(e) => Err(e.to_string()),
+            },
+            DataFormat::Toml => text.parse::<toml::Table>().map(drop).map_err(|e| e.to_string()),
+            DataFormat::Csv => {
+                let mut reader = csv::Reader::from_reader(text.as_bytes());
+                let columns = reader.headers().map_err(|e| e.to_string())?.len();
+                if columns < 2 {
+                    return Err("the header has fewer than two columns".into());
+                }
+                for record in reader.records() {
+                    record.map_err(|e| e.to_string())?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn prompt(instruction: &str, data: &str, from: Option<DataFormat>, to: DataFormat) -> String {
+    let source = from.map_or("data".to_string(), |format| format!("{} data", format.name()));
+    format!(
+        "Transform the {} below as instructed, and reply with the result as {} only: no explanation, no code fences. \
+         Keep every value exactly as it is unless the instruction says otherwise.\n\n\
+         Instruction: {}\n\n{}",
+        source,
+        to.name(),
+        instruction,
+        data
+    )
+}
+
+/// The answer without a code fence the model put around it anyway.
+fn unwrap(answer: &str) -> String {
+    let fenced = answer.trim_start().starts_with("```");
+    match crate::postprocess::extract_code_block(answer).filter(|_| fenced) {
+        Some(code) => code,
+        None => answer.trim().to_string(),
+    }
+}
+
+/// `data` transformed by `instruction` into `to`, checked to parse.
+pub async fn transform(
+    config: &Config,
+    instruction: &str,
+    data: &str,
+    from: Option<DataFormat>,
+    to: DataFormat,
+    retries: u32,
+) -> Result<String, XaError> {
+    let request = prompt(instruction, data, from, to);
+    let mut prompt = request.clone();
+    for attempt in 0..=retries {
+        let spinner = crate::spinner::Spinner::start(if attempt == 0 { "Transforming" } else { "Retrying" });
+        let answer = crate::llm::complete(config, &prompt).await;
+        spinner.stop();
+        let result = unwrap(&answer?.text);
+        let error = match to.check(&result) {
+            Ok(()) => return Ok(result),
+            Err(error) => error,
+        };
+        if attempt == retries {
+            let attempts = if retries == 0 { "1 attempt".to_string() } else { format!("{} attempts", retries + 1) };
+            return Err(XaError::Other(format!("the answer is not valid {} after {}: {}", to.name(), attempts, error)));
+        }
+        eprintln!("{}", paint("33", &format!("The answer is not valid {} ({}); asking again.", to.name(), error)));
+        prompt = format!(
+            "{}\n\nYour previous answer was not valid {}: {}\n\nPrevious answer:\n{}\n\nReply again with the corrected {} only.",
+            request,
+            to.name(),
+            error,
+            result,
+            to.name()
+        );
+    }
+    unreachable!("the last attempt returns")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_and_guesses_formats() {
+        assert!(DataFormat::Json.check("{\"a\": [1, 2]}").is_ok());
+        assert!(DataFormat::Json.check("{\"a\": [1, 2}").is_err());
+        assert!(DataFormat::Yaml.check("users:\n  - name: Ann\n    age: 31\n").is_ok());
+        assert_eq!(DataFormat::Yaml.check("Sure, here you go").unwrap_err(), "not a YAML mapping or list");
+        assert!(DataFormat::Yaml.check("a: [1, 2").is_err());
+        assert!(DataFormat::Toml.check("[server]\nport = 80\n").is_ok());
+        assert!(DataFormat::Csv.check("name,age\nAnn,31\n\"Lee, Bo\",40\n").is_ok());
+        assert!(DataFormat::Csv.check("name,age\nAnn,31,extra\n").is_err());
+        assert!(DataFormat::Csv.check("just one column\n").is_err());
+        assert!(DataFormat::Csv.check("  \n").is_err());
+
+        assert_eq!(DataFormat::guess("[1, 2]"), Some(DataFormat::Json));
+        assert_eq!(DataFormat::guess("a: 1\nb: 2"), Some(DataFormat::Yaml));
+        assert_eq!(DataFormat::guess("a = 1"), Some(DataFormat::Toml));
+        assert_eq!(DataFormat::guess("a,b\n1,2"), Some(DataFormat::Csv));
+        assert_eq!(DataFormat::guess("hello"), None);
+        assert_eq!(DataFormat::from_path(Path::new("users.YML")), Some(DataFormat::Yaml));
+
+        assert_eq!(unwrap("```yaml\na: 1\n```"), "a: 1");
+        assert_eq!(unwrap("  a,b\n1,2\n"), "a,b\n1,2");
+    }
+}
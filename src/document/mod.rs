@@ -0,0 +1,406 @@
+//! Structure-preserving file transformation, used by `xa translate-file`.
+//!
+//! A Markdown, HTML or plain-text file is cut into segments: prose to be
+//! transformed, and everything else kept byte for byte (front matter, code
+//! blocks, comments, markup, list and heading markers, whitespace). Inside
+//! prose, inline code, link targets, tags and URLs are swapped for `⟦n⟧`
+//! placeholders before it is sent and put back afterwards. The prose goes
+//! out in numbered batches through any prompt command's template, so
+//! `translate` is one use among others.
+
+use crate::chunk::estimate_tokens;
+use crate::config::Config;
+use crate::error::XaError;
+use crate::prompt::{process_template_with_args, PromptEntry};
+use regex::Regex;
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// Prose per request; smaller batches keep the markers intact.
+const BATCH_TOKENS: usize = 1_500;
+
+/// Appended to the filled template of a batch.
+const BATCH_RULES: &str = "The text is split into segments, each starting with a marker line such as <<1>>. \
+Reply with every marker, unchanged and on its own line, each followed by its transformed segment, and nothing else. \
+Keep placeholders like ⟦0⟧ exactly as they are, and keep Markdown syntax.";
+
+/// Appended when a single segment is sent on its own.
+const SEGMENT_RULES: &str = "Keep placeholders like ⟦0⟧ exactly as they are. Reply with the result only.";
+
+static INLINE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"`[^`\n]+`|\]\([^)\s]*(?:\s+"[^"\n]*")?\)|\]\[[^\]\n]*\]|<[^<>\n]+>|https?://[^\s)>\]]+"#).expect("valid regex")
+});
+static BLOCK_MARKER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[ \t]*(?:#{1,6}[ \t]+|[-*+][ \t]+(?:\[[ xX]\][ \t]+)?|\d{1,9}[.)][ \t]+|>[ \t]?)+").expect("valid regex"));
+static MARKER_LINE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?m)^[ \t]*<<(\d+)>>[ \t]*$").expect("valid regex"));
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    Markdown,
+    Html,
+    Text,
+}
+
+impl Format {
+    /// The format of `path`, by extension.
+    pub fn of(path: &Path) -> Format {
+        match path.extension().and_then(|ext| ext.to_str()).map(str::to_lowercase).as_deref() {
+            Some("md" | "markdown" | "mdx") => Format::Markdown,
+            Some("html" | "htm" | "xhtml") => Format::Html,
+            _ => Format::Text,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Segment {
+    pub text: String,
+    /// Prose to transform; other segments are kept as they are.
+    pub prose: bool,
+}
+
+/// `text` as segments that join back into it exactly.
+pub fn segments(text: &str, format: Format) -> Vec<Segment> {
+    let mut out = Segments::default();
+    match format {
+        Format::Markdown => markdown(text, &mut out),
+        Format::Html => html(text, &mut out),
+        Format::Text => {
+            for line in text.split_inclusive('\n') {
+                if line.trim().is_empty() {
+                    out.flush_block();
+                    out.keep(line);
+                } else {
+                    out.block.push_str(line);
+                }
+            }
+            out.flush_block();
+        }
+    }
+    out.flush_block();
+    out.list
+}
+
+/// Run `entry` with `args` over the prose of `segments` and join the
+/// result. Returns the text and how many segments were kept unchanged
+/// because the answer lost their placeholders.
+pub async fn transform(config: &Config, entry: &PromptEntry, args: &[String], segments: &[Segment]) -> Result<(String, usize), XaError> {
+    let (texts, kept) = transform_each(config, entry, args, segments).await?;
+    Ok((texts.concat(), kept))
+}
+
+/// [`transform`] over the file at `path`, cut into segments by its
+/// extension; `cmd` names the command for the error when it has no prose.
+pub async fn transform_file(config: &Config, entry: &PromptEntry, args: &[String], path: &Path, cmd: &str) -> Result<(String, usize), XaError> {
+    let text = std::fs::read_to_string(path).map_err(|e| XaError::Usage(format!("cannot read {}: {}", path.display(), e)))?;
+    let segments = segments(&text, Format::of(path));
+    if !segments.iter().any(|segment| segment.prose) {
+        return Err(XaError::Usage(format!("{} has no text to {}.", path.display(), cmd)));
+    }
+    transform(config, entry, args, &segments).await
+}
+
+/// [`transform`], with the result of each segment on its own: for callers
+/// such as `xa subs` that put the file back together themselves.
+pub async fn transform_each(config: &Config, entry: &PromptEntry, args: &[String], segments: &[Segment]) -> Result<(Vec<String>, usize), XaError> {
+    let prose: Vec<usize> = (0..segments.len()).filter(|&i| segments[i].prose).collect();
+    let mut results: Vec<Option<String>> = vec![None; segments.len()];
+    let batches = batches(&prose, segments);
+    let mut kept = 0;
+    for (number, batch) in batches.iter().enumerate() {
+        let protected: Vec<(String, Vec<String>)> = batch.iter().map(|&i| protect(&segments[i].text)).collect();
+        let spinner = crate::spinner::Spinner::start(&format!("Part {}/{}", number + 1, batches.len()));
+        let answers = run_batch(config, entry, args, &protected).await;
+        spinner.stop();
+        let answers = answers?;
+        for (position, (&index, (protected_text, saved))) in batch.iter().zip(&protected).enumerate() {
+            let answer = match &answers {
+                Some(answers) => answers[position].clone(),
+                // The markers did not come back: one segment at a time
+                None => run_one(config, entry, args, protected_text).await?,
+            };
+            match restore(&answer, saved) {
+                Some(text) => results[index] = Some(text),
+                None => kept += 1,
+            }
+        }
+    }
+    let texts = segments
+        .iter()
+        .zip(results)
+        .map(|(segment, result)| result.unwrap_or_else(|| segment.text.clone()))
+        .collect();
+    Ok((texts, kept))
+}
+
+/// Prose segment indices grouped into batches of about `BATCH_TOKENS`.
+fn batches(prose: &[usize], segments: &[Segment]) -> Vec<Vec<usize>> {
+    let mut batches: Vec<Vec<usize>> = Vec::new();
+    let mut size = 0;
+    for &index in prose {
+        let tokens = estimate_tokens(&segments[index].text);
+        match batches.last_mut() {
+            Some(batch) if size + tokens <= BATCH_TOKENS => batch.push(index),
+            _ => {
+                batches.push(vec![index]);
+                size = 0;
+            }
+        }
+        size += tokens;
+    }
+    batches
+}
+
+/// The batch's answers in order, or `None` when the markers did not come
+/// back as sent.
+async fn run_batch(config: &Config, entry: &PromptEntry, args: &[String], batch: &[(String, Vec<String>)]) -> Result<Option<Vec<String>>, XaError> {
+    if batch.len() == 1 {
+        return Ok(Some(vec![run_one(config, entry, args, &batch[0].0).await?]));
+    }
+    let marked: String = batch.iter().enumerate().map(|(i, (text, _))| format!("<<{}>>\n{}\n", i + 1, text)).collect();
+    let prompt = format!("{}\n\n{}", process_template_with_args(&entry.template, &marked, args, entry.args.as_ref()), BATCH_RULES);
+    let done = crate::llm::complete(config, &prompt).await?;
+    Ok(split_answer(&done.text, batch.len()))
+}
+
+async fn run_one(config: &Config, entry: &PromptEntry, args: &[String], text: &str) -> Result<String, XaError> {
+    let prompt = format!("{}\n\n{}", process_template_with_args(&entry.template, text, args, entry.args.as_ref()), SEGMENT_RULES);
+    let done = crate::llm::complete(config, &prompt).await?;
+    Ok(done.text.trim().to_string())
+}
+
+/// The `count` segments of a batch answer, by marker.
+fn split_answer(answer: &str, count: usize) -> Option<Vec<String>> {
+    let markers: Vec<_> = MARKER_LINE.captures_iter(answer).collect();
+    let numbers: Vec<usize> = markers.iter().filter_map(|m| m[1].parse().ok()).collect();
+    if numbers != (1..=count).collect::<Vec<_>>() {
+        return None;
+    }
+    let bounds: Vec<(usize, usize)> = markers.iter().map(|m| {
+        let whole = m.get(0).expect("group 0");
+        (whole.start(), whole.end())
+    }).collect();
+    Some(
+        bounds
+            .iter()
+            .enumerate()
+            .map(|(i, &(_, end))| {
+                let next = bounds.get(i + 1).map_or(answer.len(), |&(start, _)| start);
+                answer[end..next].trim().to_string()
+            })
+            .collect(),
+    )
+}
+
+/// `text` with inline code, link targets, tags and URLs replaced by
+/// numbered placeholders, and what they replaced.
+fn protect(text: &str) -> (String, Vec<String>) {
+    let mut saved = Vec::new();
+    let protected = INLINE
+        .replace_all(text, |caps: &regex::Captures| {
+            saved.push(caps[0].to_string());
+            format!("⟦{}⟧", saved.len() - 1)
+        })
+        .into_owned();
+    (protected, saved)
+}
+
+/// `text` with the placeholders put back, or `None` when one is missing.
+fn restore(text: &str, saved: &[String]) -> Option<String> {
+    let mut text = text.to_string();
+    for (i, original) in saved.iter().enumerate() {
+        let placeholder = format!("⟦{}⟧", i);
+        if !text.contains(&placeholder) {
+            return None;
+        }
+        text = text.replacen(&placeholder, original, 1);
+    }
+    Some(text)
+}
+
+#[derive(Default)]
+struct Segments {
+    list: Vec<Segment>,
+    /// Paragraph lines gathered before they become one prose segment.
+    block: String,
+}
+
+impl Segments {
+    fn keep(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        match self.list.last_mut() {
+            Some(last) if !last.prose => last.text.push_str(text),
+            _ => self.list.push(Segment { text: text.to_string(), prose: false }),
+        }
+    }
+
+    /// Prose, with the whitespace around it kept out.
+    fn prose(&mut self, text: &str) {
+        let core = text.trim();
+        if core.is_empty() {
+            self.keep(text);
+            return;
+        }
+        let start = text.len() - text.trim_start().len();
+        self.keep(&text[..start]);
+        self.list.push(Segment { text: core.to_string(), prose: true });
+        self.keep(&text[start + core.len()..]);
+    }
+
+    fn flush_block(&mut self) {
+        let block = std::mem::take(&mut self.block);
+        self.prose(&block);
+    }
+}
+
+fn markdown(text: &str, out: &mut Segments) {
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let mut i = 0;
+    // Front matter: YAML between `---` lines or TOML between `+++` lines
+    if let Some(delimiter) = lines.first().map(|line| line.trim_end()).filter(|line| *line == "---" || *line == "+++") {
+        if let Some(end) = lines[1..].iter().position(|line| line.trim_end() == delimiter) {
+            lines[..end + 2].iter().for_each(|line| out.keep(line));
+            i = end + 2;
+        }
+    }
+    while i < lines.len() {
+        let line = lines[i];
+        let trimmed = line.trim();
+        if let Some(fence) = fence(trimmed) {
+            out.flush_block();
+            out.keep(line);
+            i += 1;
+            while i < lines.len() {
+                out.keep(lines[i]);
+                i += 1;
+                let closing = lines[i - 1].trim();
+                if closing.starts_with(fence) && closing.chars().all(|c| fence.starts_with(c)) {
+                    break;
+                }
+            }
+            continue;
+        }
+        if trimmed.starts_with("<!--") {
+            out.flush_block();
+            while i < lines.len() {
+                out.keep(lines[i]);
+                i += 1;
+                if lines[i - 1].contains("-->") {
+                    break;
+                }
+            }
+            continue;
+        }
+        i += 1;
+        if trimmed.is_empty() || is_reference_definition(trimmed) || is_rule(trimmed) || is_table_divider(trimmed) {
+            out.flush_block();
+            out.keep(line);
+        } else if trimmed.starts_with('|') {
+            out.flush_block();
+            table_row(line, out);
+        } else if let Some(marker) = BLOCK_MARKER.find(line) {
+            // Headings, list items and quotes: the marker stays, the rest is one segment
+            out.flush_block();
+            out.keep(marker.as_str());
+            out.prose(&line[marker.end()..]);
+        } else {
+            out.block.push_str(line);
+        }
+    }
+}
+
+/// The opening fence of a fenced code block: three or more backticks or
+/// tildes.
+fn fence(line: &str) -> Option<&str> {
+    let c = line.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let run = line.len() - line.trim_start_matches(c).len();
+    (run >= 3).then(|| &line[..run])
+}
+
+fn is_reference_definition(line: &str) -> bool {
+    line.starts_with('[') && line.contains("]:")
+}
+
+fn is_rule(line: &str) -> bool {
+    let marks: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+    marks.len() >= 3 && ["-", "*", "_"].iter().any(|mark| marks.chars().all(|c| mark.starts_with(c)))
+}
+
+fn is_table_divider(line: &str) -> bool {
+    line.contains('-') && line.chars().all(|c| matches!(c, '|' | '-' | ':' | ' ' | '\t'))
+}
+
+/// A table row: pipes kept, each cell one segment.
+fn table_row(line: &str, out: &mut Segments) {
+    let mut rest = line;
+    while let Some(pipe) = rest.find('|') {
+        out.prose(&rest[..pipe]);
+        out.keep("|");
+        rest = &rest[pipe + 1..];
+    }
+    out.prose(rest);
+}
+
+/// Elements whose content is never prose.
+const RAW_ELEMENTS: &[&str] = &["script", "style", "pre", "code", "textarea"];
+
+fn html(text: &str, out: &mut Segments) {
+    let mut i = 0;
+    while i < text.len() {
+        let rest = &text[i..];
+        if rest.starts_with("<!--") {
+            let end = rest.find("-->").map_or(rest.len(), |at| at + 3);
+            out.keep(&rest[..end]);
+            i += end;
+        } else if rest.starts_with('<') {
+            let end = rest.find('>').map_or(rest.len(), |at| at + 1);
+            let tag = &rest[..end];
+            out.keep(tag);
+            i += end;
+            let name: String = tag[1..].chars().take_while(|c| c.is_ascii_alphanumeric()).collect::<String>().to_lowercase();
+            if RAW_ELEMENTS.contains(&name.as_str()) {
+                // Everything up to the closing tag, which the next turn keeps
+                let closing = format!("</{}", name);
+                let content = text[i..].to_lowercase().find(&closing).unwrap_or(text.len() - i);
+                out.keep(&text[i..i + content]);
+                i += content;
+            }
+        } else {
+            let end = rest.find('<').unwrap_or(rest.len());
+            out.prose(&rest[..end]);
+            i += end;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prose(segments: &[Segment]) -> Vec<&str> {
+        segments.iter().filter(|s| s.prose).map(|s| s.text.as_str()).collect()
+    }
+
+    #[test]
+    fn keeps_structure_out_of_the_prose() {
+        let md = "---\ntitle: Hi\n---\n# Getting started\n\nRun `xa` or see [the docs](https://x.dev).\nIt is fast.\n\n```sh\nxa translate\n```\n- one\n| Name | Use |\n|---|---|\n";
+        let parts = segments(md, Format::Markdown);
+        assert_eq!(parts.iter().map(|s| s.text.as_str()).collect::<String>(), md);
+        assert_eq!(prose(&parts), ["Getting started", "Run `xa` or see [the docs](https://x.dev).\nIt is fast.", "one", "Name", "Use"]);
+
+        let html = "<p>Hello <b>world</b></p><pre>keep  me</pre><script>var a = 1 < 2;</script>";
+        let parts = segments(html, Format::Html);
+        assert_eq!(parts.iter().map(|s| s.text.as_str()).collect::<String>(), html);
+        assert_eq!(prose(&parts), ["Hello", "world"]);
+
+        let (protected, saved) = protect("Run `xa` or see [the docs](https://x.dev).");
+        assert_eq!(protected, "Run ⟦0⟧ or see [the docs⟦1⟧.");
+        assert_eq!(restore("Lancez ⟦0⟧ ou voyez [la doc⟦1⟧.", &saved).unwrap(), "Lancez `xa` ou voyez [la doc](https://x.dev).");
+        assert_eq!(restore("Lancez xa", &saved), None);
+
+        assert_eq!(split_answer("<<1>>\nUn\n<<2>>\nDeux\n", 2), Some(vec!["Un".to_string(), "Deux".to_string()]));
+        assert_eq!(split_answer("<<1>>\nUn deux\n", 2), None);
+    }
+}
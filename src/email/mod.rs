@@ -0,0 +1,180 @@
+//! Pasted email threads, split into their messages for the `reply`
+//! command. A thread is recognized by the ways mail clients quote: `>`
+//! lines under an "On <date>, <name> wrote:" attribution (Gmail, Apple
+//! Mail, Thunderbird), header blocks after "-----Original Message-----"
+//! or a forwarded-message marker (Outlook), and a `From:`/`Date:` block
+//! at the top of a whole message.
+
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// "On Tue, Mar 3, 2026 at 9:14 AM Jane Doe <jane@example.com> wrote:":
+/// the date runs to the time when there is one, else to the last comma.
+/// The attribution may be wrapped over two lines.
+static ON_WROTE_TIMED: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^on\s+(?<date>.*\d{1,2}:\d{2}(?::\d{2})?(?:\s*[ap]\.?m\b\.?)?),?\s+(?<from>.*?)\s*wrote:$").expect("valid regex")
+});
+static ON_WROTE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)^on\s+(?<date>.+),\s*(?<from>[^,]*?)\s*wrote:$").expect("valid regex"));
+
+static SEPARATOR: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)^-{2,}\s*(original message|forwarded message)\s*-{2,}$|^begin forwarded message:$").expect("valid regex"));
+
+static HEADER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)^(?<name>from|sent|date|to|cc|subject):\s*(?<value>.*)$").expect("valid regex"));
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Message {
+    pub from: Option<String>,
+    pub date: Option<String>,
+    pub subject: Option<String>,
+    pub body: String,
+}
+
+/// The date and sender of an attribution line.
+fn attribution(line: &str) -> Option<(String, String)> {
+    let captures = ON_WROTE_TIMED.captures(line).or_else(|| ON_WROTE.captures(line))?;
+    Some((captures["date"].trim().to_string(), captures["from"].trim().to_string()))
+}
+
+/// The header block starting at `lines[0]`, if it is one (a `From:` line
+/// with a `Date:`/`Sent:` or `Subject:` line before the first blank line),
+/// and the number of lines it takes.
+fn header_block(lines: &[&str]) -> Option<(Message, usize)> {
+    let mut message = Message::default();
+    let mut count = 0;
+    for line in lines {
+        let Some(captures) = HEADER.captures(line.trim()) else { break };
+        let value = captures["value"].trim().to_string();
+        match captures["name"].to_lowercase().as_str() {
+            "from" => message.from = Some(value),
+            "sent" | "date" => message.date = Some(value),
+            "subject" => message.subject = Some(value),
+            _ => {}
+        }
+        count += 1;
+    }
+    (message.from.is_some() && (message.date.is_some() || message.subject.is_some())).then_some((message, count))
+}
+
+/// `text` without one level of `>` quoting.
+fn unquote(lines: &[&str]) -> String {
+    lines
+        .iter()
+        .map(|line| line.strip_prefix("> ").or_else(|| line.strip_prefix('>')).unwrap_or(line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The messages of a thread, newest (the top one) first. A text with no
+/// quoting at all is one message.
+pub fn parse_thread(text: &str) -> Vec<Message> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut messages: Vec<Message> = Vec::new();
+    let mut current = Message::default();
+    let mut body: Vec<&str> = Vec::new();
+    let mut index = 0;
+    let finish = |current: Message, body: &mut Vec<&str>, messages: &mut Vec<Message>| {
+        let text = body.join("\n").trim().to_string();
+        body.clear();
+        if !text.is_empty() || current.from.is_some() {
+            messages.push(Message { body: text, ..current });
+        }
+    };
+    while index < lines.len() {
+        let line = lines[index].trim_end();
+        let trimmed = line.trim();
+
+        if let Some((header, count)) = (index == 0).then(|| header_block(&lines)).flatten() {
+            current = header;
+            index += count;
+            continue;
+        }
+
+        let wrapped = || lines.get(index + 1).and_then(|next| attribution(&format!("{} {}", trimmed, next.trim())));
+        let found = attribution(trimmed).map(|found| (found, 1)).or_else(|| wrapped().map(|found| (found, 2)));
+        if let Some(((date, from), count)) = found {
+            index += count;
+            let start = index;
+            while index < lines.len() && (lines[index].starts_with('>') || lines[index].trim().is_empty()) {
+                index += 1;
+            }
+            finish(std::mem::take(&mut current), &mut body, &mut messages);
+            let mut quoted = parse_thread(&unquote(&lines[start..index]));
+            if let Some(first) = quoted.first_mut() {
+                first.from = first.from.take().or(Some(from).filter(|from| !from.is_empty()));
+                first.date = first.date.take().or(Some(date));
+            }
+            messages.extend(quoted);
+            continue;
+        }
+
+        if SEPARATOR.is_match(trimmed) || (trimmed.to_lowercase().starts_with("from:") && (index > 0)) {
+            let from = if SEPARATOR.is_match(trimmed) { index + 1 } else { index };
+            let skip_blank = lines[from..].iter().take_while(|line| line.trim().is_empty()).count();
+            if let Some((header, count)) = header_block(&lines[from + skip_blank..]) {
+                finish(std::mem::replace(&mut current, header), &mut body, &mut messages);
+                index = from + skip_blank + count;
+                continue;
+            }
+        }
+
+        if line.starts_with('>') {
+            let start = index;
+            while index < lines.len() && lines[index].starts_with('>') {
+                index += 1;
+            }
+            finish(std::mem::take(&mut current), &mut body, &mut messages);
+            messages.extend(parse_thread(&unquote(&lines[start..index])));
+            continue;
+        }
+
+        body.push(line);
+        index += 1;
+    }
+    finish(current, &mut body, &mut messages);
+    messages
+}
+
+/// The thread as the model reads it: each message numbered, newest first,
+/// under a line with its sender, date and subject.
+pub fn render_thread(messages: &[Message]) -> String {
+    let mut out = String::from("The email thread, newest message first:");
+    for (number, message) in messages.iter().enumerate() {
+        let mut header = vec![format!("[{}]", number + 1)];
+        for (name, value) in [("From", &message.from), ("Date", &message.date), ("Subject", &message.subject)] {
+            if let Some(value) = value {
+                header.push(format!("{}: {}", name, value));
+            }
+        }
+        out.push_str(&format!("\n\n{}\n{}", header.join(" "), message.body));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_threads_by_their_quoting() {
+        let gmail = "Sounds good, Thursday works.\n\nOn Tue, Mar 3, 2026 at 9:14 AM Jane Doe <jane@example.com>\nwrote:\n> Can we move the call to Thursday?\n>\n> On Mon, Mar 2, 2026, Bob wrote:\n>> Call is on Wednesday.\n";
+        let messages = parse_thread(gmail);
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0], Message { body: "Sounds good, Thursday works.".into(), ..Default::default() });
+        assert_eq!(messages[1].from.as_deref(), Some("Jane Doe <jane@example.com>"));
+        assert_eq!(messages[1].date.as_deref(), Some("Tue, Mar 3, 2026 at 9:14 AM"));
+        assert_eq!(messages[1].body, "Can we move the call to Thursday?");
+        assert_eq!((messages[2].from.as_deref(), messages[2].body.as_str()), (Some("Bob"), "Call is on Wednesday."));
+
+        let outlook = "From: Ann Lee <ann@corp.com>\nSent: Monday, March 2, 2026 10:00\nSubject: Invoice\n\nPlease see the attached invoice.\n\n-----Original Message-----\nFrom: Sam\nSent: Friday, February 27, 2026 16:30\nTo: Ann Lee\nSubject: Invoice?\n\nDid you send it yet?";
+        let messages = parse_thread(outlook);
+        assert_eq!(messages.len(), 2);
+        assert_eq!((messages[0].from.as_deref(), messages[0].subject.as_deref()), (Some("Ann Lee <ann@corp.com>"), Some("Invoice")));
+        assert_eq!(messages[0].body, "Please see the attached invoice.");
+        assert_eq!((messages[1].from.as_deref(), messages[1].body.as_str()), (Some("Sam"), "Did you send it yet?"));
+
+        assert_eq!(parse_thread("Just one note.\nFrom: the team").len(), 1);
+        let rendered = render_thread(&messages);
+        assert!(rendered.starts_with("The email thread, newest message first:\n\n[1] From: Ann Lee <ann@corp.com> Date: Monday, March 2, 2026 10:00 Subject: Invoice\nPlease see"));
+        assert!(rendered.contains("\n\n[2] From: Sam Date: Friday, February 27, 2026 16:30 Subject: Invoice?\nDid you send it yet?"));
+    }
+}
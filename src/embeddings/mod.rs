@@ -0,0 +1,102 @@
+//! Text embeddings, shared by `xa embed`, the document index
+//! (`xa index`) and secret search (`xa search`).
+//!
+//! Requests go to the OpenAI-compatible `embeddings` endpoint under
+//! `embedding_url` (or `base_url` when unset). An Ollama root URL
+//! (`http://localhost:11434`, or one ending in `/api`) uses Ollama's own
+//! `/api/embed` instead, so chat can stay on a hosted provider while
+//! embeddings are computed locally.
+
+use crate::config::Config;
+use crate::error::XaError;
+use tracing::Instrument;
+
+/// Embeddings of `inputs` with `model`, in input order.
+pub async fn embed(config: &Config, model: &str, inputs: &[String]) -> Result<Vec<Vec<f32>>, XaError> {
+    let base = config.embedding_url.as_deref().unwrap_or(&config.base_url);
+    let ollama = is_ollama_root(base);
+    let body = serde_json::json!({ "model": model, "input": inputs });
+    let request = if ollama {
+        // A local server: the provider's key stays with the provider
+        let root = base.trim_end_matches('/').trim_end_matches("/api");
+        let keyless = Config { api_key: String::new(), ..config.clone() };
+        crate::llm::build_request(&keyless, &format!("{}/api/embed", root), &body)?
+    } else {
+        crate::llm::build_request(config, &crate::llm::api_url(base, "embeddings"), &body)?
+    };
+    let span = tracing::info_span!("embeddings_request", model, inputs = inputs.len(), ollama);
+    let response = request.send().instrument(span).await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        return Err(XaError::from_status(status, response.text().await.unwrap_or_default()));
+    }
+    let result: serde_json::Value = response.json().await?;
+    let embeddings = if ollama { parse_ollama(&result) } else { parse_openai(&result) };
+    match embeddings {
+        Some(embeddings) if embeddings.len() == inputs.len() => Ok(embeddings),
+        Some(embeddings) => Err(XaError::Api {
+            status: 200,
+            message: format!("expected {} embeddings, got {}", inputs.len(), embeddings.len()),
+        }),
+        None => Err(XaError::Other("invalid response: no embeddings in it".into())),
+    }
+}
+
+/// Cosine similarity; 0 when either vector is all zeros.
+pub fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 {
+        0.0
+    } else {
+        dot / norms
+    }
+}
+
+/// `http://host:11434` or `http://host/api`: Ollama's native API rather
+/// than its OpenAI-compatible `/v1`.
+fn is_ollama_root(base_url: &str) -> bool {
+    let base = base_url.trim_end_matches('/');
+    base.ends_with(":11434") || base.ends_with("/api")
+}
+
+/// `{"data": [{"index": 0, "embedding": [...]}, ...]}`, possibly out of order.
+fn parse_openai(result: &serde_json::Value) -> Option<Vec<Vec<f32>>> {
+    let mut data = result["data"].as_array()?.clone();
+    data.sort_by_key(|item| item["index"].as_u64().unwrap_or(0));
+    data.iter().map(|item| vector(&item["embedding"])).collect()
+}
+
+/// `{"embeddings": [[...], ...]}`.
+fn parse_ollama(result: &serde_json::Value) -> Option<Vec<Vec<f32>>> {
+    result["embeddings"].as_array()?.iter().map(vector).collect()
+}
+
+fn vector(values: &serde_json::Value) -> Option<Vec<f32>> {
+    values.as_array()?.iter().map(|value| value.as_f64().map(|v| v as f32)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_both_apis_and_detects_ollama() {
+        let openai = serde_json::json!({ "data": [
+            { "index": 1, "embedding": [0.0, 1.0] },
+            { "index": 0, "embedding": [1.0, 0.0] },
+        ]});
+        assert_eq!(parse_openai(&openai), Some(vec![vec![1.0, 0.0], vec![0.0, 1.0]]));
+        let ollama = serde_json::json!({ "embeddings": [[0.5, 0.5]] });
+        assert_eq!(parse_ollama(&ollama), Some(vec![vec![0.5, 0.5]]));
+        assert_eq!(parse_openai(&serde_json::json!({ "error": "nope" })), None);
+
+        assert!(is_ollama_root("http://localhost:11434/"));
+        assert!(is_ollama_root("http://gpu-box/api"));
+        assert!(!is_ollama_root("http://localhost:11434/v1"));
+
+        assert!((cosine(&[1.0, 0.0], &[0.6, 0.8]) - 0.6).abs() < 1e-6);
+        assert_eq!(cosine(&[0.0, 0.0], &[1.0, 1.0]), 0.0);
+    }
+}
@@ -0,0 +1,193 @@
+//! The failure causes a script may want to branch on, and the exit code and
+//! `--json-errors` form of each.
+//!
+//! Errors from other crates convert into [`XaError::Other`], except TOML
+//! parse errors, which can only come from xa's own files and are
+//! [`XaError::Config`]. The TUI works with `Box<dyn Error>`;
+//! [`XaError::from_boxed`] brings its errors back.
+//!
+//! | code | kind               |
+//! |------|--------------------|
+//! | 1    | other              |
+//! | 2    | usage              |
+//! | 3    | config             |
+//! | 4    | command_not_found  |
+//! | 5    | auth               |
+//! | 6    | rate_limit         |
+//! | 7    | network            |
+//! | 8    | api                |
+//! | 9    | budget             |
+//! | any  | exited             |
+//!
+//! `exited` is a plugin or command xa ran that failed: xa exits with its
+//! code and leaves the reporting to it.
+
+use thiserror::Error;
+
+#[derive(Debug, Clone, Error)]
+pub enum XaError {
+    /// Wrong invocation: missing input, empty pipeline and the like.
+    #[error("{0}")]
+    Usage(String),
+    #[error("API key not configured. Please run 'xa set openai' first.")]
+    ConfigMissing,
+    /// config.toml, prompts.toml or `.xa.toml` is unreadable or invalid.
+    #[error("{0}")]
+    Config(String),
+    #[error("Command '{0}' not found. Use 'xa ls' to see available commands.")]
+    CommandNotFound(String),
+    /// HTTP 401/403: a wrong or expired key.
+    #[error("Authentication failed (HTTP {status}): {message}")]
+    Auth { status: u16, message: String },
+    /// HTTP 429.
+    #[error("Rate limited by the API: {0}")]
+    RateLimit(String),
+    /// The request never got an HTTP response (DNS, connect, TLS, timeout).
+    #[error("Network error: {0}")]
+    Network(String),
+    /// Any other unsuccessful HTTP status or an error inside the stream.
+    #[error("API error (HTTP {status}): {message}")]
+    Api { status: u16, message: String },
+    /// `daily_token_limit` or `monthly_budget_usd` is used up.
+    #[error("{0}")]
+    Budget(String),
+    #[error("{0}")]
+    Other(String),
+    /// A plugin or command run in the foreground failed with this code.
+    #[error("exited with code {0}")]
+    Exited(u8),
+}
+
+impl XaError {
+    /// Classify an unsuccessful HTTP response.
+    pub fn from_status(status: reqwest::StatusCode, body: String) -> XaError {
+        match status.as_u16() {
+            401 | 403 => XaError::Auth { status: status.as_u16(), message: body },
+            429 => XaError::RateLimit(body),
+            code => XaError::Api { status: code, message: body },
+        }
+    }
+
+    /// Recover the `XaError` inside a boxed error, or wrap its message.
+    pub fn from_boxed(error: Box<dyn std::error::Error>) -> XaError {
+        match error.downcast::<XaError>() {
+            Ok(error) => *error,
+            Err(error) => XaError::Other(error.to_string()),
+        }
+    }
+
+    /// The code a child process exited with; 1 when it has none (killed by
+    /// a signal) or one that does not fit.
+    pub fn exited(status: std::process::ExitStatus) -> XaError {
+        XaError::Exited(status.code().and_then(|code| u8::try_from(code).ok()).filter(|&code| code != 0).unwrap_or(1))
+    }
+
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            XaError::Other(_) => 1,
+            XaError::Usage(_) => 2,
+            XaError::ConfigMissing | XaError::Config(_) => 3,
+            XaError::CommandNotFound(_) => 4,
+            XaError::Auth { .. } => 5,
+            XaError::RateLimit(_) => 6,
+            XaError::Network(_) => 7,
+            XaError::Api { .. } => 8,
+            XaError::Budget(_) => 9,
+            XaError::Exited(code) => *code,
+        }
+    }
+
+    /// Stable name used in `--json-errors` output.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            XaError::Other(_) => "other",
+            XaError::Usage(_) => "usage",
+            XaError::ConfigMissing | XaError::Config(_) => "config",
+            XaError::CommandNotFound(_) => "command_not_found",
+            XaError::Auth { .. } => "auth",
+            XaError::RateLimit(_) => "rate_limit",
+            XaError::Network(_) => "network",
+            XaError::Api { .. } => "api",
+            XaError::Budget(_) => "budget",
+            XaError::Exited(_) => "exited",
+        }
+    }
+
+    /// Print the error on stderr, as one JSON object per line with `json`.
+    pub fn report(&self, json: bool) {
+        if json {
+            let mut error = serde_json::json!({
+                "kind": self.kind(),
+                "code": self.exit_code(),
+                "message": self.to_string(),
+            });
+            if let XaError::Auth { status, .. } | XaError::Api { status, .. } = self {
+                error["status"] = (*status).into();
+            }
+            eprintln!("{}", serde_json::json!({ "error": error }));
+        } else if !matches!(self, XaError::Exited(_)) {
+            eprintln!("Error: {}", self);
+        }
+    }
+}
+
+impl From<reqwest::Error> for XaError {
+    fn from(error: reqwest::Error) -> Self {
+        match error.status() {
+            Some(status) => XaError::from_status(status, error.to_string()),
+            None if error.is_decode() => XaError::Other(format!("invalid response: {}", error)),
+            None => XaError::Network(error.to_string()),
+        }
+    }
+}
+
+impl From<std::io::Error> for XaError {
+    fn from(error: std::io::Error) -> Self {
+        XaError::Other(error.to_string())
+    }
+}
+
+impl From<serde_json::Error> for XaError {
+    fn from(error: serde_json::Error) -> Self {
+        XaError::Other(error.to_string())
+    }
+}
+
+/// TOML is only read from xa's own files, so a parse error is a config error.
+impl From<toml::de::Error> for XaError {
+    fn from(error: toml::de::Error) -> Self {
+        XaError::Config(error.to_string())
+    }
+}
+
+impl From<toml_edit::TomlError> for XaError {
+    fn from(error: toml_edit::TomlError) -> Self {
+        XaError::Config(error.to_string())
+    }
+}
+
+impl From<toml::ser::Error> for XaError {
+    fn from(error: toml::ser::Error) -> Self {
+        XaError::Other(error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn statuses_and_boxed_errors_keep_their_cause() {
+        let auth = XaError::from_status(reqwest::StatusCode::UNAUTHORIZED, "bad key".into());
+        assert_eq!((auth.kind(), auth.exit_code()), ("auth", 5));
+        let limited = XaError::from_status(reqwest::StatusCode::TOO_MANY_REQUESTS, String::new());
+        assert_eq!(limited.exit_code(), 6);
+        assert_eq!(XaError::from_status(reqwest::StatusCode::BAD_GATEWAY, String::new()).kind(), "api");
+
+        let boxed: Box<dyn std::error::Error> = XaError::CommandNotFound("nope".into()).into();
+        assert_eq!(XaError::from_boxed(boxed).exit_code(), 4);
+        let boxed: Box<dyn std::error::Error> = "plain failure".into();
+        assert_eq!(XaError::from_boxed(boxed).exit_code(), 1);
+        assert_eq!(XaError::Exited(42).exit_code(), 42);
+    }
+}
@@ -0,0 +1,380 @@
+//! `xa explain <path>[:START-END]`: an explanation of a file or part of
+//! one. The language is told from the extension (or a shebang), the lines
+//! are numbered so the answer can point at them, and with `--imports` the
+//! local files the code imports are sent along as context. Code blocks in
+//! the answer are highlighted on the terminal.
+
+use crate::config::Config;
+use crate::error::XaError;
+use crate::output::paint;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+/// A file up to this size is sent whole, with the lines to explain marked;
+/// a range in a larger one is sent with `CONTEXT_LINES` around it.
+const MAX_FILE: usize = 40_000;
+const CONTEXT_LINES: u32 = 40;
+
+/// Imported files sent along, and the most of each.
+const MAX_IMPORTS: usize = 4;
+const MAX_IMPORT: usize = 12_000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Language {
+    Rust,
+    Python,
+    JavaScript,
+    TypeScript,
+    Go,
+    C,
+    Cpp,
+    Java,
+    Ruby,
+    Shell,
+    Other,
+}
+
+impl Language {
+    /// By extension, else by the `#!` line.
+    pub fn detect(path: &Path, text: &str) -> Language {
+        let extension = path.extension().and_then(|ext| ext.to_str()).map(str::to_lowercase).unwrap_or_default();
+        match extension.as_str() {
+            "rs" => return Language::Rust,
+            "py" | "pyi" => return Language::Python,
+            "js" | "jsx" | "mjs" | "cjs" => return Language::JavaScript,
+            "ts" | "tsx" | "mts" | "cts" => return Language::TypeScript,
+            "go" => return Language::Go,
+            "c" | "h" => return Language::C,
+            "cc" | "cpp" | "cxx" | "hh" | "hpp" | "hxx" => return Language::Cpp,
+            "java" => return Language::Java,
+            "rb" => return Language::Ruby,
+            "sh" | "bash" | "zsh" => return Language::Shell,
+            _ => {}
+        }
+        let shebang = text.lines().next().filter(|line| line.starts_with("#!")).unwrap_or_default();
+        if shebang.contains("python") {
+            Language::Python
+        } else if shebang.contains("node") {
+            Language::JavaScript
+        } else if shebang.contains("ruby") {
+            Language::Ruby
+        } else if ["sh", "bash", "zsh"].iter().any(|shell| shebang.ends_with(shell) || shebang.contains(&format!("{} ", shell))) {
+            Language::Shell
+        } else {
+            Language::Other
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::Rust => "Rust",
+            Language::Python => "Python",
+            Language::JavaScript => "JavaScript",
+            Language::TypeScript => "TypeScript",
+            Language::Go => "Go",
+            Language::C => "C",
+            Language::Cpp => "C++",
+            Language::Java => "Java",
+            Language::Ruby => "Ruby",
+            Language::Shell => "shell",
+            Language::Other => "",
+        }
+    }
+
+    /// The language of a code fence's info string (`rust`, `py`, …).
+    fn from_fence(tag: &str) -> Language {
+        match tag.trim().to_lowercase().as_str() {
+            "rust" | "rs" => Language::Rust,
+            "python" | "py" => Language::Python,
+            "javascript" | "js" | "jsx" => Language::JavaScript,
+            "typescript" | "ts" | "tsx" => Language::TypeScript,
+            "go" | "golang" => Language::Go,
+            "c" => Language::C,
+            "cpp" | "c++" => Language::Cpp,
+            "java" => Language::Java,
+            "ruby" | "rb" => Language::Ruby,
+            "sh" | "bash" | "shell" | "zsh" => Language::Shell,
+            _ => Language::Other,
+        }
+    }
+
+    fn keywords(self) -> &'static [&'static str] {
+        match self {
+            Language::Rust => &[
+                "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "false", "fn", "for", "if", "impl", "in",
+                "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true",
+                "type", "unsafe", "use", "where", "while",
+            ],
+            Language::Python => &[
+                "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del", "elif", "else", "except", "False", "finally",
+                "for", "from", "global", "if", "import", "in", "is", "lambda", "None", "nonlocal", "not", "or", "pass", "raise", "return", "True",
+                "try", "while", "with", "yield",
+            ],
+            Language::JavaScript | Language::TypeScript => &[
+                "async", "await", "break", "case", "catch", "class", "const", "continue", "default", "else", "export", "extends", "false", "for",
+                "from", "function", "if", "import", "in", "instanceof", "interface", "let", "new", "null", "of", "return", "switch", "this",
+                "throw", "true", "try", "type", "typeof", "undefined", "var", "while", "yield",
+            ],
+            Language::Go => &[
+                "break", "case", "chan", "const", "continue", "default", "defer", "else", "false", "for", "func", "go", "if", "import",
+                "interface", "map", "nil", "package", "range", "return", "select", "struct", "switch", "true", "type", "var",
+            ],
+            Language::C | Language::Cpp | Language::Java => &[
+                "auto", "break", "case", "catch", "char", "class", "const", "continue", "default", "delete", "do", "double", "else", "enum",
+                "extends", "false", "final", "float", "for", "if", "import", "int", "long", "namespace", "new", "null", "nullptr", "private",
+                "protected", "public", "return", "short", "sizeof", "static", "struct", "switch", "template", "this", "throw", "true", "try",
+                "typedef", "unsigned", "using", "virtual", "void", "while",
+            ],
+            Language::Ruby => &[
+                "begin", "class", "def", "do", "else", "elsif", "end", "ensure", "false", "if", "in", "module", "nil", "require", "rescue",
+                "return", "self", "then", "true", "unless", "until", "when", "while", "yield",
+            ],
+            Language::Shell => &[
+                "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if", "in", "local", "return", "then", "while",
+            ],
+            Language::Other => &[],
+        }
+    }
+
+    /// Whether `#` starts a comment.
+    fn hash_comments(self) -> bool {
+        matches!(self, Language::Python | Language::Ruby | Language::Shell | Language::Other)
+    }
+}
+
+static RUST_MOD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?m)^\s*(?:pub(?:\([^)]*\))?\s+)?mod\s+(\w+)\s*;").expect("valid regex"));
+static PYTHON_IMPORT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^\s*(?:from\s+(\.*[\w.]*)\s+import|import\s+([\w.]+))").expect("valid regex"));
+static JS_IMPORT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?:from\s+|require\(\s*|import\s*\(?\s*)['"](\.{1,2}/[^'"]+)['"]"#).expect("valid regex"));
+static C_INCLUDE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"(?m)^\s*#\s*include\s+"([^"]+)""#).expect("valid regex"));
+
+/// The local files `text` (the file at `path`) imports and that exist:
+/// Rust `mod x;`, Python relative and same-directory imports, JavaScript
+/// and TypeScript `./` imports, and C `#include "x.h"`. Packages and
+/// anything outside the file's directory tree are not followed.
+pub fn imports(path: &Path, language: Language, text: &str) -> Vec<PathBuf> {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let candidates: Vec<PathBuf> = match language {
+        Language::Rust => {
+            // `mod x;` in main.rs, lib.rs or mod.rs is a sibling; elsewhere it is in a directory named after the file
+            let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default();
+            let base = if ["main", "lib", "mod"].contains(&stem) { dir.to_path_buf() } else { dir.join(stem) };
+            RUST_MOD
+                .captures_iter(text)
+                .flat_map(|captures| {
+                    let name = &captures[1];
+                    [base.join(format!("{}.rs", name)), base.join(name).join("mod.rs")]
+                })
+                .collect()
+        }
+        Language::Python => PYTHON_IMPORT
+            .captures_iter(text)
+            .filter_map(|captures| captures.get(1).or(captures.get(2)).map(|module| module.as_str().to_string()))
+            .flat_map(|module| {
+                let dots = module.chars().take_while(|c| *c == '.').count();
+                let mut base = dir.to_path_buf();
+                for _ in 1..dots {
+                    base.pop();
+                }
+                let relative = module[dots..].replace('.', "/");
+                [base.join(format!("{}.py", relative)), base.join(relative).join("__init__.py")]
+            })
+            .collect(),
+        Language::JavaScript | Language::TypeScript => JS_IMPORT
+            .captures_iter(text)
+            .flat_map(|captures| {
+                let target = dir.join(&captures[1]);
+                let mut candidates = vec![target.clone()];
+                for extension in ["ts", "tsx", "js", "jsx", "mjs"] {
+                    let mut with_extension = target.clone().into_os_string();
+                    with_extension.push(format!(".{}", extension));
+                    candidates.push(with_extension.into());
+                    candidates.push(target.join(format!("index.{}", extension)));
+                }
+                candidates
+            })
+            .collect(),
+        Language::C | Language::Cpp => C_INCLUDE.captures_iter(text).map(|captures| dir.join(&captures[1])).collect(),
+        _ => Vec::new(),
+    };
+    let mut found: Vec<PathBuf> = Vec::new();
+    for candidate in candidates {
+        if candidate.is_file() && candidate != path && !found.contains(&candidate) {
+            found.push(candidate);
+        }
+    }
+    found
+}
+
+/// Lines `start..=end` of `text`, numbered.
+fn numbered(text: &str, start: u32, end: u32) -> String {
+    text.lines()
+        .zip(1u32..)
+        .filter(|(_, number)| (start..=end).contains(number))
+        .map(|(line, number)| format!("{:>5} {}\n", number, line))
+        .collect()
+}
+
+fn truncate(text: &str, max: usize) -> &str {
+    if text.len() <= max {
+        return text;
+    }
+    let mut end = max;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..text[..end].rfind('\n').unwrap_or(end)]
+}
+
+/// The request: the file (or the range with some context), numbered, and
+/// the imported files.
+pub fn prompt(path: &str, language: Language, text: &str, range: Option<(u32, u32)>, context: &[(String, String)]) -> String {
+    let what = match range {
+        Some((start, end)) => format!("lines {}-{} of {}", start, end, path),
+        None => path.to_string(),
+    };
+    let language_name = if language == Language::Other { "code" } else { language.name() };
+    let mut prompt = format!(
+        "Explain {} ({}) to a developer who is new to this code. Start with what it does and why in a few sentences, \
+         then walk through how it works in the order it runs, pointing at line numbers, and end with anything \
+         surprising: edge cases, side effects, error handling, performance. Quote short snippets in fenced code blocks \
+         tagged with the language when they help. Numbers at the start of lines are line numbers, not code.",
+        what, language_name
+    );
+    let lines = text.lines().count() as u32;
+    let (from, to) = match range {
+        Some((start, end)) if text.len() > MAX_FILE => (start.saturating_sub(CONTEXT_LINES).max(1), end.saturating_add(CONTEXT_LINES)),
+        _ => (1, lines),
+    };
+    if range.is_some() {
+        prompt.push_str(" The surrounding lines are there for context; explain only the requested ones.");
+    }
+    prompt.push_str(&format!("\n\nFile: {}\n{}", path, truncate(&numbered(text, from, to), MAX_FILE)));
+    for (path, text) in context {
+        prompt.push_str(&format!("\n\nImported by it, for context — {}:\n{}", path, truncate(text, MAX_IMPORT)));
+    }
+    prompt
+}
+
+/// Explain `path`, lines `range` (all when `None`), with the files it
+/// imports when `with_imports`. Returns the file's language with the answer.
+pub async fn explain(config: &Config, path: &str, range: Option<(u32, u32)>, with_imports: bool) -> Result<(Language, String), XaError> {
+    let text = std::fs::read_to_string(path).map_err(|e| XaError::Usage(format!("cannot read {}: {}", path, e)))?;
+    let language = Language::detect(Path::new(path), &text);
+    let mut context = Vec::new();
+    if with_imports {
+        for import in imports(Path::new(path), language, &text).into_iter().take(MAX_IMPORTS) {
+            if let Ok(text) = std::fs::read_to_string(&import) {
+                eprintln!("{}", paint("90", &format!("With {}", import.display())));
+                context.push((import.display().to_string(), text));
+            }
+        }
+    }
+    let spinner = crate::spinner::Spinner::start("Explaining");
+    let done = crate::llm::complete(config, &prompt(path, language, &text, range, &context)).await;
+    spinner.stop();
+    Ok((language, done?.text))
+}
+
+static TOKEN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?P<comment>//.*|/\*.*?\*/)|(?P<hash>#.*)|(?P<string>"(?:[^"\\]|\\.)*"|'(?:[^'\\]|\\.)*'|`[^`]*`)|(?P<number>\b\d[\d_.]*\b)|(?P<word>\b[A-Za-z_]\w*\b)"#)
+        .expect("valid regex")
+});
+
+/// One line of code with comments, strings, numbers and keywords colored.
+fn highlight_line(line: &str, language: Language) -> String {
+    let mut out = String::new();
+    let mut last = 0;
+    for captures in TOKEN.captures_iter(line) {
+        let token = captures.get(0).expect("whole match");
+        let color = if captures.name("comment").is_some() && !matches!(language, Language::Python | Language::Ruby | Language::Shell) {
+            Some("90")
+        } else if captures.name("hash").is_some() {
+            language.hash_comments().then_some("90")
+        } else if captures.name("string").is_some() {
+            Some("32")
+        } else if captures.name("number").is_some() {
+            Some("33")
+        } else if captures.name("word").is_some() {
+            language.keywords().contains(&token.as_str()).then_some("35")
+        } else {
+            None
+        };
+        let Some(color) = color else { continue };
+        out.push_str(&line[last..token.start()]);
+        out.push_str(&paint(color, token.as_str()));
+        last = token.end();
+    }
+    out.push_str(&line[last..]);
+    out
+}
+
+/// The explanation for the terminal: prose rendered as Markdown, fenced
+/// code highlighted in the fence's language (else the file's).
+pub fn print(answer: &str, language: Language) {
+    let mut prose = String::new();
+    let mut code: Option<(Language, Vec<&str>)> = None;
+    for line in answer.lines() {
+        let fence = line.trim_start().strip_prefix("```");
+        match (&mut code, fence) {
+            (None, Some(tag)) => {
+                crate::output::skin().print_text(&prose);
+                prose.clear();
+                let fence_language = Language::from_fence(tag);
+                code = Some((if fence_language == Language::Other { language } else { fence_language }, Vec::new()));
+            }
+            (Some((code_language, lines)), Some(_)) => {
+                for line in lines.iter() {
+                    println!("  {}", highlight_line(line, *code_language));
+                }
+                code = None;
+            }
+            (Some((_, lines)), None) => lines.push(line),
+            (None, None) => {
+                prose.push_str(line);
+                prose.push('\n');
+            }
+        }
+    }
+    if let Some((code_language, lines)) = code {
+        for line in lines {
+            println!("  {}", highlight_line(line, code_language));
+        }
+    }
+    crate::output::skin().print_text(&prose);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_languages_and_follows_local_imports() {
+        let dir = std::env::temp_dir().join(format!("xa-explain-test-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("src/net")).unwrap();
+        std::fs::create_dir_all(dir.join("app")).unwrap();
+        for file in ["src/net/mod.rs", "src/config.rs", "app/util.py", "app/__init__.py", "lib.ts"] {
+            std::fs::write(dir.join(file), "").unwrap();
+        }
+
+        let main = dir.join("src/main.rs");
+        assert_eq!(Language::detect(&main, ""), Language::Rust);
+        assert_eq!(Language::detect(Path::new("deploy"), "#!/usr/bin/env bash\nset -e"), Language::Shell);
+        assert_eq!(Language::detect(Path::new("tool"), "#!/usr/bin/python3"), Language::Python);
+        assert_eq!(Language::detect(Path::new("notes"), "hello"), Language::Other);
+
+        let rust = imports(&main, Language::Rust, "mod net;\npub(crate) mod config;\nmod missing;\nuse std::io;");
+        assert_eq!(rust, [dir.join("src/net/mod.rs"), dir.join("src/config.rs")]);
+        let python = imports(&dir.join("app/main.py"), Language::Python, "from .util import x\nimport os\n");
+        assert_eq!(python, [dir.join("app/util.py")]);
+        let ts = imports(&dir.join("index.ts"), Language::TypeScript, "import { a } from './lib';\nimport b from 'react';");
+        assert_eq!(ts, [dir.join("lib.ts")]);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let text = "a\nb\nc\nd\n";
+        assert!(prompt("x.rs", Language::Rust, text, Some((2, 3)), &[]).contains("lines 2-3 of x.rs (Rust)"));
+        assert!(prompt("x.rs", Language::Rust, text, None, &[]).contains("    1 a\n    2 b\n"));
+    }
+}
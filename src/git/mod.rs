@@ -0,0 +1,175 @@
+//! Git helpers: the inputs of `xa commit` (the staged diff), `xa pr` (the
+//! branch's log and diff) and `xa changelog` (a range's commits), and the `prepare-commit-msg` hook that
+//! `xa git install-hooks` writes so `git commit` opens with a drafted
+//! message.
+
+use crate::error::XaError;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Diff text sent to the model; the rest is cut off with a note.
+const MAX_DIFF: usize = 24_000;
+
+/// Marks a hook as xa's, so installing again may replace it.
+const HOOK_MARKER: &str = "# Installed by `xa git install-hooks`";
+
+/// Drafts a message only when git did not get one (`-m`, `-F`) and the
+/// commit is not a merge, squash or amend. A failing xa never blocks
+/// the commit.
+const PREPARE_COMMIT_MSG: &str = r#"#!/bin/sh
+# Installed by `xa git install-hooks`: drafts the commit message with xa.
+command -v xa >/dev/null 2>&1 || exit 0
+case "$2" in
+    ""|template) xa commit --hook "$1" || true ;;
+esac
+"#;
+
+/// Run git with `args` and return its output.
+fn git(args: &[&str]) -> Result<String, XaError> {
+    let output = Command::new("git").args(args).output().map_err(|e| XaError::Other(format!("cannot run git: {}", e)))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(XaError::Usage(format!("git {} failed: {}", args.join(" "), stderr.trim())));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// `git diff` with `args` (`--cached`, a range, paths), without color.
+pub fn diff(args: &[&str]) -> Result<String, XaError> {
+    let mut all = vec!["diff", "--no-color"];
+    all.extend_from_slice(args);
+    git(&all)
+}
+
+/// The staged changes: a stat summary, then the diff. `None` when nothing
+/// is staged.
+pub fn staged_diff() -> Result<Option<String>, XaError> {
+    let stat = git(&["diff", "--cached", "--stat"])?;
+    if stat.trim().is_empty() {
+        return Ok(None);
+    }
+    let diff = git(&["diff", "--cached", "--no-color"])?;
+    Ok(Some(format!("{}\n{}", stat.trim_end(), truncate(&diff, MAX_DIFF))))
+}
+
+/// The commits on HEAD since it left `base`, and their combined diff.
+pub fn branch_summary(base: &str) -> Result<String, XaError> {
+    if git(&["rev-parse", "--verify", "--quiet", &format!("{}^{{commit}}", base)]).is_err() {
+        return Err(XaError::Usage(format!("Unknown base branch '{}'.", base)));
+    }
+    let range = format!("{}..HEAD", base);
+    let log = git(&["log", "--reverse", "--format=- %s%n%w(0,2,2)%b", &range])?;
+    if log.trim().is_empty() {
+        return Err(XaError::Usage(format!("No commits on HEAD since {}.", base)));
+    }
+    let changes = format!("{}...HEAD", base);
+    let stat = git(&["diff", "--stat", &changes])?;
+    let diff = git(&["diff", "--no-color", &changes])?;
+    Ok(format!("Commits:\n{}\n\nChanges:\n{}\n{}", log.trim_end(), stat.trim_end(), truncate(&diff, MAX_DIFF)))
+}
+
+/// The branch `xa pr` compares against: the remote's default branch, or
+/// a local `main` or `master`.
+pub fn default_base() -> Result<String, XaError> {
+    if let Ok(head) = git(&["symbolic-ref", "--short", "refs/remotes/origin/HEAD"]) {
+        return Ok(head.trim().to_string());
+    }
+    for name in ["main", "master"] {
+        if git(&["rev-parse", "--verify", "--quiet", name]).is_ok() {
+            return Ok(name.to_string());
+        }
+    }
+    Err(XaError::Usage("Cannot tell the base branch; pass --base <branch>.".into()))
+}
+
+/// A commit's message, as `xa changelog` reads it.
+pub struct LogEntry {
+    pub subject: String,
+    pub body: String,
+}
+
+/// The commits in `range` (e.g. `v1.2.0..HEAD`), oldest first, merges left
+/// out.
+pub fn log(range: &str) -> Result<Vec<LogEntry>, XaError> {
+    let log = git(&["log", "--reverse", "--no-merges", "--format=%s%x1f%b%x1e", range, "--"])?;
+    Ok(log
+        .split('\x1e')
+        .filter_map(|record| {
+            let (subject, body) = record.trim_start_matches('\n').split_once('\x1f')?;
+            Some(LogEntry { subject: subject.trim().to_string(), body: body.trim().to_string() })
+        })
+        .collect())
+}
+
+/// The most recent tag reachable from HEAD, if any.
+pub fn latest_tag() -> Option<String> {
+    git(&["describe", "--tags", "--abbrev=0"]).ok().map(|tag| tag.trim().to_string()).filter(|tag| !tag.is_empty())
+}
+
+/// The date (`YYYY-MM-DD`) of the commit `tag` points at, when it is a tag.
+pub fn tag_date(tag: &str) -> Option<String> {
+    git(&["rev-parse", "--verify", "--quiet", &format!("refs/tags/{}", tag)]).ok()?;
+    git(&["log", "-1", "--format=%cs", tag]).ok().map(|date| date.trim().to_string())
+}
+
+/// Write the `prepare-commit-msg` hook into the repository's hooks
+/// directory (honouring `core.hooksPath`). Another tool's hook is only
+/// replaced with `force`.
+pub fn install_hooks(force: bool) -> Result<PathBuf, XaError> {
+    let dir = PathBuf::from(git(&["rev-parse", "--git-path", "hooks"])?.trim());
+    let path = dir.join("prepare-commit-msg");
+    if let Ok(existing) = std::fs::read_to_string(&path) {
+        if !existing.contains(HOOK_MARKER) && !force {
+            return Err(XaError::Usage(format!("{} already exists; pass --force to replace it.", path.display())));
+        }
+    }
+    let write = || -> std::io::Result<()> {
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(&path, PREPARE_COMMIT_MSG)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+        }
+        Ok(())
+    };
+    write().map_err(|e| XaError::Other(format!("cannot write {}: {}", path.display(), e)))?;
+    Ok(path)
+}
+
+/// `message` above what git put in the message file (the status comments,
+/// or a template).
+pub fn with_message(existing: &str, message: &str) -> String {
+    format!("{}\n\n{}", message.trim(), existing.trim_start_matches('\n'))
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    if text.len() <= max {
+        return text.to_string();
+    }
+    let mut end = max;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    let cut = &text[..text[..end].rfind('\n').map_or(end, |newline| newline + 1)];
+    format!("{}[... {} more lines of diff not shown]\n", cut, text[cut.len()..].lines().count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drafts_above_the_template_and_truncates_diffs() {
+        let existing = "\n# Please enter the commit message for your changes.\n";
+        assert_eq!(
+            with_message(existing, "Fix the parser\n"),
+            "Fix the parser\n\n# Please enter the commit message for your changes.\n"
+        );
+        assert!(PREPARE_COMMIT_MSG.contains(HOOK_MARKER));
+
+        let diff = "+one\n+two\n+three\n";
+        assert_eq!(truncate(diff, 100), diff);
+        assert_eq!(truncate(diff, 10), "+one\n+two\n[... 1 more lines of diff not shown]\n");
+    }
+}
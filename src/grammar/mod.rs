@@ -0,0 +1,148 @@
+//! Structured corrections for the built-in `grammar` command (and any
+//! command with `show_corrections = true`): the model answers with JSON
+//! listing each mistake instead of a rewritten text, and the input is shown
+//! with every fix marked in place and numbered, followed by the reasons.
+
+use crate::output::paint;
+use serde::Deserialize;
+
+/// One fix: the exact `span` of the input, what should replace it, and why.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Correction {
+    pub span: String,
+    #[serde(default)]
+    pub replacement: String,
+    #[serde(default)]
+    pub reason: String,
+}
+
+#[derive(Deserialize)]
+struct Answer {
+    #[serde(default)]
+    corrections: Vec<Correction>,
+}
+
+/// The corrections in a model's answer, which may wrap the JSON in a code
+/// block or text.
+pub fn parse_corrections(answer: &str) -> Result<Vec<Correction>, String> {
+    let text = crate::postprocess::extract_code_block(answer).unwrap_or_else(|| answer.to_string());
+    let json = match (text.find('{'), text.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &text[start..=end],
+        _ => return Err("no JSON in the answer".into()),
+    };
+    serde_json::from_str::<Answer>(json).map(|answer| answer.corrections).map_err(|e| e.to_string())
+}
+
+/// Where each correction applies in `input`: the byte range of its span,
+/// searched from the end of the previous one (the model lists them in
+/// order), or `None` when the span is not in the text.
+fn locate(input: &str, corrections: &[Correction]) -> Vec<Option<(usize, usize)>> {
+    let mut from = 0;
+    corrections
+        .iter()
+        .map(|correction| {
+            if correction.span.is_empty() {
+                return None;
+            }
+            let start = input[from..].find(&correction.span).map(|at| from + at).or_else(|| input.find(&correction.span))?;
+            let end = start + correction.span.len();
+            if start >= from {
+                from = end;
+            }
+            Some((start, end))
+        })
+        .collect()
+}
+
+/// `input` with every correction that can be placed applied.
+pub fn apply(input: &str, corrections: &[Correction]) -> String {
+    let mut placed: Vec<((usize, usize), &Correction)> =
+        locate(input, corrections).into_iter().zip(corrections).filter_map(|(range, correction)| Some((range?, correction))).collect();
+    placed.sort_by_key(|(range, _)| *range);
+    let mut out = String::with_capacity(input.len());
+    let mut last = 0;
+    for ((start, end), correction) in placed {
+        // Overlapping spans: the first one wins
+        if start < last {
+            continue;
+        }
+        out.push_str(&input[last..start]);
+        out.push_str(&correction.replacement);
+        last = end;
+    }
+    out.push_str(&input[last..]);
+    out
+}
+
+/// The annotated input and the numbered list of reasons. Without colors
+/// (`plain`) a fix reads `[-old-]{+new+}[1]`, as in `--diff`.
+pub fn render(input: &str, corrections: &[Correction], plain: bool) -> String {
+    if corrections.is_empty() {
+        return paint("32", "No corrections.");
+    }
+    let ranges = locate(input, corrections);
+    let mut placed: Vec<(usize, usize, usize)> =
+        ranges.iter().enumerate().filter_map(|(index, range)| range.map(|(start, end)| (start, end, index))).collect();
+    placed.sort_unstable();
+    let (deleted, inserted) = if plain { (("[-", "-]"), ("{+", "+}")) } else { (("\x1b[31;9m", "\x1b[0m"), ("\x1b[32m", "\x1b[0m")) };
+    let mut text = String::new();
+    let mut last = 0;
+    for (start, end, index) in placed {
+        if start < last {
+            continue;
+        }
+        let correction = &corrections[index];
+        text.push_str(&input[last..start]);
+        text.push_str(&format!("{}{}{}", deleted.0, correction.span, deleted.1));
+        if !correction.replacement.is_empty() {
+            text.push_str(&format!("{}{}{}", inserted.0, correction.replacement, inserted.1));
+        }
+        text.push_str(&paint("90", &format!("[{}]", index + 1)));
+        last = end;
+    }
+    text.push_str(&input[last..]);
+
+    let mut out = format!("{}\n\n", text.trim_end());
+    for (index, (correction, range)) in corrections.iter().zip(&ranges).enumerate() {
+        let replacement = if correction.replacement.is_empty() { "(remove)".to_string() } else { format!("\"{}\"", correction.replacement) };
+        out.push_str(&format!("{} \"{}\" → {}", paint("1", &format!("{:>2}.", index + 1)), correction.span, replacement));
+        if range.is_none() {
+            out.push_str(&paint("33", "  (not found in the text)"));
+        }
+        out.push('\n');
+        if !correction.reason.is_empty() {
+            out.push_str(&format!("    {}\n", paint("90", &correction.reason)));
+        }
+    }
+    out.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_places_and_applies_corrections() {
+        let answer = "```json\n{\"corrections\": [\
+            {\"span\": \"has went\", \"replacement\": \"has gone\", \"reason\": \"past participle of go\"},\
+            {\"span\": \"teh\", \"replacement\": \"the\", \"reason\": \"spelling\"},\
+            {\"span\": \" really\", \"replacement\": \"\"},\
+            {\"span\": \"missing\", \"replacement\": \"x\"}]}\n```";
+        let corrections = parse_corrections(answer).unwrap();
+        assert_eq!(corrections.len(), 4);
+        assert_eq!(corrections[2].reason, "");
+        assert!(parse_corrections("Looks fine to me").is_err());
+
+        let input = "She has went to teh really big store.";
+        assert_eq!(apply(input, &corrections), "She has gone to the big store.");
+        let report = render(input, &corrections, true);
+        let lines: Vec<&str> = report.lines().collect();
+        assert!(lines[0].starts_with("She [-has went-]{+has gone+}"));
+        assert!(lines[0].contains("[-teh-]{+the+}"));
+        assert!(lines[0].contains("[- really-]"));
+        assert!(report.contains("\" really\" → (remove)"));
+        assert!(report.contains("\"missing\" → \"x\""));
+        assert!(report.contains("not found in the text"));
+        assert!(report.contains("past participle of go"));
+    }
+}
@@ -0,0 +1,255 @@
+//! Run history: one JSON line per one-shot invocation in
+//! `xa_dir()/history.jsonl`, browsed with `xa history` and replayed with
+//! `xa history --rerun <n>`.
+//!
+//! The log is append-only. Inputs are kept whole so a run can be replayed;
+//! results are cut to a preview since the full answer is already in the
+//! clipboard, the terminal or an `--output` file.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::Instant;
+
+use crate::config::{xa_dir, Config};
+use crate::error::XaError;
+use crate::llm::{Completion, StreamTiming, Usage};
+use crate::redact::{RedactConfig, Redactor};
+use serde::{Deserialize, Serialize};
+
+/// Characters of the result kept in the log.
+const RESULT_PREVIEW_CHARS: usize = 200;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HistoryEntry {
+    /// Unix seconds.
+    pub time: i64,
+    /// Command name, or the step spec for `xa pipe`.
+    pub command: String,
+    /// Whether `command` is an `xa pipe` spec.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pipe: bool,
+    pub input: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    /// [`prompt_hash`] of what was sent, to tell apart runs whose template
+    /// changed in between.
+    pub prompt_hash: String,
+    pub model: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+    pub duration_ms: u64,
+    /// Time to first token and tokens per second, for streamed runs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timing: Option<StreamTiming>,
+    pub result: String,
+    /// The whole answer so far when its stream broke off (`result` is only
+    /// the start), for `xa history --continue` to finish.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub partial: Option<String>,
+}
+
+impl HistoryEntry {
+    /// The entry for a run of `command` on `input` and `args` that sent
+    /// `prompt` to `model`, started at `started` and answered `completion`.
+    pub fn finished(command: &str, input: &str, args: &[String], prompt: &str, model: &str, completion: &Completion, started: Instant) -> HistoryEntry {
+        HistoryEntry {
+            time: chrono::Utc::now().timestamp(),
+            command: command.to_string(),
+            pipe: false,
+            input: input.to_string(),
+            args: args.to_vec(),
+            prompt_hash: prompt_hash(prompt),
+            model: model.to_string(),
+            usage: completion.usage,
+            duration_ms: started.elapsed().as_millis() as u64,
+            timing: completion.timing,
+            result: HistoryEntry::preview(&completion.text),
+            partial: completion.interrupted.is_some().then(|| completion.text.clone()),
+        }
+    }
+
+    /// `result` cut to the logged preview length.
+    pub fn preview(result: &str) -> String {
+        truncate(result.trim(), RESULT_PREVIEW_CHARS)
+    }
+}
+
+/// Stable 64-bit FNV-1a hash of a filled prompt, as 16 hex digits.
+pub fn prompt_hash(prompt: &str) -> String {
+    let hash = prompt.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+fn history_path() -> Option<PathBuf> {
+    xa_dir().map(|dir| dir.join("history.jsonl"))
+}
+
+/// Append `entry` to the log, which only the user can read, unless
+/// `history` is off. With redaction on, the input, arguments and answer
+/// are logged with their secrets masked, as the provider saw them. A
+/// failure is reported but never fails the command that produced the entry.
+pub fn record(config: &Config, entry: &HistoryEntry) {
+    if !config.history {
+        return;
+    }
+    let result = (|| -> io::Result<()> {
+        let path = history_path().ok_or_else(|| io::Error::other("Could not determine config directory"))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let line = match config.redact.enabled {
+            true => serde_json::to_string(&redacted(&config.redact, entry).map_err(io::Error::other)?)?,
+            false => serde_json::to_string(entry)?,
+        };
+        let mut options = OpenOptions::new();
+        options.create(true).append(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(path)?;
+        // A log from before it was created private
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if file.metadata()?.permissions().mode() & 0o077 != 0 {
+                file.set_permissions(fs::Permissions::from_mode(0o600))?;
+            }
+        }
+        writeln!(file, "{}", line)
+    })();
+    if let Err(error) = result {
+        eprintln!("Warning: Could not write run history: {}", error);
+    }
+}
+
+/// `entry` with `[redact]`'s patterns masked in everything the user typed
+/// or got back, one placeholder per distinct secret across the fields.
+fn redacted(config: &RedactConfig, entry: &HistoryEntry) -> Result<HistoryEntry, XaError> {
+    let mut redactor = Redactor::new(config)?;
+    Ok(HistoryEntry {
+        input: redactor.redact(&entry.input),
+        args: entry.args.iter().map(|arg| redactor.redact(arg)).collect(),
+        result: redactor.redact(&entry.result),
+        partial: entry.partial.as_deref().map(|partial| redactor.redact(partial)),
+        ..entry.clone()
+    })
+}
+
+/// Every logged run, oldest first. Unreadable lines are skipped.
+pub fn load() -> Vec<HistoryEntry> {
+    let Some(content) = history_path().and_then(|path| fs::read_to_string(path).ok()) else {
+        return Vec::new();
+    };
+    content.lines().filter_map(|line| serde_json::from_str(line).ok()).collect()
+}
+
+/// The `n`th most recent run (1 = latest).
+pub fn nth_latest(n: usize) -> Option<HistoryEntry> {
+    let entries = load();
+    n.checked_sub(1).and_then(|back| entries.iter().rev().nth(back).cloned())
+}
+
+/// `xa history`: the latest `limit` runs, newest first, numbered for
+/// `--rerun`.
+pub fn print_history(limit: usize) {
+    let entries = load();
+    if entries.is_empty() {
+        println!("No runs recorded yet.");
+        return;
+    }
+    let shown: Vec<&HistoryEntry> = entries.iter().rev().take(limit).collect();
+    let width = shown.iter().map(|entry| entry.command.chars().count()).max().unwrap_or(0);
+    for (i, entry) in shown.iter().enumerate() {
+        let time = chrono::DateTime::from_timestamp(entry.time, 0)
+            .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+        let tokens = entry
+            .usage
+            .map(|u| format!("{}+{} tokens · ", u.prompt_tokens, u.completion_tokens))
+            .unwrap_or_default();
+        let speed = entry
+            .timing
+            .map(|timing| {
+                let rate = timing.tokens_per_second.map(|rate| format!(", {:.0} tok/s", rate)).unwrap_or_default();
+                format!(" (first token {:.2}s{})", timing.first_token_ms as f64 / 1000.0, rate)
+            })
+            .unwrap_or_default();
+        let command = if entry.args.is_empty() {
+            entry.command.clone()
+        } else {
+            format!("{} {}", entry.command, entry.args.join(" "))
+        };
+        println!(
+            "{:>3}  {}  {:<width$}  \x1b[90m{} · {}{:.1}s{}{}\x1b[0m",
+            i + 1,
+            time,
+            command,
+            entry.model,
+            tokens,
+            entry.duration_ms as f64 / 1000.0,
+            speed,
+            if entry.partial.is_some() { " · cut off" } else { "" },
+            width = width
+        );
+        println!("     \x1b[90m>\x1b[0m {}", truncate(&one_line(&entry.input), 100));
+        println!("     \x1b[90m<\x1b[0m {}", truncate(&one_line(&entry.result), 100));
+    }
+    if entries.len() > shown.len() {
+        println!("\n{} older runs not shown (use --limit).", entries.len() - shown.len());
+    }
+}
+
+fn one_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let cut: String = text.chars().take(max.saturating_sub(1)).collect();
+    format!("{}…", cut)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prompt_hash_is_stable_and_distinguishes_prompts() {
+        assert_eq!(prompt_hash(""), "cbf29ce484222325");
+        assert_eq!(prompt_hash("Translate: hi"), prompt_hash("Translate: hi"));
+        assert_ne!(prompt_hash("Translate: hi"), prompt_hash("Translate: ho"));
+    }
+
+    #[test]
+    fn preview_is_truncated() {
+        let long = "x".repeat(500);
+        assert_eq!(HistoryEntry::preview(&long).chars().count(), RESULT_PREVIEW_CHARS);
+        assert_eq!(HistoryEntry::preview("  short \n"), "short");
+    }
+
+    #[test]
+    fn redaction_masks_every_field_with_one_placeholder_per_secret() {
+        let entry = HistoryEntry {
+            time: 0,
+            command: "polish".into(),
+            pipe: false,
+            input: "Mail ana@example.com, key sk-abcdefghijklmnopqrstuvwx".into(),
+            args: vec!["ana@example.com".into()],
+            prompt_hash: prompt_hash(""),
+            model: "gpt-4o-mini".into(),
+            usage: None,
+            duration_ms: 0,
+            timing: None,
+            result: "Wrote to ana@example.com".into(),
+            partial: None,
+        };
+        let logged = redacted(&RedactConfig { enabled: true, ..RedactConfig::default() }, &entry).unwrap();
+        assert_eq!(logged.input, "Mail [EMAIL_1], key [API_KEY_1]");
+        assert_eq!(logged.args, ["[EMAIL_1]"]);
+        assert_eq!(logged.result, "Wrote to [EMAIL_1]");
+    }
+}
@@ -0,0 +1,68 @@
+//! `pre_hook` and `post_hook` on prompt entries: shell commands the input
+//! (before it fills the template) or the answer (after `postprocess`) is
+//! piped through, their output replacing the text. For example:
+//!
+//! ```toml
+//! [prompts.explain-log]
+//! template = "Explain this failure:\n\n{input}"
+//! pre_hook = "sed 's/\u001b\\[[0-9;]*m//g'"
+//!
+//! [prompts.rust-fn]
+//! template = "Write a Rust function that {input}. Reply with code only."
+//! postprocess = { extract_code_block = true }
+//! post_hook = "rustfmt --edition 2021"
+//! ```
+
+use crate::error::XaError;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// Pipe `text` through `command` (run by the shell) and return what it
+/// printed, without the final newline. `hook` names it in errors.
+pub async fn run(hook: &str, command: &str, text: &str) -> Result<String, XaError> {
+    tracing::debug!(hook, command, "running hook");
+    #[cfg(windows)]
+    let mut shell = Command::new("cmd");
+    #[cfg(windows)]
+    shell.args(["/C", command]);
+    #[cfg(not(windows))]
+    let mut shell = Command::new("sh");
+    #[cfg(not(windows))]
+    shell.args(["-c", command]);
+
+    let mut child = shell
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| XaError::Other(format!("{} `{}` could not be run: {}", hook, command, e)))?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = format!("{}\n", text);
+    // Written alongside reading the output, so a hook that streams (like
+    // `sed`) never blocks on a full pipe
+    let write = async move {
+        // A hook that ignores its input may exit before reading it
+        let _ = stdin.write_all(input.as_bytes()).await;
+    };
+    let (_, output) = tokio::join!(write, child.wait_with_output());
+    let output = output.map_err(|e| XaError::Other(format!("{} `{}` failed: {}", hook, command, e)))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(XaError::Other(format!("{} `{}` failed ({}): {}", hook, command, output.status, stderr.trim())));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim_end_matches(['\n', '\r']).to_string())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn pipes_text_through_the_shell() {
+        assert_eq!(run("post_hook", "tr a-z A-Z", "hello\nworld").await.unwrap(), "HELLO\nWORLD");
+        assert_eq!(run("pre_hook", "echo replaced", "ignored").await.unwrap(), "replaced");
+        let error = run("pre_hook", "echo oops >&2; exit 3", "x").await.unwrap_err().to_string();
+        assert!(error.starts_with("pre_hook `echo oops >&2; exit 3` failed") && error.ends_with("oops"), "{}", error);
+    }
+}
@@ -0,0 +1,75 @@
+//! The reqwest client shared by every API call (one-shot prompts, the agent,
+//! model listing), configured from config.toml's network settings.
+
+use crate::config::{self, Config};
+use std::fs;
+use std::sync::OnceLock;
+
+static CLIENT: OnceLock<Result<reqwest::Client, String>> = OnceLock::new();
+
+/// The shared client, built from the current config on first use.
+/// Cloning is cheap and shares the connection pool.
+pub fn client() -> Result<reqwest::Client, String> {
+    CLIENT
+        .get_or_init(|| {
+            let config = config::load_config_sync().map_err(|e| e.to_string())?;
+            build_client(&config)
+        })
+        .clone()
+}
+
+/// A client honouring `proxy`, `no_proxy`, `ca_bundle` and `insecure_tls`.
+pub fn build_client(config: &Config) -> Result<reqwest::Client, String> {
+    tracing::debug!(
+        proxy = config.proxy.as_deref(),
+        no_proxy = config.no_proxy.as_deref(),
+        ca_bundle = config.ca_bundle.as_deref(),
+        insecure_tls = config.insecure_tls,
+        "building HTTP client"
+    );
+    let mut builder = reqwest::Client::builder();
+    if let Some(url) = &config.proxy {
+        let proxy = reqwest::Proxy::all(url)
+            .map_err(|e| format!("invalid proxy '{}': {}", url, e))?
+            .no_proxy(config.no_proxy.as_deref().and_then(reqwest::NoProxy::from_string));
+        builder = builder.proxy(proxy);
+    }
+    if let Some(path) = &config.ca_bundle {
+        let pem = fs::read(path).map_err(|e| format!("cannot read ca_bundle {}: {}", path, e))?;
+        let certs = reqwest::Certificate::from_pem_bundle(&pem)
+            .map_err(|e| format!("invalid ca_bundle {}: {}", path, e))?;
+        if certs.is_empty() {
+            return Err(format!("ca_bundle {} contains no certificates", path));
+        }
+        builder = builder.tls_certs_merge(certs);
+    }
+    if config.insecure_tls {
+        builder = builder.tls_danger_accept_invalid_certs(true);
+    }
+    builder.build().map_err(|e| format!("cannot create HTTP client: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_with_proxy_and_rejects_bad_settings() {
+        let config = Config {
+            proxy: Some("socks5h://127.0.0.1:1080".to_string()),
+            no_proxy: Some("localhost,.internal".to_string()),
+            insecure_tls: true,
+            ..Config::default()
+        };
+        assert!(build_client(&config).is_ok());
+
+        let missing = Config {
+            ca_bundle: Some("/nonexistent/xa-ca.pem".to_string()),
+            ..Config::default()
+        };
+        assert!(build_client(&missing).unwrap_err().contains("/nonexistent/xa-ca.pem"));
+
+        let bad_proxy = Config { proxy: Some("not a url".to_string()), ..Config::default() };
+        assert!(build_client(&bad_proxy).unwrap_err().contains("invalid proxy"));
+    }
+}
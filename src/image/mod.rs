@@ -0,0 +1,81 @@
+//! Images for multimodal models, from `xa ask -i <path|url>`. They go out
+//! as `image_url` content parts next to the question: URLs as they are,
+//! local files inlined as base64 `data:` URLs.
+
+use crate::error::XaError;
+use std::fs;
+use std::path::Path;
+
+/// Providers reject larger images (OpenAI's limit is 20 MB per image).
+pub const MAX_IMAGE_BYTES: u64 = 20 * 1024 * 1024;
+
+/// The `image_url` for `source`: an `http(s)://` or `data:` URL unchanged,
+/// otherwise the file at that path as a `data:` URL.
+pub fn load(source: &str) -> Result<String, XaError> {
+    if source.starts_with("https://") || source.starts_with("http://") || source.starts_with("data:image/") {
+        return Ok(source.to_string());
+    }
+    let path = Path::new(source);
+    let mime = mime_type(path).ok_or_else(|| {
+        XaError::Usage(format!("{} is not a supported image (png, jpeg, gif or webp)", source))
+    })?;
+    let size = fs::metadata(path).map_err(|e| XaError::Usage(format!("cannot read {}: {}", source, e)))?.len();
+    if size > MAX_IMAGE_BYTES {
+        return Err(XaError::Usage(format!("{} is over the {} MB image limit", source, MAX_IMAGE_BYTES / 1024 / 1024)));
+    }
+    let bytes = fs::read(path).map_err(|e| XaError::Usage(format!("cannot read {}: {}", source, e)))?;
+    Ok(format!("data:{};base64,{}", mime, base64(&bytes)))
+}
+
+/// Image type by extension.
+fn mime_type(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
+
+/// Standard, padded base64.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for group in bytes.chunks(3) {
+        let n = group.iter().enumerate().fold(0u32, |n, (i, &byte)| n | (byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= group.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_files_and_passes_urls_through() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(&[0xff, 0xfe, 0xfd, 0x00]), "//79AA==");
+
+        assert_eq!(load("https://example.com/a.png").unwrap(), "https://example.com/a.png");
+        assert_eq!(mime_type(Path::new("shot.JPG")), Some("image/jpeg"));
+        assert!(matches!(load("notes.txt"), Err(XaError::Usage(_))));
+
+        let path = std::env::temp_dir().join(format!("xa-image-test-{}.png", std::process::id()));
+        fs::write(&path, b"\x89PNG").unwrap();
+        let url = load(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+        assert_eq!(url.unwrap(), "data:image/png;base64,iVBORw==");
+    }
+}
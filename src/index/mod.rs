@@ -0,0 +1,258 @@
+//! Local document retrieval: `xa index <dir>` splits the text files under a
+//! directory into chunks and stores their embeddings, and
+//! `xa ask --with-index <name>` puts the chunks closest to the question in
+//! front of it.
+//!
+//! An index is one JSON file, `<name>.json`, under the data directory
+//! (`~/.local/share/xa/index/` on Linux). Search is a linear cosine scan,
+//! which stays fast for the few thousand chunks of a notes folder or a
+//! repository; re-running `xa index` rebuilds the whole index.
+
+use crate::config::Config;
+use crate::embeddings;
+use crate::error::XaError;
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Target chunk size in bytes; chunks end on a line break.
+const CHUNK_BYTES: usize = 1500;
+/// Lines repeated at the start of the next chunk, so a passage cut in two
+/// is still found whole in one of them.
+const OVERLAP_LINES: usize = 2;
+/// Files larger than this are skipped (logs, dumps, generated code).
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+/// Chunks sent per embeddings request.
+const BATCH: usize = 64;
+/// Chunks put in front of a question.
+pub const TOP_CHUNKS: usize = 5;
+
+/// Directories never descended into.
+const SKIPPED_DIRS: &[&str] = &["node_modules", "target", "dist", "build", "__pycache__", "venv"];
+
+#[derive(Serialize, Deserialize)]
+pub struct Index {
+    pub name: String,
+    /// The indexed directory, absolute.
+    pub root: PathBuf,
+    /// Embedding model; questions must be embedded with the same one.
+    pub model: String,
+    /// Unix seconds.
+    pub created: i64,
+    pub chunks: Vec<Chunk>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Chunk {
+    /// Relative to the index root.
+    pub path: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub text: String,
+    pub embedding: Vec<f32>,
+}
+
+/// `$XA_CONFIG_DIR/index` when set, otherwise `<data dir>/xa/index`.
+pub fn index_dir() -> Option<PathBuf> {
+    match std::env::var_os("XA_CONFIG_DIR") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir).join("index")),
+        _ => dirs::data_dir().map(|dir| dir.join("xa").join("index")),
+    }
+}
+
+fn index_path(name: &str) -> Result<PathBuf, XaError> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(XaError::Usage(format!("Invalid index name '{}'", name)));
+    }
+    let dir = index_dir().ok_or_else(|| XaError::Other("Could not determine data directory".into()))?;
+    Ok(dir.join(format!("{}.json", name)))
+}
+
+/// Chunk and embed the text files under `root` and save them as index
+/// `name`, replacing any index of that name.
+pub async fn build(config: &Config, root: &Path, name: &str) -> Result<Index, XaError> {
+    let path = index_path(name)?;
+    let root = root
+        .canonicalize()
+        .map_err(|e| XaError::Usage(format!("cannot index {}: {}", root.display(), e)))?;
+    let mut chunks = Vec::new();
+    for file in text_files(&root) {
+        let Ok(text) = fs::read_to_string(&file) else { continue };
+        let relative = file.strip_prefix(&root).unwrap_or(&file).to_string_lossy().into_owned();
+        for (start_line, end_line, text) in chunk_text(&text) {
+            chunks.push(Chunk { path: relative.clone(), start_line, end_line, text, embedding: Vec::new() });
+        }
+    }
+    if chunks.is_empty() {
+        return Err(XaError::Usage(format!("No text files found under {}", root.display())));
+    }
+
+    let model = config.embedding_model();
+    let progress = indicatif::ProgressBar::new(chunks.len() as u64).with_style(
+        indicatif::ProgressStyle::with_template("Embedding {bar:30.cyan} {pos}/{len} chunks").expect("valid template"),
+    );
+    for batch in chunks.chunks_mut(BATCH) {
+        let inputs: Vec<String> = batch.iter().map(|chunk| format!("{}\n{}", chunk.path, chunk.text)).collect();
+        let embeddings = embeddings::embed(config, &model, &inputs).await?;
+        for (chunk, embedding) in batch.iter_mut().zip(embeddings) {
+            chunk.embedding = embedding;
+        }
+        progress.inc(batch.len() as u64);
+    }
+    progress.finish_and_clear();
+
+    let index = Index { name: name.to_string(), root, model, created: Local::now().timestamp(), chunks };
+    let json = serde_json::to_string(&index)?;
+    let write = || -> std::io::Result<()> {
+        fs::create_dir_all(path.parent().unwrap_or(Path::new(".")))?;
+        let partial = path.with_extension("json.tmp");
+        fs::write(&partial, json)?;
+        fs::rename(&partial, &path)
+    };
+    write().map_err(|e| XaError::Other(format!("cannot save index {}: {}", path.display(), e)))?;
+    Ok(index)
+}
+
+pub fn load(name: &str) -> Result<Index, XaError> {
+    let path = index_path(name)?;
+    let content = fs::read_to_string(&path).map_err(|_| {
+        XaError::Usage(format!("No index named '{}'. Create it with `xa index <dir> --name {}`.", name, name))
+    })?;
+    serde_json::from_str(&content).map_err(|e| XaError::Config(format!("invalid index {}: {}", path.display(), e)))
+}
+
+/// `xa index` with no directory: the saved indexes.
+pub fn print_list() -> Result<(), XaError> {
+    let dir = index_dir().ok_or_else(|| XaError::Other("Could not determine data directory".into()))?;
+    let mut names: Vec<String> = fs::read_dir(&dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok()?.file_name().to_str()?.strip_suffix(".json").map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default();
+    if names.is_empty() {
+        println!("No indexes yet. Create one with `xa index <dir>`.");
+        return Ok(());
+    }
+    names.sort();
+    for name in names {
+        match load(&name) {
+            Ok(index) => println!("{:<20} {:>6} chunks  {}", name, index.chunks.len(), index.root.display()),
+            Err(error) => println!("{:<20} {}", name, error),
+        }
+    }
+    Ok(())
+}
+
+/// The chunks of `index` most similar to `question`, best first.
+pub async fn retrieve<'a>(config: &Config, index: &'a Index, question: &str) -> Result<Vec<&'a Chunk>, XaError> {
+    let embedding = embeddings::embed(config, &index.model, &[question.to_string()]).await?.remove(0);
+    Ok(nearest(&index.chunks, &embedding, TOP_CHUNKS))
+}
+
+fn nearest<'a>(chunks: &'a [Chunk], query: &[f32], count: usize) -> Vec<&'a Chunk> {
+    let mut scored: Vec<(f32, &Chunk)> = chunks.iter().map(|chunk| (embeddings::cosine(&chunk.embedding, query), chunk)).collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored.into_iter().take(count).map(|(_, chunk)| chunk).collect()
+}
+
+/// The retrieved chunks as one labeled block to put ahead of the question.
+pub fn context(index: &Index, chunks: &[&Chunk]) -> String {
+    let mut context = format!("Excerpts from the local documents in {}:\n\n", index.root.display());
+    for chunk in chunks {
+        context.push_str(&format!(
+            "<excerpt source=\"{}:{}-{}\">\n{}\n</excerpt>\n\n",
+            chunk.path,
+            chunk.start_line,
+            chunk.end_line,
+            chunk.text.trim_end()
+        ));
+    }
+    context
+}
+
+/// Files under `root` worth indexing: not hidden, not in a build or
+/// dependency directory, not too large and not binary.
+fn text_files(root: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else { continue };
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with('.') {
+                continue;
+            }
+            let Ok(kind) = entry.file_type() else { continue };
+            if kind.is_dir() {
+                if !SKIPPED_DIRS.contains(&name.as_ref()) {
+                    pending.push(entry.path());
+                }
+            } else if kind.is_file() && entry.metadata().is_ok_and(|meta| meta.len() <= MAX_FILE_BYTES) && is_text(&entry.path()) {
+                files.push(entry.path());
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+fn is_text(path: &Path) -> bool {
+    fs::read(path).is_ok_and(|bytes| !bytes.contains(&0) && std::str::from_utf8(&bytes).is_ok())
+}
+
+/// Split `text` into chunks of about [`CHUNK_BYTES`] on line breaks, as
+/// (first line, last line, text) with 1-based line numbers.
+fn chunk_text(text: &str) -> Vec<(usize, usize, String)> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < lines.len() {
+        let mut end = start;
+        let mut size = 0;
+        while end < lines.len() && (size == 0 || size + lines[end].len() < CHUNK_BYTES) {
+            size += lines[end].len() + 1;
+            end += 1;
+        }
+        let chunk = lines[start..end].join("\n");
+        if !chunk.trim().is_empty() {
+            chunks.push((start + 1, end, chunk));
+        }
+        if end == lines.len() {
+            break;
+        }
+        start = (end - OVERLAP_LINES).max(start + 1);
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_overlap_and_nearest_ranks_by_cosine() {
+        let text: String = (1..=100).map(|i| format!("line {:03} {}\n", i, "x".repeat(40))).collect();
+        let chunks = chunk_text(&text);
+        assert!(chunks.len() > 2);
+        assert_eq!(chunks[0].0, 1);
+        assert_eq!(chunks[1].0, chunks[0].1 - OVERLAP_LINES + 1);
+        assert_eq!(chunks.last().unwrap().1, 100);
+        assert!(chunks.iter().all(|(_, _, chunk)| chunk.len() <= CHUNK_BYTES));
+        assert!(chunk_text("\n\n").is_empty());
+
+        let chunk = |path: &str, embedding: Vec<f32>| Chunk {
+            path: path.to_string(),
+            start_line: 1,
+            end_line: 1,
+            text: String::new(),
+            embedding,
+        };
+        let chunks = [chunk("a", vec![1.0, 0.0]), chunk("b", vec![0.6, 0.8]), chunk("c", vec![0.0, 0.0])];
+        let best: Vec<_> = nearest(&chunks, &[0.0, 1.0], 2).iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(best, ["b", "a"]);
+    }
+}
@@ -0,0 +1,94 @@
+//! Which way `translate` goes. The input's language is detected locally
+//! (with whatlang, nothing is sent); when it is already the target
+//! language, the text is translated into the secondary language instead,
+//! so one command toggles between two languages the way bilingual users
+//! use a translator.
+
+use crate::config::Config;
+use whatlang::Lang;
+
+/// ISO 639-1 codes of the languages whatlang detects.
+const CODES: &[(&str, Lang)] = &[
+    ("af", Lang::Afr), ("ak", Lang::Aka), ("am", Lang::Amh), ("ar", Lang::Ara), ("az", Lang::Aze), ("be", Lang::Bel),
+    ("bg", Lang::Bul), ("bn", Lang::Ben), ("ca", Lang::Cat), ("cs", Lang::Ces), ("da", Lang::Dan), ("de", Lang::Deu),
+    ("el", Lang::Ell), ("en", Lang::Eng), ("eo", Lang::Epo), ("es", Lang::Spa), ("et", Lang::Est), ("fa", Lang::Pes),
+    ("fi", Lang::Fin), ("fr", Lang::Fra), ("gu", Lang::Guj), ("he", Lang::Heb), ("hi", Lang::Hin), ("hr", Lang::Hrv),
+    ("hu", Lang::Hun), ("hy", Lang::Hye), ("id", Lang::Ind), ("it", Lang::Ita), ("ja", Lang::Jpn), ("jv", Lang::Jav),
+    ("ka", Lang::Kat), ("km", Lang::Khm), ("kn", Lang::Kan), ("ko", Lang::Kor), ("la", Lang::Lat), ("lt", Lang::Lit),
+    ("lv", Lang::Lav), ("mk", Lang::Mkd), ("ml", Lang::Mal), ("mr", Lang::Mar), ("my", Lang::Mya), ("nb", Lang::Nob),
+    ("ne", Lang::Nep), ("nl", Lang::Nld), ("no", Lang::Nob), ("or", Lang::Ori), ("pa", Lang::Pan), ("pl", Lang::Pol),
+    ("pt", Lang::Por), ("ro", Lang::Ron), ("ru", Lang::Rus), ("si", Lang::Sin), ("sk", Lang::Slk), ("sl", Lang::Slv),
+    ("sn", Lang::Sna), ("sr", Lang::Srp), ("sv", Lang::Swe), ("ta", Lang::Tam), ("te", Lang::Tel), ("th", Lang::Tha),
+    ("tk", Lang::Tuk), ("tl", Lang::Tgl), ("tr", Lang::Tur), ("uk", Lang::Ukr), ("ur", Lang::Urd), ("uz", Lang::Uzb),
+    ("vi", Lang::Vie), ("yi", Lang::Yid), ("zh", Lang::Cmn), ("zu", Lang::Zul),
+];
+
+/// The language a `target_lang` value names: an ISO 639-1 or 639-3 code,
+/// with or without a region or script (`zh-Hant`, `pt_BR`), or an English
+/// or native name (`Japanese`, `Deutsch`).
+fn lang_for(name: &str) -> Option<Lang> {
+    let name = name.trim().to_lowercase();
+    let code = name.split(['-', '_']).next().unwrap_or_default();
+    if let Some((_, lang)) = CODES.iter().find(|(iso, _)| *iso == code) {
+        return Some(*lang);
+    }
+    if name.contains("chinese") || name == "中文" {
+        return Some(Lang::Cmn);
+    }
+    Lang::from_code(code).or_else(|| {
+        Lang::all().iter().copied().find(|lang| lang.eng_name().to_lowercase() == name || lang.name().to_lowercase() == name)
+    })
+}
+
+/// The language of `text`, when whatlang is sure of it.
+fn detect(text: &str) -> Option<Lang> {
+    whatlang::detect(text).filter(|info| info.is_reliable()).map(|info| info.lang())
+}
+
+/// The language to flip to from `target`: `secondary_target_lang`, else
+/// English (none when the target is English). `off` turns flipping off.
+pub fn secondary(config: &Config, target: &str) -> Option<String> {
+    match config.secondary_target_lang.as_deref().map(str::trim) {
+        Some("off") => None,
+        Some(language) if !language.is_empty() => Some(language.to_string()),
+        _ => (lang_for(target) != Some(Lang::Eng)).then(|| "en".to_string()),
+    }
+}
+
+/// `secondary` when `input` is already written in `target` (and the two
+/// are different languages).
+pub fn flip(input: &str, target: &str, secondary: &str) -> Option<String> {
+    let target = lang_for(target)?;
+    (detect(input) == Some(target) && lang_for(secondary) != Some(target)).then(|| secondary.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flips_input_already_in_the_target_language() {
+        assert_eq!(lang_for("zh-Hant"), Some(Lang::Cmn));
+        assert_eq!(lang_for("pt_BR"), Some(Lang::Por));
+        assert_eq!(lang_for("Japanese"), Some(Lang::Jpn));
+        assert_eq!(lang_for("Simplified Chinese"), Some(Lang::Cmn));
+        assert_eq!(lang_for("deu"), Some(Lang::Deu));
+        assert_eq!(lang_for("Klingon"), None);
+
+        let chinese = "今天天气很好，我们一起去公园散步吧。";
+        let english = "The weather is lovely today, so let's take a walk in the park together.";
+        assert_eq!(flip(chinese, "zh", "en").as_deref(), Some("en"));
+        assert_eq!(flip(english, "zh", "en"), None);
+        assert_eq!(flip(english, "English", "zh").as_deref(), Some("zh"));
+        assert_eq!(flip(chinese, "zh", "Chinese"), None);
+        assert_eq!(flip(chinese, "Klingon", "en"), None);
+
+        let mut config = Config::default();
+        assert_eq!(secondary(&config, "zh").as_deref(), Some("en"));
+        assert_eq!(secondary(&config, "en"), None);
+        config.secondary_target_lang = Some("zh".into());
+        assert_eq!(secondary(&config, "en").as_deref(), Some("zh"));
+        config.secondary_target_lang = Some("off".into());
+        assert_eq!(secondary(&config, "zh"), None);
+    }
+}
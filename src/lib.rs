@@ -0,0 +1,82 @@
+//! The prompt-command engine behind the `xa` CLI, for programs that want to
+//! run xa's commands without scraping its output.
+//!
+//! The public API is four modules:
+//!
+//! - [`config`]: the user's `config.toml` (provider, model, personas, ...)
+//! - [`prompt`]: the prompt library (`prompts.toml`) and template filling
+//! - [`llm`]: the OpenAI-compatible chat client
+//! - [`store`]: the local secret store and its natural-language search
+//!
+//! with [`error::XaError`] for their errors. Everything reads the same files
+//! as the CLI, so a program gets the user's own providers and commands:
+//!
+//! ```no_run
+//! use xa_core::{config, llm, prompt};
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let config = config::load_config().await?;
+//! let prompts = prompt::load_prompt_config().await?;
+//! let translate = &prompts.prompts["translate"];
+//! let filled = prompt::process_template_with_args(&translate.template, "Good morning", &["fr".into()], translate.args.as_ref());
+//! let answer = llm::complete(&config.for_entry(translate), &filled).await?;
+//! println!("{}", answer.text);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The other modules are shared with the `xa` binary and may change between
+//! releases.
+
+pub mod config;
+pub mod error;
+pub mod llm;
+pub mod prompt;
+pub mod store;
+
+#[doc(hidden)]
+pub mod agent;
+#[doc(hidden)]
+pub mod budget;
+#[doc(hidden)]
+pub mod chunk;
+#[doc(hidden)]
+pub mod command;
+#[doc(hidden)]
+pub mod document;
+#[doc(hidden)]
+pub mod email;
+#[doc(hidden)]
+pub mod embeddings;
+#[doc(hidden)]
+pub mod history;
+#[doc(hidden)]
+pub mod hooks;
+#[doc(hidden)]
+pub mod http;
+#[doc(hidden)]
+pub mod language;
+#[doc(hidden)]
+pub mod local;
+#[doc(hidden)]
+pub mod output;
+#[doc(hidden)]
+pub mod output_filter;
+#[doc(hidden)]
+pub mod postprocess;
+#[doc(hidden)]
+pub mod project;
+#[doc(hidden)]
+pub mod redact;
+#[doc(hidden)]
+pub mod spinner;
+#[doc(hidden)]
+pub mod tools;
+#[doc(hidden)]
+pub mod transcript;
+#[doc(hidden)]
+pub mod update;
+#[doc(hidden)]
+pub mod watch;
+#[doc(hidden)]
+pub mod web;
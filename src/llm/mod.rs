@@ -1,112 +1,947 @@
 use crate::config::Config;
-use openai_api_rs::v1::api::OpenAIClient;
-use openai_api_rs::v1::chat_completion::{self, Content, MessageRole};
-use openai_api_rs::v1::chat_completion::chat_completion::ChatCompletionRequest;
-use openai_api_rs::v1::chat_completion::chat_completion_stream::{ChatCompletionStreamRequest, ChatCompletionStreamResponse};
+use crate::error::XaError;
+use crate::output::StreamView;
+use crate::redact::Redactor;
+use crate::spinner::Spinner;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, LazyLock, Mutex, PoisonError};
+use tokio::sync::OnceCell;
 use tokio_stream::StreamExt;
-use std::io::Write;
+use tracing::Instrument;
 use tokio::time::Instant;
 
-pub async fn process_with_llm(config: &Config, prompt: &str, stream: bool) -> Result<String, Box<dyn std::error::Error>> {
-    // Determine if we're using OpenRouter or OpenAI based on the base_url
-    let api_key = config.api_key.clone();
-    let mut client = OpenAIClient::builder()
-        .with_api_key(api_key);
+mod rate_limit;
 
-    // Set custom base URL if needed (for OpenRouter or other OpenAI-compatible APIs)
-    if !config.base_url.is_empty() && config.base_url != "https://api.openai.com/v1" {
-        client = client.with_endpoint(&config.base_url);
+/// Token counts reported by the API for one request (or summed over a
+/// pipeline's steps).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+}
+
+impl Usage {
+    /// The `usage` object of a response or final stream chunk, if present.
+    fn from_response(payload: &serde_json::Value) -> Option<Usage> {
+        let usage = payload.get("usage").filter(|u| u.is_object())?;
+        let count = |key: &str| usage[key].as_u64().unwrap_or(0) as u32;
+        Some(Usage { prompt_tokens: count("prompt_tokens"), completion_tokens: count("completion_tokens") })
+    }
+
+    /// `a + b`, where a missing side counts as nothing.
+    pub fn sum(a: Option<Usage>, b: Option<Usage>) -> Option<Usage> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(Usage {
+                prompt_tokens: a.prompt_tokens + b.prompt_tokens,
+                completion_tokens: a.completion_tokens + b.completion_tokens,
+            }),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+/// How fast a streamed answer arrived.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StreamTiming {
+    /// From sending the request to the first token, reasoning included.
+    pub first_token_ms: u64,
+    /// Completion tokens per second after the first one: the API's count
+    /// when it reports usage, otherwise one per chunk. `None` when the
+    /// answer came in a single chunk.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens_per_second: Option<f64>,
+}
+
+/// When a stream's chunks arrived, for its [`StreamTiming`].
+struct ChunkClock {
+    start: Instant,
+    first: Option<Instant>,
+    last: Instant,
+    chunks: u32,
+}
+
+impl ChunkClock {
+    fn start() -> ChunkClock {
+        let now = Instant::now();
+        ChunkClock { start: now, first: None, last: now, chunks: 0 }
     }
 
-    let client = client.build()?;
+    /// A chunk with text or reasoning in it arrived.
+    fn tick(&mut self) {
+        self.last = Instant::now();
+        self.first.get_or_insert(self.last);
+        self.chunks += 1;
+    }
+
+    fn timing(&self, usage: Option<Usage>) -> Option<StreamTiming> {
+        let first = self.first?;
+        let tokens = usage.map_or(self.chunks, |usage| usage.completion_tokens);
+        let generating = self.last.duration_since(first).as_secs_f64();
+        Some(StreamTiming {
+            first_token_ms: first.duration_since(self.start).as_millis() as u64,
+            tokens_per_second: (tokens > 1 && generating > 0.0).then(|| (tokens - 1) as f64 / generating),
+        })
+    }
+}
+
+/// A finished answer and what it cost.
+#[derive(Clone)]
+pub struct Completion {
+    pub text: String,
+    pub usage: Option<Usage>,
+    /// `text` is already on the terminal as rendered Markdown.
+    pub rendered: bool,
+    /// Time to first token and generation speed, for streamed answers.
+    pub timing: Option<StreamTiming>,
+    /// Why the stream broke off before the answer was finished; `text` is
+    /// what arrived until then.
+    pub interrupted: Option<String>,
+}
+
+/// Characters of a cut-off answer quoted back when asking for the rest.
+const CONTINUE_TAIL: usize = 600;
+
+/// `prompt` again, asking for the rest of `partial`, the answer to it that
+/// was cut off.
+pub fn continuation_prompt(prompt: &str, partial: &str) -> String {
+    let chars = partial.chars().count();
+    let tail: String = partial.chars().skip(chars.saturating_sub(CONTINUE_TAIL)).collect();
+    let ellipsis = if chars > CONTINUE_TAIL { "…" } else { "" };
+    format!(
+        "{}\n\n---\nYour answer to this was cut off. Continue from: {}{}\n\nReply with only the rest of the answer, starting exactly where it stopped, without repeating any of it.",
+        prompt, ellipsis, tail
+    )
+}
+
+/// `chat/completions` URL for an OpenAI-compatible base URL.
+fn chat_url(base_url: &str) -> String {
+    api_url(base_url, "chat/completions")
+}
+
+/// `endpoint` under an OpenAI-compatible base URL.
+pub fn api_url(base_url: &str, endpoint: &str) -> String {
+    let base = if base_url.is_empty() { "https://api.openai.com/v1" } else { base_url };
+    format!("{}/{}", base.trim_end_matches('/'), endpoint)
+}
+
+/// Text of one streamed `data:` payload. Reasoning deltas are read by
+/// [`delta_reasoning`]: the answer holds user-visible text only.
+fn delta_content(payload: &serde_json::Value) -> Option<&str> {
+    payload["choices"][0]["delta"]["content"].as_str()
+}
+
+/// Reasoning of one streamed `data:` payload from a thinking model:
+/// `reasoning_content` (DeepSeek and most servers) or `reasoning`
+/// (OpenRouter).
+fn delta_reasoning(payload: &serde_json::Value) -> Option<&str> {
+    let delta = &payload["choices"][0]["delta"];
+    delta["reasoning_content"].as_str().or_else(|| delta["reasoning"].as_str())
+}
+
+const THINK_OPEN: &str = "<think>";
+const THINK_CLOSE: &str = "</think>";
+
+/// A piece of a streamed answer.
+enum Part {
+    Reasoning(String),
+    Text(String),
+}
+
+/// Splits the `<think>…</think>` block that models served without a
+/// reasoning field put at the start of their answer off the text. Tags
+/// may be cut across deltas.
+#[derive(Default)]
+struct LeadingThink {
+    state: ThinkState,
+    held: String,
+}
+
+#[derive(Default, PartialEq)]
+enum ThinkState {
+    /// Not yet known whether the answer opens with `<think>`.
+    #[default]
+    Start,
+    Inside,
+    Done,
+}
+
+impl LeadingThink {
+    fn feed(&mut self, delta: &str) -> Vec<Part> {
+        if self.state == ThinkState::Done {
+            return vec![Part::Text(delta.to_string())];
+        }
+        self.held.push_str(delta);
+        let mut parts = Vec::new();
+        if self.state == ThinkState::Start {
+            let start = self.held.trim_start();
+            if let Some(rest) = start.strip_prefix(THINK_OPEN) {
+                self.held = rest.to_string();
+                self.state = ThinkState::Inside;
+            } else if THINK_OPEN.starts_with(start) {
+                return parts;
+            } else {
+                self.state = ThinkState::Done;
+                parts.push(Part::Text(std::mem::take(&mut self.held)));
+                return parts;
+            }
+        }
+        match self.held.find(THINK_CLOSE) {
+            Some(end) => {
+                let rest = self.held[end + THINK_CLOSE.len()..].trim_start().to_string();
+                self.held.truncate(end);
+                parts.push(Part::Reasoning(std::mem::take(&mut self.held)));
+                parts.push(Part::Text(rest));
+                self.state = ThinkState::Done;
+            }
+            None => {
+                // Hold back what may be the start of `</think>`
+                let keep = (1..THINK_CLOSE.len()).rev().find(|&n| self.held.ends_with(&THINK_CLOSE[..n])).unwrap_or(0);
+                let held = self.held.split_off(self.held.len() - keep);
+                parts.push(Part::Reasoning(std::mem::replace(&mut self.held, held)));
+            }
+        }
+        parts.retain(|part| !matches!(part, Part::Reasoning(text) | Part::Text(text) if text.is_empty()));
+        parts
+    }
+
+    /// What is still held when the stream ends.
+    fn finish(&mut self) -> Option<Part> {
+        let held = std::mem::take(&mut self.held);
+        match self.state {
+            _ if held.is_empty() => None,
+            ThinkState::Inside => Some(Part::Reasoning(held)),
+            _ => Some(Part::Text(held)),
+        }
+    }
+}
 
-    let model = config.default_model.as_deref().unwrap_or("gpt-4o-mini").to_string();
+/// `text` without a leading `<think>` block.
+fn strip_think(text: &str) -> String {
+    let mut split = LeadingThink::default();
+    split.feed(text).into_iter().chain(split.finish()).filter_map(|part| match part {
+        Part::Text(text) => Some(text),
+        Part::Reasoning(_) => None,
+    }).collect()
+}
 
+/// Chat request for `prompt`, after the persona's system message if there
+/// is one and with any images as content parts, followed by the
+/// `assistant_prefix` to continue, with `extra_body` merged over the
+/// defaults.
+fn request_body(config: &Config, prompt: &str, stream: bool) -> serde_json::Value {
+    let mut body = serde_json::json!({
+        "model": config.model(),
+        "stream": stream,
+        "messages": messages(config, prompt),
+    });
+    if let (Some(prefix), Some(messages)) = (&config.assistant_prefix, body["messages"].as_array_mut()) {
+        messages.push(serde_json::json!({ "role": "assistant", "content": prefix }));
+    }
     if stream {
-        // Streaming mode
-        // Don't print "Processing..." in interactive mode to avoid clutter
-
-        let start_time = Instant::now();
-
-        let req = ChatCompletionStreamRequest::new(
-            model,
-            vec![chat_completion::ChatCompletionMessage {
-                role: MessageRole::user,
-                content: Content::Text(prompt.to_string()),
-                name: None,
-                tool_calls: None,
-                tool_call_id: None,
-            }],
-        );
+        body["stream_options"] = serde_json::json!({ "include_usage": true });
+    }
+    if let Some(fields) = body.as_object_mut() {
+        fields.extend(config.extra_body.clone());
+        fields.extend(config.body_overrides.clone());
+    }
+    body
+}
+
+/// The system message, if any, and the user message for `prompt`.
+fn messages(config: &Config, prompt: &str) -> Vec<serde_json::Value> {
+    let mut messages = Vec::new();
+    if let Some(system) = &config.system_prompt {
+        messages.push(serde_json::json!({ "role": "system", "content": system }));
+    }
+    if config.images.is_empty() {
+        messages.push(serde_json::json!({ "role": "user", "content": prompt }));
+    } else {
+        let mut parts = vec![serde_json::json!({ "type": "text", "text": prompt })];
+        parts.extend(config.images.iter().map(|url| serde_json::json!({ "type": "image_url", "image_url": { "url": url } })));
+        messages.push(serde_json::json!({ "role": "user", "content": parts }));
+    }
+    messages
+}
 
-        let mut stream = client.chat_completion_stream(req).await?;
+/// POST of `body` to `url` with the key and `extra_headers` applied.
+pub fn build_request(config: &Config, url: &str, body: &serde_json::Value) -> Result<reqwest::RequestBuilder, XaError> {
+    let client = crate::http::client().map_err(XaError::Config)?;
 
-        let mut full_response = String::new();
+    tracing::debug!(url = %url, headers = config.extra_headers.len(), "POST");
+    tracing::trace!(body = %body, "request body");
+    authorize(config, client.post(url).json(body))
+}
 
-        while let Some(result) = stream.next().await {
-            match result {
-                ChatCompletionStreamResponse::Content(content) => {
-                    // Only print if content is not empty to avoid printing artifacts like >>>>>>>>
-                    if !content.is_empty() {
-                        print!("{}", content);
-                        std::io::stdout().flush()?;
-                        full_response.push_str(&content);
-                    }
-                }
-                // v10 surfaces reasoning separately. Legacy prompt mode only
-                // returns user-visible completion text, so keep it out of the
-                // rendered response just as providers that embed reasoning do.
-                ChatCompletionStreamResponse::Reasoning(_) => {}
-                ChatCompletionStreamResponse::ToolCall(tool_calls) => {
-                    // Handle tool calls if needed
-                    eprintln!("Tool call received: {:?}", tool_calls);
-                }
-                ChatCompletionStreamResponse::Done => {
-                    // Stream completed
-                    break;
-                }
+/// `request` with the key and `extra_headers` applied.
+pub fn authorize(config: &Config, mut request: reqwest::RequestBuilder) -> Result<reqwest::RequestBuilder, XaError> {
+    if !config.api_key.is_empty() {
+        request = request.bearer_auth(&config.api_key);
+    }
+    for (name, value) in &config.extra_headers {
+        let name = reqwest::header::HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| XaError::Config(format!("Invalid header name in extra_headers: '{}'", name)))?;
+        let value = reqwest::header::HeaderValue::from_str(value)
+            .map_err(|_| XaError::Config(format!("Invalid value for header '{}' in extra_headers", name)))?;
+        request = request.header(name, value);
+    }
+    Ok(request)
+}
+
+/// One non-streaming completion, printing nothing.
+pub async fn complete(config: &Config, prompt: &str) -> Result<Completion, XaError> {
+    let key = format!("{}\n{:?}\n{}", config.base_url, config.redact, request_body(config, prompt, false));
+    coalesce(key, || complete_once(config, prompt)).await
+}
+
+/// A request being answered, shared by everyone who made it.
+type Flight = Arc<OnceCell<Result<Completion, XaError>>>;
+
+/// The requests [`complete`] is waiting on, by what they send. The same
+/// request made meanwhile (a hotkey pressed twice, a line repeated in a
+/// batch, two clients of `xa serve`) waits for that answer instead of
+/// paying for another; one made after it arrived is sent again.
+static IN_FLIGHT: LazyLock<Mutex<HashMap<String, Flight>>> = LazyLock::new(Default::default);
+
+/// The result of `request`, or of the identical one (same `key`) already
+/// in flight.
+async fn coalesce<F>(key: String, request: impl FnOnce() -> F) -> Result<Completion, XaError>
+where
+    F: Future<Output = Result<Completion, XaError>>,
+{
+    let flight = IN_FLIGHT.lock().unwrap_or_else(PoisonError::into_inner).entry(key.clone()).or_default().clone();
+    let mut shared = true;
+    let result = flight
+        .get_or_init(|| {
+            shared = false;
+            request()
+        })
+        .await
+        .clone();
+    if shared {
+        tracing::debug!("answered by an identical request in flight");
+    }
+    let mut flights = IN_FLIGHT.lock().unwrap_or_else(PoisonError::into_inner);
+    if flights.get(&key).is_some_and(|current| Arc::ptr_eq(current, &flight)) {
+        flights.remove(&key);
+    }
+    result
+}
+
+async fn complete_once(config: &Config, prompt: &str) -> Result<Completion, XaError> {
+    let mut redactor = Redactor::new(&config.redact)?;
+    let body = request_body(config, &redactor.redact(prompt), false);
+    let start_time = Instant::now();
+    let result = send(config, &body, &redactor).instrument(request_span(&body)).await;
+    if let Err(error) = &result {
+        tracing::warn!(error = %error, "request failed");
+    }
+    if config.log_transcripts {
+        let error = result.as_ref().err().map(|e| e.to_string()).unwrap_or_default();
+        crate::transcript::record(config, &body, result.as_ref().map_err(|_| error.as_str()), start_time.elapsed());
+    }
+    result
+}
+
+/// Span around one chat request; closing it logs the request's duration.
+fn request_span(body: &serde_json::Value) -> tracing::Span {
+    tracing::info_span!(
+        "chat_request",
+        model = body["model"].as_str().unwrap_or_default(),
+        stream = body["stream"].as_bool().unwrap_or(false)
+    )
+}
+
+/// Account for a response: charge its completion tokens (`estimate` when
+/// the API left them out) to the rate limit, and add its reported usage to
+/// the budget ledger.
+fn settle(config: &Config, usage: Option<Usage>, estimate: u32) {
+    rate_limit::charge(config, usage.map_or(estimate, |usage| usage.completion_tokens));
+    if let Some(usage) = usage {
+        crate::budget::record(&config.model(), usage);
+    }
+}
+
+async fn send(config: &Config, body: &serde_json::Value, redactor: &Redactor) -> Result<Completion, XaError> {
+    Ok(send_choices(config, body, redactor).await?.swap_remove(0))
+}
+
+/// One non-streaming request; a completion per choice in the reply (at
+/// least one, each after the `assistant_prefix` and with `redactor`'s
+/// placeholders restored), the usage on the first.
+async fn send_choices(config: &Config, body: &serde_json::Value, redactor: &Redactor) -> Result<Vec<Completion>, XaError> {
+    let (texts, mut usage) = match crate::local::model_path(config) {
+        Some(path) => {
+            let (text, usage) = crate::local::complete(path, body).await?;
+            (vec![text], usage)
+        }
+        None => {
+            rate_limit::acquire(config, body).await;
+            let request = build_request(config, &chat_url(&config.base_url), body)?;
+            let response = request.send().await?;
+            tracing::info!(status = %response.status(), "response");
+            if !response.status().is_success() {
+                let status = response.status();
+                return Err(XaError::from_status(status, response.text().await.unwrap_or_default()));
             }
+            let result: serde_json::Value = response.json().await?;
+            let texts: Vec<String> = result["choices"]
+                .as_array()
+                .map(|choices| choices.iter().map(|choice| choice["message"]["content"].as_str().unwrap_or_default().to_string()).collect())
+                .unwrap_or_default();
+            let usage = Usage::from_response(&result);
+            let estimate = texts.iter().map(|text| text.len() / 4).sum::<usize>() as u32;
+            settle(config, usage, estimate);
+            (texts, usage)
         }
+    };
+    let mut texts: Vec<String> = texts.iter().map(|text| redactor.restore(&strip_think(text))).collect();
+    if texts.is_empty() {
+        texts.push(String::new());
+    }
+    if let Some(prefix) = &config.assistant_prefix {
+        texts.iter_mut().for_each(|text| text.insert_str(0, prefix));
+    }
+    Ok(texts.into_iter().map(|text| Completion { text, usage: usage.take(), rendered: false, timing: None, interrupted: None }).collect())
+}
 
-        let duration = start_time.elapsed();
-        // Only print timing info if we actually received content
-        if !full_response.trim().is_empty() {
-            println!("\n\n(Completed in {:.2?})", duration);
+/// `n` completions of `prompt`, printing nothing. They are asked for in
+/// one request with `n`; what a provider without `n` leaves out (all of
+/// them, when it refuses the field) is asked for in parallel requests.
+pub async fn complete_n(config: &Config, prompt: &str, n: usize) -> Result<Vec<Completion>, XaError> {
+    let mut redactor = Redactor::new(&config.redact)?;
+    let mut body = request_body(config, &redactor.redact(prompt), false);
+    body["n"] = n.into();
+    let start_time = Instant::now();
+    let result = send_choices(config, &body, &redactor).instrument(request_span(&body)).await;
+    if config.log_transcripts {
+        let error = result.as_ref().err().map(|e| e.to_string()).unwrap_or_default();
+        let first = result.as_ref().map(|completions| &completions[0]);
+        crate::transcript::record(config, &body, first.map_err(|_| error.as_str()), start_time.elapsed());
+    }
+    let mut completions = match result {
+        Ok(completions) => completions,
+        Err(XaError::Api { status: 400 | 422, message }) => {
+            tracing::debug!(message = %message, "n refused; sending separate requests");
+            Vec::new()
         }
+        Err(e) => return Err(e),
+    };
+    completions.truncate(n);
+    let tasks: Vec<_> = (completions.len()..n)
+        .map(|_| {
+            let (config, prompt) = (config.clone(), prompt.to_string());
+            tokio::spawn(async move { complete(&config, &prompt).await })
+        })
+        .collect();
+    for task in tasks {
+        completions.push(task.await.map_err(|e| XaError::Other(e.to_string()))??);
+    }
+    Ok(completions)
+}
+
+pub async fn process_with_llm(config: &Config, prompt: &str, stream: bool) -> Result<String, XaError> {
+    let completion = run_llm(config, prompt, stream).await?;
+    match completion.interrupted {
+        Some(why) => Err(XaError::Network(format!("the answer was cut off ({})", why))),
+        None => Ok(completion.text),
+    }
+}
 
-        Ok(full_response)
+/// [`process_with_llm`], also returning the token usage the API reported.
+pub async fn run_llm(config: &Config, prompt: &str, stream: bool) -> Result<Completion, XaError> {
+    let mut redactor = Redactor::new(&config.redact)?;
+    let body = request_body(config, &redactor.redact(prompt), stream);
+    let start_time = Instant::now();
+    let result = if stream {
+        stream_completion(config, &body, &redactor).instrument(request_span(&body)).await
     } else {
         // Non-streaming mode
-        println!("Processing...");
-
-        let start_time = Instant::now();
-
-        let req = ChatCompletionRequest::new(
-            model,
-            vec![chat_completion::ChatCompletionMessage {
-                role: MessageRole::user,
-                content: Content::Text(prompt.to_string()),
-                name: None,
-                tool_calls: None,
-                tool_call_id: None,
-            }],
-        );
+        let spinner = Spinner::start("Processing");
+        let result = send(config, &body, &redactor).instrument(request_span(&body)).await;
+        spinner.stop();
+        if result.is_ok() {
+            println!("(Completed in {:.2?})", start_time.elapsed());
+        }
+        result
+    };
+    if let Err(error) = &result {
+        tracing::warn!(error = %error, "request failed");
+    }
+    if config.log_transcripts {
+        let error = result.as_ref().err().map(|e| e.to_string()).unwrap_or_default();
+        crate::transcript::record(config, &body, result.as_ref().map_err(|_| error.as_str()), start_time.elapsed());
+    }
+    result
+}
 
-        let result = client.chat_completion(req).await?;
+/// Model turns one `xa ask --tools` question may take before giving up.
+const MAX_TOOL_ROUNDS: usize = 10;
 
-        let content = if !result.inner.choices.is_empty() {
-            if let Some(content) = &result.inner.choices[0].message.content {
-                content.clone()
-            } else {
-                String::new()
+/// Answer `prompt` with `tools` available: each tool call the model makes
+/// is run (shell commands and file writes only once `confirm` agrees), its
+/// result sent back, and so on until the model answers in text. Progress
+/// goes to stderr; the answer is returned unprinted.
+pub async fn run_agent(
+    config: &Config,
+    prompt: &str,
+    tools: &[std::sync::Arc<dyn crate::tools::Tool>],
+    confirm: &dyn Fn(&str) -> bool,
+) -> Result<Completion, XaError> {
+    if crate::local::model_path(config).is_some() {
+        return Err(XaError::Usage("local models cannot call tools; use a provider's model with --tools".into()));
+    }
+    let start_time = Instant::now();
+    let mut redactor = Redactor::new(&config.redact)?;
+    let mut messages = messages(config, &redactor.redact(prompt));
+    let declared: Vec<serde_json::Value> = tools
+        .iter()
+        .map(|tool| {
+            serde_json::json!({
+                "type": "function",
+                "function": { "name": tool.name(), "description": tool.description(), "parameters": tool.parameters() },
+            })
+        })
+        .collect();
+    let mut usage = None;
+    for _ in 0..MAX_TOOL_ROUNDS {
+        let mut body = serde_json::json!({
+            "model": config.model(),
+            "stream": false,
+            "messages": messages,
+            "tools": declared,
+            "tool_choice": "auto",
+        });
+        if let Some(fields) = body.as_object_mut() {
+            fields.extend(config.extra_body.clone());
+            fields.extend(config.body_overrides.clone());
+        }
+        let spinner = Spinner::start("Thinking");
+        let result = send_message(config, &body).instrument(request_span(&body)).await;
+        spinner.stop();
+        if config.log_transcripts {
+            let record = result.as_ref().map(|(message, usage)| Completion {
+                text: message.to_string(),
+                usage: *usage,
+                rendered: false,
+                timing: None,
+                interrupted: None,
+            });
+            let error = result.as_ref().err().map(|e| e.to_string()).unwrap_or_default();
+            crate::transcript::record(config, &body, record.as_ref().map_err(|_| error.as_str()), start_time.elapsed());
+        }
+        let (message, round_usage) = result?;
+        usage = Usage::sum(usage, round_usage);
+        let calls = message["tool_calls"].as_array().cloned().unwrap_or_default();
+        if calls.is_empty() {
+            println!("(Completed in {:.2?})", start_time.elapsed());
+            let text = redactor.restore(&strip_think(message["content"].as_str().unwrap_or_default()));
+            return Ok(Completion { text, usage, rendered: false, timing: None, interrupted: None });
+        }
+        messages.push(message);
+        for call in calls {
+            let name = call["function"]["name"].as_str().unwrap_or_default();
+            // Tools work on the real values; the model only sees placeholders
+            let mut args: serde_json::Value =
+                serde_json::from_str(call["function"]["arguments"].as_str().unwrap_or("{}")).unwrap_or_default();
+            redactor.restore_value(&mut args);
+            let output = redactor.redact(&run_tool(name, args, tools, confirm).await);
+            messages.push(serde_json::json!({ "role": "tool", "tool_call_id": call["id"], "content": output }));
+        }
+    }
+    Err(XaError::Other(format!("no answer after {} rounds of tool calls", MAX_TOOL_ROUNDS)))
+}
+
+/// Run one tool call, announcing it on stderr, and return what to send
+/// back to the model (errors and refusals included).
+async fn run_tool(
+    name: &str,
+    args: serde_json::Value,
+    tools: &[std::sync::Arc<dyn crate::tools::Tool>],
+    confirm: &dyn Fn(&str) -> bool,
+) -> String {
+    let arg = |key: &str| args[key].as_str().unwrap_or_default().to_string();
+    let (summary, question) = match name {
+        "bash" => (format!("$ {}", arg("command")), format!("Run `{}`? (Y/n): ", arg("command"))),
+        "write" => (format!("write {}", arg("path")), format!("Write {} ({} bytes)? (Y/n): ", arg("path"), arg("content").len())),
+        "read" => (format!("read {}", arg("path")), format!("Read {}, outside the working directory? (Y/n): ", arg("path"))),
+        "fetch" => (format!("fetch {}", arg("url")), format!("Fetch {}? (Y/n): ", arg("url"))),
+        _ => (format!("{} {}", name, args), format!("Call {}? (Y/n): ", name)),
+    };
+    eprintln!("{}", crate::output::paint("90", &format!("→ {}", summary)));
+    let guarded = crate::tools::find_tool(name, tools).is_some_and(|tool| tool.needs_confirmation(&args));
+    if guarded && !confirm(&question) {
+        return "The user declined to run this.".to_string();
+    }
+    let command = (name == "bash").then(|| arg("command"));
+    let cancel = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let raw = match crate::tools::call_tool(name, args, tools, cancel).await {
+        Ok(output) => output,
+        Err(error) => format!("error: {}", error),
+    };
+    crate::output_filter::process(name, command.as_deref(), raw).output
+}
+
+/// One non-streaming request; the reply's `message` and usage.
+async fn send_message(config: &Config, body: &serde_json::Value) -> Result<(serde_json::Value, Option<Usage>), XaError> {
+    rate_limit::acquire(config, body).await;
+    let request = build_request(config, &chat_url(&config.base_url), body)?;
+    let response = request.send().await?;
+    tracing::info!(status = %response.status(), "response");
+    if !response.status().is_success() {
+        let status = response.status();
+        return Err(XaError::from_status(status, response.text().await.unwrap_or_default()));
+    }
+    let result: serde_json::Value = response.json().await?;
+    let message = result["choices"][0]["message"].clone();
+    if !message.is_object() {
+        return Err(XaError::Other(format!("invalid response: {}", result)));
+    }
+    let usage = Usage::from_response(&result);
+    settle(config, usage, message.to_string().len() as u32 / 4);
+    Ok((message, usage))
+}
+
+/// Streaming mode: print deltas as they arrive.
+async fn stream_completion(config: &Config, body: &serde_json::Value, redactor: &Redactor) -> Result<Completion, XaError> {
+    // No spinner here: the first delta arrives quickly and replaces it
+
+    let start_time = Instant::now();
+    let mut view = StreamView::new();
+    let rendered = view.is_live();
+    // With the reasoning hidden, a spinner covers the wait for the answer
+    let mut thinking: Option<Spinner> = None;
+    let mut completion = read_stream(config, body, redactor, |part| match part {
+        Part::Reasoning(_) if config.hide_thinking => {
+            thinking.get_or_insert_with(|| Spinner::start("Thinking"));
+            Ok(())
+        }
+        Part::Reasoning(text) => view.push_reasoning(&text),
+        Part::Text(text) => {
+            if let Some(spinner) = thinking.take() {
+                spinner.stop();
+            }
+            view.push(&text)
+        }
+    })
+    .await?;
+    if let Some(spinner) = thinking.take() {
+        spinner.stop();
+    }
+
+    view.finish()?;
+    let duration = start_time.elapsed();
+    // Only print timing info if we actually received a whole answer
+    if !completion.text.trim().is_empty() && completion.interrupted.is_none() {
+        // Rendered Markdown already ends its last line
+        let gap = if rendered { "" } else { "\n" };
+        println!("{}\n(Completed in {:.2?})", gap, duration);
+    }
+
+    completion.rendered = rendered;
+    Ok(completion)
+}
+
+/// Stream a completion of `prompt`, handing each delta to `on_delta`
+/// instead of printing it (`xa serve --http`).
+pub async fn stream_to(
+    config: &Config,
+    prompt: &str,
+    mut on_delta: impl FnMut(&str),
+) -> Result<Completion, XaError> {
+    let mut redactor = Redactor::new(&config.redact)?;
+    let body = request_body(config, &redactor.redact(prompt), true);
+    let start_time = Instant::now();
+    let result = read_stream(config, &body, &redactor, |part| {
+        if let Part::Text(text) = part {
+            on_delta(&text);
+        }
+        Ok(())
+    })
+    .instrument(request_span(&body))
+    .await
+    .and_then(|completion| match completion.interrupted {
+        Some(why) => Err(XaError::Network(format!("the answer was cut off ({})", why))),
+        None => Ok(completion),
+    });
+    if config.log_transcripts {
+        let error = result.as_ref().err().map(|e| e.to_string()).unwrap_or_default();
+        crate::transcript::record(config, &body, result.as_ref().map_err(|_| error.as_str()), start_time.elapsed());
+    }
+    result
+}
+
+/// Send a streaming request and feed its reasoning and text deltas to
+/// `on_delta`, the `assistant_prefix` ahead of the first text and
+/// `redactor`'s placeholders restored; the whole text (without the
+/// reasoning), the usage reported at the end and how fast it came. A
+/// connection lost after the first delta ends the answer early instead of
+/// failing it, as does a stream that stops without finishing.
+async fn read_stream(
+    config: &Config,
+    body: &serde_json::Value,
+    redactor: &Redactor,
+    mut on_delta: impl FnMut(Part) -> std::io::Result<()>,
+) -> Result<Completion, XaError> {
+    let mut clock = ChunkClock::start();
+    let mut interrupted = None;
+    let mut full_response = String::new();
+    let mut usage = None;
+    let mut think = LeadingThink::default();
+    let mut prefix = config.assistant_prefix.clone();
+    let mut restorer = redactor.restorer();
+    let mut restore = |part: Part| match part {
+        Part::Text(text) => Some(restorer.feed(&text)).filter(|text| !text.is_empty()).map(Part::Text),
+        reasoning => Some(reasoning),
+    };
+    let mut deliver = |part: Part, full_response: &mut String| {
+        let part = match part {
+            Part::Text(text) => Part::Text(prefix.take().unwrap_or_default() + &text),
+            reasoning => reasoning,
+        };
+        if let Part::Text(text) = &part {
+            full_response.push_str(text);
+        }
+        on_delta(part)
+    };
+
+    match crate::local::model_path(config) {
+        Some(path) => {
+            let mut events = crate::local::start(path, body)?;
+            while let Some(event) = events.recv().await {
+                match event.map_err(XaError::Other)? {
+                    crate::local::Event::Text(piece) => {
+                        clock.tick();
+                        for part in think.feed(&piece).into_iter().filter_map(&mut restore) {
+                            deliver(part, &mut full_response)?;
+                        }
+                    }
+                    crate::local::Event::Done(done) => usage = Some(done),
+                }
+            }
+        }
+        None => {
+            rate_limit::acquire(config, body).await;
+            let request = build_request(config, &chat_url(&config.base_url), body)?;
+            let response = request.send().await.map_err(XaError::from)?;
+            tracing::info!(status = %response.status(), "response");
+            if !response.status().is_success() {
+                let status = response.status();
+                return Err(XaError::from_status(status, response.text().await.unwrap_or_default()));
             }
-        } else {
-            String::new()
+
+            let status = response.status().as_u16();
+            let mut stream = response.bytes_stream();
+            let mut buf: Vec<u8> = Vec::new();
+            let mut finished = false;
+
+            'read: while let Some(chunk) = stream.next().await {
+                let chunk = match chunk {
+                    Ok(chunk) => chunk,
+                    Err(error) if clock.first.is_some() => {
+                        interrupted = Some(XaError::from(error).to_string());
+                        break;
+                    }
+                    Err(error) => return Err(XaError::from(error)),
+                };
+                buf.extend_from_slice(&chunk);
+                // Split on complete lines only so multi-byte characters that
+                // straddle two chunks are decoded intact.
+                while let Some(nl) = buf.iter().position(|&b| b == b'\n') {
+                    let line: Vec<u8> = buf.drain(..=nl).collect();
+                    let line = String::from_utf8_lossy(&line);
+                    let Some(data) = line.trim().strip_prefix("data:") else { continue };
+                    let data = data.trim();
+                    if data == "[DONE]" {
+                        finished = true;
+                        break 'read;
+                    }
+                    let Ok(payload) = serde_json::from_str::<serde_json::Value>(data) else { continue };
+                    if let Some(error) = payload.get("error") {
+                        // Reported inside a successful response, so keep its status.
+                        return Err(XaError::Api { status, message: error.to_string() });
+                    }
+                    usage = Usage::from_response(&payload).or(usage);
+                    finished |= payload["choices"][0]["finish_reason"].is_string();
+                    let reasoning = delta_reasoning(&payload).filter(|r| !r.is_empty());
+                    let content = delta_content(&payload).filter(|c| !c.is_empty());
+                    if reasoning.is_some() || content.is_some() {
+                        clock.tick();
+                    }
+                    if let Some(reasoning) = reasoning {
+                        deliver(Part::Reasoning(reasoning.to_string()), &mut full_response)?;
+                    }
+                    // Only print if content is not empty to avoid printing artifacts like >>>>>>>>
+                    if let Some(content) = content {
+                        for part in think.feed(content).into_iter().filter_map(&mut restore) {
+                            deliver(part, &mut full_response)?;
+                        }
+                    }
+                }
+            }
+            if !finished && interrupted.is_none() && clock.first.is_some() {
+                interrupted = Some("the stream ended before the answer was finished".to_string());
+            }
+            settle(config, usage, clock.chunks);
+        }
+    }
+    if let Some(part) = think.finish().and_then(&mut restore) {
+        deliver(part, &mut full_response)?;
+    }
+    let held = restorer.finish();
+    if !held.is_empty() || (full_response.is_empty() && config.assistant_prefix.is_some()) {
+        deliver(Part::Text(held), &mut full_response)?;
+    }
+    let timing = clock.timing(usage);
+    if let Some(why) = &interrupted {
+        tracing::warn!(chars = full_response.len(), reason = %why, "stream cut off");
+    }
+    tracing::debug!(chars = full_response.len(), usage = ?usage, timing = ?timing, "stream finished");
+    Ok(Completion { text: full_response, usage, rendered: false, timing, interrupted })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_chat_url_and_reads_deltas() {
+        assert_eq!(chat_url("http://localhost:11434/v1/"), "http://localhost:11434/v1/chat/completions");
+        assert_eq!(chat_url(""), "https://api.openai.com/v1/chat/completions");
+
+        let text: serde_json::Value =
+            serde_json::from_str(r#"{"choices":[{"delta":{"content":"Hi"}}]}"#).unwrap();
+        let reasoning: serde_json::Value =
+            serde_json::from_str(r#"{"choices":[{"delta":{"reasoning_content":"hmm"}}]}"#).unwrap();
+        assert_eq!(delta_content(&text), Some("Hi"));
+        assert_eq!(delta_content(&reasoning), None);
+        assert_eq!(delta_reasoning(&reasoning), Some("hmm"));
+    }
+
+    #[test]
+    fn times_the_first_token_and_the_rate_after_it() {
+        let start = Instant::now();
+        let at = |ms| start + std::time::Duration::from_millis(ms);
+        let clock = ChunkClock { start, first: Some(at(250)), last: at(2250), chunks: 21 };
+        assert_eq!(clock.timing(None), Some(StreamTiming { first_token_ms: 250, tokens_per_second: Some(10.0) }));
+        let usage = Usage { prompt_tokens: 5, completion_tokens: 81 };
+        assert_eq!(clock.timing(Some(usage)).unwrap().tokens_per_second, Some(40.0));
+
+        let single = ChunkClock { start, first: Some(at(90)), last: at(90), chunks: 1 };
+        assert_eq!(single.timing(None), Some(StreamTiming { first_token_ms: 90, tokens_per_second: None }));
+        assert_eq!(ChunkClock { first: None, ..single }.timing(None), None);
+    }
+
+    #[test]
+    fn asks_for_the_rest_of_a_cut_off_answer() {
+        let prompt = continuation_prompt("Explain TCP.", "TCP is a connection-oriented");
+        assert!(prompt.starts_with("Explain TCP.\n\n---\n"));
+        assert!(prompt.contains("Continue from: TCP is a connection-oriented\n"));
+        let long = "x".repeat(CONTINUE_TAIL) + "end";
+        assert!(continuation_prompt("Q", &long).contains(&format!("Continue from: …{}end\n", "x".repeat(CONTINUE_TAIL - 3))));
+    }
+
+    #[tokio::test]
+    async fn identical_requests_in_flight_share_one_answer() {
+        let sent = std::sync::atomic::AtomicUsize::new(0);
+        let request = |text: &'static str| {
+            let sent = &sent;
+            move || async move {
+                sent.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                Ok(Completion { text: text.to_string(), usage: None, rendered: false, timing: None, interrupted: None })
+            }
+        };
+        let (a, b, other) = tokio::join!(
+            coalesce("same".into(), request("first")),
+            coalesce("same".into(), request("second")),
+            coalesce("other".into(), request("other")),
+        );
+        assert_eq!((a.unwrap().text, b.unwrap().text, other.unwrap().text), ("first".into(), "first".into(), "other".into()));
+        assert_eq!(sent.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+        assert_eq!(coalesce("same".into(), request("again")).await.unwrap().text, "again");
+        assert!(!IN_FLIGHT.lock().unwrap().contains_key("same"));
+
+        let refused = || async {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            Err(XaError::Auth { status: 401, message: "bad key".into() })
+        };
+        let (a, b) = tokio::join!(coalesce("refused".into(), refused), coalesce("refused".into(), refused));
+        assert!(matches!((a, b), (Err(XaError::Auth { .. }), Err(XaError::Auth { .. }))));
+    }
+
+    #[tokio::test]
+    async fn declined_reads_and_fetches_do_not_run() {
+        let tools = crate::tools::ask_tools();
+        let outside = std::env::temp_dir().join(format!("xa-tool-read-{}", std::process::id()));
+        std::fs::write(&outside, "api_key = \"sk-private\"").unwrap();
+        let asked = std::cell::Cell::new(0);
+        let decline = |_: &str| {
+            asked.set(asked.get() + 1);
+            false
         };
+        let read_outside = run_tool("read", serde_json::json!({ "path": outside }), &tools, &decline).await;
+        let fetch = run_tool("fetch", serde_json::json!({ "url": "http://127.0.0.1:9/?key=sk-private" }), &tools, &decline).await;
+        let read_inside = run_tool("read", serde_json::json!({ "path": "Cargo.toml" }), &tools, &decline).await;
+        let _ = std::fs::remove_file(&outside);
+
+        assert_eq!(read_outside, "The user declined to run this.");
+        assert_eq!(fetch, "The user declined to run this.");
+        assert!(read_inside.contains("[package]"));
+        assert_eq!(asked.get(), 2);
+    }
+
+    #[test]
+    fn splits_a_leading_think_block_across_deltas() {
+        let mut split = LeadingThink::default();
+        let mut reasoning = String::new();
+        let mut text = String::new();
+        for delta in ["\n<th", "ink>Let me", " see.</thi", "nk>\n\nParis", " it is."] {
+            for part in split.feed(delta) {
+                match part {
+                    Part::Reasoning(r) => reasoning.push_str(&r),
+                    Part::Text(t) => text.push_str(&t),
+                }
+            }
+        }
+        assert!(split.finish().is_none());
+        assert_eq!((reasoning.as_str(), text.as_str()), ("Let me see.", "Paris it is."));
+
+        assert_eq!(strip_think("<think>hmm</think>Answer"), "Answer");
+        assert_eq!(strip_think("Plain <think> answer"), "Plain <think> answer");
+        assert_eq!(strip_think("<thin"), "<thin");
+    }
+
+    #[test]
+    fn extra_body_is_merged_into_request() {
+        let mut config = Config::default();
+        config.extra_body.insert("temperature".to_string(), serde_json::json!(0.2));
+        config.extra_body.insert("provider".to_string(), serde_json::json!({ "order": ["groq"] }));
+        let body = request_body(&config, "hi", true);
+        assert_eq!(body["temperature"], 0.2);
+        assert_eq!(body["provider"]["order"][0], "groq");
+        assert_eq!(body["messages"][0]["content"], "hi");
+        assert_eq!(body["stream"], true);
+
+        config.body_overrides.insert("temperature".to_string(), serde_json::json!(0.0));
+        assert_eq!(request_body(&config, "hi", true)["temperature"], 0.0);
+        config.body_overrides.clear();
+
+        config.system_prompt = Some("Be terse.".to_string());
+        let body = request_body(&config, "hi", false);
+        assert_eq!(body["messages"][0]["role"], "system");
+        assert_eq!(body["messages"][1]["content"], "hi");
 
-        let duration = start_time.elapsed();
-        println!("\n(Completed in {:.2?})", duration);
+        config.images = vec!["data:image/png;base64,iVBORw==".to_string()];
+        let body = request_body(&config, "hi", false);
+        assert_eq!(body["messages"][1]["content"][0]["text"], "hi");
+        assert_eq!(body["messages"][1]["content"][1]["image_url"]["url"], "data:image/png;base64,iVBORw==");
 
-        Ok(content)
+        let entry = crate::prompt::PromptEntry { stop: vec!["```".to_string()], assistant_prefix: Some("{".to_string()), ..Default::default() };
+        let body = request_body(&Config::default().for_entry(&entry), "hi", false);
+        assert_eq!(body["stop"][0], "```");
+        assert_eq!(body["messages"][1], serde_json::json!({ "role": "assistant", "content": "{" }));
     }
 }
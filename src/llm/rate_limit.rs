@@ -0,0 +1,154 @@
+//! Client-side rate limits: `requests_per_minute` and `tokens_per_minute`
+//! as token buckets, one pair per endpoint and key, shared by every request
+//! this process sends there. A bucket holds a minute's allowance and
+//! refills continuously; a request waits until both have room for it.
+//! Completion tokens are only known afterwards, so they are charged then
+//! and may leave the token bucket owing, which the next request waits out.
+
+use crate::config::Config;
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex, PoisonError};
+use std::time::Duration;
+use tokio::time::Instant;
+
+struct Bucket {
+    per_minute: f64,
+    available: f64,
+    refilled: Instant,
+}
+
+impl Bucket {
+    fn new(per_minute: u32, now: Instant) -> Bucket {
+        Bucket { per_minute: per_minute as f64, available: per_minute as f64, refilled: now }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if now <= self.refilled {
+            return;
+        }
+        let minutes = now.duration_since(self.refilled).as_secs_f64() / 60.0;
+        self.available = (self.available + minutes * self.per_minute).min(self.per_minute);
+        self.refilled = now;
+    }
+
+    /// How long until `amount` fits. More than a whole minute's allowance
+    /// only waits for a full bucket, and then overdraws it.
+    fn wait(&mut self, amount: f64, now: Instant) -> Duration {
+        self.refill(now);
+        let missing = amount.min(self.per_minute) - self.available;
+        Duration::from_secs_f64((missing.max(0.0) / self.per_minute) * 60.0)
+    }
+}
+
+#[derive(Default)]
+struct Limits {
+    requests: Option<Bucket>,
+    tokens: Option<Bucket>,
+}
+
+/// A bucket for `per_minute`, kept across calls while the limit stays the
+/// same. A limit of 0, which only a hand-edited file can hold, is no limit.
+fn bucket(slot: &mut Option<Bucket>, per_minute: Option<u32>, now: Instant) -> Option<&mut Bucket> {
+    match per_minute.filter(|&limit| limit > 0) {
+        None => *slot = None,
+        Some(limit) if slot.as_ref().is_none_or(|bucket| bucket.per_minute != limit as f64) => *slot = Some(Bucket::new(limit, now)),
+        Some(_) => {}
+    }
+    slot.as_mut()
+}
+
+static LIMITS: LazyLock<Mutex<HashMap<String, Limits>>> = LazyLock::new(Default::default);
+
+fn key(config: &Config) -> String {
+    format!("{}\n{}", config.base_url, config.api_key)
+}
+
+/// Take a request of about `tokens` prompt tokens from `config`'s limits,
+/// or the time until there is room for it.
+fn try_take(config: &Config, tokens: u32, now: Instant) -> Result<(), Duration> {
+    let mut all = LIMITS.lock().unwrap_or_else(PoisonError::into_inner);
+    let limits = all.entry(key(config)).or_default();
+    let mut requests = bucket(&mut limits.requests, config.requests_per_minute, now);
+    let mut budget = bucket(&mut limits.tokens, config.tokens_per_minute, now);
+    let wait = [requests.as_deref_mut().map(|bucket| bucket.wait(1.0, now)), budget.as_deref_mut().map(|bucket| bucket.wait(tokens as f64, now))]
+        .into_iter()
+        .flatten()
+        .max()
+        .unwrap_or_default();
+    if !wait.is_zero() {
+        return Err(wait);
+    }
+    if let Some(bucket) = requests {
+        bucket.available -= 1.0;
+    }
+    if let Some(bucket) = budget {
+        bucket.available -= tokens as f64;
+    }
+    Ok(())
+}
+
+/// Wait until `config`'s limits allow a request whose body is `body`, and
+/// count it. Nothing to wait for without limits.
+pub async fn acquire(config: &Config, body: &serde_json::Value) {
+    if config.requests_per_minute.is_none() && config.tokens_per_minute.is_none() {
+        return;
+    }
+    // About four characters a token
+    let tokens = (body["messages"].to_string().len() / 4) as u32;
+    while let Err(wait) = try_take(config, tokens, Instant::now()) {
+        tracing::info!(wait = ?wait, "rate limit reached; waiting");
+        tokio::time::sleep(wait).await;
+    }
+}
+
+/// Count `tokens` completion tokens against `config`'s tokens per minute.
+pub fn charge(config: &Config, tokens: u32) {
+    if config.tokens_per_minute.is_some() && tokens > 0 {
+        charge_at(config, tokens, Instant::now());
+    }
+}
+
+fn charge_at(config: &Config, tokens: u32, now: Instant) {
+    let mut all = LIMITS.lock().unwrap_or_else(PoisonError::into_inner);
+    let limits = all.entry(key(config)).or_default();
+    if let Some(bucket) = bucket(&mut limits.tokens, config.tokens_per_minute, now) {
+        bucket.refill(now);
+        bucket.available -= tokens as f64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waits_for_room_in_every_bucket() {
+        let config = Config {
+            base_url: "http://rate-limit.test/v1".into(),
+            requests_per_minute: Some(2),
+            tokens_per_minute: Some(600),
+            ..Config::default()
+        };
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let take = |tokens, now| try_take(&config, tokens, now).map_err(|wait| wait.as_secs_f64().round() as u64);
+        assert_eq!(take(100, at(0)), Ok(()));
+        assert_eq!(take(100, at(0)), Ok(()));
+        // The third request waits for half a minute's refill of one request
+        assert_eq!(take(100, at(0)), Err(30));
+        assert_eq!(take(100, at(30)), Ok(()));
+
+        // 500 tokens left; 800 completion tokens overdraw the bucket by 300,
+        // which the next half minute pays back
+        charge_at(&config, 800, at(30));
+        assert_eq!(take(100, at(60)), Err(10));
+        assert_eq!(take(100, at(70)), Ok(()));
+
+        // 0 in config.toml or providers.toml is no limit, not a division by zero
+        let unlimited = Config { requests_per_minute: Some(0), tokens_per_minute: Some(0), ..config.clone() };
+        for _ in 0..3 {
+            assert_eq!(try_take(&unlimited, 100, start), Ok(()));
+        }
+        charge_at(&unlimited, 800, start);
+    }
+}
@@ -0,0 +1,294 @@
+//! Models run in-process from a GGUF file, for `--model file:/path/model.gguf`
+//! (or an alias to one): no server, no key, no network. Running them needs
+//! the `local` cargo feature, which builds llama.cpp through the
+//! `llama-cpp-2` crate; without it such a model is a config error.
+//!
+//! The request body a provider would get is read back here (messages,
+//! `temperature`, `top_p`, `max_tokens`, `stop`), so a local run takes the
+//! same road as a remote one up to the HTTP call, and its pieces are
+//! streamed, rendered and post-processed like a provider's deltas.
+
+use crate::config::Config;
+use crate::error::XaError;
+use crate::llm::Usage;
+use std::path::PathBuf;
+use tokio::sync::mpsc;
+
+/// Tokens the answer may take when the request sets no `max_tokens`.
+#[cfg_attr(not(feature = "local"), allow(dead_code))]
+const DEFAULT_MAX_TOKENS: u32 = 2048;
+
+/// The GGUF file `config`'s model names, if it is a `file:` model.
+pub fn model_path(config: &Config) -> Option<PathBuf> {
+    config.model().strip_prefix("file:").map(PathBuf::from)
+}
+
+/// What a generation sends back: pieces of text, then the token counts.
+pub enum Event {
+    Text(String),
+    Done(Usage),
+}
+
+/// The parts of a chat request body a local model uses.
+#[cfg_attr(not(feature = "local"), allow(dead_code))]
+struct Request {
+    /// Role and text of each message; images are left out.
+    messages: Vec<(String, String)>,
+    temperature: Option<f32>,
+    top_p: Option<f32>,
+    max_tokens: u32,
+    stop: Vec<String>,
+}
+
+impl Request {
+    #[cfg_attr(not(feature = "local"), allow(dead_code))]
+    fn from_body(body: &serde_json::Value) -> Request {
+        let text = |content: &serde_json::Value| match content.as_array() {
+            Some(parts) => parts.iter().filter_map(|part| part["text"].as_str()).collect::<Vec<_>>().join("\n"),
+            None => content.as_str().unwrap_or_default().to_string(),
+        };
+        let messages = body["messages"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|message| (message["role"].as_str().unwrap_or("user").to_string(), text(&message["content"])))
+            .collect();
+        let stop = match &body["stop"] {
+            serde_json::Value::String(stop) => vec![stop.clone()],
+            stops => stops.as_array().into_iter().flatten().filter_map(|stop| stop.as_str().map(String::from)).collect(),
+        };
+        Request {
+            messages,
+            temperature: body["temperature"].as_f64().map(|t| t as f32),
+            top_p: body["top_p"].as_f64().map(|p| p as f32),
+            max_tokens: body["max_tokens"].as_u64().map_or(DEFAULT_MAX_TOKENS, |n| n as u32),
+            stop: stop.into_iter().filter(|stop| !stop.is_empty()).collect(),
+        }
+    }
+}
+
+/// Turns the model's token bytes into text: characters split across
+/// tokens are put together, and text that may be the start of a stop
+/// sequence is held back until it is known not to be one.
+#[derive(Default)]
+#[cfg_attr(not(feature = "local"), allow(dead_code))]
+struct Pieces {
+    bytes: Vec<u8>,
+    held: String,
+    stops: Vec<String>,
+    /// A stop sequence was generated; nothing after it is kept.
+    stopped: bool,
+}
+
+#[cfg_attr(not(feature = "local"), allow(dead_code))]
+impl Pieces {
+    /// The text that is ready after `bytes`.
+    fn push(&mut self, bytes: &[u8]) -> String {
+        self.bytes.extend_from_slice(bytes);
+        let valid = match std::str::from_utf8(&self.bytes) {
+            Ok(_) => self.bytes.len(),
+            // A character still incomplete waits for the next token
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => self.bytes.len(),
+        };
+        let decoded: Vec<u8> = self.bytes.drain(..valid).collect();
+        self.held.push_str(&String::from_utf8_lossy(&decoded));
+        if let Some(at) = self.stops.iter().filter_map(|stop| self.held.find(stop.as_str())).min() {
+            self.stopped = true;
+            self.held.truncate(at);
+            return std::mem::take(&mut self.held);
+        }
+        let partial = self
+            .stops
+            .iter()
+            .flat_map(|stop| stop.char_indices().skip(1).map(|(at, _)| &stop[..at]))
+            .filter(|start| self.held.ends_with(start))
+            .map(str::len)
+            .max()
+            .unwrap_or(0);
+        self.held.drain(..self.held.len() - partial).collect()
+    }
+
+    /// What is still held back once the model has finished.
+    fn finish(&mut self) -> String {
+        let rest = String::from_utf8_lossy(&std::mem::take(&mut self.bytes)).into_owned();
+        std::mem::take(&mut self.held) + &rest
+    }
+}
+
+/// Start generating an answer to `body` with the model at `path`, on a
+/// blocking thread.
+#[cfg(feature = "local")]
+pub fn start(path: PathBuf, body: &serde_json::Value) -> Result<mpsc::UnboundedReceiver<Result<Event, String>>, XaError> {
+    if !path.is_file() {
+        return Err(XaError::Config(format!("no model file at {}", path.display())));
+    }
+    let request = Request::from_body(body);
+    let (events, receiver) = mpsc::unbounded_channel();
+    tokio::task::spawn_blocking(move || {
+        let done = llama::generate(&path, &request, |text| events.send(Ok(Event::Text(text))).is_ok());
+        let _ = events.send(done.map(Event::Done));
+    });
+    Ok(receiver)
+}
+
+#[cfg(not(feature = "local"))]
+pub fn start(path: PathBuf, _body: &serde_json::Value) -> Result<mpsc::UnboundedReceiver<Result<Event, String>>, XaError> {
+    Err(XaError::Config(format!(
+        "{} is a local model, but this xa was built without local model support; reinstall with `cargo install xa --features local`",
+        path.display()
+    )))
+}
+
+/// The whole answer to `body` from the model at `path`, and its usage.
+pub async fn complete(path: PathBuf, body: &serde_json::Value) -> Result<(String, Option<Usage>), XaError> {
+    let mut events = start(path, body)?;
+    let (mut text, mut usage) = (String::new(), None);
+    while let Some(event) = events.recv().await {
+        match event.map_err(XaError::Other)? {
+            Event::Text(piece) => text.push_str(&piece),
+            Event::Done(done) => usage = Some(done),
+        }
+    }
+    Ok((text, usage))
+}
+
+#[cfg(feature = "local")]
+mod llama {
+    use super::{Pieces, Request};
+    use crate::llm::Usage;
+    use llama_cpp_2::context::params::LlamaContextParams;
+    use llama_cpp_2::llama_backend::LlamaBackend;
+    use llama_cpp_2::llama_batch::LlamaBatch;
+    use llama_cpp_2::model::params::LlamaModelParams;
+    use llama_cpp_2::model::{AddBos, LlamaChatMessage, LlamaModel, Special};
+    use llama_cpp_2::sampling::LlamaSampler;
+    use std::num::NonZeroU32;
+    use std::path::Path;
+    use std::sync::OnceLock;
+
+    /// llama.cpp may only be set up once per process.
+    fn backend() -> Result<&'static LlamaBackend, String> {
+        static BACKEND: OnceLock<Result<LlamaBackend, String>> = OnceLock::new();
+        BACKEND
+            .get_or_init(|| {
+                // llama.cpp's own logging goes to -v / --log-file, not the terminal
+                llama_cpp_2::send_logs_to_tracing(llama_cpp_2::LogOptions::default());
+                LlamaBackend::init().map_err(|e| e.to_string())
+            })
+            .as_ref()
+            .map_err(Clone::clone)
+    }
+
+    /// Run `request` to the end, handing each piece of text to `on_text`
+    /// until it returns false.
+    pub(super) fn generate(path: &Path, request: &Request, mut on_text: impl FnMut(String) -> bool) -> Result<Usage, String> {
+        let backend = backend()?;
+        tracing::info!(path = %path.display(), "loading local model");
+        let model = LlamaModel::load_from_file(backend, path, &LlamaModelParams::default()).map_err(|e| format!("cannot load {}: {}", path.display(), e))?;
+
+        // An assistant message at the end is the start of the answer
+        let mut messages = request.messages.clone();
+        let prefix = match messages.last() {
+            Some((role, _)) if role == "assistant" => messages.pop().map(|(_, text)| text),
+            _ => None,
+        };
+        let chat = messages
+            .into_iter()
+            .map(|(role, text)| LlamaChatMessage::new(role, text).map_err(|e| e.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let template = model.chat_template(None).map_err(|e| format!("the model has no chat template: {}", e))?;
+        let mut prompt = model.apply_chat_template(&template, &chat, true).map_err(|e| e.to_string())?;
+        prompt.push_str(prefix.as_deref().unwrap_or_default());
+        let tokens = model.str_to_token(&prompt, AddBos::Always).map_err(|e| e.to_string())?;
+
+        let n_ctx = (tokens.len() as u32 + request.max_tokens).min(model.n_ctx_train());
+        if tokens.len() as u32 >= n_ctx {
+            return Err(format!("the prompt is {} tokens, more than the model's {}", tokens.len(), n_ctx));
+        }
+        let params = LlamaContextParams::default().with_n_ctx(NonZeroU32::new(n_ctx)).with_n_batch(n_ctx);
+        let mut ctx = model.new_context(backend, params).map_err(|e| e.to_string())?;
+        let mut batch = LlamaBatch::new(n_ctx as usize, 1);
+        let last = tokens.len() as i32 - 1;
+        for (at, token) in (0_i32..).zip(tokens.iter().copied()) {
+            batch.add(token, at, &[0], at == last).map_err(|e| e.to_string())?;
+        }
+        ctx.decode(&mut batch).map_err(|e| e.to_string())?;
+
+        let mut samplers = Vec::new();
+        match request.temperature {
+            Some(t) if t <= 0.0 => samplers.push(LlamaSampler::greedy()),
+            t => {
+                samplers.push(LlamaSampler::top_p(request.top_p.unwrap_or(1.0), 1));
+                samplers.push(LlamaSampler::temp(t.unwrap_or(0.8)));
+                samplers.push(LlamaSampler::dist(rand_seed()));
+            }
+        }
+        let mut sampler = LlamaSampler::chain_simple(samplers);
+        let mut pieces = Pieces { stops: request.stop.clone(), ..Default::default() };
+        let mut position = tokens.len() as i32;
+        let mut generated = 0;
+        while generated < request.max_tokens && (position as u32) < n_ctx {
+            let token = sampler.sample(&ctx, batch.n_tokens() - 1);
+            sampler.accept(token);
+            if model.is_eog_token(token) {
+                break;
+            }
+            generated += 1;
+            let bytes = model.token_to_bytes(token, Special::Tokenize).map_err(|e| e.to_string())?;
+            let text = pieces.push(&bytes);
+            if (!text.is_empty() && !on_text(text)) || pieces.stopped {
+                break;
+            }
+            batch.clear();
+            batch.add(token, position, &[0], true).map_err(|e| e.to_string())?;
+            position += 1;
+            ctx.decode(&mut batch).map_err(|e| e.to_string())?;
+        }
+        let rest = pieces.finish();
+        if !rest.is_empty() {
+            on_text(rest);
+        }
+        tracing::debug!(prompt = tokens.len(), generated, "local generation finished");
+        Ok(Usage { prompt_tokens: tokens.len() as u32, completion_tokens: generated })
+    }
+
+    fn rand_seed() -> u32 {
+        std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |t| t.subsec_nanos())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_request_and_holds_back_stop_sequences() {
+        let body = serde_json::json!({
+            "messages": [
+                { "role": "system", "content": "Be terse." },
+                { "role": "user", "content": [{ "type": "text", "text": "hi" }, { "type": "image_url", "image_url": {} }] },
+            ],
+            "temperature": 0.0,
+            "stop": "```",
+        });
+        let request = Request::from_body(&body);
+        assert_eq!(request.messages, [("system".to_string(), "Be terse.".to_string()), ("user".to_string(), "hi".to_string())]);
+        assert_eq!((request.temperature, request.max_tokens, request.stop.as_slice()), (Some(0.0), DEFAULT_MAX_TOKENS, ["```".to_string()].as_slice()));
+
+        let mut pieces = Pieces { stops: request.stop, ..Default::default() };
+        let e_acute = "é".as_bytes();
+        assert_eq!(pieces.push(&[b'c', b'a', b'f', e_acute[0]]), "caf");
+        assert_eq!(pieces.push(&[e_acute[1], b' ', b'`']), "é ");
+        assert_eq!(pieces.push(b"`x"), "``x");
+        assert_eq!(pieces.push(b" ``"), " ");
+        assert!(!pieces.stopped);
+        assert_eq!(pieces.push(b"`end"), "");
+        assert!(pieces.stopped);
+        assert_eq!(pieces.finish(), "");
+
+        let config = Config { default_model: Some("file:/models/qwen.gguf".into()), ..Config::default() };
+        assert_eq!(model_path(&config), Some(PathBuf::from("/models/qwen.gguf")));
+        assert_eq!(model_path(&Config::default()), None);
+    }
+}
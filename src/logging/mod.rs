@@ -0,0 +1,52 @@
+//! Diagnostics through `tracing`: off unless asked for with `-v` (info),
+//! `-vv`/`--debug` (debug), `-vvv` (trace), `RUST_LOG`, or `--log-file`.
+//!
+//! Logs go to stderr, or to the file given with `--log-file` (appended,
+//! without colours) — the better choice for the TUI, whose screen stderr
+//! output would overwrite. Request spans are closed with their duration.
+
+use crate::error::XaError;
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::sync::Mutex;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::EnvFilter;
+
+/// Filter used when `RUST_LOG` is unset. xa's own events get the requested
+/// level; dependencies (reqwest, hyper) only from `-vvv`.
+fn default_directive(verbosity: u8) -> &'static str {
+    match verbosity {
+        0 => "off",
+        1 => "xa=info",
+        2 => "xa=debug",
+        _ => "trace",
+    }
+}
+
+/// Install the global subscriber. A log file with no `-v` records at info.
+pub fn init(verbosity: u8, log_file: Option<&Path>) -> Result<(), XaError> {
+    let verbosity = if log_file.is_some() { verbosity.max(1) } else { verbosity };
+    let filter = match std::env::var("RUST_LOG") {
+        Ok(directives) if !directives.is_empty() => {
+            EnvFilter::try_new(&directives).map_err(|e| XaError::Config(format!("invalid RUST_LOG '{}': {}", directives, e)))?
+        }
+        _ => EnvFilter::new(default_directive(verbosity)),
+    };
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_target(verbosity >= 3);
+    match log_file {
+        Some(path) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| XaError::Usage(format!("cannot open log file {}: {}", path.display(), e)))?;
+            builder.with_ansi(false).with_writer(Mutex::new(file)).try_init()
+        }
+        None => builder.with_writer(std::io::stderr).try_init(),
+    }
+    .map_err(|e| XaError::Other(e.to_string()))
+}
+
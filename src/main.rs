@@ -1,30 +1,58 @@
-mod config;
-mod prompt;
-mod llm;
-mod output;
 mod utils;
-mod store;
-mod agent;
-mod output_filter;
+mod api;
+mod attachment;
+mod batch;
+mod bench;
+mod cards;
+mod changelog;
+mod daemon;
+mod data;
+mod explain;
+mod git;
+mod grammar;
+mod image;
+mod index;
+mod logging;
+mod mcp;
+mod minutes;
+mod ocr;
+mod passgen;
+mod patch;
+mod pattern;
+mod plugin;
+mod review;
+mod screenshot;
+mod search;
 mod session;
-mod tools;
+mod shell;
+mod subs;
+mod testgen;
+mod tmux;
+mod transcribe;
 mod tui;
 
+// The engine lives in the xa_core library; importing its modules here keeps
+// `crate::config` and friends working in the CLI-only modules.
+use xa_core::{agent, command, config, document, embeddings, error, history, http, llm, output, output_filter, postprocess, prompt, redact, spinner, store, tools, transcript, update, watch, web};
+
 use clap::{Parser, Subcommand};
 use chrono::{Local, TimeZone};
 use std::collections::BTreeMap;
+use command::{command_input, flipped_target, with_target_lang};
 use config::load_config;
-use prompt::{load_prompt_config, find_command, process_template_with_args};
-use llm::process_with_llm;
-use output::render_output;
+use prompt::{load_prompt_config, find_command, find_command_match, MatchKind};
+use llm::{process_with_llm, run_llm, Usage};
+use output::{paint, render_output, OutputFormat};
 use utils::copy_to_clipboard;
-use store::{add_secret_with_tag, search_secret};
+use store::EntryKind;
+use redact::DebugMode;
 use session::Session;
+use error::XaError;
 
-#[derive(Parser)]
+#[derive(Parser, Clone)]
 #[command(name = "xa")]
 #[command(about = "xa - a lightweight coding-agent CLI (like codex / claude-code)")]
-#[command(after_help = "Launch the agent with `xa` or `xa chat`. Configure a provider with `xa login`.\nInside the TUI use:\n  /login [name]  - set a provider (custom endpoint + key + model)\n  /models [name] - switch provider or set the model\n  /save [title]  - save the conversation as a session\n  /sessions      - list saved sessions\nResume a session: xa resume [id]\nReview saved tool-output gains: xa gain [--daily|--weekly|--monthly|--all]")]
+#[command(after_help = "Launch the agent with `xa` or `xa chat`. Configure a provider with `xa login`.\nInside the TUI use:\n  /login [name]  - set a provider (custom endpoint + key + model)\n  /models [name] - switch provider or set the model\n  /save [title]  - save the conversation as a session\n  /sessions      - list saved sessions\nResume a session: xa resume [id]\nExport one to share: xa sessions export <name> -o notes.md (or .html)\nReview saved tool-output gains: xa gain [--daily|--weekly|--monthly|--all]")]
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
@@ -33,9 +61,59 @@ struct Cli {
     #[arg(long = "no-stream", global = true)]
     no_stream: bool,
 
-    /// Enable debug mode to print filled prompt
-    #[arg(long = "debug", global = true)]
-    debug: bool,
+    /// Print each filled prompt, with secrets, [redact] patterns and the
+    /// store's secrets masked; --debug=full prints it as sent (also logs
+    /// at -vv level)
+    #[arg(long = "debug", global = true, value_enum, value_name = "MODE", num_args = 0..=1, require_equals = true, default_missing_value = "masked")]
+    debug: Option<DebugMode>,
+
+    /// Log requests, responses and timings to stderr (-vv more detail, -vvv
+    /// everything; RUST_LOG overrides). With `xa ls`, show templates and overrides
+    #[arg(short = 'v', long = "verbose", global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Append logs to this file instead of stderr (recommended for the TUI)
+    #[arg(long = "log-file", global = true, value_name = "PATH")]
+    log_file: Option<std::path::PathBuf>,
+
+    /// Report failures on stderr as one JSON object ({"error": {kind, code,
+    /// message}}) instead of text; the exit code is the same either way
+    #[arg(long = "json-errors", global = true)]
+    json_errors: bool,
+
+    /// Model (or alias from config.toml) to use for this run
+    #[arg(short = 'm', long = "model", global = true, value_name = "MODEL")]
+    model: Option<String>,
+
+    /// Write the raw result to a file instead of rendering and copying it
+    #[arg(short = 'o', long = "output", global = true, value_name = "PATH")]
+    output: Option<std::path::PathBuf>,
+
+    /// Format for --output (default: from the file extension, else txt)
+    #[arg(long = "format", global = true, value_enum, value_name = "FORMAT", requires = "output")]
+    format: Option<OutputFormat>,
+
+    /// Append to the --output file instead of replacing it
+    #[arg(long = "append", global = true, requires = "output")]
+    append: bool,
+
+    /// Show the result as a word diff against the input (for polish,
+    /// rewrite and other editing commands); the result is still copied
+    #[arg(long = "diff", global = true)]
+    diff: bool,
+
+    /// Copy only the first (or `--copy-code=last`) fenced code block of the
+    /// answer instead of the whole text
+    #[arg(
+        long = "copy-code",
+        global = true,
+        value_enum,
+        value_name = "WHICH",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "first"
+    )]
+    copy_code: Option<output::CodeBlock>,
 
     /// TUI color theme: auto (detect terminal), dark, or light
     #[arg(
@@ -46,6 +124,72 @@ struct Cli {
     )]
     theme: Option<String>,
 
+    /// Print results without colors or styling (also when NO_COLOR is set)
+    #[arg(long = "plain", global = true)]
+    plain: bool,
+
+    /// Sampling temperature for this run (0-2), over config.toml, the
+    /// command's extra_body and the persona; 0 for extraction and the like
+    #[arg(long = "temperature", global = true, value_name = "T", value_parser = sampling_value(2.0))]
+    temperature: Option<f64>,
+
+    /// Nucleus sampling for this run (0-1)
+    #[arg(long = "top-p", global = true, value_name = "P", value_parser = sampling_value(1.0))]
+    top_p: Option<f64>,
+
+    /// Most tokens the answer may take in this run
+    #[arg(long = "max-tokens", global = true, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    max_tokens: Option<u32>,
+
+    /// Ask for N answers (2-9), shown side by side, and pick the one to
+    /// copy. Goes before the command: xa -n 3 ask ...
+    #[arg(short = 'n', long = "choices", value_name = "N", value_parser = clap::value_parser!(u8).range(2..=9))]
+    choices: Option<u8>,
+
+    /// Run even when daily_token_limit or monthly_budget_usd is used up.
+    /// Goes before the command: xa --force summarize ...
+    #[arg(long)]
+    force: bool,
+
+    /// Mask emails, keys and [redact] patterns in the prompt before it is
+    /// sent, as with redact.enabled in config.toml
+    #[arg(long, global = true)]
+    redact: bool,
+
+    /// Don't show the reasoning thinking models stream before the answer
+    #[arg(long = "hide-thinking", global = true)]
+    hide_thinking: bool,
+
+    /// Use the text on the clipboard as the input (the default for a
+    /// command given no text and no piped input)
+    #[arg(long = "from-clipboard", global = true)]
+    from_clipboard: bool,
+
+    /// Use the text of a tmux pane as the input: this pane by default, or
+    /// another with --from-tmux=<pane>
+    #[arg(
+        long = "from-tmux",
+        global = true,
+        value_name = "PANE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "",
+        conflicts_with = "from_clipboard"
+    )]
+    from_tmux: Option<String>,
+
+    /// Language to translate into: sets the command's target_lang
+    /// argument, over default_target_lang and the locale, e.g.
+    /// xa translate --to ja "text"
+    #[arg(long = "to", value_name = "LANG")]
+    to: Option<String>,
+
+    /// Run the command on each file named after it (globs such as
+    /// 'src/**/*.rs' are expanded), writing <file>.<command>.md next to
+    /// each one, or a single report with -o
+    #[arg(long = "each")]
+    each: bool,
+
     /// Input text to process
     input: Option<String>,
 
@@ -54,38 +198,84 @@ struct Cli {
     args: Vec<String>,
 }
 
-#[derive(Subcommand)]
+#[derive(Subcommand, Clone)]
 enum Commands {
     /// Set configuration (e.g., xa set openai)
     #[command(short_flag = 's')]
     Set {
         /// Configuration type
         config_type: String,
+        /// API endpoint, e.g. https://api.openai.com/v1 (skips the question)
+        #[arg(long, value_name = "URL")]
+        base_url: Option<String>,
+        /// API key (skips the question)
+        #[arg(long, value_name = "KEY")]
+        api_key: Option<String>,
+        /// Don't ask anything; unset values come from XA_BASE_URL, XA_API_KEY,
+        /// XA_MODEL or the existing config. The default model is set with the
+        /// global --model flag
+        #[arg(short, long)]
+        yes: bool,
     },
 
     /// List all commands or specific items
     #[command(short_flag = 'l', alias = "list")]
     Ls {
-        /// Type of items to list (prompts, stores)
-        #[arg(value_name = "TYPE")]
+        /// Type of items to list (prompts, stores, plugins), or a pattern to filter commands by
+        #[arg(value_name = "TYPE|PATTERN")]
         list_type: Option<String>,
     },
 
-    /// Add a new command/prompt
+    /// Add a new command/prompt; with --note, --snippet or --url, store
+    /// an entry for `xa search` instead, e.g.
+    /// xa add --note "kubectl rollout undo deploy/foo" "rollback command"
     #[command(short_flag = 'a')]
-    Add,
+    Add {
+        /// Store a note: the text, then what it is for (`-` reads the text
+        /// from stdin)
+        #[arg(long, value_name = "TEXT", group = "entry", requires = "description")]
+        note: Option<String>,
+        /// Store a code or shell snippet, printed as it is by `xa search`
+        #[arg(long, value_name = "CODE", group = "entry", requires = "description")]
+        snippet: Option<String>,
+        /// Store a URL
+        #[arg(long, value_name = "URL", group = "entry", requires = "description")]
+        url: Option<String>,
+        /// Store a secret (same as `xa add-secret`): never sent to the model
+        /// and copied to the clipboard instead of shown
+        #[arg(long, value_name = "VALUE", group = "entry", requires = "description")]
+        secret: Option<String>,
+        /// What the stored entry is for; `xa search` finds it by this
+        #[arg(requires = "entry")]
+        description: Option<String>,
+    },
 
-    /// Remove a command/prompt
+    /// Remove a command/prompt (moved to the trash, see `xa prompts restore`)
     #[command(short_flag = 'r')]
     Rm {
         /// Command name to remove
         command_name: String,
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
     },
 
     /// Reset to default prompts
     #[command(alias = "reset")]
     ResetDefaults,
 
+    /// Read or change config.toml settings without the setup wizard
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+
+    /// Manage prompt templates
+    Prompts {
+        #[command(subcommand)]
+        action: PromptsAction,
+    },
+
     /// Add a secret with auto tag
     #[command(short_flag = 'A', visible_alias = "as")]
     AddSecret {
@@ -95,15 +285,458 @@ enum Commands {
         note: String,
     },
 
-    /// Search secrets by natural language
+    /// Search stored secrets, notes, snippets and URLs by natural language
     #[command(visible_alias = "se")]
     Search {
         /// Search query
         query: String,
+        /// Print every entry that matches instead of choosing one (secrets
+        /// stay hidden)
+        #[arg(long)]
+        all: bool,
+    },
+
+    /// Generate a password (or with --words, a passphrase) locally and
+    /// copy it, e.g. xa gen --len 24 --save "staging db password"
+    Gen {
+        /// Password length in characters
+        #[arg(long = "len", default_value_t = 32, value_name = "N", value_parser = clap::value_parser!(u16).range(4..=1024))]
+        length: u16,
+        /// Characters to use: lower, upper, digits, symbols, alnum or hex,
+        /// comma-separated, or the characters themselves
+        #[arg(long, value_name = "SET", conflicts_with = "words")]
+        charset: Option<String>,
+        /// Make a passphrase of N random words instead
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(2..=64))]
+        words: Option<u16>,
+        /// What goes between passphrase words
+        #[arg(long, default_value = "-", value_name = "TEXT", requires = "words")]
+        separator: String,
+        /// Also add it to the store as a secret with this note
+        #[arg(long, value_name = "NOTE")]
+        save: Option<String>,
+    },
+
+    /// Manage the entries `xa add` and `xa add-secret` stored, e.g.
+    /// xa store retag db-pass staging-db, or xa store sync
+    Store {
+        #[command(subcommand)]
+        action: StoreAction,
+    },
+
+    /// Run commands in sequence, e.g. xa pipe "summarize -> translate fr" "text"
+    Pipe {
+        /// Steps separated by `->` or `|`; arguments follow the command name
+        /// or are attached with `:` (translate:fr)
+        spec: String,
+        /// Input text for the first step
+        text: String,
+    },
+
+    /// Index the text files under a directory for `xa ask --with-index`;
+    /// without a directory, list the indexes
+    Index {
+        dir: Option<std::path::PathBuf>,
+        /// Index name (default: the directory's name)
+        #[arg(long, value_name = "NAME")]
+        name: Option<String>,
+    },
+
+    /// Print the embedding of each text as a JSON array, one per line;
+    /// reads lines from stdin when no text is given
+    Embed {
+        texts: Vec<String>,
+    },
+
+    /// Fetch a web page and run a command on its article text, e.g.
+    /// xa web https://example.com/post translate fr
+    Web {
+        url: String,
+        /// Command to run on the article (default: summarize)
+        command_name: Option<String>,
+        /// Additional arguments for the command
+        #[arg(trailing_var_arg = true)]
+        args: Vec<String>,
+    },
+
+    /// Read the text in an image (png, jpeg, ...), e.g. xa ocr error.png
+    /// translate en; without a command, print the text
+    Ocr {
+        image: std::path::PathBuf,
+        /// Read it with a vision model or a local tesseract (default:
+        /// ocr_backend in config.toml, else vision)
+        #[arg(long, value_enum)]
+        backend: Option<ocr::Backend>,
+        /// Tesseract language(s), e.g. eng or eng+deu
+        #[arg(long, value_name = "LANG")]
+        lang: Option<String>,
+        /// Command to run on the text
+        command_name: Option<String>,
+        /// Additional arguments for the command
+        #[arg(trailing_var_arg = true)]
+        args: Vec<String>,
+    },
+
+    /// Transcribe an audio file (mp3, wav, m4a, ...), e.g. xa transcribe
+    /// talk.mp3 summarize; without a command, print the transcript
+    Transcribe {
+        file: std::path::PathBuf,
+        /// Spoken language as an ISO 639-1 code (e.g. en), as a hint
+        #[arg(long, value_name = "CODE")]
+        language: Option<String>,
+        /// Command to run on the transcript
+        command_name: Option<String>,
+        /// Additional arguments for the command
+        #[arg(trailing_var_arg = true)]
+        args: Vec<String>,
+    },
+
+    /// Serve the prompt commands to other programs, e.g. xa serve --mcp
+    /// for editors and agents that speak the Model Context Protocol, or
+    /// xa serve --http 127.0.0.1:7777 for scripts and launchers
+    Serve {
+        /// Speak MCP over stdin/stdout, one tool per prompt command
+        #[arg(long, conflicts_with = "http")]
+        mcp: bool,
+        /// Serve a REST API on this address (POST /run/<command>, POST /ask)
+        #[arg(long, value_name = "ADDR")]
+        http: Option<String>,
+        /// Require `Authorization: Bearer <TOKEN>` on every HTTP request
+        #[arg(long, requires = "http")]
+        token: Option<String>,
+    },
+
+    /// Run a command on many inputs at once, one per line; results are
+    /// printed one per line in the same order
+    Batch {
+        /// Command to run on each input
+        command_name: String,
+        /// Arguments for the command, e.g. the target language
+        args: Vec<String>,
+        /// Read inputs from a file, one per line (repeatable); stdin otherwise
+        #[arg(short, long = "file", value_name = "PATH")]
+        files: Vec<std::path::PathBuf>,
+        /// Number of requests in flight at once
+        #[arg(short, long, default_value_t = 4, value_name = "N")]
+        jobs: usize,
+    },
+
+    /// Send a command's prompt to several models at once and compare their
+    /// latency, tokens, cost and answers, e.g.
+    /// xa bench --models gpt-4o-mini,fast,local translate "Good morning" fr
+    Bench {
+        /// Models or aliases to compare, comma-separated
+        #[arg(long, value_delimiter = ',', required = true, value_name = "A,B,...")]
+        models: Vec<String>,
+        /// Command whose prompt is sent
+        command_name: String,
+        /// Input text (piped input when omitted)
+        input: Option<String>,
+        /// Arguments for the command
+        args: Vec<String>,
+    },
+
+    /// Transform JSON, YAML, TOML or CSV as instructed, checking that the
+    /// answer parses, e.g. xa data "keep users over 30" -f users.json --to yaml
+    Data {
+        /// What to do with the data
+        instruction: String,
+        /// File with the data (default: piped stdin)
+        #[arg(short = 'f', long = "file", value_name = "PATH")]
+        file: Option<std::path::PathBuf>,
+        /// Format of the data (default: from the file's extension, else guessed)
+        #[arg(long, value_enum)]
+        from: Option<data::DataFormat>,
+        /// Format of the result (default: the data's, or the -o file's)
+        #[arg(long, value_enum)]
+        to: Option<data::DataFormat>,
+        /// How often to ask again when the answer does not parse
+        #[arg(long, default_value_t = 2, value_name = "N")]
+        retries: u32,
+    },
+
+    /// Write a regular expression and try it on sample lines, e.g.
+    /// xa regex "ISO dates but not times" --test samples.txt
+    Regex {
+        /// What the pattern should match
+        description: Vec<String>,
+        /// Sample lines to try it on; mark lines with "+ " (must match) or
+        /// "- " (must not) to have the model fix a pattern that gets them wrong
+        #[arg(short = 't', long = "test", value_name = "PATH")]
+        test: Option<std::path::PathBuf>,
+        /// How often to ask again when the pattern does not compile or
+        /// gets a marked line wrong
+        #[arg(long, default_value_t = 3, value_name = "N")]
+        retries: u32,
+    },
+
+    /// Edit files as instructed: the model writes a unified diff, which is
+    /// checked, shown and applied once confirmed, e.g.
+    /// xa patch "rename parse_args to parse_cli" -f src/main.rs
+    Patch {
+        /// What to change
+        instruction: Vec<String>,
+        /// File the change may touch (repeat for several)
+        #[arg(short = 'f', long = "file", value_name = "PATH", required = true)]
+        files: Vec<String>,
+        /// Apply without asking
+        #[arg(short = 'y', long)]
+        yes: bool,
+        /// How often to ask again when the diff does not apply
+        #[arg(long, default_value_t = 2, value_name = "N")]
+        retries: u32,
+    },
+
+    /// Flashcards about a topic or from a text (piped, or -f), written as an
+    /// Anki import file or JSON, e.g. xa cards "German kitchen words" --to de --deck kitchen.tsv
+    Cards {
+        /// Topic, or the text to make cards from
+        source: Vec<String>,
+        /// Read the text from a file
+        #[arg(short = 'f', long = "file", value_name = "PATH")]
+        file: Option<std::path::PathBuf>,
+        /// Deck file: .tsv for Anki's File > Import, .json for genanki and
+        /// other tools (default: print the cards)
+        #[arg(long, value_name = "PATH")]
+        deck: Option<std::path::PathBuf>,
+        /// Language being learned: vocabulary cards get the word in it on
+        /// the front and the meaning on the back
+        #[arg(long = "to", value_name = "LANG")]
+        to: Option<String>,
+        /// Deck name inside Anki (default: the deck file's name)
+        #[arg(long, value_name = "NAME")]
+        name: Option<String>,
+        /// How many cards to ask for
+        #[arg(long, default_value_t = 20, value_name = "N")]
+        count: u32,
+        /// How often to ask again when the answer has no usable cards
+        #[arg(long, default_value_t = 2, value_name = "N")]
+        retries: u32,
+    },
+
+    /// Suggest a corrected version of the last shell command and run it once
+    /// confirmed (needs the hook from xa init)
+    Fix {
+        /// Run the failed command again to show the model its output
+        #[arg(long)]
+        rerun: bool,
+    },
+
+    /// Explain why the last shell command failed (needs the hook from xa init)
+    Why {
+        /// Run the failed command again to show the model its output
+        #[arg(long)]
+        rerun: bool,
+    },
+
+    /// Draft a commit message for the staged changes
+    Commit {
+        /// Write the message into git's message file (used by the
+        /// prepare-commit-msg hook from xa git install-hooks)
+        #[arg(long, value_name = "MSG_FILE")]
+        hook: Option<std::path::PathBuf>,
     },
 
-    /// Interactive conversation mode
-    Ask,
+    /// Draft a pull request title and description from the commits and diff
+    /// since the base branch
+    Pr {
+        /// Branch to compare against (default: origin's default branch, main or master)
+        #[arg(long, value_name = "BRANCH")]
+        base: Option<String>,
+    },
+
+    /// Draft Keep a Changelog release notes from the commits in a range,
+    /// e.g. xa changelog v1.2.0..HEAD (default: since the latest tag)
+    Changelog {
+        /// Commit range, or a ref to start after (REF..HEAD)
+        range: Option<String>,
+        /// Version for the heading (default: the range's end when it is a
+        /// tag, else Unreleased)
+        #[arg(long)]
+        version: Option<String>,
+    },
+
+    /// Explain a file or part of one, e.g. xa explain src/api.rs:40-120
+    Explain {
+        /// File to explain (PATH or PATH:START-END)
+        target: String,
+        /// Send along the local files it imports (mod, import, #include)
+        #[arg(long)]
+        imports: bool,
+    },
+
+    /// Write unit tests for a source file and save them where the
+    /// language's tooling looks (or at -o), e.g. xa tests src/parser.py
+    Tests {
+        /// Source file to test
+        path: String,
+        /// Test framework, e.g. pytest, unittest, jest, vitest, rspec
+        /// (default: the usual one for the language)
+        #[arg(long, value_name = "NAME")]
+        framework: Option<String>,
+        /// Overwrite the test file if it exists
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Review changes or files for bugs and risky code: the uncommitted
+    /// changes by default, e.g. xa review --staged, xa review src/api.rs:40-120
+    Review {
+        /// Files to review (PATH or PATH:START-END); with --staged or
+        /// --range, paths the diff is limited to
+        targets: Vec<String>,
+        /// Review the staged changes
+        #[arg(long, conflicts_with = "range")]
+        staged: bool,
+        /// Review the changes in a commit range, e.g. main..HEAD
+        #[arg(long, value_name = "A..B")]
+        range: Option<String>,
+        /// Print the issues as JSON (for CI annotations)
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Git integration
+    Git {
+        #[command(subcommand)]
+        action: GitAction,
+    },
+
+    /// Print the shell hook for xa fix and xa why, e.g. eval "$(xa init zsh)"
+    Init {
+        #[arg(value_enum)]
+        shell: shell::Shell,
+    },
+
+    /// Translate a Markdown, HTML or text file, leaving code, links and
+    /// markup as they are, e.g. xa translate-file README.md --to fr -o README.fr.md
+    TranslateFile {
+        /// File to translate
+        path: std::path::PathBuf,
+        /// Target language (default: that of the translate command)
+        #[arg(long, value_name = "LANG")]
+        to: Option<String>,
+        /// Run another command over the file's text instead, e.g. --with polish
+        #[arg(long, value_name = "COMMAND", default_value = "translate")]
+        with: String,
+        /// Arguments for the command
+        args: Vec<String>,
+        /// Replace the file with the result
+        #[arg(long)]
+        in_place: bool,
+    },
+
+    /// Translate a subtitle file (.srt or .vtt), keeping its indices and
+    /// timings; written next to it as <name>.<lang>.srt unless -o is given
+    Subs {
+        /// Subtitle file
+        path: std::path::PathBuf,
+        /// Target language (default: that of the translate command)
+        #[arg(long, value_name = "LANG")]
+        to: Option<String>,
+        /// Run another command over the cues instead, e.g. --with polish
+        #[arg(long, value_name = "COMMAND", default_value = "translate")]
+        with: String,
+        /// Arguments for the command
+        args: Vec<String>,
+    },
+
+    /// Run a command on a file again each time it is saved, e.g.
+    /// xa watch polish -f draft.md (add -o to write each result to a file)
+    Watch {
+        /// Command to run on the file's text
+        command_name: String,
+        /// Arguments for the command, e.g. the target language
+        args: Vec<String>,
+        /// File to watch
+        #[arg(short, long, value_name = "PATH")]
+        file: std::path::PathBuf,
+    },
+
+    /// Watch the clipboard and run a command on each new copy, e.g.
+    /// xa daemon --on-copy translate zh (the result replaces the copy)
+    ///
+    /// Every copy it handles is sent to the provider and printed, whatever
+    /// application it came from. Copies that match a [redact] pattern or
+    /// hold one of the store's secrets are skipped, but a password that
+    /// looks like ordinary text is not recognised: narrow what is sent
+    /// with --only, and stop the daemon before copying secrets.
+    Daemon {
+        /// Command to run on copied text
+        #[arg(long = "on-copy", value_name = "COMMAND")]
+        on_copy: String,
+        /// Arguments for the command, e.g. the target language
+        args: Vec<String>,
+        /// Skip copies longer than this many characters
+        #[arg(long, default_value_t = 2000, value_name = "N")]
+        max_chars: usize,
+        /// Only handle copies matching this regex (repeatable)
+        #[arg(long, value_name = "REGEX")]
+        only: Vec<String>,
+        /// Show the result as a desktop notification instead of copying it
+        #[arg(long)]
+        notify: bool,
+    },
+
+    /// List recent one-shot runs, or replay one
+    History {
+        /// Number of runs to show
+        #[arg(short = 'n', long, default_value_t = 20, value_name = "N")]
+        limit: usize,
+        /// Run entry N again (1 = the latest), with the same input and arguments
+        #[arg(long, value_name = "N")]
+        rerun: Option<usize>,
+        /// Have the model finish the answer of entry N (default 1, the
+        /// latest) whose stream was cut off
+        #[arg(long = "continue", value_name = "N", num_args = 0..=1, default_missing_value = "1", conflicts_with = "rerun")]
+        continue_run: Option<usize>,
+    },
+
+    /// Inspect the request/response log kept when log_transcripts = true
+    Transcript {
+        #[command(subcommand)]
+        action: TranscriptAction,
+    },
+
+    /// Ask one question, or start the interactive conversation mode
+    Ask {
+        /// Persona from config.toml's [personas] (default: "default")
+        #[arg(long, value_name = "NAME")]
+        persona: Option<String>,
+        /// Give a text file to the model as context (repeatable)
+        #[arg(short = 'f', long = "file", value_name = "PATH")]
+        files: Vec<String>,
+        /// Send an image to a vision model (repeatable); a file or a URL
+        #[arg(short = 'i', long = "image", value_name = "PATH|URL")]
+        images: Vec<String>,
+        /// Select a region of the screen and send it as an image
+        #[arg(long)]
+        screenshot: bool,
+        /// Let the model run shell commands, read and write files and fetch
+        /// web pages to answer (commands and writes are confirmed first)
+        #[arg(long)]
+        tools: bool,
+        /// Answer from the closest excerpts of a local index (see `xa index`)
+        #[arg(long = "with-index", value_name = "NAME")]
+        with_index: Option<String>,
+        /// Search the web first and answer from the results, citing them
+        /// (backend set under [search] in config.toml)
+        #[arg(long)]
+        search: bool,
+        /// Continue the conversation of the last `xa ask -c` (or of the
+        /// saved session NAME, started if there is none) and add this turn
+        #[arg(short = 'c', long = "continue", value_name = "NAME", num_args = 0..=1, require_equals = true, default_missing_value = "")]
+        continue_session: Option<String>,
+        /// The question; without one (and nothing piped), start a conversation
+        question: Vec<String>,
+    },
+
+    /// Ask about a region of the screen, e.g. xa see "translate this UI";
+    /// same as xa ask --screenshot
+    See {
+        /// The question (default: describe the screenshot)
+        question: Vec<String>,
+    },
 
     /// Launch the codex-like interactive coding TUI
     Chat,
@@ -120,6 +753,13 @@ enum Commands {
         id: Option<String>,
     },
 
+    /// List saved TUI sessions, or export one to share, e.g.
+    /// xa sessions export standup -o standup.html
+    Sessions {
+        #[command(subcommand)]
+        action: SessionsAction,
+    },
+
     /// Review saved tool-output and API token usage across sessions
     Gain {
         /// Break down totals by calendar day
@@ -135,232 +775,2073 @@ enum Commands {
         #[arg(long)]
         all: bool,
     },
+
+    /// Update xa to the latest GitHub release (the download is checked
+    /// against the release's SHA256SUMS)
+    SelfUpdate {
+        /// Only report whether a newer version is available
+        #[arg(long)]
+        check: bool,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+enum ConfigAction {
+    /// Print a setting's value (api_key is printed unmasked)
+    Get {
+        /// Setting name, as listed by `xa config show`
+        key: String,
+    },
+
+    /// Change a setting; an empty value clears optional settings
+    Set {
+        /// Setting name, as listed by `xa config show`
+        key: String,
+        value: String,
+    },
+
+    /// Print all settings with the API key masked
+    Show,
+}
+
+#[derive(Subcommand, Clone)]
+enum GitAction {
+    /// Add a prepare-commit-msg hook to this repository so git commit opens
+    /// with a message drafted by xa commit
+    InstallHooks {
+        /// Replace an existing hook that xa did not install
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+enum PromptsAction {
+    /// Edit an existing command in $EDITOR (or interactively without one)
+    Edit {
+        /// Command name to edit
+        name: String,
+    },
+
+    /// Restore a command removed with `xa rm` (lists the trash without a name)
+    Restore {
+        /// Command name to restore
+        name: Option<String>,
+    },
+
+    /// Check prompt templates for placeholder problems (exits 1 on failure)
+    Lint {
+        /// Prompts file to check instead of the configured prompts.toml
+        #[arg(long, value_name = "PATH")]
+        file: Option<std::path::PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+enum SessionsAction {
+    /// List saved sessions, newest first, with their size and last use
+    #[command(visible_alias = "ls")]
+    List,
+
+    /// Print a session's conversation
+    Show {
+        /// Session id, id prefix or title
+        name: String,
+    },
+
+    /// Give a session a new title
+    Rename {
+        /// Session id, id prefix or title
+        name: String,
+        /// The new title
+        title: String,
+    },
+
+    /// Delete sessions by name, or all those unused for a number of days
+    #[command(visible_alias = "remove")]
+    Rm {
+        /// Session ids, id prefixes or titles
+        #[arg(required_unless_present = "older_than")]
+        names: Vec<String>,
+        /// Delete every session last used more than DAYS days ago
+        #[arg(long, value_name = "DAYS")]
+        older_than: Option<u32>,
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+    },
+
+    /// Print a session as Markdown, or write it to -o (Markdown, or HTML in
+    /// the [theme] colors for a .html file)
+    Export {
+        /// Session id, id prefix or title
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+enum StoreAction {
+    /// Rename an entry's tag; without a new tag, let the model pick one
+    /// again from its note
+    Retag {
+        /// The entry's current tag
+        tag: String,
+        /// The new tag (made lowercase with hyphens, and unique)
+        new: Option<String>,
+    },
+    /// Merge the store with the git repository set as `store_remote` and
+    /// push the result, to share it between machines (secrets in plain
+    /// text, so only with `store_sync_plaintext` set)
+    Sync,
+}
+
+#[derive(Subcommand, Clone)]
+enum TranscriptAction {
+    /// List recent transcripts, newest first
+    List {
+        /// Number of transcripts to show
+        #[arg(short = 'n', long, default_value_t = 20, value_name = "N")]
+        limit: usize,
+    },
+
+    /// Print one request and its full response
+    Show {
+        /// Transcript id, as printed by `xa transcript list`
+        id: String,
+    },
 }
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() -> std::process::ExitCode {
     let cli = Cli::parse();
 
-    // Install TUI palette early (chat / resume / login all share it).
+    let verbosity = if cli.debug.is_some() { cli.verbose.max(2) } else { cli.verbose };
+    if let Err(e) = logging::init(verbosity, cli.log_file.as_deref()) {
+        eprintln!("Warning: logging disabled: {}", e);
+    }
+
+    // Install TUI palette early (chat / resume / login all share it), and
+    // the Markdown skin one-shot output uses.
     init_tui_theme(&cli);
 
+    let json_errors = cli.json_errors;
+    match run(cli).await {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(error) => {
+            error.report(json_errors);
+            std::process::ExitCode::from(error.exit_code())
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), XaError> {
     // Handle commands via subcommand matching
     match cli.command {
-        Some(Commands::Set { config_type }) => {
+        Some(Commands::Set { config_type, base_url, api_key, yes }) => {
             if config_type == "openai" {
-                config::configure_openai().await?;
-                return Ok(());
+                let options = config::SetupOptions { base_url, api_key, model: cli.model.clone(), yes };
+                config::configure_openai(options).await?;
+                Ok(())
             } else {
-                eprintln!("Unknown configuration type: {}", config_type);
-                eprintln!("Available configuration types: openai");
-                std::process::exit(1);
+                Err(XaError::Usage(format!(
+                    "Unknown configuration type: {}. Available configuration types: openai",
+                    config_type
+                )))
             }
         }
         Some(Commands::Ls { list_type }) => {
+            // With `ls`, -v also shows templates, arguments and overrides
+            let verbose = cli.verbose > 0;
             match list_type.as_deref() {
-                Some("prompts") => {
-                    prompt::list_prompts().await?;
-                    return Ok(());
-                }
-                Some("stores") => {
-                    store::list_stores().await?;
-                    return Ok(());
+                Some("prompts") => prompt::list_prompts().await?,
+                Some("stores") => store::list_stores().await?,
+                Some("plugins") => plugin::list_plugins(&config::load_config_sync()?),
+                pattern => prompt::list_commands(pattern, verbose).await?,
+            }
+            Ok(())
+        }
+        Some(Commands::Add { ref note, ref snippet, ref url, ref secret, ref description }) => {
+            let kinds = [(EntryKind::Note, note), (EntryKind::Snippet, snippet), (EntryKind::Url, url), (EntryKind::Secret, secret)];
+            let Some((kind, value)) = kinds.into_iter().find_map(|(kind, value)| Some((kind, value.as_deref()?))) else {
+                prompt::add_command().await?;
+                return Ok(());
+            };
+            let value = match value {
+                "-" => read_piped_stdin()?.ok_or_else(|| XaError::Usage(format!("--{} -: nothing was piped in", kind.name())))?,
+                value => value.to_string(),
+            };
+            let config = load_run_config(&cli).await?;
+            if config.missing_api_key() {
+                return Err(XaError::ConfigMissing);
+            }
+            store::add_entry(&config, kind, &value, description.as_deref().unwrap_or_default(), &resolve_duplicate).await?;
+            Ok(())
+        }
+        Some(Commands::Rm { command_name, yes }) => {
+            prompt::remove_command(&command_name, yes).await?;
+            Ok(())
+        }
+        Some(Commands::ResetDefaults) => {
+            prompt::reset_default_prompts()?;
+            Ok(())
+        }
+        Some(Commands::Config { action }) => {
+            match action {
+                ConfigAction::Get { key } => config::print_value(&key)?,
+                ConfigAction::Set { key, value } => config::update_value(&key, &value)?,
+                ConfigAction::Show => config::show_config()?,
+            }
+            Ok(())
+        }
+        Some(Commands::Prompts { action }) => {
+            match action {
+                PromptsAction::Edit { name } => prompt::edit_command(&name).await?,
+                PromptsAction::Restore { name } => prompt::restore_command(name.as_deref())?,
+                PromptsAction::Lint { file } => {
+                    if !prompt::lint_prompts(file.as_deref())? {
+                        return Err(XaError::Other("prompt lint found problems".into()));
+                    }
                 }
-                Some(other) => {
-                    eprintln!("Unknown list type: {}", other);
-                    eprintln!("Available list types: prompts, stores");
-                    eprintln!("Usage: xa ls prompts  or  xa ls stores");
-                    std::process::exit(1);
+            }
+            Ok(())
+        }
+        Some(Commands::Transcript { ref action }) => {
+            let result = match action {
+                TranscriptAction::List { limit } => transcript::print_list(*limit),
+                TranscriptAction::Show { id } => transcript::show(id),
+            };
+            result?;
+            Ok(())
+        }
+        Some(Commands::AddSecret { ref secret, ref note }) => {
+            let config = load_run_config(&cli).await?;
+            if config.missing_api_key() {
+                return Err(XaError::ConfigMissing);
+            }
+            store::add_entry(&config, EntryKind::Secret, secret, note, &resolve_duplicate).await?;
+            Ok(())
+        }
+        Some(Commands::Search { ref query, all }) => {
+            let config = load_run_config(&cli).await?;
+            if config.missing_api_key() {
+                return Err(XaError::ConfigMissing);
+            }
+            let entries = store::search(&config, query).await?;
+            match entries.as_slice() {
+                [] => println!("No found such thing."),
+                [entry] => print_store_entry(&config, entry),
+                entries if all => print_store_entries(entries),
+                entries => {
+                    if let Some(entry) = pick_store_entry(entries)? {
+                        print_store_entry(&config, entry);
+                    }
                 }
+            }
+            Ok(())
+        }
+        Some(Commands::Gen { length, ref charset, words, ref separator, ref save }) => {
+            let (secret, bits) = match words {
+                Some(count) => (passgen::passphrase(count.into(), separator)?, passgen::passphrase_bits(count.into())),
                 None => {
-                    prompt::list_commands().await?;
-                    return Ok(());
+                    let charset = charset.as_deref().map(passgen::Charset::parse).transpose()?.unwrap_or_default();
+                    (passgen::password(length.into(), &charset)?, passgen::password_bits(length.into(), &charset))
+                }
+            };
+            let config = load_run_config(&cli).await?;
+            if let Some(note) = save {
+                if config.missing_api_key() {
+                    return Err(XaError::ConfigMissing);
                 }
+                store::add_entry(&config, EntryKind::Secret, &secret, note, &resolve_duplicate).await?;
             }
+            println!("{}", secret);
+            let copied = io::stdout().is_terminal() && copy_result(&config, &secret, None).is_some();
+            let note = if copied { ", copied to the clipboard" } else { "" };
+            eprintln!("{}", paint("90", &format!("about {:.0} bits of entropy{}", bits, note)));
+            Ok(())
         }
-        Some(Commands::Add) => {
-            prompt::add_command().await?;
-            return Ok(());
+        Some(Commands::Store { ref action }) => {
+            match action {
+                StoreAction::Retag { tag, new } => {
+                    let config = load_run_config(&cli).await?;
+                    if new.is_none() && config.missing_api_key() {
+                        return Err(XaError::ConfigMissing);
+                    }
+                    store::retag(&config, tag, new.as_deref()).await?;
+                }
+                StoreAction::Sync => {
+                    let config = load_run_config(&cli).await?;
+                    let Some(remote) = config.store_remote.as_deref() else {
+                        return Err(XaError::Usage("No store remote; set one with `xa config set store_remote <git url>`".into()));
+                    };
+                    let report = store::sync::sync(&config, remote)?;
+                    eprintln!(
+                        "Synced with {}: {} entries received, {} sent{}",
+                        remote,
+                        report.received,
+                        report.sent,
+                        if report.retagged > 0 { format!(", {} retagged for clashing tags", report.retagged) } else { String::new() }
+                    );
+                }
+            }
+            Ok(())
         }
-        Some(Commands::Rm { command_name }) => {
-            prompt::remove_command(&command_name).await?;
-            return Ok(());
+        Some(Commands::Pipe { ref spec, ref text }) => {
+            run_pipe(&cli, spec, text).await?;
+            Ok(())
         }
-        Some(Commands::ResetDefaults) => {
-            prompt::reset_default_prompts()?;
-            return Ok(());
+        Some(Commands::Web { ref url, ref command_name, ref args }) => {
+            let input = fetch_article(url).await?;
+            process_command_with_args(&cli, command_name.as_deref().unwrap_or("summarize"), &input, args, false).await?;
+            Ok(())
         }
-        Some(Commands::AddSecret { secret, note }) => {
-            let config = load_config().await?;
+        Some(Commands::Transcribe { ref file, ref language, ref command_name, ref args }) => {
+            let config = load_run_config(&cli).await?;
             if config.api_key.is_empty() {
-                eprintln!("Error: API key not configured. Please run 'xa set openai' first.");
-                std::process::exit(1);
+                return Err(XaError::ConfigMissing);
             }
-            add_secret_with_tag(&config, &secret, &note).await?;
-            return Ok(());
+            let spinner = spinner::Spinner::start("Transcribing");
+            let transcript = transcribe::transcribe(&config, file, language.as_deref()).await;
+            spinner.stop();
+            let transcript = transcript?;
+            match command_name {
+                Some(name) => {
+                    process_command_with_args(&cli, name, &transcript, args, false).await?;
+                }
+                None => println!("{}", transcript),
+            }
+            Ok(())
         }
-        Some(Commands::Search { query }) => {
-            let config = load_config().await?;
-            if config.api_key.is_empty() {
-                eprintln!("Error: API key not configured. Please run 'xa set openai' first.");
-                std::process::exit(1);
+        Some(Commands::Ocr { ref image, backend, ref lang, ref command_name, ref args }) => {
+            let config = load_run_config(&cli).await?;
+            let backend = ocr::Backend::resolve(backend, &config)?;
+            if backend == ocr::Backend::Vision && config.missing_api_key() {
+                return Err(XaError::ConfigMissing);
             }
-            search_secret(&config, &query).await?;
-            return Ok(());
+            let spinner = spinner::Spinner::start("Reading the image");
+            let text = ocr::extract(&config, backend, image, lang.as_deref()).await;
+            spinner.stop();
+            let text = text?;
+            if text.is_empty() {
+                return Err(XaError::Other(format!("No text found in {}", image.display())));
+            }
+            match command_name {
+                Some(name) => {
+                    process_command_with_args(&cli, name, &text, args, false).await?;
+                }
+                None => println!("{}", text),
+            }
+            Ok(())
+        }
+        Some(Commands::Serve { mcp, ref http, ref token }) => {
+            if !mcp && http.is_none() {
+                return Err(XaError::Usage("Choose a protocol: xa serve --mcp or xa serve --http 127.0.0.1:7777".into()));
+            }
+            let config = load_run_config(&cli).await?;
+            if config.missing_api_key() {
+                return Err(XaError::ConfigMissing);
+            }
+            let prompt_config = load_prompt_config().await?;
+            if let Some(addr) = http {
+                let cli = cli.clone();
+                let configure = move |name: &str, entry: &prompt::PromptEntry, persona: Option<&str>| {
+                    let config = config_for_entry(&cli, &config, entry);
+                    match name {
+                        "ask" => config_for_persona(&cli, &config, persona.unwrap_or("default")),
+                        _ => Ok(config),
+                    }
+                };
+                api::serve(addr.as_str(), prompt_config.prompts, token.clone(), Box::new(configure)).await?;
+                return Ok(());
+            }
+            mcp::server::serve(&prompt_config.prompts, |entry| config_for_entry(&cli, &config, entry)).await?;
+            Ok(())
+        }
+        Some(Commands::History { limit, rerun, continue_run }) => {
+            if let Some(n) = continue_run {
+                let Some(entry) = history::nth_latest(n) else {
+                    return Err(XaError::Usage(format!("No run #{} in the history. See `xa history`.", n)));
+                };
+                let Some(partial) = entry.partial.as_deref() else {
+                    return Err(XaError::Usage(format!("Run #{} ({}) was not cut off; nothing to continue.", n, entry.command)));
+                };
+                continue_cut_off(&cli, &entry, partial).await?;
+                return Ok(());
+            }
+            match rerun {
+                Some(n) => {
+                    let Some(entry) = history::nth_latest(n) else {
+                        return Err(XaError::Usage(format!("No run #{} in the history. See `xa history`.", n)));
+                    };
+                    eprintln!("{}", paint("90", &format!("Replaying: {} ({})", entry.command, entry.model)));
+                    if entry.pipe {
+                        run_pipe(&cli, &entry.command, &entry.input).await?;
+                    } else {
+                        process_command_with_args(&cli, &entry.command, &entry.input, &entry.args, false).await?;
+                    }
+                }
+                None => history::print_history(limit),
+            }
+            Ok(())
         }
-        Some(Commands::Ask) => {
-            if cli.input.is_some() {
-                // Process with ask command if input provided
-                process_command_with_args(&cli, "ask").await?;
+        Some(Commands::TranslateFile { ref path, ref to, ref with, ref args, in_place }) => {
+            translate_file(&cli, path, to.as_deref(), with, args, in_place).await?;
+            Ok(())
+        }
+        Some(Commands::Bench { ref models, ref command_name, ref input, ref args }) => {
+            run_bench(&cli, models, command_name, input.as_deref(), args).await?;
+            Ok(())
+        }
+        Some(Commands::Subs { ref path, ref to, ref with, ref args }) => {
+            translate_subs(&cli, path, to.as_deref(), with, args).await?;
+            Ok(())
+        }
+        Some(Commands::Watch { ref command_name, ref args, ref file }) => {
+            run_watch(&cli, command_name, args, file).await?;
+            Ok(())
+        }
+        Some(Commands::Commit { hook: Some(ref file) }) => {
+            draft_commit_message(&cli, file).await?;
+            Ok(())
+        }
+        Some(Commands::Commit { hook: None }) => {
+            let Some(diff) = git::staged_diff()? else {
+                return Err(XaError::Usage("Nothing is staged; git add the changes first.".into()));
+            };
+            process_command_with_args(&cli, "commit-message", &diff, &[], false).await?;
+            Ok(())
+        }
+        Some(Commands::Pr { ref base }) => {
+            let base = match base {
+                Some(base) => base.clone(),
+                None => git::default_base()?,
+            };
+            let summary = git::branch_summary(&base)?;
+            process_command_with_args(&cli, "pr-description", &summary, &[], false).await?;
+            Ok(())
+        }
+        Some(Commands::Changelog { ref range, ref version }) => {
+            let range = match range {
+                Some(range) if range.contains("..") => range.clone(),
+                Some(start) => format!("{}..HEAD", start),
+                None => git::latest_tag().map_or("HEAD".to_string(), |tag| format!("{}..HEAD", tag)),
+            };
+            let commits = git::log(&range)?;
+            if commits.is_empty() {
+                return Err(XaError::Usage(format!("No commits in {}.", range)));
+            }
+            let end = range.rsplit_once("..").map_or("HEAD", |(_, end)| end.trim_start_matches('.'));
+            let heading = match (version, git::tag_date(end)) {
+                (Some(version), _) => changelog::heading(Some(version), &Local::now().format("%Y-%m-%d").to_string()),
+                (None, Some(date)) => changelog::heading(Some(end), &date),
+                (None, None) => changelog::heading(None, ""),
+            };
+            let input = format!("Heading: {}\n\n{}", heading, changelog::grouped(&commits));
+            process_command_with_args(&cli, "changelog", &input, &[], false).await?;
+            Ok(())
+        }
+        Some(Commands::Data { ref instruction, ref file, from, to, retries }) => {
+            run_data(&cli, instruction, file.as_deref(), from, to, retries).await?;
+            Ok(())
+        }
+        Some(Commands::Regex { ref description, ref test, retries }) => {
+            if description.is_empty() {
+                return Err(XaError::Usage("Say what the pattern should match, e.g. xa regex \"ISO dates\"".into()));
+            }
+            let samples = match test {
+                Some(path) => pattern::parse_samples(
+                    &std::fs::read_to_string(path).map_err(|e| XaError::Usage(format!("cannot read {}: {}", path.display(), e)))?,
+                ),
+                None => Vec::new(),
+            };
+            let config = load_run_config(&cli).await?;
+            if config.missing_api_key() {
+                return Err(XaError::ConfigMissing);
+            }
+            let re = pattern::generate(&config, &description.join(" "), &samples, retries).await?;
+            println!("{}", paint("1", re.as_str()));
+            if !samples.is_empty() {
+                println!("\n{}", pattern::render(&re, &samples));
+            }
+            if copy_result(&config, re.as_str(), None).is_some() {
+                eprintln!("{}", paint("90", "✓ pattern copied"));
+            }
+            Ok(())
+        }
+        Some(Commands::Explain { ref target, imports }) => {
+            let (path, range) = review::parse_target(target)?;
+            let config = load_run_config(&cli).await?;
+            if config.missing_api_key() {
+                return Err(XaError::ConfigMissing);
+            }
+            let (language, answer) = explain::explain(&config, &path, range, imports).await?;
+            if let Some(output) = &cli.output {
+                let format = cli.format.unwrap_or_else(|| OutputFormat::from_path(output));
+                output::write_output(output, format, cli.append, &output::ResultRecord { command: "explain", input: target, output: &answer })?;
+            } else if output::is_plain() {
+                println!("{}", answer);
+            } else {
+                explain::print(&answer, language);
+            }
+            Ok(())
+        }
+        Some(Commands::Tests { ref path, ref framework, force }) => {
+            let text = std::fs::read_to_string(path).map_err(|e| XaError::Usage(format!("cannot read {}: {}", path, e)))?;
+            let language = explain::Language::detect(std::path::Path::new(path), &text);
+            let framework = match framework.as_deref().or(testgen::default_framework(language)) {
+                Some(framework) => framework.to_string(),
+                None => return Err(XaError::Usage(format!("Cannot tell the language of {}; pass --framework.", path))),
+            };
+            let target = match cli.output.clone().or_else(|| testgen::test_path(std::path::Path::new(path), language, &framework)) {
+                Some(target) => target,
+                None => return Err(XaError::Usage(format!("No usual test location for {}; pass -o <file>.", path))),
+            };
+            if target.exists() && !force {
+                return Err(XaError::Usage(format!("{} already exists; pass --force to overwrite it", target.display())));
+            }
+            let config = load_run_config(&cli).await?;
+            if config.missing_api_key() {
+                return Err(XaError::ConfigMissing);
+            }
+            let code = testgen::generate(&config, path, &text, language, &framework, &target).await?;
+            testgen::write(&target, &code, force)?;
+            eprintln!("{}", paint("90", &format!("✓ {} tests written to {}", framework, target.display())));
+            Ok(())
+        }
+        Some(Commands::Patch { ref instruction, ref files, yes, retries }) => {
+            run_patch(&cli, &instruction.join(" "), files, yes, retries).await?;
+            Ok(())
+        }
+        Some(Commands::Cards { ref source, ref file, ref to, ref deck, ref name, count, retries }) => {
+            let source = match file {
+                Some(path) => std::fs::read_to_string(path).map_err(|e| XaError::Usage(format!("cannot read {}: {}", path.display(), e)))?,
+                None if !source.is_empty() => source.join(" "),
+                None => read_piped_stdin()?.ok_or_else(|| XaError::Usage("Say what the cards are about, e.g. xa cards \"Spanish travel phrases\"".into()))?,
+            };
+            let language = to.as_deref().or(cli.to.as_deref());
+            run_cards(&cli, &source, language, deck.as_deref(), name.as_deref(), count, retries).await?;
+            Ok(())
+        }
+        Some(Commands::Review { ref targets, staged, ref range, json }) => {
+            let chunks = review_chunks(targets, staged, range.as_deref())?;
+            if chunks.is_empty() {
+                return Err(XaError::Usage("Nothing to review: there are no changes.".into()));
+            }
+            let config = load_run_config(&cli).await?;
+            if config.missing_api_key() {
+                return Err(XaError::ConfigMissing);
+            }
+            let issues = review::review(&config, &chunks).await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&serde_json::json!({ "issues": issues }))?);
+            } else {
+                println!("{}", review::render(&issues));
+            }
+            Ok(())
+        }
+        Some(Commands::Git { action: GitAction::InstallHooks { force } }) => {
+            let path = git::install_hooks(force)?;
+            println!("Installed {}; git commit now starts from a message drafted by xa.", path.display());
+            Ok(())
+        }
+        Some(Commands::Init { shell }) => {
+            print!("{}", shell::init_script(shell));
+            Ok(())
+        }
+        Some(Commands::Why { rerun }) => {
+            let last = last_shell_command(rerun).await?;
+            process_command_with_args(&cli, "ask", &shell::why_prompt(&last), &[], false).await?;
+            Ok(())
+        }
+        Some(Commands::Fix { rerun }) => {
+            run_fix(&cli, rerun).await?;
+            Ok(())
+        }
+        Some(Commands::Daemon { ref on_copy, ref args, max_chars, ref only, notify }) => {
+            let only = daemon::allowlist(only)?;
+            let prompt_config = load_prompt_config().await?;
+            let Some(cmd) = find_command(on_copy, &prompt_config.prompts) else {
+                return Err(XaError::CommandNotFound(on_copy.clone()));
+            };
+            let entry = &prompt_config.prompts[&cmd];
+            if entry.pipeline.is_some() {
+                return Err(XaError::Usage(format!("'{}' is a pipeline; xa daemon runs single commands only.", cmd)));
+            }
+            let config = config_for_entry(&cli, &load_run_config(&cli).await?, entry);
+            if config.missing_api_key() {
+                return Err(XaError::ConfigMissing);
+            }
+            eprintln!(
+                "{}",
+                paint("90", &format!("Running {} on copied text (Ctrl-C to stop). Each copy it handles is sent to {}.", cmd, config.base_url))
+            );
+            daemon::run(&config, daemon::DaemonOptions { command: &cmd, entry, args, max_chars, only, notify }).await?;
+            Ok(())
+        }
+        Some(Commands::Batch { ref command_name, ref args, ref files, jobs }) => {
+            let config = load_run_config(&cli).await?;
+            if config.missing_api_key() {
+                return Err(XaError::ConfigMissing);
+            }
+            let prompt_config = load_prompt_config().await?;
+            let Some(cmd) = find_command(command_name, &prompt_config.prompts) else {
+                return Err(XaError::CommandNotFound(command_name.clone()));
+            };
+            let entry = &prompt_config.prompts[&cmd];
+            if entry.pipeline.is_some() {
+                return Err(XaError::Usage(format!("'{}' is a pipeline; xa batch runs single commands only.", cmd)));
+            }
+
+            let inputs: Vec<String> = if files.is_empty() {
+                if io::stdin().is_terminal() {
+                    return Err(XaError::Usage("No inputs. Pass --file <path> or pipe lines into xa batch.".into()));
+                }
+                io::stdin().lock().lines().collect::<Result<_, _>>()?
+            } else {
+                let mut inputs = Vec::new();
+                for path in files {
+                    let content = std::fs::read_to_string(path)
+                        .map_err(|e| XaError::Usage(format!("{}: {}", path.display(), e)))?;
+                    inputs.extend(content.lines().map(String::from));
+                }
+                inputs
+            };
+
+            let config = config_for_entry(&cli, &config, entry);
+            let (mut file, mut stdout);
+            let (writer, format, to_terminal): (&mut dyn Write, _, _) = match &cli.output {
+                Some(path) => {
+                    file = output::open_output(path, cli.append)?;
+                    (&mut file, cli.format.unwrap_or_else(|| OutputFormat::from_path(path)), false)
+                }
+                None => {
+                    stdout = io::stdout();
+                    let to_terminal = stdout.is_terminal();
+                    (&mut stdout, cli.format.unwrap_or(OutputFormat::Txt), to_terminal)
+                }
+            };
+            let options = batch::BatchOptions { jobs, debug: cli.debug, writer, format, to_terminal };
+            batch::run(&config, &cmd, entry, args, inputs, options).await?.check("inputs")
+        }
+        Some(Commands::Embed { ref texts }) => {
+            let config = load_run_config(&cli).await?;
+            if config.api_key.is_empty() && config.embedding_url.is_none() {
+                return Err(XaError::ConfigMissing);
+            }
+            let texts = if texts.is_empty() {
+                let piped = read_piped_stdin()?
+                    .ok_or_else(|| XaError::Usage("Give the text to embed, or pipe it in one per line".into()))?;
+                piped.lines().filter(|line| !line.trim().is_empty()).map(str::to_string).collect()
+            } else {
+                texts.clone()
+            };
+            let model = cli.model.clone().unwrap_or_else(|| config.embedding_model());
+            let vectors = embeddings::embed(&config, &model, &texts).await?;
+            for vector in vectors {
+                println!("{}", serde_json::to_string(&vector)?);
+            }
+            Ok(())
+        }
+        Some(Commands::Index { ref dir, ref name }) => {
+            let Some(dir) = dir else {
+                index::print_list()?;
+                return Ok(());
+            };
+            let config = load_run_config(&cli).await?;
+            if config.api_key.is_empty() && config.embedding_url.is_none() {
+                return Err(XaError::ConfigMissing);
+            }
+            let name = match name {
+                Some(name) => name.clone(),
+                None => dir
+                    .canonicalize()?
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .ok_or_else(|| XaError::Usage("Give the index a name with --name".into()))?,
+            };
+            let index = index::build(&config, dir, &name).await?;
+            let files: std::collections::BTreeSet<&str> = index.chunks.iter().map(|chunk| chunk.path.as_str()).collect();
+            println!(
+                "Indexed {} files ({} chunks) as '{}'. Ask with: xa ask --with-index {} \"...\"",
+                files.len(),
+                index.chunks.len(),
+                name,
+                name
+            );
+            Ok(())
+        }
+        Some(Commands::Ask { ref persona, ref files, ref with_index, search, ref continue_session, ref question, .. }) => {
+            let mut attachments = Vec::new();
+            for path in files {
+                let file = attachment::Attachment::read(path)?;
+                attachment::check_total(&attachments, &file)?;
+                attachments.push(file);
+            }
+            let question = if question.is_empty() { read_piped_stdin()? } else { Some(question.join(" ")) };
+            let index = with_index.as_deref().map(index::load).transpose()?;
+            if let Some(question) = question {
+                let config = load_run_config(&cli).await?;
+                let excerpts = match &index {
+                    Some(index) => index_context(&config, index, &question).await?,
+                    None => String::new(),
+                };
+                let mut rolling = match continue_session {
+                    Some(name) => Some(ask_session(name).await),
+                    None => None,
+                };
+                let asked = question.clone();
+                let question = if search { with_search_results(&config, &question).await? } else { question };
+                // Earlier turns of an `xa ask -c` session, as the conversation mode sends them
+                let history = rolling.as_ref().map(Session::transcript).unwrap_or_default();
+                let question = if history.is_empty() { question } else { format!("{}Current message: {}", history, question) };
+                let question = format!("{}{}{}", attachment::context(&attachments), excerpts, question);
+                let answer = process_command_with_args(&cli, "ask", &question, &[], false).await?;
+                if let (Some(session), Some(answer)) = (rolling.as_mut(), answer) {
+                    save_ask_turn(&config, session, &asked, &answer).await?;
+                }
             } else {
                 // Start interactive conversation mode
-                start_interactive_mode().await?;
+                start_interactive_mode(&cli, persona.as_deref().unwrap_or("default"), attachments, index).await?;
             }
-            return Ok(());
+            Ok(())
+        }
+        Some(Commands::See { ref question }) => {
+            let question = if question.is_empty() { SEE_QUESTION.to_string() } else { question.join(" ") };
+            process_command_with_args(&cli, "ask", &question, &[], false).await?;
+            Ok(())
         }
         Some(Commands::Chat) => {
             let provider = agent::load_active_provider().await;
             let session = Session::new(&provider.name, &provider.model);
-            tui::run(provider, session).await?;
-            return Ok(());
+            tui::run(provider, session).await.map_err(XaError::from_boxed)?;
+            Ok(())
         }
         Some(Commands::Login { name }) => {
             run_login(name).await?;
-            return Ok(());
+            Ok(())
         }
         Some(Commands::Resume { id }) => {
             resume_session(id).await?;
-            return Ok(());
+            Ok(())
+        }
+        Some(Commands::Sessions { ref action }) => {
+            match action {
+                SessionsAction::List => print_sessions(),
+                SessionsAction::Show { name } => output::skin().print_text(&session::export::to_markdown(&find_session(name)?)),
+                SessionsAction::Rename { name, title } => rename_session(name, title)?,
+                SessionsAction::Rm { names, older_than, yes } => remove_sessions(names, *older_than, *yes)?,
+                SessionsAction::Export { name } => export_session(name, cli.output.as_deref())?,
+            }
+            Ok(())
         }
         Some(Commands::Gain { daily, weekly, monthly, all }) => {
             print_gain(daily, weekly, monthly, all)?;
-            return Ok(());
+            Ok(())
+        }
+        Some(Commands::SelfUpdate { check }) => {
+            update::self_update(check).await?;
+            Ok(())
         }
         None => {
-            // No subcommand provided -> launch the interactive agent TUI directly.
-            if cli.input.is_some() {
-                eprintln!("Error: No command provided");
-                std::process::exit(1);
+            let piped = read_piped_stdin()?;
+            if cli.input.is_some() || piped.is_some() || cli.from_clipboard || cli.from_tmux.is_some() {
+                run_one_shot(&cli, piped).await?;
             } else {
+                // No subcommand or input -> launch the interactive agent TUI directly.
                 let provider = agent::load_active_provider().await;
                 let session = Session::new(&provider.name, &provider.model);
-                tui::run(provider, session).await?;
+                tui::run(provider, session).await.map_err(XaError::from_boxed)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Text piped into xa (`echo hi | xa translate`), or `None` on a terminal.
+fn read_piped_stdin() -> io::Result<Option<String>> {
+    if io::stdin().is_terminal() {
+        return Ok(None);
+    }
+    let mut text = String::new();
+    io::stdin().read_to_string(&mut text)?;
+    let text = text.trim_end_matches(['\n', '\r']);
+    Ok((!text.trim().is_empty()).then(|| text.to_string()))
+}
+
+/// `xa <command> [text] [args...]` and `xa "some text"`. Piped text, when
+/// present, is the input and every positional after the command is an
+/// argument. Input without a command (a quoted sentence, words that do not
+/// start with a command, or only piped text) opens the command picker.
+async fn run_one_shot(cli: &Cli, piped: Option<String>) -> Result<(), XaError> {
+    let mut words: Vec<String> = cli.input.iter().chain(&cli.args).cloned().collect();
+    // After the text, clap leaves `--to` among the arguments
+    let with_to;
+    let cli = match take_to_flag(&mut words) {
+        Some(language) => {
+            with_to = Cli { to: Some(language), ..cli.clone() };
+            &with_to
+        }
+        None => cli,
+    };
+    // Clipboard or tmux text stands in for piped text: every word after the command is an argument
+    let mut piped = match piped {
+        Some(_) if cli.from_clipboard || cli.from_tmux.is_some() => {
+            let flag = if cli.from_clipboard { "--from-clipboard" } else { "--from-tmux" };
+            return Err(XaError::Usage(format!("{} cannot be combined with piped input", flag)));
+        }
+        None if cli.from_clipboard => Some(clipboard_input()?.ok_or_else(|| XaError::Usage("The clipboard is empty.".into()))?),
+        None => cli.from_tmux.as_deref().map(tmux::capture).transpose()?,
+        piped => piped,
+    };
+    if cli.each || take_switch(&mut words, "--each") {
+        if piped.is_some() {
+            return Err(XaError::Usage("--each reads the files named after the command, not piped input".into()));
+        }
+        return run_each(cli, &words).await;
+    }
+
+    let names_a_command = match words.first() {
+        Some(word) if piped.is_some() => !word.contains(char::is_whitespace),
+        Some(word) => is_command_word(word, words.len() > 1).await?,
+        None => false,
+    };
+    if !names_a_command {
+        let (input, args) = match piped {
+            Some(text) => (text, words),
+            None if words[0].contains(char::is_whitespace) => (words[0].clone(), words[1..].to_vec()),
+            // `xa hello`: a word that is not a command is the text
+            None => (words.join(" "), Vec::new()),
+        };
+        let Some(command_name) = pick_command_for(&input).await? else {
+            return Ok(());
+        };
+        process_command_with_args(cli, &command_name, &input, &args, false).await?;
+        return Ok(());
+    }
+
+    let command_name = &words[0];
+    let rest = &words[1..];
+    if let Some(plugin) = plugin_for(command_name).await? {
+        let config = load_run_config(cli).await?;
+        let status = plugin::run(&config, command_name, &plugin, rest, piped.as_deref())?;
+        if !status.success() {
+            // The plugin has reported its own failure; only pass the code on
+            return Err(XaError::exited(status));
+        }
+        return Ok(());
+    }
+    if piped.is_none() && rest.is_empty() && !config::load_config_sync()?.no_clipboard {
+        // `xa translate` alone: whatever was just copied
+        if let Ok(Some(text)) = clipboard_input() {
+            eprintln!("{}", paint("90", &format!("Using the clipboard as input ({} lines)", text.lines().count())));
+            piped = Some(text);
+        }
+    }
+    let positional = piped.is_none();
+    let (mut input, args) = match piped {
+        Some(text) => (text, rest.to_vec()),
+        None => match rest.split_first() {
+            Some((text, args)) => (text.clone(), args.to_vec()),
+            None => {
+                return Err(XaError::Usage(format!("No input text provided. Usage: xa {} <text> [args...]", command_name)));
+            }
+        },
+    };
+    if positional && input == "-" {
+        // `xa translate -`: the previous command's result
+        input = prompt::resolve_dash_input(input, prompt::last_result)?;
+    } else if positional && web::is_url(&input) {
+        // `xa summarize https://...`: the page, not the address
+        input = fetch_article(&input).await?;
+    }
+    process_command_with_args(cli, command_name, &input, &args, positional).await?;
+    Ok(())
+}
+
+/// Remove `--to LANG` (or `--to=LANG`) from the words after the command
+/// name and return the language.
+fn take_to_flag(words: &mut Vec<String>) -> Option<String> {
+    let at = words.iter().skip(1).position(|word| word == "--to" || word.starts_with("--to="))? + 1;
+    let flag = words.remove(at);
+    match flag.strip_prefix("--to=") {
+        Some(language) => Some(language.to_string()),
+        None if at < words.len() => Some(words.remove(at)),
+        None => None,
+    }
+}
+
+/// Remove the switch `name` from the words after the command name; whether
+/// it was there.
+fn take_switch(words: &mut Vec<String>, name: &str) -> bool {
+    match words.iter().skip(1).position(|word| word == name) {
+        Some(at) => {
+            words.remove(at + 1);
+            true
+        }
+        None => false,
+    }
+}
+
+/// `xa <command> <files...> --each`: the command over the contents of each
+/// file, several at a time.
+async fn run_each(cli: &Cli, words: &[String]) -> Result<(), XaError> {
+    let Some((command_name, rest)) = words.split_first() else {
+        return Err(XaError::Usage("Usage: xa <command> <files...> --each".into()));
+    };
+    let prompt_config = load_prompt_config().await?;
+    let Some(cmd) = find_command(command_name, &prompt_config.prompts) else {
+        return Err(XaError::CommandNotFound(command_name.clone()));
+    };
+    let entry = &prompt_config.prompts[&cmd];
+    if entry.pipeline.is_some() {
+        return Err(XaError::Usage(format!("'{}' is a pipeline; --each runs single commands only.", cmd)));
+    }
+    let (files, mut args) = each_files(rest, &cmd)?;
+    if files.is_empty() {
+        return Err(XaError::Usage(format!("No files to run {} on. Usage: xa {} <files...> --each", cmd, cmd)));
+    }
+    if let Some(language) = &cli.to {
+        args = with_target_lang(&cmd, entry, args, language)?;
+    }
+    let config = load_run_config(cli).await?;
+    if config.missing_api_key() {
+        return Err(XaError::ConfigMissing);
+    }
+    let config = config_for_entry(cli, &config, entry);
+
+    let mut file;
+    let report: Option<(&mut dyn Write, OutputFormat)> = match &cli.output {
+        Some(path) => {
+            file = output::open_output(path, cli.append)?;
+            Some((&mut file, cli.format.unwrap_or_else(|| OutputFormat::from_path(path))))
+        }
+        None => None,
+    };
+    let options = batch::EachOptions { jobs: 4, debug: cli.debug, report };
+    let summary = batch::run_each(&config, &cmd, entry, &args, &files, options).await?;
+    if let Some(path) = &cli.output {
+        eprintln!("{}", paint("90", &format!("✓ written to {}", path.display())));
+    }
+    summary.check("files")
+}
+
+/// The words after the command split into the files for `--each` (globs
+/// expanded, earlier `--each` results left out) and the command's
+/// arguments, which are the words that name no file.
+fn each_files(words: &[String], cmd: &str) -> Result<(Vec<std::path::PathBuf>, Vec<String>), XaError> {
+    let suffix = format!(".{}.md", cmd);
+    let (mut files, mut args) = (Vec::new(), Vec::new());
+    for word in words {
+        if word.contains(['*', '?', '[']) {
+            let paths = glob::glob(word).map_err(|e| XaError::Usage(format!("invalid pattern '{}': {}", word, e)))?;
+            let matched: Vec<_> =
+                paths.filter_map(Result::ok).filter(|path| path.is_file() && !path.to_string_lossy().ends_with(&suffix)).collect();
+            if matched.is_empty() {
+                return Err(XaError::Usage(format!("No files match '{}'", word)));
+            }
+            files.extend(matched);
+        } else if std::path::Path::new(word).is_file() {
+            files.push(std::path::PathBuf::from(word));
+        } else {
+            args.push(word.clone());
+        }
+    }
+    let mut seen = std::collections::HashSet::new();
+    files.retain(|file| seen.insert(file.clone()));
+    Ok((files, args))
+}
+
+/// `xa translate-file`: run a command over the prose of a file and write
+/// the file back out with everything else unchanged.
+async fn translate_file(
+    cli: &Cli,
+    path: &std::path::Path,
+    to: Option<&str>,
+    with: &str,
+    args: &[String],
+    in_place: bool,
+) -> Result<(), XaError> {
+    if in_place && cli.output.is_some() {
+        return Err(XaError::Usage("--in-place and --output cannot be combined".into()));
+    }
+    let (cmd, entry, config) = file_command(cli, with, "translate-file").await?;
+    let args: Vec<String> = to.map(str::to_string).into_iter().chain(args.iter().cloned()).collect();
+    let (result, kept) = document::transform_file(&config, &entry, &args, path, &cmd).await?;
+    if kept > 0 {
+        eprintln!("{}", paint("33", &format!("{} passages were left as they were: the answer lost their code or links", kept)));
+    }
+    let target = if in_place { Some(path) } else { cli.output.as_deref() };
+    match target {
+        Some(target) => {
+            config::safe_write(target, result)?;
+            eprintln!("{}", paint("90", &format!("Wrote {}", target.display())));
+        }
+        None => print!("{}", result),
+    }
+    Ok(())
+}
+
+/// The single command `xa translate-file`, `xa subs` or `xa bench` runs,
+/// with the config to run it with.
+async fn file_command(cli: &Cli, with: &str, mode: &str) -> Result<(String, prompt::PromptEntry, config::Config), XaError> {
+    let prompt_config = load_prompt_config().await?;
+    let Some(cmd) = find_command(with, &prompt_config.prompts) else {
+        return Err(XaError::CommandNotFound(with.to_string()));
+    };
+    let entry = prompt_config.prompts[&cmd].clone();
+    if entry.pipeline.is_some() {
+        return Err(XaError::Usage(format!("'{}' is a pipeline; xa {} runs single commands only.", cmd, mode)));
+    }
+    let config = config_for_entry(cli, &load_run_config(cli).await?, &entry);
+    if config.missing_api_key() {
+        return Err(XaError::ConfigMissing);
+    }
+    Ok((cmd, entry, config))
+}
+
+async fn translate_subs(cli: &Cli, path: &std::path::Path, to: Option<&str>, with: &str, args: &[String]) -> Result<(), XaError> {
+    let Some(kind) = subs::Kind::of(path) else {
+        return Err(XaError::Usage(format!("{} is not a .srt or .vtt file.", path.display())));
+    };
+    let text = std::fs::read_to_string(path).map_err(|e| XaError::Usage(format!("cannot read {}: {}", path.display(), e)))?;
+    let mut subtitles = subs::Subtitles::parse(&text, kind).map_err(|e| XaError::Usage(format!("{}: {}", path.display(), e)))?;
+    let (cmd, entry, config) = file_command(cli, with, "subs").await?;
+    let args: Vec<String> = to.map(str::to_string).into_iter().chain(args.iter().cloned()).collect();
+
+    let (texts, kept) = document::transform_each(&config, &entry, &args, &subtitles.segments()).await?;
+    if kept > 0 {
+        eprintln!("{}", paint("33", &format!("{} cues were left as they were: the answer lost their markup", kept)));
+    }
+    subtitles.set_texts(&texts);
+    let target = match &cli.output {
+        Some(target) => target.clone(),
+        None => subs::output_path(path, to.unwrap_or(&cmd)),
+    };
+    config::safe_write(&target, subtitles.render())?;
+    let cues = subtitles.cues();
+    eprintln!("{}", paint("90", &format!("Wrote {} ({} cue{})", target.display(), cues, if cues == 1 { "" } else { "s" })));
+    Ok(())
+}
+
+/// `xa bench`: the command's prompt for `input` sent to each of `models`
+/// at once, then a table of how they did and their answers side by side.
+async fn run_bench(cli: &Cli, models: &[String], command_name: &str, input: Option<&str>, args: &[String]) -> Result<(), XaError> {
+    let input = match input {
+        Some(input) => input.to_string(),
+        None => read_piped_stdin()?.ok_or_else(|| XaError::Usage("Give the input after the command, or pipe it in.".into()))?,
+    };
+    let (_, entry, config) = file_command(cli, command_name, "bench").await?;
+    let input = entry.prepare_input(&input).await?;
+    let prompt = entry.fill_prompt(&config, &input, args).await?;
+    let spinner = spinner::Spinner::start(&format!("Asking {} models", models.len()));
+    let runs = bench::run(&config, &entry, &prompt, models).await;
+    spinner.stop();
+    println!("{}", bench::table(&config, &runs));
+    let labels: Vec<String> = runs.iter().map(|run| run.model.clone()).collect();
+    let answers: Vec<String> = runs
+        .iter()
+        .map(|run| match &run.result {
+            Ok(answer) => answer.clone(),
+            Err(error) => format!("(failed: {})", error),
+        })
+        .collect();
+    output::print_choices(&labels, &answers);
+    // When every model failed, exit as the first did (often a wrong key)
+    if runs.iter().all(|run| run.result.is_err()) {
+        if let Some(Err(error)) = runs.into_iter().next().map(|run| run.result) {
+            return Err(error);
+        }
+    }
+    Ok(())
+}
+
+/// `xa watch`: run `command_name` on `file` now and after every change,
+/// until interrupted. A failed run is reported and the watch goes on.
+async fn run_watch(cli: &Cli, command_name: &str, args: &[String], file: &std::path::Path) -> Result<(), XaError> {
+    if let Some(output) = &cli.output {
+        if std::fs::canonicalize(output).is_ok_and(|output| std::fs::canonicalize(file).is_ok_and(|file| output == file)) {
+            return Err(XaError::Usage("--output must not be the watched file; each result would trigger another run".into()));
+        }
+    }
+    watch::rerun(file, command_name, cli.json_errors, |text| async move {
+        process_command_with_args(cli, command_name, &text, args, false).await.map(|_| ())
+    })
+    .await
+}
+
+/// What `xa review` sends: the named files, or else the diff of the staged
+/// changes, of a range or of the working tree against HEAD.
+fn review_chunks(targets: &[String], staged: bool, range: Option<&str>) -> Result<Vec<String>, XaError> {
+    if !staged && range.is_none() && !targets.is_empty() {
+        let mut chunks = Vec::new();
+        for target in targets {
+            let (path, lines) = review::parse_target(target)?;
+            let text = std::fs::read_to_string(&path).map_err(|e| XaError::Usage(format!("cannot read {}: {}", path, e)))?;
+            chunks.extend(review::file_chunks(&path, &text, lines));
+        }
+        return Ok(chunks);
+    }
+    let mut args = vec![if staged { "--cached" } else { range.unwrap_or("HEAD") }];
+    if !targets.is_empty() {
+        args.push("--");
+        args.extend(targets.iter().map(String::as_str));
+    }
+    Ok(review::diff_chunks(&git::diff(&args)?))
+}
+
+/// `xa commit --hook <file>`: put a drafted message above what git wrote
+/// into the message file. Nothing staged leaves the file alone.
+async fn draft_commit_message(cli: &Cli, file: &std::path::Path) -> Result<(), XaError> {
+    let Some(diff) = git::staged_diff()? else {
+        return Ok(());
+    };
+    let prompt_config = load_prompt_config().await?;
+    let Some(entry) = prompt_config.prompts.get("commit-message") else {
+        return Err(XaError::CommandNotFound("commit-message".into()));
+    };
+    let config = config_for_entry(cli, &load_run_config(cli).await?, entry);
+    if config.missing_api_key() {
+        return Err(XaError::ConfigMissing);
+    }
+    let input = entry.prepare_input(&diff).await?;
+    let prompt = entry.fill_prompt(&config, &input, &[]).await?;
+    let spinner = spinner::Spinner::start("Drafting the commit message");
+    let done = llm::complete(&config, &prompt).await;
+    spinner.stop();
+    let message = entry.finish_output(&done?.text).await?;
+    let existing = std::fs::read_to_string(file).unwrap_or_default();
+    std::fs::write(file, git::with_message(&existing, &message))?;
+    Ok(())
+}
+
+/// The command the shell hook recorded, run again for its output with
+/// `--rerun`.
+async fn last_shell_command(rerun: bool) -> Result<shell::LastCommand, XaError> {
+    let mut last = shell::LastCommand::from_env()?;
+    if rerun {
+        eprintln!("{}", paint("90", &format!("Running `{}` again to capture its output", last.command)));
+        last.rerun().await?;
+    }
+    Ok(last)
+}
+
+/// `xa fix`: ask for a corrected command, show it, and run it if confirmed,
+/// exiting with its status.
+async fn run_fix(cli: &Cli, rerun: bool) -> Result<(), XaError> {
+    let last = last_shell_command(rerun).await?;
+    let config = load_run_config(cli).await?;
+    if config.missing_api_key() {
+        return Err(XaError::ConfigMissing);
+    }
+    let spinner = spinner::Spinner::start("Thinking");
+    let answer = llm::complete(&config, &shell::fix_prompt(&last)).await;
+    spinner.stop();
+    let Some(command) = shell::parse_fix(&answer?.text) else {
+        return Err(XaError::Other(format!("No fix suggested for `{}`", last.command)));
+    };
+    if command == last.command {
+        eprintln!("{}", paint("90", "The model suggests running the same command again."));
+    }
+    eprintln!("{}", paint("1", &command));
+    if !confirm_on_tty("Run it? [Y/n] ")? {
+        return Ok(());
+    }
+    let status = shell::run(&command).await?;
+    if !status.success() {
+        return Err(XaError::exited(status));
+    }
+    Ok(())
+}
+
+/// The clipboard's text as input, or `None` when it holds nothing.
+fn clipboard_input() -> Result<Option<String>, XaError> {
+    let text = utils::read_clipboard()?;
+    let text = text.trim_end_matches(['\n', '\r']);
+    Ok((!text.trim().is_empty()).then(|| text.to_string()))
+}
+
+/// The plugin `xa <name>` runs, unless `name` is a prompt command.
+async fn plugin_for(name: &str) -> Result<Option<config::Plugin>, XaError> {
+    let Some(plugin) = plugin::find(&config::load_config_sync()?, name) else {
+        return Ok(None);
+    };
+    let prompt_config = load_prompt_config().await?;
+    Ok((!prompt_config.prompts.contains_key(name)).then_some(plugin))
+}
+
+/// Whether `word`, the first one on the command line, is something to run:
+/// a prompt command (also by prefix or fuzzy match), a plugin, or with
+/// `fallback_to_ask` and `more` words after it, the start of a question.
+async fn is_command_word(word: &str, more: bool) -> Result<bool, XaError> {
+    if word.contains(char::is_whitespace) {
+        return Ok(false);
+    }
+    let prompt_config = load_prompt_config().await?;
+    let prompts = &prompt_config.prompts;
+    // An ambiguous prefix is still meant as a command, and says so when run
+    if prompts.keys().any(|name| name.starts_with(word)) || find_command(word, prompts).is_some() {
+        return Ok(true);
+    }
+    if plugin_for(word).await?.is_some() {
+        return Ok(true);
+    }
+    Ok(more && load_config().await?.fallback_to_ask && prompts.contains_key("ask"))
+}
+
+/// The article at `url` as prompt input, with a note on stderr of what
+/// was fetched.
+async fn fetch_article(url: &str) -> Result<String, XaError> {
+    let spinner = spinner::Spinner::start("Fetching");
+    let article = web::fetch(url).await;
+    spinner.stop();
+    let article = article?;
+    let title = article.title.as_deref().unwrap_or(url);
+    eprintln!("{}", paint("90", &format!("Fetched \"{}\" (~{} words)", title, article.word_count())));
+    Ok(article.input())
+}
+
+/// `xa patch`: show the diff and write the files once confirmed, or write
+/// the diff to `-o` instead of applying it.
+async fn run_patch(cli: &Cli, instruction: &str, paths: &[String], yes: bool, retries: u32) -> Result<(), XaError> {
+    if instruction.trim().is_empty() {
+        return Err(XaError::Usage("Say what to change, e.g. xa patch \"rename parse_args to parse_cli\" -f src/main.rs".into()));
+    }
+    let mut files = Vec::new();
+    for path in paths {
+        let text = std::fs::read_to_string(path).map_err(|e| XaError::Usage(format!("cannot read {}: {}", path, e)))?;
+        files.push((path.clone(), text));
+    }
+    let config = load_run_config(cli).await?;
+    if config.missing_api_key() {
+        return Err(XaError::ConfigMissing);
+    }
+    let (changes, diff) = patch::generate(&config, instruction, &files, retries).await?;
+    if let Some(output) = &cli.output {
+        std::fs::write(output, &diff)?;
+        eprintln!("{}", paint("90", &format!("✓ patch written to {}; apply it with git apply {}", output.display(), output.display())));
+        return Ok(());
+    }
+    println!("{}", patch::render(&diff));
+    if !yes && !confirm_on_tty("Apply it? [Y/n] ")? {
+        eprintln!("{}", paint("90", "Nothing was changed."));
+        return Ok(());
+    }
+    for change in &changes {
+        std::fs::write(&change.path, &change.after)?;
+    }
+    let names: Vec<&str> = changes.iter().map(|change| change.path.as_str()).collect();
+    eprintln!("{}", paint("90", &format!("✓ patched {}", names.join(", "))));
+    Ok(())
+}
+
+/// Yes/no question on the terminal, read from the tty even when stdin carries
+/// piped input. Enter means yes; with no terminal at all the answer is no.
+fn confirm_on_tty(question: &str) -> io::Result<bool> {
+    let Some(answer) = ask_on_tty(question)? else {
+        return Ok(false);
+    };
+    let answer = answer.trim().to_lowercase();
+    Ok(answer.is_empty() || answer == "y" || answer == "yes")
+}
+
+/// Ask `question` on stderr and read the answer from the terminal, even
+/// when stdin is piped. `None` when there is no terminal to ask on.
+fn ask_on_tty(question: &str) -> io::Result<Option<String>> {
+    eprint!("{}", question);
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    if io::stdin().is_terminal() {
+        io::stdin().read_line(&mut answer)?;
+    } else {
+        #[cfg(unix)]
+        let tty = std::fs::File::open("/dev/tty");
+        #[cfg(windows)]
+        let tty = std::fs::File::open("CONIN$");
+        match tty {
+            Ok(tty) => {
+                io::BufReader::new(tty).read_line(&mut answer)?;
             }
-            return Ok(());
+            Err(_) => {
+                eprintln!();
+                return Ok(None);
+            }
+        }
+    }
+    Ok(Some(answer))
+}
+
+/// `-n`: ask for `n` answers to `prompt`, show them and let the user pick
+/// one. `None` when none is picked. Without a terminal to ask on, the
+/// first is taken.
+async fn pick_completion(
+    config: &config::Config,
+    entry: &prompt::PromptEntry,
+    prompt: &str,
+    n: usize,
+) -> Result<Option<llm::Completion>, XaError> {
+    let spinner = spinner::Spinner::start(&format!("Asking for {} answers", n));
+    let completions = llm::complete_n(config, prompt, n).await;
+    spinner.stop();
+    let completions = completions?;
+    let usage = completions.iter().fold(None, |total, completion| llm::Usage::sum(total, completion.usage));
+    let mut texts = Vec::with_capacity(completions.len());
+    for completion in completions {
+        texts.push(entry.finish_output(&completion.text).await?);
+    }
+    let labels: Vec<String> = (1..=texts.len()).map(|i| format!("[{}]", i)).collect();
+    output::print_choices(&labels, &texts);
+    let question = format!("Copy which? [1-{}, Enter for 1, q for none]: ", texts.len());
+    let index = loop {
+        let Some(answer) = ask_on_tty(&question)? else {
+            break 0;
+        };
+        match answer.trim() {
+            "" => break 0,
+            "q" | "Q" => return Ok(None),
+            answer => match answer.parse::<usize>() {
+                Ok(number) if (1..=texts.len()).contains(&number) => break number - 1,
+                _ => eprintln!("Enter a number from 1 to {}.", texts.len()),
+            },
         }
+    };
+    Ok(Some(llm::Completion { text: texts.swap_remove(index), usage, rendered: true, timing: None, interrupted: None }))
+}
+
+/// Let the user choose which command to run on `input`.
+async fn pick_command_for(input: &str) -> Result<Option<String>, XaError> {
+    if !io::stdout().is_terminal() {
+        return Err(XaError::Usage("No command provided. Usage: xa <command> <text>".into()));
     }
+    let prompt_config = load_prompt_config().await?;
+    let mut commands: Vec<(String, String)> = prompt_config
+        .prompts
+        .iter()
+        .map(|(name, entry)| {
+            let description = entry.description.clone().unwrap_or_else(|| "Custom prompt command".to_string());
+            (name.clone(), description)
+        })
+        .collect();
+    commands.sort();
+    Ok(tui::command_picker::pick_command(&commands, input)?)
 }
 
-async fn process_command_with_args(cli: &Cli, command_name: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let input = cli.input.as_ref().unwrap();
+/// Run prompt command `command_name` on `input` and deliver the result.
+/// Returns the result text, or `None` when the user declined a fuzzy match.
+async fn process_command_with_args(
+    cli: &Cli,
+    command_name: &str,
+    input: &str,
+    args: &[String],
+    positional: bool,
+) -> Result<Option<String>, XaError> {
 
     // First check if config exists
-    let config = load_config().await?;
+    let config = load_run_config(cli).await?;
 
-    if config.api_key.is_empty() {
-        eprintln!("Error: API key not configured. Please run 'xa set openai' first.");
-        std::process::exit(1);
+    if config.missing_api_key() {
+        return Err(XaError::ConfigMissing);
     }
 
     // Get prompt configuration
     let prompt_config = load_prompt_config().await?;
 
     // Find the command in prompts (with fuzzy matching)
-    let matched_command = find_command(command_name, &prompt_config.prompts);
+    let matched_command = find_command_match(command_name, &prompt_config.prompts);
 
-    match matched_command {
+    if let Some((cmd, MatchKind::Fuzzy)) = &matched_command {
+        eprintln!("{}", paint("90", &format!("'{}' matched command '{}'", command_name, cmd)));
+        if config.confirm_fuzzy && !confirm_on_tty(&format!("Run '{}'? (Y/n): ", cmd))? {
+            eprintln!("Aborted.");
+            return Ok(None);
+        }
+    }
+
+    match matched_command.map(|(cmd, _)| cmd) {
         Some(cmd) => {
             let prompt_entry = &prompt_config.prompts[&cmd];
 
-            // Special handling for commands that have specific argument patterns
-            let (processed_input, processed_args) = if cmd == "translate" {
-                // For translate command: if input looks like a language code and we have args, swap them
-                // If input is 2-3 letters and first arg is longer text, assume input is target language
-                if input.chars().all(|c| c.is_ascii_alphabetic()) && input.len() >= 2 && input.len() <= 3
-                   && !cli.args.is_empty() {
-                    // Input looks like a language code, first arg is the text to translate
-                    let text_to_translate = &cli.args[0];
-                    (text_to_translate.clone(), vec![input.to_string()])
+            let (processed_input, processed_args) = command_input(&cmd, input, args, positional);
+
+            let processed_args = match &cli.to {
+                Some(language) => with_target_lang(&cmd, prompt_entry, processed_args, language)?,
+                None => processed_args,
+            };
+
+            let processed_input = prompt_entry.prepare_input(&processed_input).await?;
+            // Text already in the target language is translated the other way
+            let processed_args = match flipped_target(&config, prompt_entry, &processed_input, &processed_args) {
+                Some((target, secondary)) => {
+                    eprintln!("{}", paint("90", &format!("The text is already in {}; translating into {}", target, secondary)));
+                    with_target_lang(&cmd, prompt_entry, processed_args, &secondary)?
+                }
+                None => processed_args,
+            };
+            // Diffs, corrections and post-processed answers are shown once
+            // the whole answer is in: streaming would show the raw one first
+            let buffered = shows_diff(cli, Some(prompt_entry))
+                || prompt_entry.show_corrections
+                || prompt_entry.show_minutes
+                || prompt_entry.rewrites_output();
+            let started = std::time::Instant::now();
+            if cli.choices.is_some() && (prompt_entry.pipeline.is_some() || ask_uses_tools(cli)) {
+                return Err(XaError::Usage("-n works with single commands, not pipelines or --tools".into()));
+            }
+            let (completion, filled_prompt, model) = if let Some(pipeline) = &prompt_entry.pipeline {
+                let steps: Vec<_> = pipeline.iter().filter_map(|step| prompt::parse_pipeline_step(step)).collect();
+                let mut completion = run_pipeline(cli, &config, &steps, &processed_input, streams(cli) && !buffered).await?;
+                if prompt_entry.rewrites_output() {
+                    completion.text = prompt_entry.finish_output(&completion.text).await?;
+                    completion.rendered = false;
+                }
+                (completion, format!("{}\n{}", pipeline.join(" -> "), processed_input), config.model())
+            } else {
+                let mut config = config_for_entry(cli, &config, prompt_entry);
+                if cmd == "ask" {
+                    config = config_for_persona(cli, &config, ask_persona(cli))?;
+                    config.images = ask_images(cli)?;
+                }
+                // Process the template with input and arguments, fitted to the model
+                let filled_prompt = prompt_entry.fill_prompt(&config, &processed_input, &processed_args).await?;
+
+                // Print the filled prompt if debug mode is enabled
+                if let Some(mode) = cli.debug {
+                    print_debug_prompt(mode, &config, &filled_prompt)?;
+                }
+
+                // Call the LLM API with streaming option
+                let mut completion = if let Some(n) = cli.choices {
+                    let Some(picked) = pick_completion(&config, prompt_entry, &filled_prompt, n.into()).await? else {
+                        eprintln!("Nothing copied.");
+                        return Ok(None);
+                    };
+                    picked
+                } else if ask_uses_tools(cli) {
+                    llm::run_agent(&config, &filled_prompt, &ask_tools(&config).await, &confirm_tool).await?
                 } else {
-                    // Normal case: input is the text, args are additional parameters
-                    (input.to_string(), cli.args.clone())
+                    run_llm(&config, &filled_prompt, streams(cli) && !buffered).await?
+                };
+                if prompt_entry.rewrites_output() && cli.choices.is_none() && completion.interrupted.is_none() {
+                    // What was streamed is no longer the result
+                    completion.text = prompt_entry.finish_output(&completion.text).await?;
+                    completion.rendered = false;
                 }
+                (completion, filled_prompt, config.model())
+            };
+            let entry = history::HistoryEntry::finished(&cmd, &processed_input, &processed_args, &filled_prompt, &model, &completion, started);
+            history::record(&config, &entry);
+            if let Some(why) = &completion.interrupted {
+                return Err(cut_off(why, prompt_entry.pipeline.is_none()));
+            }
+            let delivery = Delivery { command: &cmd, entry: Some(prompt_entry), input: &processed_input, model: &model };
+            deliver_result(cli, &config, &delivery, &completion)?;
+            Ok(Some(completion.text))
+        }
+        None if config.fallback_to_ask && prompt_config.prompts.contains_key("ask") => {
+            // `xa how do I exit vim`: the "command" was the first word of a question
+            let mut words = vec![command_name];
+            if positional {
+                words.push(input);
+            }
+            words.extend(args.iter().map(String::as_str));
+            let question = if positional {
+                words.join(" ")
             } else {
-                // For other commands, use the original logic
-                (input.to_string(), cli.args.clone())
+                format!("{}\n\n{}", words.join(" "), input)
             };
+            Box::pin(process_command_with_args(cli, "ask", &question, &[], false)).await
+        }
+        None => Err(XaError::CommandNotFound(command_name.to_string())),
+    }
+}
 
-            // Process the template with input and arguments using the new configurable system
-            let filled_prompt = process_template_with_args(
-                &prompt_entry.template,
-                &processed_input,
-                &processed_args,
-                prompt_entry.args.as_ref()
-            );
+/// Whether to stream the answer to the terminal: not with `--no-stream`,
+/// nor when it goes to an `--output` file. Callers also skip streaming
+/// when the result is shown as a diff.
+fn streams(cli: &Cli) -> bool {
+    !cli.no_stream && cli.output.is_none()
+}
 
-            // Print the filled prompt if debug mode is enabled
-            if cli.debug {
-                eprintln!("[DEBUG] Debug mode is ON");
-                eprintln!("[DEBUG] Filled prompt:");
-                eprintln!("---");
-                eprintln!("{}", filled_prompt);
-                eprintln!("---");
-                eprintln!("[DEBUG] End of filled prompt\n");
+/// Whether the result is shown as a word diff against the input:
+/// `--diff`, or `show_diff` on the command's entry.
+fn shows_diff(cli: &Cli, entry: Option<&prompt::PromptEntry>) -> bool {
+    cli.diff || entry.is_some_and(|entry| entry.show_diff)
+}
+
+/// What a one-shot result answered: the command (or `xa pipe` spec), its
+/// prompt entry when there is one, the input text and the model used.
+struct Delivery<'a> {
+    command: &'a str,
+    entry: Option<&'a prompt::PromptEntry>,
+    input: &'a str,
+    model: &'a str,
+}
+
+/// Hand a finished one-shot result to the user: written to the `--output`
+/// file when one is given, otherwise copied and rendered (unless streaming
+/// already showed it styled), shown as a word diff against the input (see
+/// [`shows_diff`]), or, for `show_corrections`, as the marked-up input with
+/// the corrected text copied.
+fn deliver_result(
+    cli: &Cli,
+    config: &config::Config,
+    delivery: &Delivery,
+    completion: &llm::Completion,
+) -> Result<(), XaError> {
+    let result = completion.text.as_str();
+    prompt::save_last_result(result);
+    if delivery.entry.is_some_and(|entry| entry.show_minutes) {
+        match minutes::parse(result) {
+            Ok(minutes) => return deliver_minutes(cli, config, delivery, completion, &minutes),
+            Err(e) => eprintln!("{}", paint("33", &format!("Could not read the minutes ({}); showing the answer as is.", e))),
+        }
+    }
+    if let Some(path) = &cli.output {
+        let format = cli.format.unwrap_or_else(|| OutputFormat::from_path(path));
+        let record = output::ResultRecord { command: delivery.command, input: delivery.input, output: result };
+        output::write_output(path, format, cli.append, &record)?;
+        return Ok(());
+    }
+    if delivery.entry.is_some_and(|entry| entry.show_corrections) {
+        match grammar::parse_corrections(result) {
+            Ok(corrections) => {
+                let corrected = grammar::apply(delivery.input, &corrections);
+                prompt::save_last_result(&corrected);
+                let footer = output::Footer {
+                    copied: copy_result(config, &corrected, None).map(|_| "corrected text"),
+                    model: delivery.model,
+                    usage: completion.usage,
+                    timing: completion.timing,
+                };
+                println!("{}", grammar::render(delivery.input, &corrections, output::is_plain()));
+                output::print_footer(&corrected, &footer);
+                return Ok(());
             }
+            Err(e) => eprintln!("{}", paint("33", &format!("Could not read the corrections ({}); showing the answer as is.", e))),
+        }
+    }
+    let copy_code = cli.copy_code.or(delivery.entry.and_then(|entry| entry.copy_code));
+    let footer = output::Footer {
+        copied: copy_result(config, result, copy_code),
+        model: delivery.model,
+        usage: completion.usage,
+        timing: completion.timing,
+    };
+    if shows_diff(cli, delivery.entry) {
+        output::print_diff(delivery.input, result);
+        output::print_footer(result, &footer);
+    } else if completion.rendered {
+        output::print_footer(result, &footer);
+    } else {
+        render_output(result, &footer);
+    }
+    Ok(())
+}
+
+/// Minutes as Markdown, on the terminal or in the `-o` file; a JSON `-o`
+/// file gets the minutes' JSON instead, one object per line.
+fn deliver_minutes(
+    cli: &Cli,
+    config: &config::Config,
+    delivery: &Delivery,
+    completion: &llm::Completion,
+    minutes: &minutes::Minutes,
+) -> Result<(), XaError> {
+    let markdown = minutes::markdown(minutes);
+    prompt::save_last_result(&markdown);
+    if let Some(path) = &cli.output {
+        let format = cli.format.unwrap_or_else(|| OutputFormat::from_path(path));
+        if format == OutputFormat::Json {
+            let mut file = output::open_output(path, cli.append)?;
+            writeln!(file, "{}", serde_json::to_string(minutes)?)?;
+            eprintln!("{}", paint("90", &format!("✓ {} {}", if cli.append { "appended to" } else { "written to" }, path.display())));
+        } else {
+            let record = output::ResultRecord { command: delivery.command, input: delivery.input, output: &markdown };
+            output::write_output(path, format, cli.append, &record)?;
+        }
+        return Ok(());
+    }
+    let footer = output::Footer {
+        copied: copy_result(config, &markdown, None),
+        model: delivery.model,
+        usage: completion.usage,
+        timing: completion.timing,
+    };
+    render_output(&markdown, &footer);
+    Ok(())
+}
+
+/// Copy a one-shot result, or just one of its code blocks, unless
+/// `no_clipboard` is set. Says what was copied, if anything.
+fn copy_result(config: &config::Config, text: &str, copy_code: Option<output::CodeBlock>) -> Option<&'static str> {
+    if config.no_clipboard {
+        return None;
+    }
+    let (what, text) = match copy_code.map(|which| output::code_block(text, which)) {
+        Some(Some(code)) => ("code block", code),
+        Some(None) => {
+            eprintln!("Warning: No code block in the answer; copying all of it.");
+            ("result", text.to_string())
+        }
+        None => ("result", text.to_string()),
+    };
+    match copy_to_clipboard(&text) {
+        Ok(()) => Some(what),
+        Err(e) => {
+            eprintln!("Warning: Could not copy to clipboard: {}", e);
+            None
+        }
+    }
+}
+
+/// Ask whether to update, skip or add a store entry that looks like an
+/// existing one. Enter takes the suggestion: skip a value that is already
+/// stored, update the entry with a similar note. Without a terminal to ask
+/// on, it is skipped.
+fn resolve_duplicate(duplicate: &store::Duplicate) -> store::OnDuplicate {
+    use store::OnDuplicate;
+    let existing = &duplicate.entry;
+    let (why, default, choices) = if duplicate.same_value {
+        ("already stores this value", OnDuplicate::Skip, "[u/S/a]")
+    } else {
+        ("has a similar note", OnDuplicate::Update, "[U/s/a]")
+    };
+    eprintln!("{}", paint("33", &format!("[{}] {} ({}: {}).", existing.tag, why, existing.kind.name(), existing.note)));
+    match ask_on_tty(&format!("Update it, skip, or add anyway? {} ", choices)) {
+        Ok(Some(answer)) => match answer.trim().to_lowercase().as_str() {
+            "u" | "update" => OnDuplicate::Update,
+            "s" | "skip" => OnDuplicate::Skip,
+            "a" | "add" => OnDuplicate::Add,
+            _ => default,
+        },
+        _ => OnDuplicate::Skip,
+    }
+}
+
+/// Let the user choose among the entries an ambiguous `xa search` found,
+/// shown by tag and note. Without a terminal to ask on, the first is taken.
+fn pick_store_entry(entries: &[store::StoreEntry]) -> io::Result<Option<&store::StoreEntry>> {
+    for (number, entry) in entries.iter().enumerate() {
+        eprintln!("[{}] {}  {}", number + 1, paint("1", &entry.tag), paint("90", &format!("{} · {}", entry.kind.name(), entry.note)));
+    }
+    let question = format!("Which one? [1-{}, Enter for 1, q for none]: ", entries.len());
+    loop {
+        let Some(answer) = ask_on_tty(&question)? else {
+            return Ok(entries.first());
+        };
+        match answer.trim() {
+            "" => return Ok(entries.first()),
+            "q" | "Q" => return Ok(None),
+            answer => match answer.parse::<usize>() {
+                Ok(number) if (1..=entries.len()).contains(&number) => return Ok(Some(&entries[number - 1])),
+                _ => eprintln!("Enter a number from 1 to {}.", entries.len()),
+            },
+        }
+    }
+}
+
+/// `xa search --all`: every match under its tag, kind and note, with the
+/// values of secrets hidden.
+fn print_store_entries(entries: &[store::StoreEntry]) {
+    let shown: Vec<String> = entries
+        .iter()
+        .map(|entry| {
+            let value = if entry.kind == EntryKind::Secret { "***hidden***" } else { entry.value.as_str() };
+            format!("{}\n{}", paint("90", &format!("[{}] {} · {}", entry.tag, entry.kind.name(), entry.note)), value)
+        })
+        .collect();
+    println!("{}", shown.join("\n\n"));
+}
+
+/// `xa search`'s answer: a note, snippet or URL printed as it is (and
+/// copied); a secret copied to the clipboard and left off a terminal, or
+/// printed when piped or when it could not be copied.
+fn print_store_entry(config: &config::Config, entry: &store::StoreEntry) {
+    let terminal = io::stdout().is_terminal();
+    if terminal {
+        eprintln!("{}", paint("90", &format!("[{}] {} · {}", entry.tag, entry.kind.name(), entry.note)));
+    }
+    if terminal && entry.kind == EntryKind::Secret && copy_result(config, &entry.value, None).is_some() {
+        eprintln!("Secret copied to the clipboard.");
+        return;
+    }
+    println!("{}", entry.value);
+    if terminal && entry.kind != EntryKind::Secret {
+        copy_result(config, &entry.value, None);
+    }
+}
+
+fn print_debug_prompt(mode: DebugMode, config: &config::Config, filled_prompt: &str) -> Result<(), XaError> {
+    let shown = mode.show(&config.redact, filled_prompt)?;
+    eprintln!("[DEBUG] Debug mode is ON");
+    match mode {
+        DebugMode::Masked => eprintln!("[DEBUG] Filled prompt (secrets masked; --debug=full shows them):"),
+        DebugMode::Full => eprintln!("[DEBUG] Filled prompt:"),
+    }
+    eprintln!("---");
+    eprintln!("{}", shown);
+    eprintln!("---");
+    eprintln!("[DEBUG] End of filled prompt\n");
+    Ok(())
+}
+
+/// The config one run uses: [`command::load_run_config`] with the flags
+/// that change it.
+async fn load_run_config(cli: &Cli) -> Result<config::Config, XaError> {
+    let options = command::RunOptions {
+        model: cli.model.clone(),
+        force: cli.force,
+        hide_thinking: cli.hide_thinking,
+        redact: cli.redact,
+        temperature: cli.temperature,
+        top_p: cli.top_p,
+        max_tokens: cli.max_tokens,
+    };
+    command::load_run_config(&options).await
+}
+
+/// Parser for `--temperature` and `--top-p`: a number from 0 to `max`.
+fn sampling_value(max: f64) -> impl Fn(&str) -> Result<f64, String> + Clone + Send + Sync + 'static {
+    move |value| match value.parse::<f64>() {
+        Ok(number) if (0.0..=max).contains(&number) => Ok(number),
+        _ => Err(format!("expected a number from 0 to {}", max)),
+    }
+}
+
+/// `config` with the entry's `model`, `extra_headers` and `extra_body`
+/// applied over the global ones. `--model` beats both models.
+fn config_for_entry(cli: &Cli, config: &config::Config, entry: &prompt::PromptEntry) -> config::Config {
+    let mut config = config.for_entry(entry);
+    if let Some(model) = &cli.model {
+        config.default_model = Some(model.clone());
+    }
+    config
+}
+
+/// The persona chosen with `xa ask --persona`, else `default`.
+fn ask_persona(cli: &Cli) -> &str {
+    match &cli.command {
+        Some(Commands::Ask { persona: Some(name), .. }) => name,
+        _ => "default",
+    }
+}
 
-            // Call the LLM API with streaming option
-            let result = process_with_llm(&config, &filled_prompt, !cli.no_stream).await?;
+/// Whether `xa ask --tools` was given.
+fn ask_uses_tools(cli: &Cli) -> bool {
+    matches!(cli.command, Some(Commands::Ask { tools: true, .. }))
+}
+
+/// The built-in tools of `xa ask --tools` and those of the configured MCP
+/// servers.
+async fn ask_tools(config: &config::Config) -> Vec<std::sync::Arc<dyn tools::Tool>> {
+    let mut tools = tools::ask_tools();
+    tools.extend(mcp::connect_all(&config.mcp_servers).await);
+    tools
+}
+
+/// Ask on the terminal before a tool call runs a command or writes a file.
+fn confirm_tool(question: &str) -> bool {
+    confirm_on_tty(question).unwrap_or(false)
+}
 
-            // Copy result to clipboard
-            if let Err(e) = copy_to_clipboard(&result) {
-                eprintln!("Warning: Could not copy to clipboard: {}", e);
+/// `xa see` without a question.
+const SEE_QUESTION: &str = "Describe this screenshot, and transcribe any text in it.";
+
+/// The images given with `xa ask -i`, loaded, and the screenshot of
+/// `--screenshot` or `xa see`.
+fn ask_images(cli: &Cli) -> Result<Vec<String>, XaError> {
+    match &cli.command {
+        Some(Commands::Ask { images, screenshot, .. }) => {
+            let mut images: Vec<String> = images.iter().map(|source| image::load(source)).collect::<Result<_, _>>()?;
+            if *screenshot {
+                images.push(screenshot::capture()?);
             }
+            Ok(images)
+        }
+        Some(Commands::See { .. }) => Ok(vec![screenshot::capture()?]),
+        _ => Ok(Vec::new()),
+    }
+}
 
-            // Render the result with Markdown support
-            render_output(&result, true); // true for success message
+/// `xa data`: the data from `file` or stdin transformed, printed or
+/// written to `-o` as it is (no Markdown, no record wrapping).
+async fn run_data(
+    cli: &Cli,
+    instruction: &str,
+    file: Option<&std::path::Path>,
+    from: Option<data::DataFormat>,
+    to: Option<data::DataFormat>,
+    retries: u32,
+) -> Result<(), XaError> {
+    let input = match file {
+        Some(path) => std::fs::read_to_string(path).map_err(|e| XaError::Usage(format!("cannot read {}: {}", path.display(), e)))?,
+        None => read_piped_stdin()?.ok_or_else(|| XaError::Usage("No data: pass -f <file> or pipe it in.".into()))?,
+    };
+    let from = from.or_else(|| file.and_then(data::DataFormat::from_path)).or_else(|| data::DataFormat::guess(&input));
+    let to = to
+        .or_else(|| cli.output.as_deref().and_then(data::DataFormat::from_path))
+        .or(from)
+        .ok_or_else(|| XaError::Usage("Cannot tell the data's format; pass --to json|yaml|toml|csv.".into()))?;
+    let config = load_run_config(cli).await?;
+    if config.missing_api_key() {
+        return Err(XaError::ConfigMissing);
+    }
+    let result = data::transform(&config, instruction, &input, from, to, retries).await?;
+    match &cli.output {
+        Some(path) => {
+            output::open_output(path, false)?.write_all(format!("{}\n", result).as_bytes())?;
+            eprintln!("{}", paint("90", &format!("✓ {} written to {}", to.name(), path.display())));
+        }
+        None => println!("{}", result),
+    }
+    Ok(())
+}
 
-            Ok(())
+/// `xa cards`: the cards printed, or written to the deck file.
+async fn run_cards(
+    cli: &Cli,
+    source: &str,
+    language: Option<&str>,
+    deck: Option<&std::path::Path>,
+    name: Option<&str>,
+    count: u32,
+    retries: u32,
+) -> Result<(), XaError> {
+    let format = deck.map(cards::DeckFormat::from_path).transpose()?;
+    let config = load_run_config(cli).await?;
+    if config.missing_api_key() {
+        return Err(XaError::ConfigMissing);
+    }
+    let found = cards::generate(&config, source, count, language, retries).await?;
+    let (Some(path), Some(format)) = (deck, format) else {
+        println!("{}", cards::render(&found));
+        return Ok(());
+    };
+    let name = name.map(str::to_string).unwrap_or_else(|| path.file_stem().map_or("xa".to_string(), |stem| stem.to_string_lossy().into_owned()));
+    let text = match format {
+        cards::DeckFormat::Tsv => cards::tsv(&name, &found),
+        cards::DeckFormat::Json => cards::json(&name, &found),
+    };
+    output::open_output(path, false)?.write_all(text.as_bytes())?;
+    let hint = if format == cards::DeckFormat::Tsv { "; import it in Anki with File > Import" } else { "" };
+    eprintln!("{}", paint("90", &format!("✓ {} cards written to {}{}", found.len(), path.display(), hint)));
+    Ok(())
+}
+
+/// Whether `xa ask --search` was given.
+fn ask_searches(cli: &Cli) -> bool {
+    matches!(cli.command, Some(Commands::Ask { search: true, .. }))
+}
+
+/// `question` preceded by web search results to cite, or alone (with a
+/// warning) when the search finds nothing.
+async fn with_search_results(config: &config::Config, question: &str) -> Result<String, XaError> {
+    let spinner = spinner::Spinner::start("Searching");
+    let results = search::search(&config.search, question).await;
+    spinner.stop();
+    let results = results?;
+    if results.is_empty() {
+        eprintln!("{}", paint("33", "No search results; answering without them."));
+        return Ok(question.to_string());
+    }
+    let plural = if results.len() == 1 { "" } else { "s" };
+    eprintln!("{}", paint("90", &format!("Using {} search result{}", results.len(), plural)));
+    Ok(search::grounded_prompt(question, &results))
+}
+
+/// The excerpts of `index` closest to `question`, to put ahead of it.
+async fn index_context(config: &config::Config, index: &index::Index, question: &str) -> Result<String, XaError> {
+    let chunks = index::retrieve(config, index, question).await?;
+    eprintln!("{}", paint("90", &format!("Using {} excerpts from index '{}'", chunks.len(), index.name)));
+    Ok(index::context(index, &chunks))
+}
+
+/// `config` with persona `name`'s system prompt, `model` and `temperature`
+/// applied. `--model` beats the persona's model.
+fn config_for_persona(cli: &Cli, config: &config::Config, name: &str) -> Result<config::Config, XaError> {
+    let persona = config.persona(name).ok_or_else(|| {
+        XaError::Usage(format!("Unknown persona '{}'. Available: {}", name, config.persona_names().join(", ")))
+    })?;
+    let mut config = config.clone();
+    if let Some(model) = cli.model.as_ref().or(persona.model.as_ref()) {
+        config.default_model = Some(model.clone());
+    }
+    if let Some(temperature) = persona.temperature {
+        config.extra_body.insert("temperature".to_string(), temperature.into());
+    }
+    config.system_prompt = Some(persona.system);
+    Ok(config)
+}
+
+/// The error for an answer whose stream broke off, once what arrived is
+/// on the screen and in the history.
+fn cut_off(why: &str, resumable: bool) -> XaError {
+    let hint = if resumable { "; `xa history --continue` asks the model to finish it" } else { "" };
+    XaError::Network(format!("the answer was cut off ({}). What arrived is kept{}", why, hint))
+}
+
+/// `xa history --continue`: ask the model for the rest of the answer
+/// `entry` was cut off in, and hand over the whole answer.
+async fn continue_cut_off(cli: &Cli, entry: &history::HistoryEntry, partial: &str) -> Result<(), XaError> {
+    let config = load_run_config(cli).await?;
+    if config.missing_api_key() {
+        return Err(XaError::ConfigMissing);
+    }
+    let prompt_config = load_prompt_config().await?;
+    let cmd = find_command(&entry.command, &prompt_config.prompts).ok_or_else(|| XaError::CommandNotFound(entry.command.clone()))?;
+    let prompt_entry = &prompt_config.prompts[&cmd];
+    if prompt_entry.pipeline.is_some() {
+        return Err(XaError::Usage(format!("'{}' is a pipeline; rerun it with `xa history --rerun` instead", cmd)));
+    }
+    let config = config_for_entry(cli, &config, prompt_entry);
+    let filled_prompt = prompt_entry.fill_prompt(&config, &entry.input, &entry.args).await?;
+    let prompt = llm::continuation_prompt(&filled_prompt, partial);
+    if let Some(mode) = cli.debug {
+        print_debug_prompt(mode, &config, &prompt)?;
+    }
+    eprintln!("{}", paint("90", &format!("Continuing: {} ({}), {} characters so far", cmd, config.model(), partial.chars().count())));
+
+    let started = std::time::Instant::now();
+    let mut completion = run_llm(&config, &prompt, streams(cli) && !prompt_entry.rewrites_output()).await?;
+    completion.text.insert_str(0, partial);
+    if prompt_entry.rewrites_output() && completion.interrupted.is_none() {
+        completion.text = prompt_entry.finish_output(&completion.text).await?;
+        completion.rendered = false;
+    }
+    history::record(&config, &history::HistoryEntry::finished(&cmd, &entry.input, &entry.args, &filled_prompt, &config.model(), &completion, started));
+    if let Some(why) = &completion.interrupted {
+        return Err(cut_off(why, true));
+    }
+    let model = config.model();
+    let delivery = Delivery { command: &cmd, entry: Some(prompt_entry), input: &entry.input, model: &model };
+    deliver_result(cli, &config, &delivery, &completion)
+}
+
+/// `xa pipe`: run the steps in `spec` on `text`, record the run and hand
+/// over the result.
+async fn run_pipe(cli: &Cli, spec: &str, text: &str) -> Result<(), XaError> {
+    let config = load_run_config(cli).await?;
+    if config.missing_api_key() {
+        return Err(XaError::ConfigMissing);
+    }
+    let steps = prompt::parse_pipeline(spec);
+    if steps.is_empty() {
+        return Err(XaError::Usage("Empty pipeline. Example: xa pipe \"summarize -> translate fr\" \"text\"".into()));
+    }
+    let started = std::time::Instant::now();
+    let completion = run_pipeline(cli, &config, &steps, text, streams(cli) && !cli.diff).await?;
+    let prompt = format!("{}\n{}", spec, text);
+    let entry = history::HistoryEntry::finished(spec, text, &[], &prompt, &config.model(), &completion, started);
+    history::record(&config, &history::HistoryEntry { pipe: true, partial: None, ..entry });
+    if let Some(why) = &completion.interrupted {
+        return Err(cut_off(why, false));
+    }
+    let model = config.model();
+    let delivery = Delivery { command: spec, entry: None, input: text, model: &model };
+    deliver_result(cli, &config, &delivery, &completion)
+}
+
+/// Run pipeline `steps` in order, feeding each step's result to the next as
+/// its `{input}`. Only the final step streams to the terminal, and only
+/// with `stream`. Returns the final text, with the usage summed over all
+/// steps.
+async fn run_pipeline(
+    cli: &Cli,
+    config: &config::Config,
+    steps: &[prompt::PipelineStep],
+    input: &str,
+    stream: bool,
+) -> Result<llm::Completion, XaError> {
+    let prompt_config = load_prompt_config().await?;
+    let mut text = input.to_string();
+    let mut usage = None;
+    let mut rendered = false;
+    let mut timing = None;
+    let mut interrupted = None;
+
+    for (i, step) in steps.iter().enumerate() {
+        let cmd = find_command(&step.command, &prompt_config.prompts)
+            .ok_or_else(|| XaError::CommandNotFound(step.command.clone()))?;
+        let entry = &prompt_config.prompts[&cmd];
+        if entry.pipeline.is_some() {
+            return Err(XaError::Usage(format!("Pipeline step '{}' is itself a pipeline, which is not supported", cmd)));
         }
-        None => {
-            eprintln!("Error: Command '{}' not found. Use 'xa ls' to see available commands.", command_name);
-            std::process::exit(1);
+
+        let step_config = config_for_entry(cli, config, entry);
+        let last = i + 1 == steps.len();
+        eprintln!("{}", paint("90", &format!("[{}/{}] {}", i + 1, steps.len(), cmd)));
+        let filled_prompt = entry.fill_prompt(&step_config, &entry.prepare_input(&text).await?, &step.args).await?;
+        if let Some(mode) = cli.debug {
+            print_debug_prompt(mode, &step_config, &filled_prompt)?;
+        }
+
+        let completion = run_llm(&step_config, &filled_prompt, last && stream && !entry.rewrites_output()).await?;
+        usage = Usage::sum(usage, completion.usage);
+        text = completion.text;
+        rendered = completion.rendered;
+        timing = completion.timing;
+        interrupted = completion.interrupted;
+        if entry.rewrites_output() && interrupted.is_none() {
+            text = entry.finish_output(&text).await?;
+            rendered = false;
         }
     }
+
+    Ok(llm::Completion { text, usage, rendered, timing, interrupted })
 }
 
-use std::io::{self, Write};
-use termimad::{MadSkin, ansi};
+use std::io::{self, BufRead, IsTerminal, Read, Write};
 
-async fn start_interactive_mode() -> Result<(), Box<dyn std::error::Error>> {
+async fn start_interactive_mode(
+    cli: &Cli,
+    persona: &str,
+    mut attachments: Vec<attachment::Attachment>,
+    index: Option<index::Index>,
+) -> Result<(), XaError> {
     // First check if config exists
-    let config = load_config().await?;
+    let mut base_config = load_run_config(cli).await?;
 
-    if config.api_key.is_empty() {
-        eprintln!("Error: API key not configured. Please run 'xa --set openai' first.");
-        std::process::exit(1);
+    if base_config.api_key.is_empty() {
+        return Err(XaError::ConfigMissing);
     }
-
-    // Create a colorful skin for the interactive mode
-    let mut skin = MadSkin::default();
-    skin.set_headers_fg(ansi(35)); // Magenta for headers
-    skin.bold.set_fg(ansi(33)); // Yellow for bold text
+    // Images from `-i` go with every message, like attached files
+    base_config.images = ask_images(cli)?;
+    // MCP servers are started once and kept for the whole conversation
+    let agent_tools = if ask_uses_tools(cli) { ask_tools(&base_config).await } else { Vec::new() };
+    let mut persona = persona.to_string();
+    let mut config = config_for_persona(cli, &base_config, &persona)?;
 
     // Print welcome message with colors
-    skin.print_text("## Welcome to xa Interactive Mode\n\n");
-    println!("{}", "\x1b[90mType your message and press Enter. Type 'exit', 'quit', or 'bye' to end, or press Ctrl+C to exit.\x1b[0m");
-    println!("{}", "\x1b[90mUse 'clear' to clear conversation history, 'history' to view recent exchanges.\x1b[0m");
+    output::skin().print_text("## Welcome to xa Interactive Mode\n\n");
+    println!("{}", paint("90", "Type your message and press Enter. Type 'exit', 'quit', or 'bye' to end, or press Ctrl+C to exit."));
+    println!("{}", paint("90", "Use 'clear' to clear conversation history, 'history' to view recent exchanges."));
+    println!("{}", paint("90", &format!("Persona: {}. Use '/persona <name>' to switch, '/persona' to list.", persona)));
+    println!("{}", paint("90", "Use '/file <path>' to add a file as context, '/file' to list attached files."));
+    println!("{}", paint("90", "Use ':<command> [text]' to run a prompt command like ':translate fr some text' (without text, on the last answer)."));
+    println!("{}", paint("90", "Use '!<command>' to run a shell command, '!!<command>' to also send its output with your next message."));
+    for file in &attachments {
+        println!("{}", paint("90", &format!("Attached {}", file.summary())));
+    }
+    if !base_config.images.is_empty() {
+        let plural = if base_config.images.len() == 1 { "" } else { "s" };
+        println!("{}", paint("90", &format!("Attached {} image{}", base_config.images.len(), plural)));
+    }
     println!();
 
     // Initialize conversation history
-    let mut conversation_history = Vec::new();
+    let mut conversation_history: Vec<(String, String)> = Vec::new();
+    let mut pending_output = Vec::new();
 
     loop {
         // Print colorful prompt
-        print!("\x1b[36m>\x1b[0m "); // Cyan prompt
+        print!("{} ", paint("36", ">")); // Cyan prompt
         io::stdout().flush()?;
 
         let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
+        if io::stdin().read_line(&mut input)? == 0 {
+            // End of input (Ctrl+D)
+            println!();
+            break;
+        }
         let input = input.trim();
 
         // Check if input is empty (user pressed Enter without typing)
@@ -368,39 +2849,157 @@ async fn start_interactive_mode() -> Result<(), Box<dyn std::error::Error>> {
             continue;
         }
 
+        // `!cmd` runs a shell command; `!!cmd` also sends its output along
+        // with the next message
+        if let Some(command) = input.strip_prefix('!') {
+            let (command, attach) = match command.strip_prefix('!') {
+                Some(command) => (command.trim(), true),
+                None => (command.trim(), false),
+            };
+            if command.is_empty() {
+                continue;
+            }
+            match run_shell_escape(command) {
+                Ok(output) => {
+                    print!("{}", output);
+                    if !output.is_empty() && !output.ends_with('\n') {
+                        println!();
+                    }
+                    if attach {
+                        let attached = attachment::Attachment::command_output(command, output);
+                        println!("{}", paint("90", &format!("Output of {} will be sent with your next message.", attached.summary())));
+                        pending_output.push(attached);
+                    }
+                }
+                Err(error) => println!("{}", paint("31", &format!("cannot run `{}`: {}", command, error))),
+            }
+            continue;
+        }
+
         // Check for special commands
         match input.to_lowercase().as_str() {
             "exit" | "quit" | "bye" => {
-                println!("{}", "\x1b[90mGoodbye! Thanks for using xa.\x1b[0m");
+                println!("{}", paint("90", "Goodbye! Thanks for using xa."));
                 break;
             }
             "clear" => {
                 conversation_history.clear();
-                println!("{}", "\x1b[90mConversation history cleared.\x1b[0m");
+                attachments.clear();
+                pending_output.clear();
+                println!("{}", paint("90", "Conversation history and attached files cleared."));
+                continue;
+            }
+            "/file" => {
+                if attachments.is_empty() {
+                    println!("{}", paint("90", "No files attached."));
+                }
+                for file in &attachments {
+                    println!("{}", paint("90", &format!("  {}", file.summary())));
+                }
                 continue;
             }
             "history" => {
                 if conversation_history.is_empty() {
-                    println!("{}", "\x1b[90mNo conversation history yet.\x1b[0m");
+                    println!("{}", paint("90", "No conversation history yet."));
                 } else {
-                    println!("{}", "\x1b[90mRecent conversation history:\x1b[0m");
+                    println!("{}", paint("90", "Recent conversation history:"));
                     for (i, (user_msg, ai_resp)) in conversation_history.iter().enumerate() {
-                        println!("\x1b[90m[{}]\x1b[0m \x1b[33mYou:\x1b[0m {}", i + 1, user_msg);
-                        println!("\x1b[90m    \x1b[32mAI:\x1b[0m {}", ai_resp);
+                        println!("{} {} {}", paint("90", &format!("[{}]", i + 1)), paint("33", "You:"), user_msg);
+                        println!("    {} {}", paint("32", "AI:"), ai_resp);
                         println!();
                     }
                 }
                 continue;
             }
+            "/persona" => {
+                println!("{}", paint("90", "Personas:"));
+                for name in base_config.persona_names() {
+                    let marker = if name == persona { "*" } else { " " };
+                    println!("{}", paint("90", &format!("  {} {}", marker, name)));
+                }
+                continue;
+            }
             _ => {}
         }
+        if let Some(name) = input.strip_prefix("/persona ") {
+            match config_for_persona(cli, &base_config, name.trim()) {
+                Ok(switched) => {
+                    config = switched;
+                    persona = name.trim().to_string();
+                    println!("{}", paint("90", &format!("Switched to persona '{}' ({}).", persona, config.model())));
+                }
+                Err(error) => println!("{}", paint("31", &error.to_string())),
+            }
+            continue;
+        }
+
+        if let Some(path) = input.strip_prefix("/file ") {
+            let added = attachment::Attachment::read(path.trim())
+                .and_then(|file| attachment::check_total(&attachments, &file).map(|()| file));
+            match added {
+                Ok(file) => {
+                    println!("{}", paint("90", &format!("Attached {}", file.summary())));
+                    attachments.push(file);
+                }
+                Err(error) => println!("{}", paint("31", &error.to_string())),
+            }
+            continue;
+        }
+
+        // `:translate fr some text` (or `/cmd translate fr some text`) runs a
+        // prompt command; without text it works on the last answer
+        if let Some(line) = input.strip_prefix(':').or_else(|| input.strip_prefix("/cmd ")) {
+            let prompts = load_prompt_config().await?.prompts;
+            let Some((step, text)) = prompt::parse_repl_command(line, &prompts) else {
+                println!("{}", paint("90", "Usage: :<command>[:args] [text], e.g. ':translate fr some text' or ':summarize:short'"));
+                continue;
+            };
+            let Some(text) = text.or_else(|| conversation_history.last().map(|(_, answer)| answer.clone())) else {
+                println!("{}", paint("90", &format!("Nothing to {} yet: give the text after the command.", step.command)));
+                continue;
+            };
+            match process_command_with_args(cli, &step.command, &text, &step.args, false).await {
+                Ok(Some(result)) => conversation_history.push((format!(":{}", line.trim()), result)),
+                Ok(None) => {}
+                Err(error) => println!("{}", paint("31", &error.to_string())),
+            }
+            println!();
+            continue;
+        }
+
+        // With `--with-index` or `--search`, the message goes out with the
+        // excerpts or results; only the message itself stays in the history
+        let mut grounded = input.to_string();
+        if ask_searches(cli) {
+            match with_search_results(&base_config, input).await {
+                Ok(with_results) => grounded = with_results,
+                Err(error) => {
+                    println!("{}", paint("31", &error.to_string()));
+                    continue;
+                }
+            }
+        }
+        if let Some(index) = &index {
+            match index_context(&base_config, index, input).await {
+                Ok(excerpts) => grounded = format!("{}{}", excerpts, grounded),
+                Err(error) => {
+                    println!("{}", paint("31", &error.to_string()));
+                    continue;
+                }
+            }
+        }
 
-        // Add user message to conversation history
-        conversation_history.push((input.to_string(), String::new()));
+        // Add user message to conversation history, with the output of any
+        // `!!` commands run since the last one
+        let output = attachment::context(&pending_output);
+        pending_output.clear();
+        let current = format!("{}{}", output, grounded);
+        let input = format!("{}{}", output, input);
+        conversation_history.push((input.clone(), String::new()));
 
         // Build the full prompt with conversation history
-        let mut full_prompt = String::new();
-        full_prompt.push_str("You are a helpful assistant called xa, execute anything by your side.\n\n");
+        // (the persona's system prompt is sent as the system message)
+        let mut full_prompt = attachment::context(&attachments);
 
         if !conversation_history.is_empty() {
             full_prompt.push_str("Previous conversation:\n");
@@ -413,15 +3012,19 @@ async fn start_interactive_mode() -> Result<(), Box<dyn std::error::Error>> {
             full_prompt.push_str("\n");
         }
 
-        full_prompt.push_str(&format!("Current message: {}", input));
+        full_prompt.push_str(&format!("Current message: {}", current));
 
         // Call the LLM API with streaming
-        let result = process_with_llm(&config, &full_prompt, true).await?;
+        let result = if ask_uses_tools(cli) {
+            let completion = llm::run_agent(&config, &full_prompt, &agent_tools, &confirm_tool).await?;
+            output::skin().print_text(&completion.text);
+            completion.text
+        } else {
+            process_with_llm(&config, &full_prompt, true).await?
+        };
 
         // Copy result to clipboard
-        if let Err(e) = copy_to_clipboard(&result) {
-            eprintln!("Warning: Could not copy to clipboard: {}", e);
-        }
+        copy_result(&config, &result, cli.copy_code);
 
         // Update the conversation history with the AI response
         if let Some(last) = conversation_history.last_mut() {
@@ -437,6 +3040,26 @@ async fn start_interactive_mode() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Run a `!` command from the conversation mode through the shell, on the
+/// terminal's stdin, and return what it printed (stdout, then stderr), with
+/// a note when it failed.
+fn run_shell_escape(command: &str) -> io::Result<String> {
+    #[cfg(windows)]
+    let output = std::process::Command::new("cmd").args(["/C", command]).stdin(std::process::Stdio::inherit()).output()?;
+    #[cfg(not(windows))]
+    let output = std::process::Command::new("sh").args(["-c", command]).stdin(std::process::Stdio::inherit()).output()?;
+
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    if !output.status.success() {
+        if !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+        text.push_str(&format!("[{}]\n", output.status));
+    }
+    Ok(text)
+}
+
 fn get_help_text() -> String {
     r#"xa - a lightweight coding-agent CLI (like codex / claude-code)
 
@@ -449,6 +3072,8 @@ COMMANDS (agent):
     chat                        Start the interactive coding-agent TUI
     login [name]                Configure a provider (endpoint + key + model)
     resume [id]                 Resume a session, or open the session picker
+    sessions ls|show|rename|rm|export
+                                Manage saved sessions, or export one (-o file)
     gain [--daily|--weekly|--monthly|--all]
                                 Review saved token usage and tool-output gains
 
@@ -457,13 +3082,15 @@ IN-TUI SLASH COMMANDS:
     /models [name|model]        Switch active provider, or set the model
     /sessions                   List saved sessions
     /save [title]              Save the current conversation as a session
+    /export <file>              Export the conversation (.md, or .html)
     /new                        Start a fresh session
     /clear /help /exit          Clear / help / quit
 
 OPTIONS:
     --no-stream                 Disable streaming (legacy prompt mode)
-    --debug                     Print the filled prompt (legacy prompt mode)
+    --debug[=full]              Print the filled prompt, secrets masked unless =full (legacy prompt mode)
     --theme <MODE>              TUI theme: auto (default), dark, or light
+    --plain                     No colors in one-shot output (or set NO_COLOR)
     -h, --help                 Print help
 
 EXAMPLES:
@@ -478,12 +3105,13 @@ EXAMPLES:
     /models gpt-4o                   # inside TUI: switch model
 
 Theme (TUI): CLI --theme > env XA_THEME > config.toml theme = \"auto|dark|light\" > auto-detect.
+Markdown colors for one-shot output follow the theme, or a [theme] table in config.toml.
 
 For more information, visit the project repository."#.to_string()
 }
 
 /// Resolve theme preference: CLI `--theme` > `XA_THEME` > config.toml > auto.
-fn resolve_theme_preference(cli: &Cli) -> tui::ThemePreference {
+fn resolve_theme_preference(cli: &Cli, theme: &config::ThemeConfig) -> tui::ThemePreference {
     if let Some(ref s) = cli.theme {
         if let Some(pref) = tui::ThemePreference::parse(s) {
             return pref;
@@ -497,8 +3125,8 @@ fn resolve_theme_preference(cli: &Cli) -> tui::ThemePreference {
             return pref;
         }
     }
-    if let Some(s) = config::load_theme_setting() {
-        if let Some(pref) = tui::ThemePreference::parse(&s) {
+    if let Some(s) = &theme.mode {
+        if let Some(pref) = tui::ThemePreference::parse(s) {
             return pref;
         }
     }
@@ -506,26 +3134,26 @@ fn resolve_theme_preference(cli: &Cli) -> tui::ThemePreference {
 }
 
 fn init_tui_theme(cli: &Cli) {
-    let pref = resolve_theme_preference(cli);
+    let theme = config::load_theme_config();
+    let pref = resolve_theme_preference(cli, &theme);
     let mode = tui::init_from_preference(pref);
-    if cli.debug {
-        eprintln!(
-            "[DEBUG] theme preference={} resolved={}",
-            pref.as_str(),
-            match mode {
-                tui::ColorMode::Dark => "dark",
-                tui::ColorMode::Light => "light",
-            }
-        );
-    }
+    output::init_style(&theme, mode, cli.plain || output::no_color_env());
+    tracing::debug!(
+        preference = pref.as_str(),
+        resolved = match mode {
+            tui::ColorMode::Dark => "dark",
+            tui::ColorMode::Light => "light",
+        },
+        "theme"
+    );
 }
 
 /// `xa login [name]` — launch the codex-style interactive provider setup
 /// (select provider → optional API key → auto-query models → pick a model),
 /// rendered in its own alternate-screen terminal. The chosen provider is
 /// persisted as the active one.
-async fn run_login(name: Option<String>) -> Result<(), Box<dyn std::error::Error>> {
-    match tui::wizard::Wizard::run_standalone(tui::wizard::WizardMode::Login, name.as_deref()).await? {
+async fn run_login(name: Option<String>) -> Result<(), XaError> {
+    match tui::wizard::Wizard::run_standalone(tui::wizard::WizardMode::Login, name.as_deref()).await.map_err(XaError::from_boxed)? {
         Some(p) => println!("logged in as provider `{}` (model `{}`)", p.name, p.model),
         None => println!("login cancelled"),
     }
@@ -533,7 +3161,7 @@ async fn run_login(name: Option<String>) -> Result<(), Box<dyn std::error::Error
 }
 
 /// Resume a named session, or open the session picker when no id was given.
-async fn resume_session(id: Option<String>) -> Result<(), Box<dyn std::error::Error>> {
+async fn resume_session(id: Option<String>) -> Result<(), XaError> {
     let id = match id {
         Some(id) => id,
         None => match tui::resume::pick_session()? {
@@ -541,9 +3169,124 @@ async fn resume_session(id: Option<String>) -> Result<(), Box<dyn std::error::Er
             None => return Ok(()),
         },
     };
-    let session = session::load(&id).ok_or_else(|| format!("Session not found: {id}"))?;
+    let session = session::load(&id).ok_or_else(|| XaError::Usage(format!("Session not found: {id}")))?;
+    let provider = agent::load_active_provider().await;
+    tui::run(provider, session).await.map_err(XaError::from_boxed)?;
+    Ok(())
+}
+
+/// `xa sessions list`.
+fn print_sessions() {
+    let sessions = session::list_summaries();
+    if sessions.is_empty() {
+        println!("No saved sessions yet. Use /save [title] in the TUI to store one.");
+        return;
+    }
+    let title_width = sessions.iter().map(|summary| summary.title.chars().count()).max().unwrap_or(0).min(40);
+    for summary in &sessions {
+        println!(
+            "{}  {:<title_width$.title_width$}  {}",
+            paint("90", &format!("{:<14}", summary.id)),
+            summary.title,
+            paint("90", &format!("{:>8}  {:<8}  {}", session::format_size(summary.bytes), session::relative_time(summary.updated), summary.model)),
+        );
+    }
+    let total: u64 = sessions.iter().map(|summary| summary.bytes).sum();
+    let plural = if sessions.len() == 1 { "" } else { "s" };
+    println!("{}", paint("90", &format!("{} session{}, {}", sessions.len(), plural, session::format_size(total))));
+}
+
+fn find_session(name: &str) -> Result<Session, XaError> {
+    session::find(name).ok_or_else(|| XaError::Usage(format!("Session not found: {name}")))
+}
+
+/// The session `xa ask -c` continues: the saved one called `name`, or the
+/// last one continued when `name` is empty; a new one when there is none.
+async fn ask_session(name: &str) -> Session {
+    let found = if name.is_empty() { session::last_ask() } else { session::find(name) };
+    if let Some(session) = found {
+        return session;
+    }
     let provider = agent::load_active_provider().await;
-    tui::run(provider, session).await?;
+    let mut session = Session::new(&provider.name, &provider.model);
+    if !name.is_empty() {
+        session.title = name.to_string();
+    }
+    session
+}
+
+/// Add a question and its answer to an `xa ask -c` session, titling an
+/// untitled one (by the model, else after its first question), and make it
+/// the one the next `xa ask -c` continues.
+async fn save_ask_turn(config: &config::Config, session: &mut Session, question: &str, answer: &str) -> Result<(), XaError> {
+    for (role, content) in [("user", question), ("assistant", answer)] {
+        session.messages.push(session::StoredMessage { role: role.into(), content: content.into(), tool_calls: None, tool_call_id: None });
+    }
+    if session.title == "untitled" {
+        let generated = session::generate_title(config, session).await;
+        session.title = generated.unwrap_or_else(|| session::fallback_title(question));
+    }
+    session.touch();
+    session::save(session)?;
+    session::set_last_ask(&session.id)?;
+    Ok(())
+}
+
+/// `xa sessions rename <name> <title>`.
+fn rename_session(name: &str, title: &str) -> Result<(), XaError> {
+    let mut session = find_session(name)?;
+    let old = std::mem::replace(&mut session.title, title.to_string());
+    session::save(&session)?;
+    println!("Renamed {} from \"{}\" to \"{}\"", session.id, old, title);
+    Ok(())
+}
+
+/// `xa sessions rm`: the named sessions plus, with `older_than`, every one
+/// last used more than that many days ago, after a confirmation.
+fn remove_sessions(names: &[String], older_than: Option<u32>, yes: bool) -> Result<(), XaError> {
+    let mut doomed: Vec<(String, String)> = Vec::new();
+    for name in names {
+        let session = find_session(name)?;
+        if !doomed.iter().any(|(id, _)| *id == session.id) {
+            doomed.push((session.id, session.title));
+        }
+    }
+    if let Some(days) = older_than {
+        let cutoff = chrono::Utc::now().timestamp_millis() - i64::from(days) * 86_400_000;
+        for summary in session::list_summaries() {
+            if summary.updated < cutoff && !doomed.iter().any(|(id, _)| *id == summary.id) {
+                doomed.push((summary.id, summary.title));
+            }
+        }
+    }
+    if doomed.is_empty() {
+        println!("No sessions to remove.");
+        return Ok(());
+    }
+    for (id, title) in &doomed {
+        println!("  {}  {}", paint("90", id), title);
+    }
+    if !yes && !confirm_on_tty(&format!("Delete {} session(s)? (Y/n): ", doomed.len()))? {
+        println!("Nothing removed.");
+        return Ok(());
+    }
+    for (id, _) in &doomed {
+        session::delete(id)?;
+    }
+    println!("Removed {} session(s).", doomed.len());
+    Ok(())
+}
+
+/// `xa sessions export <name> [-o file]`.
+fn export_session(name: &str, output: Option<&std::path::Path>) -> Result<(), XaError> {
+    let session = find_session(name)?;
+    match output {
+        Some(path) => {
+            session::export::write(&session, path, &config::load_theme_config(), tui::color_mode())?;
+            eprintln!("{}", paint("90", &format!("✓ written to {}", path.display())));
+        }
+        None => print!("{}", session::export::to_markdown(&session)),
+    }
     Ok(())
 }
 
@@ -601,10 +3344,10 @@ impl GainTotals {
 
 /// Print the saved session-level accounting. This deliberately reads only the
 /// serialized aggregate fields, never a conversation's message content.
-fn print_gain(daily: bool, weekly: bool, monthly: bool, _all: bool) -> Result<(), Box<dyn std::error::Error>> {
+fn print_gain(daily: bool, weekly: bool, monthly: bool, _all: bool) -> Result<(), XaError> {
     let requested = [daily, weekly, monthly].into_iter().filter(|enabled| *enabled).count();
     if requested > 1 {
-        return Err("choose only one of --daily, --weekly, or --monthly".into());
+        return Err(XaError::Usage("choose only one of --daily, --weekly, or --monthly".into()));
     }
     let period = if daily { GainPeriod::Daily } else if weekly { GainPeriod::Weekly } else if monthly { GainPeriod::Monthly } else { GainPeriod::Overall };
     let records = session::gain_records();
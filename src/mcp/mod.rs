@@ -0,0 +1,344 @@
+//! Model Context Protocol client: the tools of the servers under
+//! `[mcp_servers]` in config.toml join the built-in ones in
+//! `xa ask --tools`.
+//!
+//! Local servers are started as child processes and spoken to with
+//! newline-delimited JSON-RPC over stdin/stdout; remote ones over the
+//! Streamable HTTP transport (a POST per message, answered with JSON or a
+//! short event stream). Only tools are used; resources and prompts are not.
+//! A tool is offered to the model as `<server>__<tool>`, and every call
+//! needs confirmation unless the server marks the tool read-only.
+//!
+//! The other direction, xa's prompts as an MCP server, is in [`server`].
+
+pub mod server;
+
+use crate::config::McpServer;
+use crate::error::XaError;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::Mutex;
+
+const PROTOCOL_VERSION: &str = "2025-06-18";
+/// A server that has not answered by then is treated as broken.
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// A local server's process and pipes.
+struct Stdio {
+    /// Kept so the server is stopped when the client is dropped.
+    _child: Child,
+    pipes: Mutex<(ChildStdin, BufReader<ChildStdout>)>,
+}
+
+enum Transport {
+    Stdio(Box<Stdio>),
+    Http {
+        url: String,
+        headers: Vec<(String, String)>,
+        session: Mutex<Option<String>>,
+    },
+}
+
+/// A connected, initialized server.
+pub struct McpClient {
+    name: String,
+    transport: Transport,
+    next_id: AtomicU64,
+}
+
+impl McpClient {
+    /// Start or reach server `name` and complete the initialize handshake.
+    pub async fn connect(name: &str, server: &McpServer) -> Result<McpClient, XaError> {
+        let transport = match (&server.command, &server.url) {
+            (Some(command), _) => {
+                let mut child = Command::new(command)
+                    .args(&server.args)
+                    .envs(&server.env)
+                    .stdin(std::process::Stdio::piped())
+                    .stdout(std::process::Stdio::piped())
+                    .stderr(std::process::Stdio::null())
+                    .kill_on_drop(true)
+                    .spawn()
+                    .map_err(|e| XaError::Config(format!("cannot start MCP server '{}' ({}): {}", name, command, e)))?;
+                let stdin = child.stdin.take().ok_or_else(|| XaError::Other("no stdin for MCP server".into()))?;
+                let stdout = child.stdout.take().ok_or_else(|| XaError::Other("no stdout for MCP server".into()))?;
+                Transport::Stdio(Box::new(Stdio { _child: child, pipes: Mutex::new((stdin, BufReader::new(stdout))) }))
+            }
+            (None, Some(url)) => Transport::Http {
+                url: url.clone(),
+                headers: server.headers.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+                session: Mutex::new(None),
+            },
+            (None, None) => {
+                return Err(XaError::Config(format!("mcp_servers.{} needs a command or a url", name)));
+            }
+        };
+        let client = McpClient { name: name.to_string(), transport, next_id: AtomicU64::new(1) };
+        let params = json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": {},
+            "clientInfo": { "name": "xa", "version": env!("CARGO_PKG_VERSION") },
+        });
+        client.request("initialize", params).await?;
+        client.notify("notifications/initialized").await?;
+        Ok(client)
+    }
+
+    /// The server's tools, following pagination.
+    pub async fn list_tools(&self) -> Result<Vec<Value>, XaError> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let result = self.request("tools/list", params).await?;
+            tools.extend(result["tools"].as_array().cloned().unwrap_or_default());
+            match result["nextCursor"].as_str() {
+                Some(next) if !next.is_empty() => cursor = Some(next.to_string()),
+                _ => return Ok(tools),
+            }
+        }
+    }
+
+    /// Call tool `name`; its text content, or an error carrying it when
+    /// the server flags the result as one.
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<String, String> {
+        let result = self
+            .request("tools/call", json!({ "name": name, "arguments": arguments }))
+            .await
+            .map_err(|e| e.to_string())?;
+        let text = tool_result_text(&result);
+        if result["isError"].as_bool().unwrap_or(false) {
+            Err(text)
+        } else {
+            Ok(text)
+        }
+    }
+
+    async fn request(&self, method: &str, params: Value) -> Result<Value, XaError> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        tracing::debug!(server = %self.name, method, id, "MCP request");
+        let response = tokio::time::timeout(REQUEST_TIMEOUT, self.exchange(&message, Some(id)))
+            .await
+            .map_err(|_| XaError::Other(format!("MCP server '{}' did not answer {}", self.name, method)))??;
+        if let Some(error) = response.get("error") {
+            let message = error["message"].as_str().unwrap_or("unknown error");
+            return Err(XaError::Other(format!("MCP server '{}': {} failed: {}", self.name, method, message)));
+        }
+        Ok(response["result"].clone())
+    }
+
+    async fn notify(&self, method: &str) -> Result<(), XaError> {
+        self.exchange(&json!({ "jsonrpc": "2.0", "method": method }), None).await.map(|_| ())
+    }
+
+    /// Send `message`; when `id` is set, wait for the response with that id.
+    async fn exchange(&self, message: &Value, id: Option<u64>) -> Result<Value, XaError> {
+        let broken = |e: std::io::Error| XaError::Other(format!("MCP server '{}': {}", self.name, e));
+        match &self.transport {
+            Transport::Stdio(stdio) => {
+                let mut pipes = stdio.pipes.lock().await;
+                let (stdin, stdout) = &mut *pipes;
+                stdin.write_all(format!("{}\n", message).as_bytes()).await.map_err(broken)?;
+                stdin.flush().await.map_err(broken)?;
+                let Some(id) = id else { return Ok(Value::Null) };
+                let mut line = String::new();
+                loop {
+                    line.clear();
+                    if stdout.read_line(&mut line).await.map_err(broken)? == 0 {
+                        return Err(XaError::Other(format!("MCP server '{}' exited", self.name)));
+                    }
+                    // Log lines and server-initiated messages are skipped
+                    if let Some(response) = serde_json::from_str::<Value>(&line).ok().filter(|v| is_response(v, id)) {
+                        return Ok(response);
+                    }
+                }
+            }
+            Transport::Http { url, headers, session } => {
+                let client = crate::http::client().map_err(XaError::Config)?;
+                let mut request = client
+                    .post(url)
+                    .header(reqwest::header::ACCEPT, "application/json, text/event-stream")
+                    .json(message);
+                for (name, value) in headers {
+                    request = request.header(name.as_str(), value.as_str());
+                }
+                if let Some(session) = session.lock().await.as_deref() {
+                    request = request.header("Mcp-Session-Id", session).header("MCP-Protocol-Version", PROTOCOL_VERSION);
+                }
+                let response = request.send().await?;
+                let status = response.status();
+                if !status.is_success() {
+                    let body = response.text().await.unwrap_or_default();
+                    return Err(XaError::from_status(status, format!("MCP server '{}': {}", self.name, body.trim())));
+                }
+                if let Some(new_session) = response.headers().get("mcp-session-id").and_then(|v| v.to_str().ok()) {
+                    *session.lock().await = Some(new_session.to_string());
+                }
+                let Some(id) = id else { return Ok(Value::Null) };
+                let event_stream = response
+                    .headers()
+                    .get(reqwest::header::CONTENT_TYPE)
+                    .and_then(|v| v.to_str().ok())
+                    .is_some_and(|v| v.starts_with("text/event-stream"));
+                let body = response.text().await?;
+                let response = if event_stream {
+                    sse_response(&body, id)
+                } else {
+                    serde_json::from_str(&body).ok().filter(|v| is_response(v, id))
+                };
+                response.ok_or_else(|| XaError::Other(format!("MCP server '{}' sent no response", self.name)))
+            }
+        }
+    }
+}
+
+/// A tool of an MCP server, as seen by the tool loop.
+pub struct McpTool {
+    client: Arc<McpClient>,
+    /// `<server>__<tool>`, the name the model calls.
+    name: String,
+    /// The server's own name for it.
+    remote_name: String,
+    description: String,
+    schema: Value,
+    read_only: bool,
+}
+
+impl crate::tools::Tool for McpTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn description(&self) -> &str {
+        &self.description
+    }
+    fn parameters(&self) -> Value {
+        self.schema.clone()
+    }
+    fn needs_confirmation(&self, _args: &Value) -> bool {
+        !self.read_only
+    }
+    fn execute(&self, args: Value) -> Result<String, String> {
+        // Tools run on a blocking thread, which may wait on the runtime
+        tokio::runtime::Handle::current().block_on(self.client.call_tool(&self.remote_name, args))
+    }
+}
+
+/// Connect to every configured server and collect their tools. A server
+/// that fails to start or answer is reported and left out.
+pub async fn connect_all(servers: &std::collections::BTreeMap<String, McpServer>) -> Vec<Arc<dyn crate::tools::Tool>> {
+    let mut tools: Vec<Arc<dyn crate::tools::Tool>> = Vec::new();
+    for (name, server) in servers {
+        let connected = async {
+            let client = Arc::new(McpClient::connect(name, server).await?);
+            let listed = client.list_tools().await?;
+            Ok::<_, XaError>((client, listed))
+        }
+        .await;
+        let (client, listed) = match connected {
+            Ok(connected) => connected,
+            Err(error) => {
+                eprintln!("{}", crate::output::paint("33", &format!("Skipping MCP server '{}': {}", name, error)));
+                continue;
+            }
+        };
+        for tool in listed {
+            let Some(tool_name) = tool["name"].as_str() else { continue };
+            tools.push(Arc::new(McpTool {
+                client: client.clone(),
+                name: qualified_name(name, tool_name),
+                remote_name: tool_name.to_string(),
+                description: tool["description"].as_str().unwrap_or_default().to_string(),
+                schema: input_schema(&tool),
+                read_only: tool["annotations"]["readOnlyHint"].as_bool().unwrap_or(false),
+            }));
+        }
+    }
+    tools
+}
+
+/// `<server>__<tool>` within the function-name rules of the chat API
+/// (letters, digits, `_` and `-`, at most 64 characters).
+fn qualified_name(server: &str, tool: &str) -> String {
+    let clean = |s: &str| s.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' }).collect::<String>();
+    let mut name = format!("{}__{}", clean(server), clean(tool));
+    name.truncate(64);
+    name
+}
+
+/// The tool's `inputSchema`, made a valid `parameters` object.
+fn input_schema(tool: &Value) -> Value {
+    match &tool["inputSchema"] {
+        Value::Object(schema) => {
+            let mut schema = schema.clone();
+            schema.entry("type").or_insert("object".into());
+            schema.entry("properties").or_insert(json!({}));
+            Value::Object(schema)
+        }
+        _ => json!({ "type": "object", "properties": {} }),
+    }
+}
+
+fn is_response(message: &Value, id: u64) -> bool {
+    message["id"].as_u64() == Some(id) && (message.get("result").is_some() || message.get("error").is_some())
+}
+
+/// The response with `id` among the `data:` events of an event stream.
+fn sse_response(body: &str, id: u64) -> Option<Value> {
+    body.lines()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .filter_map(|data| serde_json::from_str::<Value>(data.trim()).ok())
+        .find(|message| is_response(message, id))
+}
+
+/// Text of a `tools/call` result: its text parts, with other kinds noted.
+fn tool_result_text(result: &Value) -> String {
+    let parts: Vec<String> = result["content"]
+        .as_array()
+        .map(|content| {
+            content
+                .iter()
+                .map(|part| match part["type"].as_str() {
+                    Some("text") => part["text"].as_str().unwrap_or_default().to_string(),
+                    Some("resource") => part["resource"]["text"].as_str().unwrap_or("[resource]").to_string(),
+                    Some(kind) => format!("[{} content omitted]", kind),
+                    None => String::new(),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    if parts.is_empty() {
+        result.get("structuredContent").map(Value::to_string).unwrap_or_default()
+    } else {
+        parts.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_tool_listings_and_results() {
+        assert_eq!(qualified_name("git hub", "search.issues"), "git_hub__search_issues");
+        assert_eq!(qualified_name("s", &"x".repeat(80)).len(), 64);
+
+        let tool = json!({ "name": "list", "inputSchema": { "required": ["path"] } });
+        assert_eq!(input_schema(&tool), json!({ "type": "object", "properties": {}, "required": ["path"] }));
+        assert_eq!(input_schema(&json!({ "name": "ping" }))["type"], "object");
+
+        let result = json!({ "content": [{ "type": "text", "text": "a.txt" }, { "type": "image", "data": "..." }] });
+        assert_eq!(tool_result_text(&result), "a.txt\n[image content omitted]");
+
+        let body = "event: message\ndata: {\"jsonrpc\":\"2.0\",\"method\":\"notifications/progress\"}\n\n\
+                    data: {\"jsonrpc\":\"2.0\",\"id\":3,\"result\":{\"tools\":[]}}\n\n";
+        assert_eq!(sse_response(body, 3).unwrap()["result"]["tools"], json!([]));
+        assert!(sse_response(body, 4).is_none());
+    }
+}
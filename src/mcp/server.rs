@@ -0,0 +1,126 @@
+//! `xa serve --mcp`: the prompt library as an MCP server over stdio, so
+//! editors and other agents can call `translate`, `polish` and the user's
+//! own commands as tools.
+//!
+//! Each single-template command becomes a tool taking the text as `input`
+//! and its named arguments as optional string properties. Pipelines are
+//! left out, as in `xa batch`. Requests are answered one at a time; stdout
+//! carries nothing but protocol messages.
+
+use super::PROTOCOL_VERSION;
+use crate::config::Config;
+use crate::prompt::PromptEntry;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+/// Serve `prompts` on stdin/stdout until stdin closes. `configure` gives
+/// the request settings for a command (its model, headers and body fields).
+pub async fn serve(prompts: &HashMap<String, PromptEntry>, configure: impl Fn(&PromptEntry) -> Config) -> std::io::Result<()> {
+    let mut names: Vec<&String> = prompts.iter().filter(|(_, entry)| entry.pipeline.is_none()).map(|(name, _)| name).collect();
+    names.sort();
+    eprintln!("xa MCP server: {} prompt tools on stdio", names.len());
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdout = tokio::io::stdout();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Ok(message) = serde_json::from_str::<Value>(&line) else {
+            let error = json!({ "jsonrpc": "2.0", "id": null, "error": { "code": -32700, "message": "parse error" } });
+            stdout.write_all(format!("{}\n", error).as_bytes()).await?;
+            stdout.flush().await?;
+            continue;
+        };
+        // Notifications (no id) need no answer
+        let Some(id) = message.get("id").cloned() else { continue };
+        let method = message["method"].as_str().unwrap_or_default();
+        tracing::debug!(method, "MCP server request");
+        let result = match method {
+            "initialize" => Ok(json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": { "tools": {} },
+                "serverInfo": { "name": "xa", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": names.iter().map(|name| tool(name, &prompts[*name])).collect::<Vec<_>>() })),
+            "tools/call" => {
+                let params = &message["params"];
+                let name = params["name"].as_str().unwrap_or_default();
+                match prompts.get(name).filter(|entry| entry.pipeline.is_none()) {
+                    Some(entry) => Ok(call(&configure(entry), entry, &params["arguments"]).await),
+                    None => Err((-32602, format!("unknown tool: {}", name))),
+                }
+            }
+            _ => Err((-32601, format!("method not found: {}", method))),
+        };
+        let response = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } }),
+        };
+        stdout.write_all(format!("{}\n", response).as_bytes()).await?;
+        stdout.flush().await?;
+    }
+    Ok(())
+}
+
+/// The `tools/list` entry for command `name`.
+fn tool(name: &str, entry: &PromptEntry) -> Value {
+    let mut properties = Map::new();
+    properties.insert("input".into(), json!({ "type": "string", "description": "The text to run the command on" }));
+    for arg in entry.args.iter().flatten() {
+        let description = match &arg.description {
+            Some(description) => format!("{} (default: {})", description, arg.default_value),
+            None => format!("Default: {}", arg.default_value),
+        };
+        properties.insert(arg.name.clone(), json!({ "type": "string", "description": description }));
+    }
+    json!({
+        "name": name,
+        "description": entry.description.clone().unwrap_or_else(|| format!("Run the xa '{}' prompt", name)),
+        "inputSchema": { "type": "object", "properties": properties, "required": ["input"] },
+    })
+}
+
+/// Run command `entry` with the tool call's `arguments`; failures become
+/// an error result the caller can show, not a protocol error.
+async fn call(config: &Config, entry: &PromptEntry, arguments: &Value) -> Value {
+    let input = arguments["input"].as_str().unwrap_or_default();
+    let args: Vec<String> = entry
+        .args
+        .iter()
+        .flatten()
+        .map(|arg| arguments[&arg.name].as_str().unwrap_or(&arg.default_value).to_string())
+        .collect();
+    let result = async {
+        let input = entry.prepare_input(input).await.map_err(|e| e.to_string())?;
+        let prompt = entry.fill_prompt(config, &input, &args).await.map_err(|e| e.to_string())?;
+        let done = crate::llm::complete(config, &prompt).await.map_err(|e| e.to_string())?;
+        entry.finish_output(&done.text).await.map_err(|e| e.to_string())
+    };
+    match result.await {
+        Ok(text) => json!({ "content": [{ "type": "text", "text": text }] }),
+        Err(error) => json!({ "content": [{ "type": "text", "text": error }], "isError": true }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prompt::PromptArg;
+
+    #[test]
+    fn describes_prompts_as_tools() {
+        let entry = PromptEntry {
+            template: "Translate into {target_lang}: {input}".into(),
+            description: Some("Translate text".into()),
+            args: Some(vec![PromptArg { name: "target_lang".into(), default_value: "zh".into(), description: None }]),
+            ..Default::default()
+        };
+        let tool = tool("translate", &entry);
+        assert_eq!(tool["description"], "Translate text");
+        assert_eq!(tool["inputSchema"]["required"], json!(["input"]));
+        assert_eq!(tool["inputSchema"]["properties"]["target_lang"]["description"], "Default: zh");
+    }
+}
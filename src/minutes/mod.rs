@@ -0,0 +1,88 @@
+//! Meeting minutes for the built-in `minutes` command (and any command
+//! with `show_minutes = true`): the model answers in JSON mode with a
+//! summary, the decisions and the action items, and xa lays them out as
+//! Markdown, the action items as an owner / task / due table. With a
+//! `.json` `-o` file the JSON itself is written, for trackers and scripts.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Minutes {
+    #[serde(default)]
+    pub summary: String,
+    #[serde(default)]
+    pub decisions: Vec<String>,
+    #[serde(default)]
+    pub action_items: Vec<ActionItem>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ActionItem {
+    #[serde(default)]
+    pub owner: Option<String>,
+    pub task: String,
+    #[serde(default)]
+    pub due: Option<String>,
+}
+
+/// The minutes in a model's answer, which may wrap the JSON in a code
+/// block or text.
+pub fn parse(answer: &str) -> Result<Minutes, String> {
+    let text = crate::postprocess::extract_code_block(answer).unwrap_or_else(|| answer.to_string());
+    let json = match (text.find('{'), text.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &text[start..=end],
+        _ => return Err("no JSON in the answer".into()),
+    };
+    serde_json::from_str(json).map_err(|e| e.to_string())
+}
+
+/// A table cell: one line, with `|` escaped; `—` when empty.
+fn cell(text: Option<&str>) -> String {
+    match text.map(str::trim).filter(|text| !text.is_empty() && !text.eq_ignore_ascii_case("null")) {
+        Some(text) => text.replace('|', "\\|").replace('\n', " "),
+        None => "—".to_string(),
+    }
+}
+
+/// The minutes as Markdown: Summary, Decisions and Action items sections,
+/// leaving out the empty ones.
+pub fn markdown(minutes: &Minutes) -> String {
+    let mut sections = Vec::new();
+    if !minutes.summary.trim().is_empty() {
+        sections.push(format!("## Summary\n\n{}", minutes.summary.trim()));
+    }
+    if !minutes.decisions.is_empty() {
+        let decisions: Vec<String> = minutes.decisions.iter().map(|decision| format!("- {}", decision.trim())).collect();
+        sections.push(format!("## Decisions\n\n{}", decisions.join("\n")));
+    }
+    if !minutes.action_items.is_empty() {
+        let rows: Vec<String> = minutes
+            .action_items
+            .iter()
+            .map(|item| format!("| {} | {} | {} |", cell(item.owner.as_deref()), cell(Some(&item.task)), cell(item.due.as_deref())))
+            .collect();
+        sections.push(format!("## Action items\n\n| Owner | Task | Due |\n|---|---|---|\n{}", rows.join("\n")));
+    } else {
+        sections.push("## Action items\n\nNone.".to_string());
+    }
+    sections.join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lays_out_minutes_as_markdown() {
+        let answer = "```json\n{\"summary\": \"Planned the launch.\", \"decisions\": [\"Ship on the 10th\"], \"action_items\": [{\"owner\": \"Ann\", \"task\": \"Update the docs | FAQ\", \"due\": \"Friday\"}, {\"owner\": null, \"task\": \"Book the room\"}]}\n```";
+        let minutes = parse(answer).unwrap();
+        assert_eq!(minutes.action_items[1], ActionItem { owner: None, task: "Book the room".into(), due: None });
+        assert_eq!(
+            markdown(&minutes),
+            "## Summary\n\nPlanned the launch.\n\n## Decisions\n\n- Ship on the 10th\n\n## Action items\n\n| Owner | Task | Due |\n|---|---|---|\n\
+             | Ann | Update the docs \\| FAQ | Friday |\n| — | Book the room | — |"
+        );
+        assert_eq!(markdown(&parse("{\"summary\": \"Chat.\"}").unwrap()), "## Summary\n\nChat.\n\n## Action items\n\nNone.");
+        assert!(parse("no minutes").is_err());
+    }
+}
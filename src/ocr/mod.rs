@@ -0,0 +1,107 @@
+//! `xa ocr <image>`: the text in an image, read either by a vision model
+//! (the default) or by a local `tesseract`, which sends nothing anywhere.
+//! Chosen with `ocr_backend` in config.toml or `--backend`.
+
+use crate::config::Config;
+use crate::error::XaError;
+use std::path::Path;
+use std::process::Command;
+
+/// What the vision model is asked.
+const PROMPT: &str = "Transcribe all the text in this image exactly as written, keeping its line breaks and reading \
+                      order. Reply with the text only: no description of the image, no commentary and no code fences. \
+                      If there is no text, reply with nothing.";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, clap::ValueEnum)]
+pub enum Backend {
+    /// The configured model (or `ocr_model`), sent the image
+    Vision,
+    /// The local `tesseract` command
+    Tesseract,
+}
+
+impl Backend {
+    /// `--backend`, else `ocr_backend`, else vision.
+    pub fn resolve(flag: Option<Backend>, config: &Config) -> Result<Backend, XaError> {
+        if let Some(backend) = flag {
+            return Ok(backend);
+        }
+        match config.ocr_backend.as_deref() {
+            None | Some("vision") => Ok(Backend::Vision),
+            Some("tesseract") => Ok(Backend::Tesseract),
+            Some(other) => Err(XaError::Config(format!("Invalid ocr_backend '{}': expected vision or tesseract", other))),
+        }
+    }
+}
+
+/// The text in the image at `path`. `language` is tesseract's `-l`
+/// (e.g. `eng+deu`); the vision backend needs no hint.
+pub async fn extract(config: &Config, backend: Backend, path: &Path, language: Option<&str>) -> Result<String, XaError> {
+    let text = match backend {
+        Backend::Vision => {
+            let mut config = config.clone();
+            config.images = vec![crate::image::load(&path.to_string_lossy())?];
+            if let Some(model) = &config.ocr_model {
+                config.default_model = Some(model.clone());
+            }
+            crate::llm::complete(&config, PROMPT).await?.text
+        }
+        Backend::Tesseract => tesseract(path, language)?,
+    };
+    Ok(clean(&text))
+}
+
+fn tesseract(path: &Path, language: Option<&str>) -> Result<String, XaError> {
+    if !path.is_file() {
+        return Err(XaError::Usage(format!("cannot read {}", path.display())));
+    }
+    let mut command = Command::new("tesseract");
+    command.arg(path).arg("stdout");
+    if let Some(language) = language {
+        command.args(["-l", language]);
+    }
+    let output = command.output().map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => {
+            XaError::Config("tesseract is not installed; install it or use the vision backend (--backend vision)".into())
+        }
+        _ => XaError::Other(format!("cannot run tesseract: {}", e)),
+    })?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(XaError::Other(format!("tesseract failed: {}", stderr.trim())));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Trailing spaces, runs of blank lines and the form feed tesseract ends
+/// pages with removed; a code fence around the whole text unwrapped.
+fn clean(text: &str) -> String {
+    let text = crate::postprocess::extract_code_block(text).filter(|_| text.trim_start().starts_with("```")).unwrap_or_else(|| text.to_string());
+    let mut lines: Vec<&str> = Vec::new();
+    for line in text.lines().map(|line| line.trim_end_matches([' ', '\t', '\x0c'])) {
+        if line.is_empty() && lines.last().is_none_or(|last| last.is_empty()) {
+            continue;
+        }
+        lines.push(line);
+    }
+    lines.join("\n").trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cleans_ocr_output_and_picks_the_backend() {
+        assert_eq!(clean("\nError 0x80070005  \n\n\n\nAccess is denied.\n\x0c"), "Error 0x80070005\n\nAccess is denied.");
+        assert_eq!(clean("```\nline one\nline two\n```"), "line one\nline two");
+
+        let mut config = Config::default();
+        assert_eq!(Backend::resolve(None, &config).unwrap(), Backend::Vision);
+        config.ocr_backend = Some("tesseract".into());
+        assert_eq!(Backend::resolve(None, &config).unwrap(), Backend::Tesseract);
+        assert_eq!(Backend::resolve(Some(Backend::Vision), &config).unwrap(), Backend::Vision);
+        config.ocr_backend = Some("easyocr".into());
+        assert!(Backend::resolve(None, &config).is_err());
+    }
+}
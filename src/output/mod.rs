@@ -1,21 +1,651 @@
 use termimad::*;
-use chrono::Local;
-
-pub fn render_output(result: &str, show_success: bool) {
-    let mut skin = MadSkin::default();
-    // Set up colors - using ANSI codes for better control
-    skin.paragraph.set_fg(termimad::ansi(37)); // Light gray for text
-    skin.bold.set_fg(termimad::ansi(33)); // Yellow for bold
-    skin.italic.set_fg(termimad::ansi(36)); // Cyan for italic
-    skin.inline_code.set_fg(termimad::ansi(35)); // Magenta for inline code
-
-    skin.print_text(result);
-
-    if show_success {
-        // Count tokens approximately (words)
-        let word_count = result.split_whitespace().count();
-        let now = Local::now();
-        println!("\n\x1b[90m✓ result has been copied to clipboard · tokens: {} · {}\x1b[0m",
-                 word_count, now.format("%H:%M:%S").to_string());
-    }
-}
\ No newline at end of file
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::sync::OnceLock;
+use unicode_width::UnicodeWidthStr;
+
+use crate::config::ThemeConfig;
+use crate::llm::{StreamTiming, Usage};
+
+/// Resolved appearance after preference + detection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorMode {
+    Dark,
+    Light,
+}
+
+/// Markdown skin and whether escape codes are allowed at all, fixed by
+/// [`init_style`] at startup.
+struct Style {
+    skin: MadSkin,
+    plain: bool,
+}
+
+static STYLE: OnceLock<Style> = OnceLock::new();
+
+/// `NO_COLOR` is set to a non-empty value (see no-color.org).
+pub fn no_color_env() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+}
+
+/// Install the skin for one-shot output from `[theme]` and the resolved
+/// TUI mode. `plain` (`--plain` or `NO_COLOR`) drops all colors.
+pub fn init_style(theme: &ThemeConfig, mode: ColorMode, plain: bool) {
+    let _ = STYLE.set(Style { skin: build_skin(theme, mode, plain), plain });
+}
+
+fn style() -> &'static Style {
+    STYLE.get_or_init(|| {
+        let plain = no_color_env();
+        Style { skin: build_skin(&ThemeConfig::default(), ColorMode::Dark, plain), plain }
+    })
+}
+
+/// The active Markdown skin.
+pub fn skin() -> &'static MadSkin {
+    &style().skin
+}
+
+/// `text` wrapped in the SGR sequence `sgr` ("90" for dim gray), or
+/// unchanged in plain mode.
+pub fn paint(sgr: &str, text: &str) -> String {
+    if style().plain {
+        text.to_string()
+    } else {
+        format!("\x1b[{}m{}\x1b[0m", sgr, text)
+    }
+}
+
+/// A `[theme]` color: `red`…`white`, `gray`, `bright_<name>`, a 256-color
+/// number, or `#rrggbb`.
+pub fn parse_color(value: &str) -> Option<crossterm::style::Color> {
+    const NAMES: &[&str] = &["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+    let value = value.trim().to_lowercase();
+    if let Some(hex) = value.strip_prefix('#') {
+        let channel = |i: usize| hex.get(i..i + 2).and_then(|c| u8::from_str_radix(c, 16).ok());
+        return match hex.len() {
+            6 => Some(rgb(channel(0)?, channel(2)?, channel(4)?)),
+            _ => None,
+        };
+    }
+    if let Ok(index) = value.parse::<u8>() {
+        return Some(ansi(index));
+    }
+    if value == "gray" || value == "grey" {
+        return Some(ansi(8));
+    }
+    let (base, offset) = match value.strip_prefix("bright_") {
+        Some(base) => (base, 8),
+        None => (value.as_str(), 0),
+    };
+    NAMES.iter().position(|name| *name == base).map(|i| ansi(i as u8 + offset))
+}
+
+fn build_skin(theme: &ThemeConfig, mode: ColorMode, plain: bool) -> MadSkin {
+    if plain {
+        return MadSkin::no_style();
+    }
+    let preset = match theme.preset.as_deref() {
+        Some(preset) => preset,
+        None if mode == ColorMode::Light => "light",
+        None => "dark",
+    };
+    let mut skin = match preset {
+        "plain" => MadSkin::no_style(),
+        "light" => {
+            // Darker inks, and pale instead of dark code backgrounds
+            let mut skin = MadSkin::default();
+            skin.set_headers_fg(ansi(25));
+            skin.bold.set_fg(ansi(130));
+            skin.italic.set_fg(ansi(24));
+            skin.inline_code.set_fgbg(ansi(90), gray(21));
+            skin.code_block.set_bg(gray(21));
+            skin.quote_mark.set_fg(ansi(244));
+            skin
+        }
+        _ => {
+            let mut skin = MadSkin::default();
+            // Set up colors - using ANSI codes for better control
+            skin.paragraph.set_fg(ansi(37)); // Light gray for text
+            skin.bold.set_fg(ansi(33)); // Yellow for bold
+            skin.italic.set_fg(ansi(36)); // Cyan for italic
+            skin.inline_code.set_fg(ansi(35)); // Magenta for inline code
+            skin
+        }
+    };
+    let color = |value: &Option<String>| value.as_deref().and_then(parse_color);
+    if let Some(c) = color(&theme.headers) {
+        skin.set_headers_fg(c);
+    }
+    if let Some(c) = color(&theme.bold) {
+        skin.bold.set_fg(c);
+    }
+    if let Some(c) = color(&theme.italic) {
+        skin.italic.set_fg(c);
+    }
+    if let Some(c) = color(&theme.code) {
+        skin.inline_code.set_fg(c);
+        skin.code_block.set_fg(c);
+    }
+    if let Some(c) = color(&theme.quote) {
+        skin.quote_mark.set_fg(c);
+    }
+    skin
+}
+
+/// What the status line under a one-shot result reports.
+pub struct Footer<'a> {
+    /// What went to the clipboard ("result", "code block"), if anything.
+    pub copied: Option<&'a str>,
+    pub model: &'a str,
+    /// Token counts from the API; without them the words are counted.
+    pub usage: Option<Usage>,
+    /// Time to first token and tokens per second, for streamed answers.
+    pub timing: Option<StreamTiming>,
+}
+
+/// Print `result` as Markdown with a status footer.
+pub fn render_output(result: &str, footer: &Footer) {
+    skin().print_text(result);
+    print_footer(result, footer);
+}
+
+/// The status line under a result, for results already on screen.
+pub fn print_footer(result: &str, footer: &Footer) {
+    println!("\n{}", paint("90", &footer_line(result, footer, Local::now())));
+}
+
+fn footer_line(result: &str, footer: &Footer, now: DateTime<Local>) -> String {
+    let status = match footer.copied {
+        Some(what) => format!("{} has been copied to clipboard", what),
+        None => "done".to_string(),
+    };
+    let size = match footer.usage {
+        Some(usage) => format!("{} prompt + {} completion tokens", usage.prompt_tokens, usage.completion_tokens),
+        // The API sent no usage; a word count is only a rough size
+        None => format!("~{} words", result.split_whitespace().count()),
+    };
+    let speed = match footer.timing {
+        Some(timing) => {
+            let rate = timing.tokens_per_second.map(|rate| format!(" · {:.0} tok/s", rate)).unwrap_or_default();
+            format!(" · first token {:.2}s{}", timing.first_token_ms as f64 / 1000.0, rate)
+        }
+        None => String::new(),
+    };
+    format!("✓ {} · {} · {}{} · {}", status, footer.model, size, speed, now.format("%H:%M:%S"))
+}
+
+/// Which fenced code block `--copy-code` copies.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CodeBlock {
+    First,
+    Last,
+}
+
+/// Contents of the first or last fenced (``` or ~~~) code block in `text`,
+/// without the fences. An unclosed final block runs to the end.
+pub fn code_block(text: &str, which: CodeBlock) -> Option<String> {
+    let mut blocks = Vec::new();
+    let mut current: Option<(&str, Vec<&str>)> = None;
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        match current.take() {
+            None => {
+                if let Some(fence) = ["```", "~~~"].into_iter().find(|fence| trimmed.starts_with(fence)) {
+                    current = Some((fence, Vec::new()));
+                }
+            }
+            Some((fence, lines)) if trimmed.trim_end() == fence => blocks.push(lines.join("\n")),
+            Some((fence, mut lines)) => {
+                lines.push(line);
+                current = Some((fence, lines));
+            }
+        }
+    }
+    if let Some((_, lines)) = current {
+        blocks.push(lines.join("\n"));
+    }
+    match which {
+        CodeBlock::First => blocks.into_iter().next(),
+        CodeBlock::Last => blocks.pop(),
+    }
+}
+
+/// Word-level diff from `before` to `after`: removed words red and struck
+/// through, added ones green; in plain mode `[-removed-]` and `{+added+}`.
+/// Also returns the number of words removed and added.
+pub fn word_diff(before: &str, after: &str, plain: bool) -> (String, usize, usize) {
+    let diff = similar::TextDiff::from_words(before, after);
+    let (mut text, mut removed, mut added) = (String::new(), 0, 0);
+    let (deleted, inserted) = if plain { (("[-", "-]"), ("{+", "+}")) } else { (("\x1b[31;9m", "\x1b[0m"), ("\x1b[32m", "\x1b[0m")) };
+    for op in diff.ops() {
+        // Each op is a run of tokens; mark runs, not single words, so the
+        // spaces between changed words stay inside one marker.
+        let (mut old, mut new) = (String::new(), String::new());
+        for change in diff.iter_changes(op) {
+            match change.tag() {
+                similar::ChangeTag::Equal => text.push_str(change.value()),
+                similar::ChangeTag::Delete => old.push_str(change.value()),
+                similar::ChangeTag::Insert => new.push_str(change.value()),
+            }
+        }
+        removed += old.split_whitespace().count();
+        added += new.split_whitespace().count();
+        text.push_str(&mark(&old, deleted));
+        text.push_str(&mark(&new, inserted));
+    }
+    (text, removed, added)
+}
+
+/// `run` wrapped in `open`/`close`, with its leading and trailing whitespace
+/// left outside. Whitespace-only runs are returned unchanged.
+fn mark(run: &str, (open, close): (&str, &str)) -> String {
+    let core = run.trim();
+    if core.is_empty() {
+        return run.to_string();
+    }
+    let start = run.len() - run.trim_start().len();
+    format!("{}{}{}{}{}", &run[..start], open, core, close, &run[start + core.len()..])
+}
+
+/// Print `result` as a word diff against `input`, for `--diff`.
+pub fn print_diff(input: &str, result: &str) {
+    let (text, removed, added) = word_diff(input.trim(), result.trim(), style().plain);
+    println!("{}", text);
+    println!("\n{}", paint("90", &format!("−{} +{} words", removed, added)));
+}
+
+/// Whether colors are off (`--plain` or `NO_COLOR`).
+pub fn is_plain() -> bool {
+    style().plain
+}
+
+/// Whether streamed answers are styled as they arrive: stdout is a terminal
+/// and colors are on. Otherwise deltas are printed raw.
+pub fn renders_live() -> bool {
+    io::stdout().is_terminal() && !style().plain
+}
+
+/// Prints a streamed answer. Live, each Markdown block is shown raw while it
+/// arrives and redrawn with the skin once complete (a blank line outside a
+/// code fence, or the closing fence).
+pub struct StreamView {
+    live: bool,
+    /// Text of the unfinished block, shown raw below the rendered ones.
+    pending: String,
+    /// Reasoning shown dimmed above the answer until the answer starts.
+    thought: String,
+    thinking_since: Option<std::time::Instant>,
+    answered: bool,
+}
+
+impl Default for StreamView {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StreamView {
+    pub fn new() -> StreamView {
+        StreamView { live: renders_live(), pending: String::new(), thought: String::new(), thinking_since: None, answered: false }
+    }
+
+    /// Whether this view styles what it prints.
+    pub fn is_live(&self) -> bool {
+        self.live
+    }
+
+    /// Show reasoning, dimmed, until the answer starts; it then folds into
+    /// one line. Only shown live: piped output carries the answer alone.
+    pub fn push_reasoning(&mut self, delta: &str) -> io::Result<()> {
+        // Reasoning often opens with blank lines
+        let delta = if self.thought.is_empty() { delta.trim_start() } else { delta };
+        if !self.live || self.answered || delta.is_empty() {
+            return Ok(());
+        }
+        self.thinking_since.get_or_insert_with(std::time::Instant::now);
+        self.thought.push_str(delta);
+        let mut stdout = io::stdout();
+        write!(stdout, "{}", paint("2;3", delta))?;
+        stdout.flush()
+    }
+
+    /// Replace the reasoning on screen with a line saying how long it took.
+    /// Reasoning taller than the screen has scrolled away and stays.
+    fn fold_reasoning(&mut self) -> io::Result<()> {
+        self.answered = true;
+        let Some(since) = self.thinking_since.take() else {
+            return Ok(());
+        };
+        let (cols, rows) = terminal_size();
+        let cols = if cols == 0 { usize::MAX } else { cols as usize };
+        let mut stdout = io::stdout();
+        let shown = screen_rows(&self.thought, cols);
+        if rows > 0 && shown >= rows as usize {
+            write!(stdout, "\n\n")?;
+        } else {
+            if shown > 1 {
+                write!(stdout, "\x1b[{}A", shown - 1)?;
+            }
+            write!(stdout, "\r\x1b[J{}\n\n", paint("2;3", &format!("Thought for {:.1}s", since.elapsed().as_secs_f32())))?;
+        }
+        self.thought.clear();
+        stdout.flush()
+    }
+
+    pub fn push(&mut self, delta: &str) -> io::Result<()> {
+        if !self.answered {
+            self.fold_reasoning()?;
+        }
+        let mut stdout = io::stdout();
+        if !self.live {
+            write!(stdout, "{}", delta)?;
+            return stdout.flush();
+        }
+        let shown = self.pending.len();
+        self.pending.push_str(delta);
+        match block_end(&self.pending) {
+            Some(end) => {
+                let rest = self.pending.split_off(end);
+                self.redraw(shown)?;
+                self.pending = rest;
+                write!(stdout, "{}", self.pending)?;
+            }
+            None => write!(stdout, "{}", delta)?,
+        }
+        stdout.flush()
+    }
+
+    /// Render whatever is left of the last block.
+    pub fn finish(mut self) -> io::Result<()> {
+        if !self.answered {
+            self.fold_reasoning()?;
+        }
+        if self.live && !self.pending.is_empty() {
+            let shown = self.pending.len();
+            self.redraw(shown)?;
+            self.pending.clear();
+        }
+        io::stdout().flush()
+    }
+
+    /// Replace the first `shown` bytes of `pending`, already printed raw,
+    /// with the whole of `pending` rendered. A block taller than the screen
+    /// has partly scrolled away and cannot be erased, so it stays raw.
+    fn redraw(&self, shown: usize) -> io::Result<()> {
+        // Some ptys report a 0x0 size: then count no wrapping and assume
+        // the block fits
+        let (cols, rows) = terminal_size();
+        let cols = if cols == 0 { usize::MAX } else { cols as usize };
+        let mut stdout = io::stdout();
+        let raw_rows = screen_rows(&self.pending[..shown], cols);
+        if rows > 0 && raw_rows >= rows as usize {
+            return write!(stdout, "{}", &self.pending[shown..]);
+        }
+        if raw_rows > 1 {
+            write!(stdout, "\x1b[{}A", raw_rows - 1)?;
+        }
+        write!(stdout, "\r\x1b[J")?;
+        stdout.flush()?;
+        skin().print_text(&self.pending);
+        Ok(())
+    }
+}
+
+/// End of the first complete Markdown block in `text`, which starts outside
+/// a code fence: just past a blank line, or past the line closing a fence.
+fn block_end(text: &str) -> Option<usize> {
+    let mut in_fence = false;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        if !line.ends_with('\n') {
+            break;
+        }
+        offset += line.len();
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            if !in_fence {
+                return Some(offset);
+            }
+        } else if !in_fence && trimmed.is_empty() {
+            return Some(offset);
+        }
+    }
+    None
+}
+
+/// Terminal rows `text` takes when printed from the start of a row into
+/// `cols` columns, counting the row the cursor ends on.
+fn screen_rows(text: &str, cols: usize) -> usize {
+    text.split('\n')
+        .map(|line| UnicodeWidthStr::width(line).div_ceil(cols).max(1))
+        .sum()
+}
+
+/// Narrowest column [`print_choices`] puts answers side by side in.
+const MIN_CHOICE_WIDTH: usize = 24;
+
+/// Several answers to the same prompt under their `labels` (`[1]`, a
+/// model name): side by side when the terminal has room for the columns,
+/// otherwise one below the other.
+pub fn print_choices(labels: &[String], choices: &[String]) {
+    let n = choices.len().max(1);
+    let (cols, _) = terminal_size();
+    let width = (cols as usize).saturating_sub(3 * (n - 1)) / n;
+    if !renders_live() || width < MIN_CHOICE_WIDTH {
+        for (label, choice) in labels.iter().zip(choices) {
+            println!("{}\n{}\n", paint("1", label), choice.trim());
+        }
+        return;
+    }
+    let columns: Vec<Vec<String>> = choices.iter().map(|choice| wrap(choice.trim(), width)).collect();
+    let headers: Vec<String> = labels.iter().map(|label| format!("{:<width$.width$}", label, width = width)).collect();
+    println!("{}", paint("1", &headers.join("   ")));
+    for row in 0..columns.iter().map(Vec::len).max().unwrap_or(0) {
+        let cells: Vec<String> = columns
+            .iter()
+            .map(|lines| {
+                let line = lines.get(row).map(String::as_str).unwrap_or_default();
+                format!("{}{}", line, " ".repeat(width.saturating_sub(UnicodeWidthStr::width(line))))
+            })
+            .collect();
+        println!("{}", cells.join(" │ ").trim_end());
+    }
+    println!();
+}
+
+/// `text` wrapped into lines at most `width` columns wide, between words
+/// where possible.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_inclusive(' ') {
+            if UnicodeWidthStr::width(line.as_str()) + UnicodeWidthStr::width(word.trim_end()) > width && !line.is_empty() {
+                lines.push(line.trim_end().to_string());
+                line.clear();
+            }
+            for c in word.chars() {
+                let c_width = unicode_width::UnicodeWidthChar::width(c).unwrap_or(0);
+                if c != ' ' && UnicodeWidthStr::width(line.as_str()) + c_width > width {
+                    lines.push(std::mem::take(&mut line));
+                }
+                line.push(c);
+            }
+        }
+        lines.push(line.trim_end().to_string());
+    }
+    lines
+}
+
+/// File format for `-o/--output`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, clap::ValueEnum)]
+pub enum OutputFormat {
+    Md,
+    Txt,
+    Json,
+}
+
+impl OutputFormat {
+    /// Guess from the extension: `.json`/`.jsonl` → json, `.md` → md,
+    /// anything else → txt.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()).map(str::to_lowercase).as_deref() {
+            Some("json" | "jsonl") => OutputFormat::Json,
+            Some("md" | "markdown") => OutputFormat::Md,
+            _ => OutputFormat::Txt,
+        }
+    }
+}
+
+/// One result as written by `-o/--output`.
+pub struct ResultRecord<'a> {
+    pub command: &'a str,
+    pub input: &'a str,
+    pub output: &'a str,
+}
+
+impl ResultRecord<'_> {
+    /// The record in `format`, newline-terminated. JSON is one object per
+    /// line so a file appended to across runs stays parseable as JSONL.
+    pub fn format(&self, format: OutputFormat) -> String {
+        match format {
+            OutputFormat::Json => {
+                let record = serde_json::json!({
+                    "time": Local::now().to_rfc3339(),
+                    "command": self.command,
+                    "input": self.input,
+                    "output": self.output,
+                });
+                format!("{}\n", record)
+            }
+            OutputFormat::Md | OutputFormat::Txt => format!("{}\n", self.output.trim_end()),
+        }
+    }
+}
+
+/// Open the `-o` file, replacing it or, with `append`, adding to the end.
+pub fn open_output(path: &Path, append: bool) -> io::Result<File> {
+    if let Some(parent) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .open(path)
+}
+
+/// Write one result to the `-o` file and say so on stderr. Appended
+/// Markdown results are separated by a horizontal rule.
+pub fn write_output(path: &Path, format: OutputFormat, append: bool, record: &ResultRecord) -> io::Result<()> {
+    let separate = append && format == OutputFormat::Md && fs::metadata(path).is_ok_and(|m| m.len() > 0);
+    let mut file = open_output(path, append)?;
+    if separate {
+        file.write_all(b"\n---\n\n")?;
+    }
+    file.write_all(record.format(format).as_bytes())?;
+    let action = if append { "appended to" } else { "written to" };
+    eprintln!("{}", paint("90", &format!("✓ {} {}", action, path.display())));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn format_follows_extension_and_json_is_one_line() {
+        assert_eq!(OutputFormat::from_path(Path::new("out.jsonl")), OutputFormat::Json);
+        assert_eq!(OutputFormat::from_path(Path::new("notes.MD")), OutputFormat::Md);
+        assert_eq!(OutputFormat::from_path(Path::new("out")), OutputFormat::Txt);
+
+        let record = ResultRecord { command: "translate", input: "hi\nthere", output: "salut\nla\n" };
+        let json = record.format(OutputFormat::Json);
+        assert_eq!(json.lines().count(), 1);
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed["output"], "salut\nla\n");
+        assert_eq!(record.format(OutputFormat::Txt), "salut\nla\n");
+    }
+
+    #[test]
+    fn wraps_choices_into_columns() {
+        assert_eq!(wrap("one two three four", 9), ["one two", "three", "four"]);
+        assert_eq!(wrap("abcdefghij\n\nok", 4), ["abcd", "efgh", "ij", "", "ok"]);
+        assert_eq!(wrap("你好世界", 4), ["你好", "世界"]);
+    }
+
+    #[test]
+    fn theme_colors_and_presets() {
+        assert_eq!(parse_color("blue"), Some(ansi(4)));
+        assert_eq!(parse_color("Bright_Cyan"), Some(ansi(14)));
+        assert_eq!(parse_color("208"), Some(ansi(208)));
+        assert_eq!(parse_color("#1e90ff"), Some(rgb(0x1e, 0x90, 0xff)));
+        assert_eq!(parse_color("#12"), None);
+        assert_eq!(parse_color("chartreuse"), None);
+
+        let theme = ThemeConfig { bold: Some("red".into()), ..ThemeConfig::default() };
+        assert_eq!(build_skin(&theme, ColorMode::Dark, false).bold.get_fg(), Some(ansi(1)));
+        assert_eq!(build_skin(&theme, ColorMode::Dark, true), MadSkin::no_style());
+        assert_ne!(build_skin(&ThemeConfig::default(), ColorMode::Light, false), build_skin(&ThemeConfig::default(), ColorMode::Dark, false));
+    }
+
+    #[test]
+    fn footer_prefers_api_usage_over_word_count() {
+        let now = Local.with_ymd_and_hms(2026, 3, 9, 14, 5, 7).unwrap();
+        let usage = Usage { prompt_tokens: 12, completion_tokens: 34 };
+        let footer = Footer { copied: Some("result"), model: "gpt-4o-mini", usage: Some(usage), timing: None };
+        assert_eq!(
+            footer_line("two words", &footer, now),
+            "✓ result has been copied to clipboard · gpt-4o-mini · 12 prompt + 34 completion tokens · 14:05:07"
+        );
+        let footer = Footer { copied: None, model: "local", usage: None, timing: None };
+        assert_eq!(footer_line("two words", &footer, now), "✓ done · local · ~2 words · 14:05:07");
+        let timing = StreamTiming { first_token_ms: 420, tokens_per_second: Some(57.4) };
+        let footer = Footer { copied: None, model: "local", usage: Some(usage), timing: Some(timing) };
+        assert_eq!(
+            footer_line("two words", &footer, now),
+            "✓ done · local · 12 prompt + 34 completion tokens · first token 0.42s · 57 tok/s · 14:05:07"
+        );
+    }
+
+    #[test]
+    fn code_block_picks_first_or_last_fence() {
+        let answer = "Run this:\n\n```bash\nls -la\n```\n\nor, on Windows:\n\n~~~\ndir\n  /w\n~~~\n";
+        assert_eq!(code_block(answer, CodeBlock::First).as_deref(), Some("ls -la"));
+        assert_eq!(code_block(answer, CodeBlock::Last).as_deref(), Some("dir\n  /w"));
+        assert_eq!(code_block("```sh\necho cut off", CodeBlock::Last).as_deref(), Some("echo cut off"));
+        assert_eq!(code_block("no code here", CodeBlock::First), None);
+    }
+
+    #[test]
+    fn word_diff_marks_changed_words() {
+        let (text, removed, added) = word_diff("the quick brown fox", "the quick red fox jumps", true);
+        assert_eq!(text, "the quick [-brown-]{+red+} fox {+jumps+}");
+        assert_eq!((removed, added), (1, 2));
+        let (text, _, _) = word_diff("keep these old words here", "keep here", true);
+        assert_eq!(text, "keep [-these old words-] here");
+        let (same, removed, added) = word_diff("unchanged text", "unchanged text", false);
+        assert_eq!((same.as_str(), removed, added), ("unchanged text", 0, 0));
+    }
+
+    #[test]
+    fn blocks_end_at_blank_lines_and_closing_fences() {
+        assert_eq!(block_end("# Title\nSome text"), None);
+        assert_eq!(block_end("# Title\nSome text\n\nNext"), Some(19));
+        assert_eq!(block_end("```rust\nfn main() {\n\n}\n"), None);
+        assert_eq!(block_end("```rust\nfn main() {\n\n}\n```\nafter"), Some(27));
+
+        assert_eq!(screen_rows("abc", 80), 1);
+        assert_eq!(screen_rows("abc\n", 80), 2);
+        assert_eq!(screen_rows(&"x".repeat(100), 40), 3);
+        assert_eq!(screen_rows(&"x".repeat(100), usize::MAX), 1);
+    }
+}
@@ -0,0 +1,141 @@
+//! `xa gen`: passwords and passphrases made locally from the operating
+//! system's random numbers; nothing is sent to the model. Passphrase words
+//! come from the BIP-39 English list (2048 words, so 11 bits each), whose
+//! words are told apart by their first four letters.
+
+use crate::error::XaError;
+
+static WORDS: &str = include_str!("words.txt");
+
+/// The named character classes `--charset` combines.
+const CLASSES: [(&str, &str); 4] = [
+    ("lower", "abcdefghijklmnopqrstuvwxyz"),
+    ("upper", "ABCDEFGHIJKLMNOPQRSTUVWXYZ"),
+    ("digits", "0123456789"),
+    ("symbols", "!#$%&()*+,-./:;<=>?@[]^_{|}~"),
+];
+
+/// The characters a password is drawn from, in groups that each must
+/// appear in it (one group for a literal set).
+#[derive(Debug, PartialEq)]
+pub struct Charset {
+    groups: Vec<Vec<char>>,
+}
+
+impl Charset {
+    /// A comma-separated list of `lower`, `upper`, `digits`, `symbols`,
+    /// `alnum` (the first three) and `hex`; anything else is the literal
+    /// set of characters to use.
+    pub fn parse(spec: &str) -> Result<Charset, XaError> {
+        let named: Option<Vec<Vec<char>>> = spec
+            .split(',')
+            .map(|name| match name.trim() {
+                "alnum" => Some(CLASSES[..3].iter().map(|(_, chars)| chars.chars().collect()).collect()),
+                "hex" => Some(vec!["0123456789abcdef".chars().collect()]),
+                name => CLASSES.iter().find(|(class, _)| *class == name).map(|(_, chars)| vec![chars.chars().collect()]),
+            })
+            .collect::<Option<Vec<Vec<Vec<char>>>>>()
+            .map(|groups| groups.concat());
+        let mut groups = named.unwrap_or_else(|| vec![spec.chars().collect()]);
+        for group in &mut groups {
+            group.sort_unstable();
+            group.dedup();
+        }
+        groups.dedup();
+        if groups.iter().map(Vec::len).sum::<usize>() < 2 {
+            return Err(XaError::Usage(format!("--charset '{}' needs at least two different characters", spec)));
+        }
+        Ok(Charset { groups })
+    }
+
+    fn chars(&self) -> Vec<char> {
+        let mut chars = self.groups.concat();
+        chars.sort_unstable();
+        chars.dedup();
+        chars
+    }
+}
+
+impl Default for Charset {
+    fn default() -> Self {
+        Charset { groups: CLASSES.iter().map(|(_, chars)| chars.chars().collect()).collect() }
+    }
+}
+
+/// A uniformly random number below `n`, by rejection so no value is
+/// likelier than another.
+fn below(n: usize) -> Result<usize, XaError> {
+    let n = n as u64;
+    let limit = u64::MAX - u64::MAX % n;
+    loop {
+        let mut bytes = [0u8; 8];
+        getrandom::getrandom(&mut bytes).map_err(|e| XaError::Other(format!("no random numbers from the system: {}", e)))?;
+        let value = u64::from_le_bytes(bytes);
+        if value < limit {
+            return Ok((value % n) as usize);
+        }
+    }
+}
+
+/// A `length`-character password from `charset`, with at least one
+/// character of each of its groups when it is long enough to hold them.
+pub fn password(length: usize, charset: &Charset) -> Result<String, XaError> {
+    let chars = charset.chars();
+    loop {
+        let mut password = String::with_capacity(length);
+        for _ in 0..length {
+            password.push(chars[below(chars.len())?]);
+        }
+        let covered = charset.groups.iter().all(|group| password.chars().any(|c| group.contains(&c)));
+        if covered || length < charset.groups.len() {
+            return Ok(password);
+        }
+    }
+}
+
+/// `count` random words joined by `separator`.
+pub fn passphrase(count: usize, separator: &str) -> Result<String, XaError> {
+    let words: Vec<&str> = WORDS.lines().collect();
+    let picked = (0..count).map(|_| below(words.len()).map(|index| words[index])).collect::<Result<Vec<_>, _>>()?;
+    Ok(picked.join(separator))
+}
+
+/// The strength of a password or passphrase in bits: how many guesses
+/// (as a power of two) trying every one the same options could make takes.
+pub fn entropy_bits(choices: usize, picks: usize) -> f64 {
+    picks as f64 * (choices as f64).log2()
+}
+
+/// [`entropy_bits`] for a password from `charset`.
+pub fn password_bits(length: usize, charset: &Charset) -> f64 {
+    entropy_bits(charset.chars().len(), length)
+}
+
+/// [`entropy_bits`] for a passphrase of `count` words.
+pub fn passphrase_bits(count: usize) -> f64 {
+    entropy_bits(WORDS.lines().count(), count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn makes_passwords_and_passphrases() {
+        let charset = Charset::default();
+        let made = password(32, &charset).unwrap();
+        assert_eq!(made.chars().count(), 32);
+        assert!(made.chars().any(|c| c.is_ascii_digit()) && made.chars().any(|c| c.is_ascii_uppercase()));
+        assert!(made.chars().any(|c| CLASSES[3].1.contains(c)));
+        assert_eq!(password(4, &Charset::parse("hex").unwrap()).unwrap().trim_matches(|c: char| c.is_ascii_hexdigit()), "");
+        assert_eq!(password(6, &Charset::parse("ab").unwrap()).unwrap().replace(['a', 'b'], ""), "");
+        assert!(Charset::parse("aaa").is_err());
+        assert_eq!(Charset::parse("alnum").unwrap(), Charset::parse("lower,upper,digits").unwrap());
+        assert_eq!(password_bits(10, &Charset::parse("digits").unwrap()).round(), 33.0);
+
+        let phrase = passphrase(4, "-").unwrap();
+        assert_eq!(phrase.split('-').count(), 4);
+        assert!(phrase.split('-').all(|word| WORDS.lines().any(|listed| listed == word)));
+        assert_eq!(passphrase_bits(4), 44.0);
+    }
+}
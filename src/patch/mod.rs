@@ -0,0 +1,304 @@
+//! `xa patch "<instruction>" -f <file>`: an edit asked for in words, made
+//! as a unified diff. The model writes the diff; it is parsed and applied
+//! here, in memory, before anything is shown, and a diff that does not
+//! apply goes back to the model with the hunk that failed. Files are only
+//! written once the user has seen the diff and agreed.
+//!
+//! Hunks are placed by their context and removed lines, not by the line
+//! numbers in the `@@` header, which models often get wrong; a hunk may be
+//! found anywhere after the previous one, nearest its stated line first.
+
+use crate::config::Config;
+use crate::error::XaError;
+use crate::output::paint;
+use std::path::Path;
+
+#[derive(Debug, PartialEq)]
+enum Line {
+    Context(String),
+    Remove(String),
+    Add(String),
+}
+
+#[derive(Debug)]
+struct Hunk {
+    /// The stated first line in the old file, 1-based.
+    old_start: usize,
+    lines: Vec<Line>,
+}
+
+/// The hunks for one file.
+#[derive(Debug)]
+pub struct FilePatch {
+    pub path: String,
+    hunks: Vec<Hunk>,
+}
+
+/// A file's text before and after the patch.
+#[derive(Debug)]
+pub struct Change {
+    pub path: String,
+    pub before: String,
+    pub after: String,
+}
+
+/// `a/src/x.rs` → `src/x.rs`, without a trailing timestamp.
+fn header_path(header: &str) -> &str {
+    let path = header.split('\t').next().unwrap_or_default().trim();
+    path.strip_prefix("a/").or_else(|| path.strip_prefix("b/")).unwrap_or(path)
+}
+
+/// The old start line of an `@@ -12,7 +12,8 @@` header.
+fn hunk_start(header: &str) -> Option<usize> {
+    let old = header.strip_prefix("@@")?.trim_start().strip_prefix('-')?;
+    old.split([',', ' ']).next()?.parse().ok()
+}
+
+/// The file patches in a unified diff. Hunk line counts are not trusted:
+/// a hunk runs until a line that cannot belong to one. An empty line
+/// (a context line whose space was lost) counts as context unless it
+/// ends the hunk.
+pub fn parse(diff: &str) -> Result<Vec<FilePatch>, String> {
+    let lines: Vec<&str> = diff.lines().collect();
+    let mut patches: Vec<FilePatch> = Vec::new();
+    let mut in_hunk = false;
+    let mut index = 0;
+    while index < lines.len() {
+        let line = lines[index];
+        index += 1;
+        if let (Some(old), Some(new)) = (line.strip_prefix("--- "), lines.get(index).and_then(|next| next.strip_prefix("+++ "))) {
+            index += 1;
+            let path = header_path(new);
+            if path == "/dev/null" {
+                return Err(format!("the diff deletes {}; only edits to existing files are applied", header_path(old)));
+            }
+            patches.push(FilePatch { path: path.to_string(), hunks: Vec::new() });
+            in_hunk = false;
+            continue;
+        }
+        if line.starts_with("@@") {
+            let patch = patches.last_mut().ok_or_else(|| "a hunk comes before any --- / +++ file header".to_string())?;
+            let old_start = hunk_start(line).ok_or_else(|| format!("cannot read the hunk header {}", line))?;
+            patch.hunks.push(Hunk { old_start, lines: Vec::new() });
+            in_hunk = true;
+            continue;
+        }
+        if !in_hunk {
+            continue;
+        }
+        let hunk = patches.last_mut().and_then(|patch| patch.hunks.last_mut()).expect("in a hunk");
+        let parsed = match line.chars().next() {
+            Some(' ') => Line::Context(line[1..].to_string()),
+            Some('-') => Line::Remove(line[1..].to_string()),
+            Some('+') => Line::Add(line[1..].to_string()),
+            Some('\\') => continue,
+            None => Line::Context(String::new()),
+            Some(_) => {
+                in_hunk = false;
+                continue;
+            }
+        };
+        hunk.lines.push(parsed);
+    }
+    // lost-space context lines at a hunk's end are more likely blank lines after the diff
+    for hunk in patches.iter_mut().flat_map(|patch| patch.hunks.iter_mut()) {
+        while hunk.lines.last() == Some(&Line::Context(String::new())) {
+            hunk.lines.pop();
+        }
+    }
+    patches.retain(|patch| !patch.hunks.is_empty());
+    if patches.is_empty() {
+        return Err("the answer has no diff hunks".to_string());
+    }
+    Ok(patches)
+}
+
+/// `patch` applied to `text`, and the hunks as they were applied, with
+/// their real line numbers (a diff `git apply` takes).
+pub fn apply(text: &str, patch: &FilePatch) -> Result<(String, String), String> {
+    let newline = if text.contains("\r\n") { "\r\n" } else { "\n" };
+    let lines: Vec<&str> = text.lines().collect();
+    let mut out: Vec<&str> = Vec::new();
+    let mut applied = String::new();
+    let mut cursor = 0;
+    let mut offset: isize = 0;
+    for (number, hunk) in patch.hunks.iter().enumerate() {
+        let old: Vec<&str> = hunk
+            .lines
+            .iter()
+            .filter_map(|line| match line {
+                Line::Context(text) | Line::Remove(text) => Some(text.as_str()),
+                Line::Add(_) => None,
+            })
+            .collect();
+        let expected = hunk.old_start.saturating_sub(1).clamp(cursor, lines.len());
+        let matches_at = |at: usize| at + old.len() <= lines.len() && lines[at..at + old.len()].iter().zip(&old).all(|(a, b)| a.trim_end() == b.trim_end());
+        let at = if old.is_empty() {
+            Some(expected)
+        } else {
+            // nearest the stated line first, then outwards
+            (0..=lines.len()).flat_map(|distance| [expected.checked_add(distance), expected.checked_sub(distance)]).flatten().find(|&at| {
+                at >= cursor && matches_at(at)
+            })
+        };
+        let Some(at) = at else {
+            let first = old.first().map_or(String::new(), |line| format!(" (starting \"{}\")", line.trim()));
+            return Err(format!("hunk {} for {}, at line {}{}, does not match the file", number + 1, patch.path, hunk.old_start, first));
+        };
+        out.extend(&lines[cursor..at]);
+        let new_len = hunk.lines.iter().filter(|line| !matches!(line, Line::Remove(_))).count();
+        let start = |len: usize, at: usize| if len == 0 { at } else { at + 1 };
+        let new_at = (at as isize + offset) as usize;
+        applied.push_str(&format!("@@ -{},{} +{},{} @@\n", start(old.len(), at), old.len(), start(new_len, new_at), new_len));
+        for line in &hunk.lines {
+            let (mark, text) = match line {
+                Line::Context(text) => (' ', text),
+                Line::Remove(text) => ('-', text),
+                Line::Add(text) => ('+', text),
+            };
+            applied.push_str(&format!("{}{}\n", mark, text));
+        }
+        let mut old_lines = lines[at..at + old.len()].iter();
+        for line in &hunk.lines {
+            match line {
+                // the file's own line, so whitespace the diff lost is kept
+                Line::Context(_) => out.extend(old_lines.next()),
+                Line::Remove(_) => {
+                    old_lines.next();
+                }
+                Line::Add(text) => out.push(text),
+            }
+        }
+        cursor = at + old.len();
+        offset += new_len as isize - old.len() as isize;
+    }
+    out.extend(&lines[cursor..]);
+    let mut after = out.join(newline);
+    if !after.is_empty() && (text.ends_with('\n') || text.is_empty()) {
+        after.push_str(newline);
+    }
+    Ok((after, applied))
+}
+
+/// The path among `files` a diff header names: the same path, or the only
+/// file with that name.
+fn resolve<'a>(header: &str, files: &'a [(String, String)]) -> Option<&'a str> {
+    let clean = |path: &str| path.trim_start_matches("./").to_string();
+    let header = clean(header);
+    files.iter().map(|(path, _)| path.as_str()).find(|path| clean(path) == header).or_else(|| {
+        let name = Path::new(&header).file_name()?;
+        let mut same_name = files.iter().map(|(path, _)| path.as_str()).filter(|path| Path::new(path).file_name() == Some(name));
+        let only = same_name.next()?;
+        same_name.next().is_none().then_some(only)
+    })
+}
+
+/// `diff` applied to `files` (path, text): the changed files and the diff
+/// as applied.
+fn apply_all(diff: &str, files: &[(String, String)]) -> Result<(Vec<Change>, String), String> {
+    let mut changes: Vec<Change> = Vec::new();
+    let mut applied = String::new();
+    for patch in parse(diff)? {
+        let path = resolve(&patch.path, files).ok_or_else(|| format!("the diff changes {}, which is not one of the files given", patch.path))?;
+        let index = match changes.iter().position(|change| change.path == path) {
+            Some(index) => index,
+            None => {
+                let before = files.iter().find(|(file, _)| file == path).map(|(_, text)| text.clone()).unwrap_or_default();
+                changes.push(Change { path: path.to_string(), after: before.clone(), before });
+                changes.len() - 1
+            }
+        };
+        let (after, hunks) = apply(&changes[index].after, &FilePatch { path: path.to_string(), hunks: patch.hunks })?;
+        changes[index].after = after;
+        applied.push_str(&format!("--- a/{}\n+++ b/{}\n{}", path, path, hunks));
+    }
+    changes.retain(|change| change.after != change.before);
+    Ok((changes, applied))
+}
+
+fn prompt(instruction: &str, files: &[(String, String)]) -> String {
+    let mut prompt = format!(
+        "Change the code below: {}\n\n\
+         Reply with a unified diff (as `git diff` prints it) in a single fenced code block: `--- a/<path>` and \
+         `+++ b/<path>` headers with the paths as given, then `@@` hunks with three lines of unchanged context, \
+         unchanged lines starting with a space, removed lines with `-` and added lines with `+`. Change only what \
+         the instruction asks for and only these files.",
+        instruction
+    );
+    for (path, text) in files {
+        prompt.push_str(&format!("\n\nFile: {}\n```\n{}\n```", path, text.trim_end()));
+    }
+    prompt
+}
+
+/// A diff for `instruction` over `files` (path, text) that applies, asking
+/// again up to `retries` times with what went wrong. Returns the changed
+/// files and the diff as applied.
+pub async fn generate(config: &Config, instruction: &str, files: &[(String, String)], retries: u32) -> Result<(Vec<Change>, String), XaError> {
+    let request = prompt(instruction, files);
+    let mut prompt = request.clone();
+    for attempt in 0..=retries {
+        let spinner = crate::spinner::Spinner::start(if attempt == 0 { "Writing the patch" } else { "Trying again" });
+        let answer = crate::llm::complete(config, &prompt).await;
+        spinner.stop();
+        let answer = answer?.text;
+        let diff = crate::postprocess::extract_code_block(&answer).unwrap_or(answer);
+        let error = match apply_all(&diff, files) {
+            Ok((changes, _)) if changes.is_empty() => "the diff changes nothing".to_string(),
+            Ok(done) => return Ok(done),
+            Err(error) => error,
+        };
+        if attempt == retries {
+            return Err(XaError::Other(format!("The patch does not apply after {} attempts: {}", retries + 1, error)));
+        }
+        eprintln!("{}", paint("33", &format!("The patch does not apply: {}; asking again.", error)));
+        prompt = format!("{}\n\nYour previous diff does not apply: {}.\n\nReply with a corrected diff only.", request, error);
+    }
+    unreachable!("the last attempt returns")
+}
+
+/// The diff with file headers in bold, hunk headers in cyan, removed lines
+/// in red and added ones in green.
+pub fn render(diff: &str) -> String {
+    diff.lines()
+        .map(|line| {
+            if line.starts_with("--- ") || line.starts_with("+++ ") {
+                paint("1", line)
+            } else if line.starts_with("@@") {
+                paint("36", line)
+            } else if line.starts_with('-') {
+                paint("31", line)
+            } else if line.starts_with('+') {
+                paint("32", line)
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applies_diffs_by_their_context() {
+        let text = "fn main() {\n    let x = 1;\n\n    println!(\"{}\", x);\n}\n\nfn helper() {\n    old();\n}\n";
+        let files = [("src/main.rs".to_string(), text.to_string())];
+        // wrong line numbers, a lost context space and prose around the diff
+        let diff = "Here you go:\n--- a/src/main.rs\n+++ b/src/main.rs\n@@ -20,4 +20,4 @@\n fn main() {\n-    let x = 1;\n+    let count = 1;\n\n-    println!(\"{}\", x);\n+    println!(\"{}\", count);\n@@ -8,1 +8,2 @@\n-    old();\n+    new();\n+    newer();\n\nDone.";
+        let (changes, applied) = apply_all(diff, &files).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].after, "fn main() {\n    let count = 1;\n\n    println!(\"{}\", count);\n}\n\nfn helper() {\n    new();\n    newer();\n}\n");
+        assert!(applied.starts_with("--- a/src/main.rs\n+++ b/src/main.rs\n@@ -1,4 +1,4 @@\n fn main() {\n"));
+        assert!(applied.contains("@@ -8,1 +8,2 @@\n-    old();\n+    new();\n+    newer();\n"));
+
+        let stale = "--- a/main.rs\n+++ b/main.rs\n@@ -2 +2 @@\n-    let y = 1;\n+    let y = 2;\n";
+        assert!(apply_all(stale, &files).unwrap_err().contains("hunk 1 for src/main.rs, at line 2 (starting \"let y = 1;\")"));
+        let elsewhere = "--- a/lib.rs\n+++ b/lib.rs\n@@ -1 +1 @@\n-a\n+b\n";
+        assert!(apply_all(elsewhere, &files).unwrap_err().contains("lib.rs, which is not one of the files"));
+        assert!(parse("no diff here").is_err());
+        assert!(render("@@ -1 +1 @@\n-a\n+b").contains("-a"));
+    }
+}
@@ -0,0 +1,172 @@
+//! `xa regex "<description>"`: a regular expression written by the model,
+//! then compiled and tried here. A pattern that does not compile, or that
+//! gets a marked sample wrong, goes back to the model with what failed.
+//!
+//! Sample lines (`--test <file>`) may be marked: `+ ` for lines the pattern
+//! must match and `- ` for lines it must not. Unmarked lines are only
+//! shown with their matches.
+
+use crate::config::Config;
+use crate::error::XaError;
+use crate::output::paint;
+use regex::Regex;
+
+pub struct Sample {
+    pub line: String,
+    /// Whether the line must match (`+ `) or must not (`- `).
+    pub expect: Option<bool>,
+}
+
+/// The non-blank lines of a samples file.
+pub fn parse_samples(text: &str) -> Vec<Sample> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| match (line.strip_prefix("+ "), line.strip_prefix("- ")) {
+            (Some(line), _) => Sample { line: line.to_string(), expect: Some(true) },
+            (_, Some(line)) => Sample { line: line.to_string(), expect: Some(false) },
+            _ => Sample { line: line.to_string(), expect: None },
+        })
+        .collect()
+}
+
+fn prompt(description: &str, samples: &[Sample]) -> String {
+    let mut prompt = format!(
+        "Write a regular expression that matches {}.\n\
+         It is compiled with Rust's regex crate: no lookahead, lookbehind or backreferences. Reply with the pattern \
+         only, on one line, without slashes, quotes, flags outside the pattern or code fences.",
+        description
+    );
+    let lines = |expect: Option<bool>| samples.iter().filter(|sample| sample.expect == expect).map(|sample| sample.line.as_str()).collect::<Vec<_>>();
+    for (expect, heading) in [(Some(true), "It must match these lines"), (Some(false), "It must not match these lines"), (None, "It will also be tried on these lines")] {
+        let lines = lines(expect);
+        if !lines.is_empty() {
+            prompt.push_str(&format!("\n\n{}:\n{}", heading, lines.join("\n")));
+        }
+    }
+    prompt
+}
+
+/// The pattern in the model's answer: the first line of it (or of its code
+/// block), without the `/…/` or quotes models like to add.
+fn pattern_in(answer: &str) -> String {
+    let text = crate::postprocess::extract_code_block(answer).unwrap_or_else(|| answer.to_string());
+    let line = text.lines().map(str::trim).find(|line| !line.is_empty()).unwrap_or_default();
+    let line = line.strip_prefix("regex:").or_else(|| line.strip_prefix("Regex:")).map_or(line, str::trim);
+    for (open, close) in [("/", "/"), ("`", "`"), ("\"", "\""), ("'", "'"), ("r\"", "\"")] {
+        if line.len() > open.len() + close.len() && line.starts_with(open) && line.ends_with(close) {
+            return line[open.len()..line.len() - close.len()].to_string();
+        }
+    }
+    line.to_string()
+}
+
+/// The marked samples `re` gets wrong.
+fn misses<'a>(re: &Regex, samples: &'a [Sample]) -> Vec<&'a Sample> {
+    samples.iter().filter(|sample| sample.expect.is_some_and(|expect| re.is_match(&sample.line) != expect)).collect()
+}
+
+/// A pattern for `description` that compiles and, within `retries` more
+/// attempts, gets every marked sample right. If the samples are still
+/// wrong after the last attempt, that pattern is returned anyway.
+pub async fn generate(config: &Config, description: &str, samples: &[Sample], retries: u32) -> Result<Regex, XaError> {
+    let request = prompt(description, samples);
+    let mut prompt = request.clone();
+    let mut best: Option<Regex> = None;
+    for attempt in 0..=retries {
+        let spinner = crate::spinner::Spinner::start(if attempt == 0 { "Writing the pattern" } else { "Trying again" });
+        let answer = crate::llm::complete(config, &prompt).await;
+        spinner.stop();
+        let pattern = pattern_in(&answer?.text);
+        let (notice, feedback) = match Regex::new(&pattern) {
+            Err(e) => (
+                format!("{} does not compile", pattern),
+                format!("Your previous pattern {} does not compile:\n{}", pattern, e),
+            ),
+            Ok(re) => {
+                let missed = misses(&re, samples);
+                if missed.is_empty() {
+                    return Ok(re);
+                }
+                let lines: Vec<String> = missed
+                    .iter()
+                    .map(|sample| format!("{} {}", if sample.expect == Some(true) { "should match:" } else { "should not match:" }, sample.line))
+                    .collect();
+                best = Some(re);
+                (
+                    format!("{} gets {} marked line{} wrong", pattern, missed.len(), if missed.len() == 1 { "" } else { "s" }),
+                    format!("Your previous pattern {} got these lines wrong:\n{}", pattern, lines.join("\n")),
+                )
+            }
+        };
+        if attempt < retries {
+            eprintln!("{}", paint("33", &format!("{}; asking again.", notice)));
+            prompt = format!("{}\n\n{}\n\nReply with a corrected pattern only.", request, feedback);
+        } else if let Some(re) = best.take() {
+            eprintln!("{}", paint("33", &format!("{} after {} attempts; showing it anyway.", notice, retries + 1)));
+            return Ok(re);
+        } else {
+            return Err(XaError::Other(format!("The pattern {}", feedback.trim_start_matches("Your previous pattern "))));
+        }
+    }
+    unreachable!("the last attempt returns")
+}
+
+/// The samples with their matches highlighted, each marked `✓`/`✗` when it
+/// was marked with an expectation (`●` matched, `·` not, otherwise), and a
+/// count.
+pub fn render(re: &Regex, samples: &[Sample]) -> String {
+    let mut out = String::new();
+    let mut matched = 0;
+    for sample in samples {
+        let is_match = re.is_match(&sample.line);
+        matched += usize::from(is_match);
+        let mark = match sample.expect {
+            Some(expect) if expect == is_match => paint("32", "✓"),
+            Some(_) => paint("31", "✗"),
+            None if is_match => paint("32", "●"),
+            None => paint("90", "·"),
+        };
+        let mut line = String::new();
+        let mut last = 0;
+        for found in re.find_iter(&sample.line) {
+            line.push_str(&sample.line[last..found.start()]);
+            line.push_str(&paint("1;4;32", found.as_str()));
+            last = found.end();
+        }
+        line.push_str(&sample.line[last..]);
+        out.push_str(&format!("{} {}\n", mark, line));
+    }
+    let wrong = misses(re, samples).len();
+    let mut summary = format!("{} of {} lines match", matched, samples.len());
+    if wrong > 0 {
+        summary.push_str(&format!(", {} marked line{} wrong", wrong, if wrong == 1 { "" } else { "s" }));
+    }
+    out.push_str(&paint("90", &summary));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_patterns_and_checks_samples() {
+        assert_eq!(pattern_in("```regex\n/\\d{4}-\\d{2}-\\d{2}/\n```"), r"\d{4}-\d{2}-\d{2}");
+        assert_eq!(pattern_in("`^a+$`"), "^a+$");
+        assert_eq!(pattern_in("Regex: \\bfoo\\b\nExplanation: ..."), r"\bfoo\b");
+        assert_eq!(pattern_in("/"), "/");
+
+        let samples = parse_samples("+ 2024-01-31\n- 2024-01-31T10:00\n\nnotes 1999-12-01\n");
+        assert_eq!(samples.len(), 3);
+        assert_eq!((samples[0].expect, samples[1].expect, samples[2].expect), (Some(true), Some(false), None));
+        let text = prompt("ISO dates but not times", &samples);
+        assert!(text.contains("It must match these lines:\n2024-01-31"));
+        assert!(text.contains("It must not match these lines:\n2024-01-31T10:00"));
+
+        let loose = Regex::new(r"\d{4}-\d{2}-\d{2}").unwrap();
+        assert_eq!(misses(&loose, &samples).len(), 1);
+        let strict = Regex::new(r"\d{4}-\d{2}-\d{2}\b(?:[^T]|$)").unwrap();
+        assert!(misses(&strict, &samples).is_empty());
+        assert!(render(&loose, &samples).contains("3 of 3 lines match, 1 marked line wrong"));
+    }
+}
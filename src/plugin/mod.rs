@@ -0,0 +1,150 @@
+//! Plugins: external programs run as `xa <name>`, git-style. A plugin is an
+//! `xa-<name>` executable on PATH, or a `[plugins.<name>]` entry in
+//! config.toml (which wins when both exist). Prompt commands of the same
+//! name take precedence over either.
+//!
+//! The plugin gets the words after its name as arguments and piped text on
+//! stdin. The active provider comes in the environment (`XA_BASE_URL`,
+//! `XA_API_KEY`, `XA_MODEL`), with `XA_CONFIG_DIR` and `XA_BIN` for calling
+//! back into xa. Its output and exit status pass through untouched.
+
+use crate::config::{Config, Plugin};
+use crate::error::XaError;
+use std::ffi::OsStr;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+
+const PREFIX: &str = "xa-";
+
+/// The plugin `xa <name>` runs, if there is one.
+pub fn find(config: &Config, name: &str) -> Option<Plugin> {
+    if let Some(plugin) = config.plugins.get(name) {
+        return Some(plugin.clone());
+    }
+    // Only a plain name, never a path
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return None;
+    }
+    let path = on_path(&std::env::var_os("PATH")?, name)?;
+    Some(Plugin { command: path.to_string_lossy().into_owned(), ..Plugin::default() })
+}
+
+/// Run plugin `name` with `args`, feeding it `input` on stdin when given
+/// (otherwise it shares xa's stdin).
+pub fn run(config: &Config, name: &str, plugin: &Plugin, args: &[String], input: Option<&str>) -> Result<ExitStatus, XaError> {
+    let mut command = Command::new(&plugin.command);
+    command
+        .args(&plugin.args)
+        .args(args)
+        .env("XA_PLUGIN", name)
+        .env("XA_BASE_URL", &config.base_url)
+        .env("XA_API_KEY", &config.api_key)
+        .env("XA_MODEL", config.model())
+        .envs(&plugin.env)
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::inherit() });
+    if let Some(dir) = crate::config::xa_dir() {
+        command.env("XA_CONFIG_DIR", dir);
+    }
+    if let Ok(exe) = std::env::current_exe() {
+        command.env("XA_BIN", exe);
+    }
+    tracing::debug!(plugin = name, command = %plugin.command, "running plugin");
+    let mut child = command.spawn().map_err(|e| XaError::Other(format!("cannot run plugin '{}' ({}): {}", name, plugin.command, e)))?;
+    if let (Some(text), Some(mut stdin)) = (input, child.stdin.take()) {
+        // xa trims the final newline off piped text; line-based tools want it back.
+        // A plugin that exits without reading its input is fine.
+        if let Err(e) = stdin.write_all(format!("{}\n", text).as_bytes()) {
+            if e.kind() != std::io::ErrorKind::BrokenPipe {
+                return Err(XaError::Other(format!("cannot send input to plugin '{}': {}", name, e)));
+            }
+        }
+    }
+    child.wait().map_err(|e| XaError::Other(format!("plugin '{}' failed: {}", name, e)))
+}
+
+/// `xa ls plugins`: configured plugins, then those found on PATH.
+pub fn list_plugins(config: &Config) {
+    let mut found = Vec::new();
+    for (name, plugin) in &config.plugins {
+        found.push((name.clone(), plugin.description.clone().unwrap_or_else(|| plugin.command.clone())));
+    }
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    for (name, path) in all_on_path(&path) {
+        if !found.iter().any(|(known, _)| *known == name) {
+            found.push((name, path.display().to_string()));
+        }
+    }
+    if found.is_empty() {
+        println!("No plugins found. Put an xa-<name> executable on PATH or add a [plugins.<name>] table to config.toml.");
+        return;
+    }
+    let width = found.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    for (name, about) in found {
+        println!("{:width$}  {}", name, about, width = width);
+    }
+}
+
+/// The first `xa-<name>` executable in the directories of `path`.
+fn on_path(path: &OsStr, name: &str) -> Option<PathBuf> {
+    let file = format!("{}{}{}", PREFIX, name, std::env::consts::EXE_SUFFIX);
+    std::env::split_paths(path).map(|dir| dir.join(&file)).find(|candidate| is_executable(candidate))
+}
+
+/// Every `xa-<name>` executable in the directories of `path`, by name,
+/// earlier directories winning.
+fn all_on_path(path: &OsStr) -> Vec<(String, PathBuf)> {
+    let mut found: Vec<(String, PathBuf)> = Vec::new();
+    for dir in std::env::split_paths(path) {
+        let Ok(entries) = std::fs::read_dir(&dir) else { continue };
+        for entry in entries.flatten() {
+            let file = entry.file_name().to_string_lossy().into_owned();
+            let Some(name) = file.strip_prefix(PREFIX).and_then(|rest| rest.strip_suffix(std::env::consts::EXE_SUFFIX)) else {
+                continue;
+            };
+            if !name.is_empty() && is_executable(&entry.path()) && !found.iter().any(|(known, _)| known == name) {
+                found.push((name.to_string(), entry.path()));
+            }
+        }
+    }
+    found.sort();
+    found
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata().is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_executables_on_path() {
+        let dir = std::env::temp_dir().join(format!("xa-plugin-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let plugin = dir.join(format!("xa-jira{}", std::env::consts::EXE_SUFFIX));
+        std::fs::write(&plugin, "#!/bin/sh\n").unwrap();
+        std::fs::write(dir.join("xa-notes.txt"), "").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&plugin, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        let path = std::env::join_paths([dir.join("missing"), dir.clone()]).unwrap();
+        let jira = on_path(&path, "jira");
+        let all = all_on_path(&path);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(jira, Some(plugin.clone()));
+        assert_eq!(all, [("jira".to_string(), plugin)]);
+        assert_eq!(on_path(&path, "github"), None);
+    }
+}
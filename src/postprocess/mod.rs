@@ -0,0 +1,130 @@
+//! Per-command cleanup of model output, applied before the result is copied
+//! to the clipboard or rendered. Models like to wrap answers in markdown
+//! fences or quotes even when asked not to; these rules undo that.
+
+use serde::{Deserialize, Serialize};
+
+/// Optional `postprocess` table on a prompt entry, e.g.
+///
+/// ```toml
+/// [prompts.shell.postprocess]
+/// extract_code_block = true
+/// ```
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct PostProcess {
+    /// Keep only the body of the first fenced code block, if there is one.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub extract_code_block: bool,
+    /// Keep only the regex matches (capture group 1 when the pattern has one),
+    /// one per line. The text is left untouched when nothing matches.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regex_extract: Option<String>,
+    /// Remove one pair of surrounding quotes or backticks.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub strip_quotes: bool,
+}
+
+impl PostProcess {
+    /// Apply the enabled rules in a fixed order: code block, regex, quotes.
+    pub fn apply(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        if self.extract_code_block {
+            if let Some(code) = extract_code_block(&text) {
+                text = code;
+            }
+        }
+        if let Some(pattern) = &self.regex_extract {
+            // Invalid patterns are reported by `xa prompts lint`; here they
+            // simply leave the text as it is.
+            if let Ok(re) = regex::Regex::new(pattern) {
+                if let Some(extracted) = regex_extract(&re, &text) {
+                    text = extracted;
+                }
+            }
+        }
+        if self.strip_quotes {
+            text = strip_quotes(&text);
+        }
+        text
+    }
+
+    /// Configuration problems, reported alongside template problems.
+    pub fn validate(&self) -> Vec<String> {
+        match &self.regex_extract {
+            Some(pattern) => match regex::Regex::new(pattern) {
+                Ok(_) => Vec::new(),
+                Err(e) => vec![format!("postprocess.regex_extract is not a valid regex: {}", e)],
+            },
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Body of the first fenced (```) code block, without the fence lines.
+pub fn extract_code_block(text: &str) -> Option<String> {
+    let mut body: Option<Vec<&str>> = None;
+    for line in text.lines() {
+        let is_fence = line.trim_start().starts_with("```");
+        match body.as_mut() {
+            None if is_fence => body = Some(Vec::new()),
+            None => {}
+            Some(lines) if is_fence => return Some(lines.join("\n")),
+            Some(lines) => lines.push(line),
+        }
+    }
+    // An unterminated fence (e.g. a truncated answer) still yields its body.
+    body.map(|lines| lines.join("\n"))
+}
+
+fn regex_extract(re: &regex::Regex, text: &str) -> Option<String> {
+    let matches: Vec<&str> = re
+        .captures_iter(text)
+        .filter_map(|caps| caps.get(1).or_else(|| caps.get(0)))
+        .map(|m| m.as_str())
+        .collect();
+    if matches.is_empty() {
+        None
+    } else {
+        Some(matches.join("\n"))
+    }
+}
+
+fn strip_quotes(text: &str) -> String {
+    let trimmed = text.trim();
+    for (open, close) in [('"', '"'), ('\'', '\''), ('`', '`'), ('“', '”'), ('‘', '’'), ('「', '」')] {
+        if let Some(inner) = trimmed.strip_prefix(open).and_then(|rest| rest.strip_suffix(close)) {
+            return inner.to_string();
+        }
+    }
+    trimmed.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_first_fenced_block() {
+        let text = "Here you go:\n```bash\nls -la\necho hi\n```\nand another\n```\nx\n```";
+        assert_eq!(extract_code_block(text).as_deref(), Some("ls -la\necho hi"));
+        assert_eq!(extract_code_block("no fences"), None);
+    }
+
+    #[test]
+    fn rules_apply_in_order() {
+        let rules = PostProcess {
+            extract_code_block: true,
+            regex_extract: Some(r"name: (\w+)".into()),
+            strip_quotes: true,
+        };
+        assert_eq!(rules.apply("```\nname: alpha\nname: beta\n```"), "alpha\nbeta");
+        assert_eq!(rules.apply("\"quoted\""), "quoted");
+    }
+
+    #[test]
+    fn invalid_regex_is_reported() {
+        let rules = PostProcess { regex_extract: Some("(".into()), ..Default::default() };
+        assert_eq!(rules.validate().len(), 1);
+        assert_eq!(rules.apply("text"), "text");
+    }
+}
@@ -0,0 +1,158 @@
+//! Per-project overlay: the nearest `.xa.toml` found by walking up from the
+//! working directory, merged over the global config and prompts.
+//!
+//! A project file may pick the model, one of the user's own providers, the
+//! one-shot behaviour flags and add or override prompt commands. It may not
+//! set `base_url`, `api_key` or `api_key_cmd`: those would let a cloned
+//! repository send the user's key elsewhere or run commands on load. For the
+//! same reason its commands may not have a `pre_hook` or `post_hook`.
+
+use crate::config::Config;
+use crate::error::XaError;
+use crate::prompt::PromptEntry;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const PROJECT_FILE: &str = ".xa.toml";
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectConfig {
+    /// Model for this project (`default_model` is accepted too).
+    #[serde(default, alias = "default_model")]
+    pub model: Option<String>,
+    /// Name of a provider from providers.toml (`xa login <name>`) whose
+    /// endpoint, key, model and rate limits to use in this project.
+    #[serde(default)]
+    pub provider: Option<String>,
+    #[serde(default)]
+    pub fallback_to_ask: Option<bool>,
+    #[serde(default)]
+    pub confirm_fuzzy: Option<bool>,
+    #[serde(default)]
+    pub no_clipboard: Option<bool>,
+    /// Project-local commands; same format as prompts.toml, and an entry
+    /// with a global command's name replaces it here.
+    #[serde(default)]
+    pub prompts: HashMap<String, PromptEntry>,
+    #[serde(skip)]
+    pub path: PathBuf,
+}
+
+/// The nearest `.xa.toml` in `start` or one of its ancestors.
+pub fn find_from(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(PROJECT_FILE))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Load the overlay for the current directory, if there is one.
+pub fn load() -> Result<Option<ProjectConfig>, XaError> {
+    let Some(path) = std::env::current_dir().ok().and_then(|cwd| find_from(&cwd)) else {
+        return Ok(None);
+    };
+    load_from(&path).map(Some)
+}
+
+fn load_from(path: &Path) -> Result<ProjectConfig, XaError> {
+    let content = fs::read_to_string(path)?;
+    let mut project: ProjectConfig = toml::from_str(&content).map_err(|e| {
+        let hint = if ["base_url", "api_key"].iter().any(|key| e.message().contains(&format!("`{}", key))) {
+            "\nbase_url, api_key and api_key_cmd can only be set in the global config.toml"
+        } else {
+            ""
+        };
+        XaError::Config(format!("{}: {}{}", path.display(), e, hint))
+    })?;
+    let mut hooked: Vec<&String> =
+        project.prompts.iter().filter(|(_, entry)| entry.pre_hook.is_some() || entry.post_hook.is_some()).map(|(name, _)| name).collect();
+    if !hooked.is_empty() {
+        hooked.sort();
+        return Err(XaError::Config(format!(
+            "{}: [prompts.{}] has a pre_hook or post_hook; hooks can only be set in the global prompts.toml",
+            path.display(),
+            hooked[0]
+        )));
+    }
+    project.path = path.to_path_buf();
+    Ok(project)
+}
+
+impl ProjectConfig {
+    /// Merge the overlay's settings into `config`.
+    pub fn apply(&self, config: &mut Config) -> Result<(), XaError> {
+        if let Some(name) = &self.provider {
+            let providers = crate::agent::ProvidersConfig::load();
+            let provider = providers.providers.get(name).ok_or_else(|| {
+                XaError::Config(format!(
+                    "{}: provider '{}' is not configured (add it with `xa login {}`)",
+                    self.path.display(),
+                    name,
+                    name
+                ))
+            })?;
+            config.base_url = provider.endpoint.clone();
+            config.api_key = provider.api_key.clone();
+            if !provider.model.is_empty() {
+                config.default_model = Some(provider.model.clone());
+            }
+            config.requests_per_minute = provider.requests_per_minute.or(config.requests_per_minute);
+            config.tokens_per_minute = provider.tokens_per_minute.or(config.tokens_per_minute);
+        }
+        if let Some(model) = &self.model {
+            config.default_model = Some(model.clone());
+        }
+        if let Some(flag) = self.fallback_to_ask {
+            config.fallback_to_ask = flag;
+        }
+        if let Some(flag) = self.confirm_fuzzy {
+            config.confirm_fuzzy = flag;
+        }
+        if let Some(flag) = self.no_clipboard {
+            config.no_clipboard = flag;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_nearest_file_and_overrides_settings() {
+        let root = std::env::temp_dir().join(format!("xa-project-test-{}", std::process::id()));
+        let nested = root.join("src").join("deep");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(
+            root.join(PROJECT_FILE),
+            "model = \"gpt-4o\"\nconfirm_fuzzy = true\n\n[prompts.review]\ntemplate = \"Review {input}\"\n",
+        )
+        .unwrap();
+
+        let path = find_from(&nested).unwrap();
+        let project = load_from(&path).unwrap();
+        let mut config = Config::default();
+        project.apply(&mut config).unwrap();
+        fs::write(root.join(PROJECT_FILE), "[prompts.review]\ntemplate = \"{input}\"\npost_hook = \"sh evil.sh\"\n").unwrap();
+        let hooked = load_from(&path).err().map(|e| e.to_string());
+        let _ = fs::remove_dir_all(&root);
+
+        assert_eq!(path, root.join(PROJECT_FILE));
+        assert_eq!(config.default_model.as_deref(), Some("gpt-4o"));
+        assert!(config.confirm_fuzzy);
+        assert!(!config.no_clipboard);
+        assert_eq!(project.prompts["review"].template, "Review {input}");
+        assert!(hooked.is_some_and(|e| e.contains("hooks can only be set in the global prompts.toml")));
+    }
+
+    #[test]
+    fn rejects_credentials() {
+        let error = toml::from_str::<ProjectConfig>("base_url = \"https://evil.example\"").err().unwrap();
+        assert!(error.to_string().contains("base_url"));
+        assert!(toml::from_str::<ProjectConfig>("api_key_cmd = \"curl evil\"").is_err());
+    }
+}
@@ -1,13 +1,23 @@
-use dirs::config_dir;
+use crate::output::CodeBlock;
+use crate::chunk::ChunkStrategy;
+use crate::postprocess::PostProcess;
+use crate::config::{lock, safe_write, xa_dir, Config};
+use crate::error::XaError;
 use fuzzy_matcher::FuzzyMatcher;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::io::{self, Write};
+use std::path::Path;
+use toml_edit::{DocumentMut, Item, Table};
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct PromptConfig {
     pub prompts: HashMap<String, PromptEntry>,
+    /// Entries removed with `xa rm`, kept until restored with
+    /// `xa prompts restore <name>`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub trash: HashMap<String, PromptEntry>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -17,11 +27,124 @@ pub struct PromptArg {
     pub description: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct PromptEntry {
+    /// Unused (and may be omitted) when the entry is a `pipeline`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub template: String,
     pub description: Option<String>,
     pub args: Option<Vec<PromptArg>>,
+    /// Run other commands in sequence instead of a template, each step's
+    /// result becoming the next step's `{input}`. Steps use the same syntax
+    /// as `xa pipe`: `"translate fr"` or `"translate:fr"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pipeline: Option<Vec<String>>,
+    /// Cleanup rules applied to the model's answer before it is copied and
+    /// rendered (see [`crate::postprocess::PostProcess`]).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub postprocess: Option<PostProcess>,
+    /// Model to use for this command instead of the configured default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Headers added to this command's requests, over config.toml's.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extra_headers: BTreeMap<String, String>,
+    /// Request body fields for this command (`temperature = 0.2`), merged
+    /// over config.toml's `extra_body`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extra_body: BTreeMap<String, serde_json::Value>,
+    /// Sequences the model stops generating at (sent as `stop`), e.g.
+    /// "```" to end an extraction before any commentary.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+    /// Start of the answer, sent as an assistant message for the model to
+    /// continue (e.g. `{` to force JSON) and kept at the front of the result.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assistant_prefix: Option<String>,
+    /// Show the result as a word diff against the input, as with `--diff`
+    /// (for editing commands like `polish`).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub show_diff: bool,
+    /// The answer is JSON corrections to the input (`span`, `replacement`,
+    /// `reason`), shown as the input with each fix marked and the corrected
+    /// text copied; see the built-in `grammar`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub show_corrections: bool,
+    /// The answer is meeting minutes as JSON (`summary`, `decisions`,
+    /// `action_items` with `owner`, `task`, `due`), shown as Markdown with
+    /// the action items in a table; see the built-in `minutes`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub show_minutes: bool,
+    /// Copy only the `first` or `last` fenced code block of the answer, as
+    /// with `--copy-code`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub copy_code: Option<CodeBlock>,
+    /// The input is a pasted email or thread: it is split into its
+    /// messages, numbered newest first (see [`crate::email`]).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub email_thread: bool,
+    /// Append config.toml's `email_signature` to the answer.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub signed: bool,
+    /// Shell command the input is piped through before it fills the
+    /// template; its output becomes the input (see [`crate::hooks`]).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_hook: Option<String>,
+    /// Shell command the answer is piped through after `postprocess`; its
+    /// output becomes the result.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_hook: Option<String>,
+    /// What to do when the filled prompt is too long for the model:
+    /// `error` (the default), `truncate` or `map_reduce` (see
+    /// [`crate::chunk`]).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk_strategy: Option<ChunkStrategy>,
+}
+
+impl PromptEntry {
+    /// `input` as this command sends it: passed through `pre_hook`, then
+    /// split into messages for an `email_thread` command.
+    pub async fn prepare_input(&self, input: &str) -> Result<String, XaError> {
+        let input = match &self.pre_hook {
+            Some(hook) => crate::hooks::run("pre_hook", hook, input).await?,
+            None => input.to_string(),
+        };
+        Ok(if self.email_thread { crate::email::render_thread(&crate::email::parse_thread(&input)) } else { input })
+    }
+
+    /// The model's `answer` as this command returns it: cleaned up by
+    /// `postprocess`, signed for a `signed` command, then passed through
+    /// `post_hook`.
+    pub async fn finish_output(&self, answer: &str) -> Result<String, XaError> {
+        let mut text = match &self.postprocess {
+            Some(rules) => rules.apply(answer),
+            None => answer.to_string(),
+        };
+        if self.signed {
+            if let Some(signature) = crate::config::load_config_sync().ok().and_then(|config| config.email_signature).filter(|signature| !signature.trim().is_empty()) {
+                text = format!("{}\n\n{}", text.trim_end(), signature);
+            }
+        }
+        match &self.post_hook {
+            Some(hook) => crate::hooks::run("post_hook", hook, &text).await,
+            None => Ok(text),
+        }
+    }
+
+    /// The template filled with `input` and `args`, fitted to `config`'s
+    /// model by `chunk_strategy`. `{last}` becomes the previous result.
+    pub async fn fill_prompt(&self, config: &Config, input: &str, args: &[String]) -> Result<String, XaError> {
+        let last = match self.template.contains("{last}") {
+            true => Some(last_result().ok_or_else(|| XaError::Usage("The template uses {last}, but there is no previous result yet.".into()))?),
+            false => None,
+        };
+        crate::chunk::fill(config, self, input, args, last.as_deref()).await
+    }
+
+    /// Whether [`PromptEntry::finish_output`] may change the answer.
+    pub fn rewrites_output(&self) -> bool {
+        self.postprocess.is_some() || self.post_hook.is_some() || self.signed
+    }
 }
 
 impl Default for PromptConfig {
@@ -29,14 +152,15 @@ impl Default for PromptConfig {
         let mut prompts = HashMap::new();
         prompts.insert("translate".to_string(), PromptEntry {
             template: "You are a professional translator, please translate the following text into natural, idiomatic {target_lang}:\n\n{input}. Avoid output anything else except the final result.".to_string(),
-            description: Some("Translate text (default target: zh)".to_string()),
+            description: Some(TRANSLATE_DESCRIPTION.to_string()),
             args: Some(vec![
                 PromptArg {
                     name: "target_lang".to_string(),
-                    default_value: "zh".to_string(),
+                    default_value: AUTO_TARGET_LANG.to_string(),
                     description: Some("Target language for translation".to_string()),
                 }
             ]),
+            ..Default::default()
         });
         prompts.insert("polish".to_string(), PromptEntry {
             template: "You are an expert editor. Please polish the following text to make it more clear, concise, and natural in a {tone} tone:\n\n{input}. Avoid output anything else except the final result.".to_string(),
@@ -48,6 +172,20 @@ impl Default for PromptConfig {
                     description: Some("Tone for polishing (e.g., casual, professional, friendly)".to_string()),
                 }
             ]),
+            ..Default::default()
+        });
+        prompts.insert("grammar".to_string(), PromptEntry {
+            template: "You are a careful {language} proofreader. Find the grammar, spelling, punctuation and word-choice mistakes in the text below. Do not rewrite for style, and leave correct text alone.\n\nReply with JSON only, in this form:\n{\"corrections\": [{\"span\": \"the exact wrong text, copied from the input\", \"replacement\": \"the corrected text\", \"reason\": \"a short explanation of the rule\"}]}\nList the corrections in the order they appear, keep each span as short as possible, and reply with {\"corrections\": []} when there is nothing to fix.\n\n{input}".to_string(),
+            description: Some("Check grammar and explain each correction".to_string()),
+            args: Some(vec![
+                PromptArg {
+                    name: "language".to_string(),
+                    default_value: "English".to_string(),
+                    description: Some("Language of the text".to_string()),
+                }
+            ]),
+            show_corrections: true,
+            ..Default::default()
         });
         prompts.insert("rewrite".to_string(), PromptEntry {
             template: "You are a skilled writer. Please rewrite the following text in a {style} style while preserving the meaning:\n\n{input}. Avoid output anything else except the final result.".to_string(),
@@ -59,6 +197,7 @@ impl Default for PromptConfig {
                     description: Some("Writing style for rewrite (e.g., casual, formal, creative)".to_string()),
                 }
             ]),
+            ..Default::default()
         });
         prompts.insert("summarize".to_string(), PromptEntry {
             template: "You are an expert summarizer. Please provide a concise summary of the following text with a {length} length:\n\n{input}. Avoid output anything else except the final result.".to_string(),
@@ -70,64 +209,185 @@ impl Default for PromptConfig {
                     description: Some("Summary length (e.g., short, medium, long)".to_string()),
                 }
             ]),
+            chunk_strategy: Some(ChunkStrategy::MapReduce),
+            ..Default::default()
+        });
+        prompts.insert("reply".to_string(), PromptEntry {
+            template: "Write a reply to the newest message ([1]) of the email thread below, as the person it was sent to, in a {tone} tone and in {language}. Use the earlier messages for context only. Answer every question and request in [1]; do not invent facts, dates, prices or commitments, and write a [placeholder] for anything only the sender of the reply would know. Reply with the email body only: greeting, message and closing line, without a subject line, a signature or a name.\n\n{input}".to_string(),
+            description: Some("Reply to a pasted email or thread".to_string()),
+            args: Some(vec![
+                PromptArg {
+                    name: "tone".to_string(),
+                    default_value: "friendly but professional".to_string(),
+                    description: Some("Tone of the reply (e.g., formal, warm, brief, firm)".to_string()),
+                },
+                PromptArg {
+                    name: "language".to_string(),
+                    default_value: "the language of the newest message".to_string(),
+                    description: Some("Language to write the reply in".to_string()),
+                },
+            ]),
+            email_thread: true,
+            signed: true,
+            ..Default::default()
+        });
+        prompts.insert("minutes".to_string(), PromptEntry {
+            template: "You are taking the minutes of a meeting. From the transcript or notes below, write a short summary of what was discussed, the decisions made, and every action item: a task someone agreed to do or was given, with its owner (the person as named in the transcript, or null) and its due date as stated (\"Friday\", \"2026-03-10\", or null). Do not invent owners, dates or tasks. Write in the language of the transcript.\n\nReply with JSON only, in this form:\n{\"summary\": \"...\", \"decisions\": [\"...\"], \"action_items\": [{\"owner\": \"...\", \"task\": \"...\", \"due\": \"...\"}]}\n\n{input}".to_string(),
+            description: Some("Meeting minutes with action items from a transcript".to_string()),
+            extra_body: BTreeMap::from([("response_format".to_string(), serde_json::json!({ "type": "json_object" }))]),
+            show_minutes: true,
+            ..Default::default()
+        });
+        prompts.insert("commit-message".to_string(), PromptEntry {
+            template: "Write a git commit message for the staged changes below: a subject line of at most 72 characters in the imperative mood, then a blank line and a short body saying what changed and why, unless the subject says it all. Reply with the message only.\n\n{input}".to_string(),
+            description: Some("Draft a commit message from a diff (used by xa commit)".to_string()),
+            postprocess: Some(PostProcess { extract_code_block: true, ..Default::default() }),
+            ..Default::default()
+        });
+        prompts.insert("pr-description".to_string(), PromptEntry {
+            template: "Write a pull request title and description for the branch below, from its commits and diff. Reply in exactly this form:\n\nTitle: <one line>\n\n## Summary\n<what the change does and why, in one to three sentences>\n\n## Changes\n- <one bullet per notable change>\n\n## Testing\n<how the change was or can be verified>\n\n{input}".to_string(),
+            description: Some("Draft a pull request title and description (used by xa pr)".to_string()),
+            ..Default::default()
+        });
+        prompts.insert("changelog".to_string(), PromptEntry {
+            template: "Write the release notes for the commits below as one Keep a Changelog entry in Markdown. Start with the heading line given, then a ### heading for each of Added, Changed, Deprecated, Removed, Fixed and Security that has changes, in that order, with one bullet per change. Write the bullets for users, not from the commit subjects word for word: merge commits that make up one change, leave out the ones users would not notice (most of the Other group; put any that matter in the right section), and start breaking changes with **Breaking:**. Reply with the entry only.\n\n{input}".to_string(),
+            description: Some("Draft release notes from commits (used by xa changelog)".to_string()),
+            postprocess: Some(PostProcess { extract_code_block: true, ..Default::default() }),
+            ..Default::default()
         });
         prompts.insert(
             "ask".to_string(),
             PromptEntry {
-                template:
-                    "You are a helpful assistant called xa, execute anything by your side. {input}"
-                        .to_string(),
+                // The system text comes from the persona (see `[personas]`)
+                template: "{input}".to_string(),
                 description: Some("Interactive conversation mode".to_string()),
                 args: None,
+                ..Default::default()
             },
         );
 
-        PromptConfig { prompts }
+        PromptConfig { prompts, trash: HashMap::new() }
     }
 }
 
-pub async fn list_commands() -> Result<(), Box<dyn std::error::Error>> {
+/// List built-in and user-defined commands. `pattern` keeps only commands
+/// whose name or description contains it (case-insensitive); `verbose` adds
+/// each command's template, arguments and overrides.
+pub async fn list_commands(pattern: Option<&str>, verbose: bool) -> Result<(), XaError> {
     // Get config directory
-    let config_dir = config_dir()
-        .ok_or("Could not determine config directory")?
-        .join("xa");
+    let config_dir = xa_dir()
+        .ok_or_else(|| XaError::Config("Could not determine config directory".into()))?;
 
     let prompt_config_file = config_dir.join("prompts.toml");
 
-    let prompt_config = if prompt_config_file.exists() {
+    let mut prompt_config = if prompt_config_file.exists() {
         let content = fs::read_to_string(&prompt_config_file)?;
         toml::from_str(&content)?
     } else {
         PromptConfig::default()
     };
+    upgrade_legacy_defaults(&mut prompt_config);
+    let project = crate::project::load()?;
+    if let Some(project) = &project {
+        prompt_config.prompts.extend(project.prompts.clone());
+    }
 
-    println!("Built-in commands:");
-    println!("  set: Configure API settings (use: xa set openai)");
-    println!("  ls: List all commands (this command)");
-    println!("  ls prompts: List all prompt templates");
-    println!("  ls stores: List all stored secrets");
-    println!("  add: Add a new command/prompt (use: xa add)");
-    println!("  add <secret> <note>: Add a secret with auto tag");
-    println!("  search <query>: Search secrets by natural language");
-    println!();
-    println!("User-defined commands:");
+    if pattern.is_none() {
+        println!("Built-in commands:");
+        println!("  set: Configure API settings (use: xa set openai)");
+        println!("  ls: List all commands (this command)");
+        println!("  ls prompts: List all prompt templates");
+        println!("  ls stores: List all stored secrets");
+        println!("  ls <pattern>: List commands matching a name or description");
+        println!("  add: Add a new command/prompt (use: xa add)");
+        println!("  add <secret> <note>: Add a secret with auto tag");
+        println!("  search <query>: Search secrets by natural language");
+        println!();
+    }
+
+    let needle = pattern.map(str::to_lowercase);
+    let mut matches: Vec<(&String, &PromptEntry)> = prompt_config
+        .prompts
+        .iter()
+        .filter(|(name, entry)| match &needle {
+            Some(needle) => {
+                name.to_lowercase().contains(needle)
+                    || entry.description.as_deref().is_some_and(|d| d.to_lowercase().contains(needle))
+            }
+            None => true,
+        })
+        .collect();
+    matches.sort_by_key(|(name, _)| *name);
+
+    match pattern {
+        Some(pattern) if matches.is_empty() => {
+            println!("No commands match '{}'.", pattern);
+            return Ok(());
+        }
+        Some(pattern) => println!("Commands matching '{}':", pattern),
+        None => println!("User-defined commands:"),
+    }
 
-    for (name, entry) in &prompt_config.prompts {
+    let width = matches.iter().map(|(name, _)| name.chars().count()).max().unwrap_or(0);
+    for (name, entry) in matches {
         let description = entry
             .description
             .as_deref()
             .unwrap_or("Custom prompt command");
-        println!("  {}: {}", name, description);
+        let origin = if project.as_ref().is_some_and(|p| p.prompts.contains_key(name)) { "  [project]" } else { "" };
+        println!("  {:<width$}  {}{}", name, description, origin, width = width);
+        if verbose {
+            print_command_details(entry, width + 4);
+        }
     }
 
     Ok(())
 }
 
-pub async fn list_prompts() -> Result<(), Box<dyn std::error::Error>> {
+/// The `--verbose` part of `xa ls`, indented under the command's name.
+fn print_command_details(entry: &PromptEntry, indent: usize) {
+    let pad = " ".repeat(indent);
+    match &entry.pipeline {
+        Some(steps) => println!("{}pipeline: {}", pad, steps.join(" -> ")),
+        None => {
+            let mut lines = entry.template.lines();
+            println!("{}template: {}", pad, lines.next().unwrap_or(""));
+            for line in lines {
+                println!("{}          {}", pad, line);
+            }
+        }
+    }
+    for arg in entry.args.iter().flatten() {
+        match &arg.description {
+            Some(description) => println!("{}arg {} = {:?}  ({})", pad, arg.name, arg.default_value, description),
+            None => println!("{}arg {} = {:?}", pad, arg.name, arg.default_value),
+        }
+    }
+    if let Some(model) = &entry.model {
+        println!("{}model: {}", pad, model);
+    }
+    if entry.show_diff {
+        println!("{}show_diff: true", pad);
+    }
+    if entry.show_corrections {
+        println!("{}show_corrections: true", pad);
+    }
+    if let Some(which) = entry.copy_code {
+        println!("{}copy_code: {}", pad, if which == CodeBlock::First { "first" } else { "last" });
+    }
+    if let Some(hook) = &entry.pre_hook {
+        println!("{}pre_hook: {}", pad, hook);
+    }
+    if let Some(hook) = &entry.post_hook {
+        println!("{}post_hook: {}", pad, hook);
+    }
+    println!();
+}
+
+pub async fn list_prompts() -> Result<(), XaError> {
     // Get config directory
-    let config_dir = config_dir()
-        .ok_or("Could not determine config directory")?
-        .join("xa");
+    let config_dir = xa_dir()
+        .ok_or_else(|| XaError::Config("Could not determine config directory".into()))?;
 
     let prompt_config_file = config_dir.join("prompts.toml");
 
@@ -142,7 +402,10 @@ pub async fn list_prompts() -> Result<(), Box<dyn std::error::Error>> {
     println!("Config directory: {:?}", config_dir);
     println!();
 
-    for (name, entry) in &prompt_config.prompts {
+    let mut names: Vec<&String> = prompt_config.prompts.keys().collect();
+    names.sort();
+    for name in names {
+        let entry = &prompt_config.prompts[name];
         println!("[{}]", name);
         if let Some(desc) = &entry.description {
             println!("  Description: {}", desc);
@@ -154,20 +417,25 @@ pub async fn list_prompts() -> Result<(), Box<dyn std::error::Error>> {
                 println!("    -- {}: {}{}", arg.name, arg.description.as_deref().unwrap_or("No description"), default_info);
             }
         }
-        println!("  Template: {}", entry.template.replace('\n', "\\n"));
+        match &entry.pipeline {
+            Some(steps) => println!("  Pipeline: {}", steps.join(" -> ")),
+            None => println!("  Template: {}", entry.template.replace('\n', "\\n")),
+        }
+        if let Some(model) = &entry.model {
+            println!("  Model: {}", model);
+        }
         println!();
     }
 
     Ok(())
 }
 
-pub async fn add_command() -> Result<(), Box<dyn std::error::Error>> {
+pub async fn add_command() -> Result<(), XaError> {
     println!("Adding a new command...");
 
     // Get config directory
-    let config_dir = config_dir()
-        .ok_or("Could not determine config directory")?
-        .join("xa");
+    let config_dir = xa_dir()
+        .ok_or_else(|| XaError::Config("Could not determine config directory".into()))?;
 
     // Create config directory if it doesn't exist
     fs::create_dir_all(&config_dir)?;
@@ -233,51 +501,7 @@ pub async fn add_command() -> Result<(), Box<dyn std::error::Error>> {
     io::stdin().read_line(&mut add_args_input)?;
     let add_args = add_args_input.trim().to_lowercase() == "y" || add_args_input.trim().to_lowercase() == "yes";
 
-    let mut args: Option<Vec<PromptArg>> = None;
-    if add_args {
-        let mut prompt_args = Vec::new();
-
-        loop {
-            print!("Enter argument name (or press Enter to finish): ");
-            io::stdout().flush()?;
-            let mut arg_name = String::new();
-            io::stdin().read_line(&mut arg_name)?;
-            let arg_name = arg_name.trim().to_string();
-
-            if arg_name.is_empty() {
-                break;
-            }
-
-            print!("Enter default value for '{}': ", arg_name);
-            io::stdout().flush()?;
-            let mut default_value = String::new();
-            io::stdin().read_line(&mut default_value)?;
-            let default_value = default_value.trim().to_string();
-
-            print!("Enter description for '{}' (optional): ", arg_name);
-            io::stdout().flush()?;
-            let mut arg_description = String::new();
-            io::stdin().read_line(&mut arg_description)?;
-            let arg_description = arg_description.trim().to_string();
-            let arg_description = if arg_description.is_empty() {
-                None
-            } else {
-                Some(arg_description)
-            };
-
-            prompt_args.push(PromptArg {
-                name: arg_name,
-                default_value,
-                description: arg_description,
-            });
-
-            println!("Added argument: {}", prompt_args.last().unwrap().name);
-        }
-
-        if !prompt_args.is_empty() {
-            args = Some(prompt_args);
-        }
-    }
+    let args = if add_args { read_prompt_args(Vec::new())? } else { None };
 
     // Add the new command
     prompt_config.prompts.insert(
@@ -286,12 +510,12 @@ pub async fn add_command() -> Result<(), Box<dyn std::error::Error>> {
             template,
             description,
             args,
+            ..Default::default()
         },
     );
 
-    // Save the updated prompts
-    let content = toml::to_string(&prompt_config)?;
-    fs::write(&prompt_config_file, content)?;
+    // Save the new command, leaving the rest of the file as the user wrote it
+    save_prompt_entry(&prompt_config_file, &name, &prompt_config.prompts[&name])?;
 
     println!("Command '{}' added successfully!", name);
     println!("Prompt file location: {:?}", prompt_config_file);
@@ -302,11 +526,10 @@ pub async fn add_command() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-pub async fn remove_command(command_name: &str) -> Result<(), Box<dyn std::error::Error>> {
+pub async fn remove_command(command_name: &str, assume_yes: bool) -> Result<(), XaError> {
     // Get config directory
-    let config_dir = config_dir()
-        .ok_or("Could not determine config directory")?
-        .join("xa");
+    let config_dir = xa_dir()
+        .ok_or_else(|| XaError::Config("Could not determine config directory".into()))?;
 
     let prompt_config_file = config_dir.join("prompts.toml");
 
@@ -316,7 +539,7 @@ pub async fn remove_command(command_name: &str) -> Result<(), Box<dyn std::error
     }
 
     // Load existing prompts
-    let mut prompt_config: PromptConfig = {
+    let prompt_config: PromptConfig = {
         let content = fs::read_to_string(&prompt_config_file)?;
         toml::from_str(&content)?
     };
@@ -336,73 +559,711 @@ pub async fn remove_command(command_name: &str) -> Result<(), Box<dyn std::error
         return Ok(());
     }
 
-    // Remove the command
-    prompt_config.prompts.remove(command_name);
+    if !assume_yes {
+        print!("Remove command '{}'? (y/N): ", command_name);
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        let answer = answer.trim().to_lowercase();
+        if answer != "y" && answer != "yes" {
+            println!("Aborted.");
+            return Ok(());
+        }
+    }
 
-    // Save the updated prompts
-    let content = toml::to_string(&prompt_config)?;
-    fs::write(&prompt_config_file, content)?;
+    // Move the command to the trash rather than deleting it outright
+    move_prompt_entry(&prompt_config_file, "prompts", "trash", command_name)?;
 
-    println!("Command '{}' removed successfully!", command_name);
+    println!("Command '{}' moved to trash.", command_name);
+    println!("Restore it with: xa prompts restore {}", command_name);
 
     Ok(())
 }
 
-pub async fn load_prompt_config() -> Result<PromptConfig, Box<dyn std::error::Error>> {
-    let config_dir = config_dir()
-        .ok_or("Could not determine config directory")?
-        .join("xa");
+/// Move a command removed with `xa rm` back from the trash, or list the
+/// trash when no name is given.
+pub fn restore_command(command_name: Option<&str>) -> Result<(), XaError> {
+    let prompt_config_file = xa_dir()
+        .ok_or_else(|| XaError::Config("Could not determine config directory".into()))?
+        .join("prompts.toml");
+
+    let prompt_config: PromptConfig = if prompt_config_file.exists() {
+        toml::from_str(&fs::read_to_string(&prompt_config_file)?)?
+    } else {
+        PromptConfig { prompts: HashMap::new(), trash: HashMap::new() }
+    };
 
+    let Some(command_name) = command_name else {
+        if prompt_config.trash.is_empty() {
+            println!("Trash is empty.");
+            return Ok(());
+        }
+        let mut names: Vec<&String> = prompt_config.trash.keys().collect();
+        names.sort();
+        println!("Removed commands:");
+        for name in names {
+            let description = prompt_config.trash[name]
+                .description
+                .as_deref()
+                .unwrap_or("Custom prompt command");
+            println!("  {}: {}", name, description);
+        }
+        return Ok(());
+    };
+
+    restore_entry(&prompt_config_file, &prompt_config, command_name)?;
+    println!("Command '{}' restored successfully!", command_name);
+
+    Ok(())
+}
+
+/// Move `name` from the trash of `prompt_config`, read from `path`, back
+/// to its commands.
+fn restore_entry(path: &Path, prompt_config: &PromptConfig, name: &str) -> Result<(), XaError> {
+    if !prompt_config.trash.contains_key(name) {
+        return Err(XaError::Usage(format!("No removed command named '{}' in the trash", name)));
+    }
+    if prompt_config.prompts.contains_key(name) {
+        return Err(XaError::Usage(format!("Command '{}' already exists; remove or rename it before restoring", name)));
+    }
+    move_prompt_entry(path, "trash", "prompts", name)
+}
+
+/// Prompt for arguments one at a time until an empty name is entered,
+/// appending them to `existing`. Returns `None` when no arguments remain.
+fn read_prompt_args(existing: Vec<PromptArg>) -> Result<Option<Vec<PromptArg>>, XaError> {
+    let mut prompt_args = existing;
+
+    loop {
+        print!("Enter argument name (or press Enter to finish): ");
+        io::stdout().flush()?;
+        let mut arg_name = String::new();
+        io::stdin().read_line(&mut arg_name)?;
+        let arg_name = arg_name.trim().to_string();
+
+        if arg_name.is_empty() {
+            break;
+        }
+
+        print!("Enter default value for '{}': ", arg_name);
+        io::stdout().flush()?;
+        let mut default_value = String::new();
+        io::stdin().read_line(&mut default_value)?;
+        let default_value = default_value.trim().to_string();
+
+        print!("Enter description for '{}' (optional): ", arg_name);
+        io::stdout().flush()?;
+        let mut arg_description = String::new();
+        io::stdin().read_line(&mut arg_description)?;
+        let arg_description = arg_description.trim().to_string();
+        let arg_description = if arg_description.is_empty() {
+            None
+        } else {
+            Some(arg_description)
+        };
+
+        prompt_args.push(PromptArg {
+            name: arg_name,
+            default_value,
+            description: arg_description,
+        });
+
+        println!("Added argument: {}", prompt_args.last().unwrap().name);
+    }
+
+    Ok(if prompt_args.is_empty() { None } else { Some(prompt_args) })
+}
+
+/// Print `label [current]: ` and return the answer, or `current` when the
+/// user just presses Enter.
+fn read_with_default(label: &str, current: &str) -> io::Result<String> {
+    print!("{} [{}]: ", label, current.replace('\n', "\\n"));
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    let answer = answer.trim();
+    Ok(if answer.is_empty() { current.to_string() } else { answer.to_string() })
+}
+
+pub async fn edit_command(command_name: &str) -> Result<(), XaError> {
+    let config_dir = xa_dir()
+        .ok_or_else(|| XaError::Config("Could not determine config directory".into()))?;
     let prompt_config_file = config_dir.join("prompts.toml");
 
+    // Project commands live in .xa.toml; only global ones are edited here
+    let prompt_config = load_global_prompt_config().await?;
+
+    let Some(entry) = prompt_config.prompts.get(command_name).cloned() else {
+        eprintln!("Error: Command '{}' does not exist. Use 'xa add' to create it.", command_name);
+        return Ok(());
+    };
+
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .ok()
+        .filter(|editor| !editor.trim().is_empty());
+
+    let edited = match editor {
+        Some(editor) => edit_entry_in_editor(command_name, &entry, &editor)?,
+        None => edit_entry_interactively(&entry)?,
+    };
+    let Some(edited) = edited else {
+        println!("Edit cancelled; '{}' was not changed.", command_name);
+        return Ok(());
+    };
+
+    save_prompt_entry(&prompt_config_file, command_name, &edited)?;
+
+    println!("Command '{}' updated successfully!", command_name);
+    Ok(())
+}
+
+/// Open `$VISUAL`/`$EDITOR` on the entry's TOML until it parses and passes
+/// validation, or the user gives up.
+fn edit_entry_in_editor(
+    command_name: &str,
+    entry: &PromptEntry,
+    editor: &str,
+) -> Result<Option<PromptEntry>, XaError> {
+    let path = std::env::temp_dir().join(format!("xa-{}-{}.toml", command_name, std::process::id()));
+    fs::write(&path, toml::to_string(entry)?)?;
+
+    let result = loop {
+        // `$EDITOR` may carry its own flags, e.g. `code --wait`.
+        let mut parts = editor.split_whitespace();
+        let program = parts.next().unwrap_or("vi");
+        let status = std::process::Command::new(program).args(parts).arg(&path).status()?;
+        if !status.success() {
+            break None;
+        }
+
+        let content = fs::read_to_string(&path)?;
+        let problems = match toml::from_str::<PromptEntry>(&content) {
+            Ok(edited) => {
+                let problems = validate_entry(&edited);
+                if problems.is_empty() {
+                    break Some(edited);
+                }
+                problems
+            }
+            Err(e) => vec![format!("invalid TOML: {}", e)],
+        };
+
+        eprintln!("The edited entry has problems:");
+        for problem in &problems {
+            eprintln!("  - {}", problem);
+        }
+        print!("Re-open the editor to fix them? (Y/n): ");
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        if answer.trim().eq_ignore_ascii_case("n") || answer.trim().eq_ignore_ascii_case("no") {
+            break None;
+        }
+    };
+
+    let _ = fs::remove_file(&path);
+    Ok(result)
+}
+
+/// Walk through template, description and args, keeping current values on
+/// Enter. Used when no editor is configured.
+fn edit_entry_interactively(entry: &PromptEntry) -> Result<Option<PromptEntry>, XaError> {
+    let template = read_with_default("Template", &entry.template)?;
+    let description = read_with_default("Description", entry.description.as_deref().unwrap_or(""))?;
+
+    let mut args = Vec::new();
+    for arg in entry.args.iter().flatten() {
+        print!("Keep argument '{}'? (Y/n): ", arg.name);
+        io::stdout().flush()?;
+        let mut keep = String::new();
+        io::stdin().read_line(&mut keep)?;
+        if keep.trim().eq_ignore_ascii_case("n") || keep.trim().eq_ignore_ascii_case("no") {
+            continue;
+        }
+        let default_value = read_with_default(&format!("Default value for '{}'", arg.name), &arg.default_value)?;
+        let arg_description = read_with_default(
+            &format!("Description for '{}'", arg.name),
+            arg.description.as_deref().unwrap_or(""),
+        )?;
+        args.push(PromptArg {
+            name: arg.name.clone(),
+            default_value,
+            description: if arg_description.is_empty() { None } else { Some(arg_description) },
+        });
+    }
+    println!("Add more arguments (press Enter to skip):");
+    let args = read_prompt_args(args)?;
+
+    let edited = PromptEntry {
+        template,
+        description: if description.is_empty() { None } else { Some(description) },
+        args,
+        ..entry.clone()
+    };
+
+    let problems = validate_entry(&edited);
+    if !problems.is_empty() {
+        eprintln!("Error: the edited entry has problems and was not saved:");
+        for problem in &problems {
+            eprintln!("  - {}", problem);
+        }
+        return Ok(None);
+    }
+    Ok(Some(edited))
+}
+
+/// One command invocation inside a pipeline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PipelineStep {
+    pub command: String,
+    pub args: Vec<String>,
+}
+
+/// Parse a pipeline such as `summarize -> translate fr` or
+/// `summarize | translate:fr` into its steps.
+pub fn parse_pipeline(spec: &str) -> Vec<PipelineStep> {
+    spec.replace("->", "|")
+        .split('|')
+        .filter_map(parse_pipeline_step)
+        .collect()
+}
+
+/// Parse a single step: the command name followed by its arguments, either
+/// whitespace-separated (`translate fr`) or colon-separated (`translate:fr`).
+pub fn parse_pipeline_step(step: &str) -> Option<PipelineStep> {
+    let mut words = step.split_whitespace();
+    let mut head = words.next()?.split(':');
+    let command = head.next()?.to_string();
+    if command.is_empty() {
+        return None;
+    }
+    let args = head
+        .filter(|arg| !arg.is_empty())
+        .map(str::to_string)
+        .chain(words.map(str::to_string))
+        .collect();
+    Some(PipelineStep { command, args })
+}
+
+/// A `:<command>` line from the conversation mode (without the colon):
+/// the step, with arguments attached as in `xa pipe` (`summarize:short`),
+/// and the text to run it on, if any. For translate a leading language code
+/// is the argument, as on the command line (`translate fr some text`).
+pub fn parse_repl_command(line: &str, prompts: &HashMap<String, PromptEntry>) -> Option<(PipelineStep, Option<String>)> {
+    let line = line.trim();
+    let (head, text) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let mut step = parse_pipeline_step(head)?;
+    let mut text = text.trim();
+    if step.args.is_empty() && find_command(&step.command, prompts).as_deref() == Some("translate") {
+        let (code, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        if (2..=3).contains(&code.len()) && code.chars().all(|c| c.is_ascii_alphabetic()) {
+            step.args.push(code.to_string());
+            text = rest.trim();
+        }
+    }
+    Some((step, (!text.is_empty()).then(|| text.to_string())))
+}
+
+/// Placeholder names (`{name}`) referenced by a template, in order of first
+/// use. Braces that don't wrap a plain identifier (JSON examples in a prompt,
+/// for instance) are not placeholders and are skipped.
+pub fn template_placeholders(template: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rest = &rest[start + 1..];
+        let end = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        let name = &rest[..end];
+        if rest[end..].starts_with('}')
+            && !name.is_empty()
+            && !name.starts_with(|c: char| c.is_ascii_digit())
+            && !names.iter().any(|n| n == name)
+        {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+/// `{input}`, `{args}`, `{argN}` and `{last}` are always filled in, whatever the entry declares.
+fn is_builtin_placeholder(name: &str) -> bool {
+    name == "input"
+        || name == "last"
+        || name == "args"
+        || name
+            .strip_prefix("arg")
+            .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Problems that would make an entry misbehave when it is filled in.
+pub fn validate_entry(entry: &PromptEntry) -> Vec<String> {
+    let mut problems = entry.postprocess.as_ref().map(PostProcess::validate).unwrap_or_default();
+    for (field, hook) in [("pre_hook", &entry.pre_hook), ("post_hook", &entry.post_hook)] {
+        if hook.as_deref().is_some_and(|command| command.trim().is_empty()) {
+            problems.push(format!("{} is empty", field));
+        }
+    }
+    if let Some(steps) = &entry.pipeline {
+        if steps.iter().all(|step| parse_pipeline_step(step).is_none()) {
+            problems.push("pipeline has no steps".to_string());
+        }
+        return problems;
+    }
+    if entry.template.trim().is_empty() {
+        problems.push("template is empty".to_string());
+    }
+    if entry.stop.iter().any(String::is_empty) {
+        problems.push("stop has an empty sequence".to_string());
+    }
+    if entry.stop.len() > 4 {
+        problems.push(format!("stop has {} sequences; most APIs accept at most 4", entry.stop.len()));
+    }
+    if entry.assistant_prefix.as_deref() == Some("") {
+        problems.push("assistant_prefix is empty".to_string());
+    }
+    if entry.show_diff && entry.show_corrections {
+        problems.push("show_diff and show_corrections are both set; only the corrections are shown".to_string());
+    }
+
+    let placeholders = template_placeholders(&entry.template);
+    if !entry.template.trim().is_empty() && !placeholders.iter().any(|name| name == "input") {
+        problems.push("template has no {input} placeholder, so the input is never sent".to_string());
+    }
+
+    let declared: Vec<&str> = entry.args.iter().flatten().map(|arg| arg.name.as_str()).collect();
+    for name in &placeholders {
+        if !is_builtin_placeholder(name) && !declared.contains(&name.as_str()) {
+            problems.push(format!("unknown placeholder {{{}}} (not a declared argument)", name));
+        }
+    }
+
+    for (i, name) in declared.iter().enumerate() {
+        let earlier = declared[..i].iter().filter(|earlier| *earlier == name).count();
+        if earlier == 1 {
+            problems.push(format!("argument '{}' is declared more than once", name));
+        } else if earlier == 0 && !placeholders.iter().any(|used| used == name) {
+            problems.push(format!("argument '{}' is declared but never used in the template", name));
+        }
+    }
+    problems
+}
+
+/// `xa prompts lint`: check every entry of a prompts file without modifying
+/// it. Returns `false` when any problem was found so the caller can exit
+/// non-zero (useful in CI for dotfile repositories).
+pub fn lint_prompts(file: Option<&std::path::Path>) -> Result<bool, XaError> {
+    let prompt_config_file = match file {
+        Some(path) => path.to_path_buf(),
+        None => xa_dir()
+            .ok_or_else(|| XaError::Config("Could not determine config directory".into()))?
+            .join("prompts.toml"),
+    };
+
+    let content = fs::read_to_string(&prompt_config_file)
+        .map_err(|e| XaError::Other(format!("cannot read {:?}: {}", prompt_config_file, e)))?;
+    let prompt_config: PromptConfig = toml::from_str(&content)
+        .map_err(|e| XaError::Config(format!("{:?} is not a valid prompts file: {}", prompt_config_file, e)))?;
+
+    let mut names: Vec<&String> = prompt_config.prompts.keys().collect();
+    names.sort();
+
+    let mut problem_count = 0;
+    let mut failing_entries = 0;
+    for name in names {
+        let entry = &prompt_config.prompts[name];
+        let mut problems = validate_entry(entry);
+        for step in entry.pipeline.iter().flatten().filter_map(|step| parse_pipeline_step(step)) {
+            match prompt_config.prompts.get(&step.command) {
+                None => problems.push(format!("pipeline step '{}' is not a command", step.command)),
+                Some(target) if target.pipeline.is_some() => problems.push(format!(
+                    "pipeline step '{}' is itself a pipeline, which is not supported",
+                    step.command
+                )),
+                Some(_) => {}
+            }
+        }
+        if problems.is_empty() {
+            continue;
+        }
+        failing_entries += 1;
+        problem_count += problems.len();
+        for problem in problems {
+            println!("{}: {}", name, problem);
+        }
+    }
+
+    if problem_count == 0 {
+        println!("{} commands checked, no problems found.", prompt_config.prompts.len());
+        Ok(true)
+    } else {
+        println!(
+            "{} problem(s) in {} of {} commands ({:?}).",
+            problem_count,
+            failing_entries,
+            prompt_config.prompts.len(),
+            prompt_config_file
+        );
+        Ok(false)
+    }
+}
+
+/// The default `ask` template from before `[personas]`.
+const LEGACY_ASK_TEMPLATE: &str = "You are a helpful assistant called xa, execute anything by your side. {input}";
+
+/// The default `translate` description from when it always meant Chinese.
+const LEGACY_TRANSLATE_DESCRIPTION: &str = "Translate text (default target: zh)";
+
+const TRANSLATE_DESCRIPTION: &str = "Translate text (default target: your language, see default_target_lang)";
+
+/// A `target_lang` default that stands for `default_target_lang` in
+/// config.toml, else the language of the locale.
+pub const AUTO_TARGET_LANG: &str = "auto";
+
+/// The language of a POSIX locale name: `de_DE.UTF-8` → `de`, with the
+/// script kept for Chinese (`zh_TW` → `zh-Hant`). `None` for `C`/`POSIX`.
+fn locale_language(locale: &str) -> Option<String> {
+    let name = locale.split(['.', '@']).next().unwrap_or_default();
+    let (language, region) = name.split_once(['_', '-']).unwrap_or((name, ""));
+    let language = language.to_lowercase();
+    if language.is_empty() || language == "c" || language == "posix" {
+        return None;
+    }
+    Some(match (language.as_str(), region.to_uppercase().as_str()) {
+        ("zh", "TW" | "HK" | "MO") => "zh-Hant".to_string(),
+        ("zh", _) => "zh".to_string(),
+        _ => language,
+    })
+}
+
+/// What `target_lang = "auto"` means: `default_target_lang`, else the
+/// language of `LC_ALL`, `LC_MESSAGES` or `LANG` (the first one set),
+/// else English.
+pub fn default_target_lang(config: &Config) -> String {
+    if let Some(language) = config.default_target_lang.as_deref().filter(|language| !language.trim().is_empty()) {
+        return language.trim().to_string();
+    }
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .find_map(|name| std::env::var(name).ok().filter(|value| !value.is_empty()))
+        .and_then(|locale| locale_language(&locale))
+        .unwrap_or_else(|| "en".to_string())
+}
+
+/// Global prompts plus the commands from the project's `.xa.toml`, which
+/// replace global commands of the same name. `target_lang` arguments that
+/// default to `auto` get the language from [`default_target_lang`].
+pub async fn load_prompt_config() -> Result<PromptConfig, XaError> {
+    let mut config = load_global_prompt_config().await?;
+    if let Some(project) = crate::project::load()? {
+        config.prompts.extend(project.prompts);
+    }
+    let language = default_target_lang(&crate::config::load_config_sync().unwrap_or_default());
+    for arg in config.prompts.values_mut().flat_map(|entry| entry.args.iter_mut().flatten()) {
+        if arg.name == "target_lang" && arg.default_value == AUTO_TARGET_LANG {
+            arg.default_value = language.clone();
+        }
+    }
+    Ok(config)
+}
+
+/// Built-in entries saved by older versions, brought up to date in memory
+/// (the file is left as it is).
+fn upgrade_legacy_defaults(config: &mut PromptConfig) {
+    // `ask` used to carry the assistant text itself, which is now the
+    // default persona's system prompt; don't send it twice
+    if let Some(ask) = config.prompts.get_mut("ask") {
+        if ask.template == LEGACY_ASK_TEMPLATE {
+            ask.template = "{input}".to_string();
+        }
+    }
+    // `translate` used to default to Chinese for everyone; an entry left
+    // as it was now follows the locale too
+    if let Some(translate) = config.prompts.get_mut("translate") {
+        if translate.description.as_deref() == Some(LEGACY_TRANSLATE_DESCRIPTION) {
+            translate.description = Some(TRANSLATE_DESCRIPTION.to_string());
+            for arg in translate.args.iter_mut().flatten() {
+                if arg.name == "target_lang" && arg.default_value == "zh" {
+                    arg.default_value = AUTO_TARGET_LANG.to_string();
+                }
+            }
+        }
+    }
+}
+
+/// prompts.toml alone, with any missing default commands added to it.
+async fn load_global_prompt_config() -> Result<PromptConfig, XaError> {
+    let config_dir = xa_dir()
+        .ok_or_else(|| XaError::Config("Could not determine config directory".into()))?;
+
+    load_prompt_file(&config_dir.join("prompts.toml"))
+}
+
+/// The commands in `prompt_config_file`, created with the defaults if it
+/// does not exist, plus any default it lacks (unless it was removed into
+/// the trash), which is added to the file.
+fn load_prompt_file(prompt_config_file: &Path) -> Result<PromptConfig, XaError> {
     let mut config = if prompt_config_file.exists() {
-        let content = fs::read_to_string(&prompt_config_file)?;
+        let content = fs::read_to_string(prompt_config_file)?;
         // Try to parse the existing content, if it fails, create a new one
         match toml::from_str(&content) {
             Ok(parsed_config) => parsed_config,
             Err(_) => {
                 // If parsing fails, backup the corrupted file and start fresh
+                let _lock = lock(prompt_config_file)?;
                 let backup_path = prompt_config_file.with_extension("toml.backup");
-                fs::rename(&prompt_config_file, &backup_path)?;
+                fs::rename(prompt_config_file, &backup_path)?;
                 eprintln!("Warning: Corrupted prompts.toml file detected. Backed up to {:?} and created a new one.", backup_path);
                 let default_config = PromptConfig::default();
-                fs::create_dir_all(&config_dir)?;
                 let new_content = toml::to_string(&default_config)?;
-                fs::write(&prompt_config_file, new_content)?;
+                safe_write(prompt_config_file, new_content)?;
                 default_config
             }
         }
     } else {
         let default_config = PromptConfig::default();
-        // Create the file with default prompts
-        fs::create_dir_all(&config_dir)?;
-        let content = toml::to_string(&default_config)?;
-        fs::write(&prompt_config_file, content)?;
+        // Create the file with default prompts, unless another xa just did
+        let _lock = lock(prompt_config_file)?;
+        if !prompt_config_file.exists() {
+            safe_write(prompt_config_file, toml::to_string(&default_config)?)?;
+        }
         default_config
     };
 
-    // Ensure default commands are always available (merge defaults with existing)
+    upgrade_legacy_defaults(&mut config);
+
+    // Ensure default commands are always available (merge defaults with
+    // existing), except those the user removed with `xa rm`
     let default_config = PromptConfig::default();
-    for (key, value) in default_config.prompts {
-        if !config.prompts.contains_key(&key) {
-            config.prompts.insert(key, value);
+    let mut missing: Vec<(String, PromptEntry)> = default_config
+        .prompts
+        .into_iter()
+        .filter(|(key, _)| !config.prompts.contains_key(key) && !config.trash.contains_key(key))
+        .collect();
+    missing.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    // Append only the new defaults; an up-to-date file is never rewritten
+    if !missing.is_empty() {
+        let _lock = lock(prompt_config_file)?;
+        // Read again under the lock; another xa may have added some already
+        let mut doc = prompt_document(prompt_config_file)?;
+        for (key, value) in &missing {
+            if doc.get("trash").and_then(Item::as_table).is_some_and(|trash| trash.contains_key(key)) {
+                continue;
+            }
+            let prompts = section(&mut doc, "prompts");
+            if !prompts.contains_key(key) {
+                prompts.insert(key, entry_item(value)?);
+            }
         }
+        safe_write(prompt_config_file, doc.to_string())?;
+        config.prompts.extend(missing);
     }
 
-    // Save back to file if there were new defaults added
-    let content = toml::to_string(&config)?;
-    fs::write(&prompt_config_file, content)?;
-
     Ok(config)
 }
 
+/// Open prompts.toml as an editable document. Writes go through this (rather
+/// than re-serializing [`PromptConfig`]) so the user's comments, entry order
+/// and formatting survive `add`, `rm`, `prompts edit` and the default merge.
+fn prompt_document(path: &Path) -> Result<DocumentMut, XaError> {
+    let content = if path.exists() { fs::read_to_string(path)? } else { String::new() };
+    Ok(content.parse()?)
+}
+
+/// The top-level `prompts` or `trash` table, created (without a header of
+/// its own) if the document does not have one yet.
+fn section<'a>(doc: &'a mut DocumentMut, key: &str) -> &'a mut Table {
+    if !doc.get(key).is_some_and(Item::is_table) {
+        let mut table = Table::new();
+        table.set_implicit(true);
+        doc.insert(key, Item::Table(table));
+    }
+    doc[key].as_table_mut().expect("section was just inserted")
+}
+
+/// Render an entry the same way `toml::to_string` would, as a detached table
+/// that can be placed anywhere in a document.
+fn entry_item(entry: &PromptEntry) -> Result<Item, XaError> {
+    let doc: DocumentMut = toml::to_string(entry)?.parse()?;
+    let mut table = doc.as_table().clone();
+    clear_positions(&mut table);
+    table.set_implicit(false);
+    Ok(Item::Table(table))
+}
+
+/// Tables parsed from another document carry that document's ordering;
+/// without a position they are emitted right after the preceding table.
+fn clear_positions(table: &mut Table) {
+    table.set_position(None);
+    for (_, item) in table.iter_mut() {
+        match item {
+            Item::Table(nested) => clear_positions(nested),
+            Item::ArrayOfTables(tables) => tables.iter_mut().for_each(clear_positions),
+            _ => {}
+        }
+    }
+}
+
+/// Insert or replace one entry in place, keeping a replaced entry's position
+/// and the comments above it.
+fn save_prompt_entry(path: &Path, name: &str, entry: &PromptEntry) -> Result<(), XaError> {
+    let _lock = lock(path)?;
+    let mut doc = prompt_document(path)?;
+    let prompts = section(&mut doc, "prompts");
+    let mut item = entry_item(entry)?;
+    if let (Some(old), Some(new)) = (prompts.get(name).and_then(Item::as_table), item.as_table_mut()) {
+        new.set_position(old.position());
+        *new.decor_mut() = old.decor().clone();
+    }
+    prompts.insert(name, item);
+    safe_write(path, doc.to_string())?;
+    Ok(())
+}
+
+/// Move an entry between the `prompts` and `trash` tables, replacing any
+/// entry of the same name at the destination. The moved table goes to the
+/// end of its new section; its comments travel with it.
+fn move_prompt_entry(path: &Path, from: &str, to: &str, name: &str) -> Result<(), XaError> {
+    let _lock = lock(path)?;
+    let mut doc = prompt_document(path)?;
+    let mut item = section(&mut doc, from)
+        .remove(name)
+        .ok_or_else(|| XaError::Other(format!("'{}' not found in [{}]", name, from)))?;
+    if let Some(table) = item.as_table_mut() {
+        clear_positions(table);
+    }
+    section(&mut doc, to).insert(name, item);
+    safe_write(path, doc.to_string())?;
+    Ok(())
+}
+
+/// How [`find_command_match`] resolved a name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchKind {
+    Exact,
+    /// The name is a prefix of exactly one command (`sum` → `summarize`).
+    Prefix,
+    /// Best fuzzy score; may be a quite different command.
+    Fuzzy,
+}
+
 pub fn find_command(
     input_cmd: &str,
     available_commands: &HashMap<String, PromptEntry>,
 ) -> Option<String> {
+    find_command_match(input_cmd, available_commands).map(|(name, _)| name)
+}
+
+pub fn find_command_match(
+    input_cmd: &str,
+    available_commands: &HashMap<String, PromptEntry>,
+) -> Option<(String, MatchKind)> {
     // First, try exact match
     if available_commands.contains_key(input_cmd) {
-        return Some(input_cmd.to_string());
+        return Some((input_cmd.to_string(), MatchKind::Exact));
     }
 
     // Then, try prefix matching
@@ -412,7 +1273,7 @@ pub fn find_command(
         .collect();
 
     if prefix_matches.len() == 1 {
-        return Some(prefix_matches[0].to_string());
+        return Some((prefix_matches[0].to_string(), MatchKind::Prefix));
     } else if prefix_matches.len() > 1 {
         let matches: Vec<String> = prefix_matches.iter().map(|s| s.to_string()).collect();
         eprintln!(
@@ -439,7 +1300,7 @@ pub fn find_command(
 
     // Only return if score is positive (meaning there's a reasonable match)
     if best_score > 0 {
-        best_match
+        best_match.map(|name| (name, MatchKind::Fuzzy))
     } else {
         None
     }
@@ -466,34 +1327,105 @@ pub fn process_template(template: &str, input: &str, args: &[String]) -> String
     result
 }
 
-pub fn reset_default_prompts() -> Result<(), Box<dyn std::error::Error>> {
-    use dirs::config_dir;
+fn last_result_path() -> Option<std::path::PathBuf> {
+    xa_dir().map(|dir| dir.join("last-result"))
+}
+
+/// The most recent one-shot result, for `{last}` and `xa <command> -`.
+pub fn last_result() -> Option<String> {
+    read_last_result(&last_result_path()?)
+}
+
+fn read_last_result(path: &Path) -> Option<String> {
+    let text = fs::read_to_string(path).ok()?;
+    (!text.is_empty()).then_some(text)
+}
+
+/// Keep `text` as the most recent result, readable only by the user. A
+/// result holding one of the store's secrets is not kept, and neither is
+/// the one before it. Failing to only costs `{last}`, so it warns instead
+/// of failing the command.
+pub fn save_last_result(text: &str) {
+    let Some(path) = last_result_path() else {
+        return;
+    };
+    if let Err(e) = write_last_result(&path, text, &crate::store::secret_values()) {
+        eprintln!("Warning: Could not keep the result for {{last}} in {}: {}", path.display(), e);
+    }
+}
+
+fn write_last_result(path: &Path, text: &str, secrets: &[String]) -> io::Result<()> {
+    let holds_secret = secrets.iter().any(|secret| secret.chars().count() >= crate::redact::MIN_STORED_SECRET && text.contains(secret.as_str()));
+    if holds_secret {
+        return match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let temp = path.with_extension(format!("tmp-{}", std::process::id()));
+    let _ = fs::remove_file(&temp);
+    let written = crate::config::private_file(&temp).and_then(|mut file| file.write_all(text.as_bytes())).and_then(|_| fs::rename(&temp, path));
+    if written.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    written
+}
+
+/// The text `xa <command> -` works on: the previous result, found by
+/// `last`, in place of the `-`. Any other input is kept as it is.
+pub fn resolve_dash_input(input: String, last: impl FnOnce() -> Option<String>) -> Result<String, XaError> {
+    if input != "-" {
+        return Ok(input);
+    }
+    last().ok_or_else(|| XaError::Usage("There is no previous result for `-` yet.".into()))
+}
+
+pub fn reset_default_prompts() -> Result<(), XaError> {
     use std::fs;
 
     // Get config directory
-    let config_dir = config_dir()
-        .ok_or("Could not determine config directory")?
-        .join("xa");
+    let config_dir = xa_dir()
+        .ok_or_else(|| XaError::Config("Could not determine config directory".into()))?;
 
     let prompt_config_file = config_dir.join("prompts.toml");
 
-    // Create default prompt config
-    let default_config = PromptConfig::default();
-
-    // Create the directory if it doesn't exist
-    fs::create_dir_all(&config_dir)?;
+    // Create default prompt config, keeping anything in the trash restorable
+    let _lock = lock(&prompt_config_file)?;
+    let mut default_config = PromptConfig::default();
+    if let Ok(existing) = fs::read_to_string(&prompt_config_file) {
+        if let Ok(existing) = toml::from_str::<PromptConfig>(&existing) {
+            default_config.trash = existing.trash;
+        }
+    }
 
     // Save the default prompts, overwriting any existing file
     let content = toml::to_string(&default_config)?;
-    fs::write(&prompt_config_file, content)?;
+    safe_write(&prompt_config_file, content)?;
 
     println!("Default prompts have been reset successfully!");
     println!("Prompt file location: {:?}", prompt_config_file);
-    println!("Default commands restored: translate, polish, rewrite, summarize, ask");
+    println!("Default commands restored: translate, polish, grammar, rewrite, summarize, ask");
 
     Ok(())
 }
 
+/// [`process_template_with_args`], with `{last}` filled with `last` when
+/// given. The previous result goes in as it is: placeholders that happen
+/// to be in it are not filled again.
+pub fn fill_template(template: &str, input: &str, args: &[String], prompt_args: Option<&Vec<PromptArg>>, last: Option<&str>) -> String {
+    match last {
+        Some(last) => template
+            .split("{last}")
+            .map(|piece| process_template_with_args(piece, input, args, prompt_args))
+            .collect::<Vec<_>>()
+            .join(last),
+        None => process_template_with_args(template, input, args, prompt_args),
+    }
+}
+
 pub fn process_template_with_args(template: &str, input: &str, args: &[String], prompt_args: Option<&Vec<PromptArg>>) -> String {
     let mut result = template.to_string();
 
@@ -531,3 +1463,206 @@ pub fn process_template_with_args(template: &str, input: &str, args: &[String],
 
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(template: &str, args: &[&str]) -> PromptEntry {
+        PromptEntry {
+            template: template.to_string(),
+            description: None,
+            args: if args.is_empty() {
+                None
+            } else {
+                Some(
+                    args.iter()
+                        .map(|name| PromptArg {
+                            name: name.to_string(),
+                            default_value: String::new(),
+                            description: None,
+                        })
+                        .collect(),
+                )
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn placeholders_skip_json_braces() {
+        let names = template_placeholders("Return {\"tag\": string} for {input} in {lang} ({input})");
+        assert_eq!(names, vec!["input", "lang"]);
+    }
+
+    #[test]
+    fn default_prompts_pass_validation() {
+        for (name, entry) in PromptConfig::default().prompts {
+            assert!(validate_entry(&entry).is_empty(), "{name} should be valid");
+        }
+    }
+
+    #[test]
+    fn translates_into_the_configured_or_locale_language() {
+        assert_eq!(locale_language("de_DE.UTF-8").as_deref(), Some("de"));
+        assert_eq!(locale_language("zh_CN.UTF-8").as_deref(), Some("zh"));
+        assert_eq!(locale_language("zh_TW.UTF-8").as_deref(), Some("zh-Hant"));
+        assert_eq!(locale_language("sr_RS@latin").as_deref(), Some("sr"));
+        assert_eq!(locale_language("C.UTF-8"), None);
+        assert_eq!(locale_language("POSIX"), None);
+
+        let config = Config { default_target_lang: Some("Japanese".into()), ..Config::default() };
+        assert_eq!(default_target_lang(&config), "Japanese");
+
+        let mut prompts = PromptConfig::default();
+        let translate = prompts.prompts.get_mut("translate").unwrap();
+        translate.description = Some(LEGACY_TRANSLATE_DESCRIPTION.into());
+        translate.args.as_mut().unwrap()[0].default_value = "zh".into();
+        upgrade_legacy_defaults(&mut prompts);
+        assert_eq!(prompts.prompts["translate"].args.as_ref().unwrap()[0].default_value, AUTO_TARGET_LANG);
+    }
+
+    #[test]
+    fn reports_unknown_unused_duplicate_and_missing_input() {
+        let problems = validate_entry(&entry("Translate to {lang} in {tone}", &["lang", "style", "lang", "lang"]));
+        assert!(problems.iter().any(|p| p.contains("no {input}")));
+        assert!(problems.iter().any(|p| p.contains("{tone}")));
+        assert!(problems.iter().any(|p| p.contains("'style' is declared but never used")));
+        assert_eq!(problems.iter().filter(|p| p.contains("more than once")).count(), 1);
+
+        let problems = validate_entry(&PromptEntry { stop: vec![String::new()], assistant_prefix: Some(String::new()), ..entry("{input}", &[]) });
+        assert_eq!(problems, ["stop has an empty sequence", "assistant_prefix is empty"]);
+    }
+
+    #[test]
+    fn parses_pipeline_syntaxes() {
+        let steps = parse_pipeline("summarize short -> translate:fr | polish");
+        assert_eq!(steps.len(), 3);
+        assert_eq!(steps[0], PipelineStep { command: "summarize".into(), args: vec!["short".into()] });
+        assert_eq!(steps[1], PipelineStep { command: "translate".into(), args: vec!["fr".into()] });
+        assert!(steps[2].args.is_empty());
+        assert!(parse_pipeline(" -> ").is_empty());
+    }
+
+    #[test]
+    fn parses_repl_commands() {
+        let prompts = PromptConfig::default().prompts;
+        let (step, text) = parse_repl_command("translate fr this paragraph", &prompts).unwrap();
+        assert_eq!((step.command.as_str(), step.args, text.as_deref()), ("translate", vec!["fr".to_string()], Some("this paragraph")));
+        let (step, text) = parse_repl_command("tr de", &prompts).unwrap();
+        assert_eq!((step.args, text), (vec!["de".to_string()], None));
+        let (step, text) = parse_repl_command("summarize:short the cat sat", &prompts).unwrap();
+        assert_eq!((step.args, text.as_deref()), (vec!["short".to_string()], Some("the cat sat")));
+        assert!(parse_repl_command("  ", &prompts).is_none());
+    }
+
+    #[test]
+    fn entry_edits_keep_comments_and_order() {
+        let path = std::env::temp_dir().join(format!("xa-prompts-test-{}.toml", std::process::id()));
+        fs::write(
+            &path,
+            "# my prompts\n[prompts.zeta]\ntemplate = \"z {input}\"\n\n# keep me\n[prompts.alpha]\ntemplate = \"a {input}\"\n\n[prompts.gone]\ntemplate = \"g {input}\"\n",
+        )
+        .unwrap();
+
+        save_prompt_entry(&path, "alpha", &entry("new {input} {lang}", &["lang"])).unwrap();
+        save_prompt_entry(&path, "beta", &entry("b {input}", &[])).unwrap();
+        move_prompt_entry(&path, "prompts", "trash", "gone").unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert!(content.starts_with("# my prompts\n[prompts.zeta]"));
+        assert!(content.contains("# keep me\n[prompts.alpha]"));
+        assert!(content.find("[prompts.zeta]").unwrap() < content.find("[prompts.alpha]").unwrap());
+        assert!(content.find("[prompts.alpha]").unwrap() < content.find("[prompts.beta]").unwrap());
+        assert!(!content.contains("[prompts.gone]"));
+        let parsed: PromptConfig = toml::from_str(&content).unwrap();
+        assert_eq!(parsed.prompts["alpha"].args.as_ref().unwrap()[0].name, "lang");
+        assert_eq!(parsed.trash["gone"].template, "g {input}");
+    }
+
+    #[test]
+    fn trashed_entries_can_be_restored() {
+        let path = std::env::temp_dir().join(format!("xa-trash-test-{}.toml", std::process::id()));
+        fs::write(&path, "[prompts.keep]\ntemplate = \"k {input}\"\n\n# handmade\n[prompts.mine]\ntemplate = \"m {input}\"\n").unwrap();
+
+        move_prompt_entry(&path, "prompts", "trash", "mine").unwrap();
+        assert!(move_prompt_entry(&path, "prompts", "trash", "missing").is_err());
+        move_prompt_entry(&path, "trash", "prompts", "mine").unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        let parsed: PromptConfig = toml::from_str(&content).unwrap();
+        assert!(parsed.trash.is_empty());
+        assert_eq!(parsed.prompts["mine"].template, "m {input}");
+        assert!(content.contains("# handmade\n[prompts.mine]"));
+    }
+
+    #[test]
+    fn match_kind_distinguishes_prefix_from_fuzzy() {
+        let prompts = PromptConfig::default().prompts;
+        assert_eq!(find_command_match("ask", &prompts), Some(("ask".to_string(), MatchKind::Exact)));
+        assert_eq!(find_command_match("summ", &prompts), Some(("summarize".to_string(), MatchKind::Prefix)));
+        assert_eq!(find_command_match("smrz", &prompts), Some(("summarize".to_string(), MatchKind::Fuzzy)));
+    }
+
+    #[test]
+    fn numbered_args_are_builtin() {
+        assert!(validate_entry(&entry("{input} {arg1} {args} {last}", &[])).is_empty());
+    }
+
+    #[test]
+    fn removed_defaults_stay_removed_until_restored() {
+        let dir = std::env::temp_dir().join(format!("xa-prompt-trash-{}", std::process::id()));
+        let path = dir.join("prompts.toml");
+        let first = load_prompt_file(&path).unwrap();
+        move_prompt_entry(&path, "prompts", "trash", "summarize").unwrap();
+        let after_rm = load_prompt_file(&path).unwrap();
+        let restored = restore_entry(&path, &after_rm, "summarize");
+        let after_restore = load_prompt_file(&path).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert!(first.prompts.contains_key("summarize"));
+        assert!(!after_rm.prompts.contains_key("summarize"));
+        assert!(after_rm.trash.contains_key("summarize"));
+        assert!(restored.is_ok());
+        assert!(after_restore.prompts.contains_key("summarize"));
+        assert!(after_restore.trash.is_empty());
+    }
+
+    #[test]
+    fn last_result_is_filled_in_as_is() {
+        let template = "Before: {last}\nNow, in {arg1}: {input}";
+        let filled = fill_template(template, "new text", &["fr".into()], None, Some("an answer about {input} and {arg1}"));
+        assert_eq!(filled, "Before: an answer about {input} and {arg1}\nNow, in fr: new text");
+        assert_eq!(fill_template("{input}", "x", &[], None, None), "x");
+    }
+
+    #[test]
+    fn dash_stands_for_the_last_result() {
+        assert_eq!(resolve_dash_input("-".into(), || Some("summary".into())).unwrap(), "summary");
+        assert_eq!(resolve_dash_input("a - b".into(), || panic!("not needed")).unwrap(), "a - b");
+        assert!(matches!(resolve_dash_input("-".into(), || None), Err(XaError::Usage(_))));
+    }
+
+    #[test]
+    fn last_result_is_private_and_never_a_secret() {
+        let dir = std::env::temp_dir().join(format!("xa-last-result-{}", std::process::id()));
+        let path = dir.join("last-result");
+        let secrets = ["sk-live-1234".to_string(), "abc".to_string()];
+        write_last_result(&path, "Résumé: abc", &secrets).unwrap();
+        let kept = read_last_result(&path);
+        #[cfg(unix)]
+        let mode = std::os::unix::fs::PermissionsExt::mode(&fs::metadata(&path).unwrap().permissions()) & 0o777;
+        write_last_result(&path, "The key is sk-live-1234", &secrets).unwrap();
+        let after_secret = read_last_result(&path);
+        let files = fs::read_dir(&dir).unwrap().count();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(kept.as_deref(), Some("Résumé: abc"));
+        #[cfg(unix)]
+        assert_eq!(mode, 0o600);
+        assert_eq!(after_secret, None);
+        assert_eq!(files, 0);
+    }
+}
@@ -0,0 +1,240 @@
+//! `xa review`: code review of a diff (the working tree, the staged changes
+//! or a commit range) or of files. The input is split into chunks that fit
+//! a request, each chunk is reviewed on its own, and the issues found are
+//! merged into one report grouped by severity, or JSON for CI.
+//!
+//! Lines are numbered before they are sent (new-file numbers for diffs) so
+//! the model can point at them instead of counting.
+
+use crate::config::Config;
+use crate::error::XaError;
+use crate::output::paint;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::path::Path;
+
+/// Text per review request; a larger file or hunk is split between lines.
+const MAX_CHUNK: usize = 12_000;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    High,
+    Medium,
+    Low,
+}
+
+impl<'de> Deserialize<'de> for Severity {
+    /// Models stray from the three names; map the usual synonyms.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Severity, D::Error> {
+        let name = String::deserialize(deserializer)?.to_lowercase();
+        Ok(match name.as_str() {
+            "high" | "critical" | "blocker" | "error" | "major" => Severity::High,
+            "medium" | "moderate" | "warning" => Severity::Medium,
+            _ => Severity::Low,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Issue {
+    pub severity: Severity,
+    pub file: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<u32>,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub detail: String,
+}
+
+#[derive(Deserialize)]
+struct Answer {
+    #[serde(default)]
+    issues: Vec<Issue>,
+}
+
+/// A unified diff as chunks of whole files, each line of a hunk prefixed
+/// with its line number in the new file.
+pub fn diff_chunks(diff: &str) -> Vec<String> {
+    let mut files: Vec<String> = Vec::new();
+    let mut new_line = 0u32;
+    for line in diff.lines() {
+        if line.starts_with("diff --git ") || files.is_empty() {
+            files.push(String::new());
+        }
+        let file = files.last_mut().expect("pushed above");
+        if let Some(range) = line.strip_prefix("@@ ") {
+            new_line = hunk_start(range).unwrap_or(1);
+            file.push_str(&format!("{}\n", line));
+        } else if (line.starts_with('+') && !line.starts_with("+++")) || line.starts_with(' ') {
+            file.push_str(&format!("{:>5} {}\n", new_line, line));
+            new_line += 1;
+        } else if line.starts_with('-') && !line.starts_with("---") {
+            file.push_str(&format!("      {}\n", line));
+        } else {
+            file.push_str(&format!("{}\n", line));
+        }
+    }
+    // A file split across chunks keeps its header in each, to be named
+    pack(files.iter().flat_map(|file| {
+        let header = file.lines().next().unwrap_or_default();
+        split_lines(file)
+            .into_iter()
+            .enumerate()
+            .map(move |(index, part)| if index == 0 { part } else { format!("{}\n{}", header, part) })
+    }))
+}
+
+/// The lines `start..=end` (1-based; all when `None`) of a file as numbered
+/// chunks, each headed with the file's name.
+pub fn file_chunks(path: &str, text: &str, range: Option<(u32, u32)>) -> Vec<String> {
+    let (start, end) = range.unwrap_or((1, u32::MAX));
+    let numbered: String = text
+        .lines()
+        .zip(1u32..)
+        .filter(|(_, number)| (start..=end).contains(number))
+        .map(|(line, number)| format!("{:>5} {}\n", number, line))
+        .collect();
+    split_lines(&numbered).into_iter().map(|part| format!("File: {}\n{}", path, part)).collect()
+}
+
+/// `path` or `path:START-END`.
+pub fn parse_target(target: &str) -> Result<(String, Option<(u32, u32)>), XaError> {
+    if let Some((path, range)) = target.rsplit_once(':') {
+        if let Some((start, end)) = range.split_once('-') {
+            if let (Ok(start), Ok(end)) = (start.parse::<u32>(), end.parse::<u32>()) {
+                if start == 0 || end < start {
+                    return Err(XaError::Usage(format!("invalid line range '{}' in {}", range, target)));
+                }
+                return Ok((path.to_string(), Some((start, end))));
+            }
+        }
+    }
+    if !Path::new(target).is_file() {
+        return Err(XaError::Usage(format!("{} is not a file", target)));
+    }
+    Ok((target.to_string(), None))
+}
+
+/// Review every chunk in turn and gather the issues, most severe first.
+/// A chunk whose answer cannot be read is reported and skipped.
+pub async fn review(config: &Config, chunks: &[String]) -> Result<Vec<Issue>, XaError> {
+    let mut issues = Vec::new();
+    for (index, chunk) in chunks.iter().enumerate() {
+        let label = if chunks.len() > 1 { format!("Reviewing part {}/{}", index + 1, chunks.len()) } else { "Reviewing".to_string() };
+        let spinner = crate::spinner::Spinner::start(&label);
+        let done = crate::llm::complete(config, &prompt(chunk)).await;
+        spinner.stop();
+        match parse_issues(&done?.text) {
+            Ok(found) => issues.extend(found),
+            Err(e) => eprintln!("{}", paint("33", &format!("Part {} of the review could not be read: {}", index + 1, e))),
+        }
+    }
+    issues.sort_by(|a, b| (a.severity, &a.file, a.line).cmp(&(b.severity, &b.file, b.line)));
+    Ok(issues)
+}
+
+fn prompt(chunk: &str) -> String {
+    format!(
+        "Review the code below as an experienced reviewer. Report real problems only: bugs, security issues, \
+         data loss, race conditions, unhandled errors, and clearly confusing code. Skip style nits and praise. \
+         Numbers at the start of lines are line numbers, not code.\n\n\
+         Reply with JSON only, in this form:\n\
+         {{\"issues\": [{{\"severity\": \"high|medium|low\", \"file\": \"path\", \"line\": 42, \"title\": \"one line\", \"detail\": \"why it is a problem and how to fix it\"}}]}}\n\
+         Reply with {{\"issues\": []}} when there is nothing to report.\n\n{}",
+        chunk
+    )
+}
+
+/// The issues in a model's answer, which may wrap the JSON in a code
+/// block or text.
+pub fn parse_issues(answer: &str) -> Result<Vec<Issue>, String> {
+    let text = crate::postprocess::extract_code_block(answer).unwrap_or_else(|| answer.to_string());
+    let json = match (text.find('{'), text.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &text[start..=end],
+        _ => return Err("no JSON in the answer".into()),
+    };
+    serde_json::from_str::<Answer>(json).map(|answer| answer.issues).map_err(|e| e.to_string())
+}
+
+/// The report printed on a terminal.
+pub fn render(issues: &[Issue]) -> String {
+    if issues.is_empty() {
+        return paint("32", "No issues found.");
+    }
+    let mut out = String::new();
+    for (severity, name, color) in [(Severity::High, "High", "31"), (Severity::Medium, "Medium", "33"), (Severity::Low, "Low", "36")] {
+        let group: Vec<&Issue> = issues.iter().filter(|issue| issue.severity == severity).collect();
+        if group.is_empty() {
+            continue;
+        }
+        out.push_str(&paint(&format!("1;{}", color), &format!("{} ({})", name, group.len())));
+        out.push('\n');
+        for issue in group {
+            let place = match issue.line {
+                Some(line) => format!("{}:{}", issue.file, line),
+                None => issue.file.clone(),
+            };
+            out.push_str(&format!("  {}  {}\n", paint("1", &place), issue.title));
+            for line in issue.detail.lines() {
+                out.push_str(&format!("      {}\n", paint("90", line)));
+            }
+        }
+        out.push('\n');
+    }
+    out.trim_end().to_string()
+}
+
+/// `+c,d` of a hunk header's `-a,b +c,d @@`.
+fn hunk_start(range: &str) -> Option<u32> {
+    let new = range.split_whitespace().find(|part| part.starts_with('+'))?;
+    new[1..].split(',').next()?.parse().ok()
+}
+
+/// `text` in pieces of at most `MAX_CHUNK` bytes, split between lines (a
+/// single longer line stays whole).
+fn split_lines(text: &str) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    for line in text.split_inclusive('\n') {
+        let part = parts.last_mut().expect("never empty");
+        if !part.is_empty() && part.len() + line.len() > MAX_CHUNK {
+            parts.push(String::new());
+        }
+        parts.last_mut().expect("never empty").push_str(line);
+    }
+    parts.retain(|part| !part.trim().is_empty());
+    parts
+}
+
+/// Pieces joined into as few chunks of at most `MAX_CHUNK` bytes as their
+/// order allows.
+fn pack(pieces: impl Iterator<Item = String>) -> Vec<String> {
+    let mut chunks: Vec<String> = Vec::new();
+    for piece in pieces {
+        match chunks.last_mut() {
+            Some(chunk) if chunk.len() + piece.len() <= MAX_CHUNK => chunk.push_str(&piece),
+            _ => chunks.push(piece),
+        }
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_diffs_and_reads_answers() {
+        let diff = "diff --git a/src/a.rs b/src/a.rs\n--- a/src/a.rs\n+++ b/src/a.rs\n@@ -10,3 +10,3 @@ fn main() {\n     let x = 1;\n-    old();\n+    new();\n }\n";
+        let chunks = diff_chunks(diff);
+        assert_eq!(chunks.len(), 1);
+        assert!(chunks[0].contains("   10      let x = 1;\n      -    old();\n   11 +    new();\n   12  }\n"), "{}", chunks[0]);
+        assert_eq!(file_chunks("a.rs", "one\ntwo\nthree\n", Some((2, 3))), ["File: a.rs\n    2 two\n    3 three\n"]);
+
+        let answer = "```json\n{\"issues\": [{\"severity\": \"low\", \"file\": \"a.rs\", \"title\": \"Naming\"}, \
+                      {\"severity\": \"Critical\", \"file\": \"a.rs\", \"line\": 11, \"title\": \"Panics\", \"detail\": \"new() unwraps\"}]}\n```";
+        let issues = parse_issues(answer).unwrap();
+        assert_eq!(issues[1].severity, Severity::High);
+        assert_eq!(issues[1].line, Some(11));
+        assert!(parse_issues("Looks good to me!").is_err());
+    }
+}
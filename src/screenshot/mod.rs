@@ -0,0 +1,119 @@
+//! Region screenshots for `xa see` and `xa ask --screenshot`, taken with the
+//! platform's own tool and sent on as an image:
+//!
+//! - Linux: `slurp` + `grim` on Wayland; `maim`, `scrot`, `gnome-screenshot`
+//!   or ImageMagick's `import` on X11 (the first one installed)
+//! - macOS: `screencapture -i`
+//! - Windows: Snipping Tool, read back from the clipboard with PowerShell
+
+use crate::error::XaError;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Let the user select a screen region and return it as an `image_url`.
+pub fn capture() -> Result<String, XaError> {
+    let path = std::env::temp_dir().join(format!("xa-screenshot-{}.png", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    eprintln!("Select a region of the screen (Esc to cancel)...");
+    let taken = take(&path);
+    let image = taken.and_then(|()| {
+        if !path.exists() {
+            return Err(XaError::Usage("Screenshot cancelled.".into()));
+        }
+        crate::image::load(&path.to_string_lossy())
+    });
+    let _ = std::fs::remove_file(&path);
+    image
+}
+
+#[cfg(target_os = "macos")]
+fn take(path: &Path) -> Result<(), XaError> {
+    // Exits 0 without writing the file when the selection is cancelled
+    run("screencapture", &["-i".into(), "-x".into(), path_arg(path)])
+}
+
+#[cfg(windows)]
+fn take(path: &Path) -> Result<(), XaError> {
+    run("snippingtool", &["/clip".into()])?;
+    let save = format!(
+        "Add-Type -AssemblyName System.Windows.Forms; $image = [Windows.Forms.Clipboard]::GetImage(); \
+         if ($image) {{ $image.Save('{}') }}",
+        path.display().to_string().replace('\'', "''")
+    );
+    run("powershell", &["-NoProfile".into(), "-STA".into(), "-Command".into(), save])
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn take(path: &Path) -> Result<(), XaError> {
+    let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some_and(|display| !display.is_empty());
+    let tools = unix_tools(wayland);
+    let Some(&(program, args)) = tools.iter().find(|(program, _)| installed(program)) else {
+        let names: Vec<&str> = tools.iter().map(|(program, _)| *program).collect();
+        return Err(XaError::Usage(format!("No screenshot tool found; install one of: {}", names.join(", "))));
+    };
+    if program == "grim" {
+        // slurp prints the selected geometry, and fails when cancelled
+        let output = Command::new("slurp")
+            .stderr(Stdio::inherit())
+            .output()
+            .map_err(|e| XaError::Other(format!("cannot run slurp: {}", e)))?;
+        if !output.status.success() {
+            return Err(XaError::Usage("Screenshot cancelled.".into()));
+        }
+        let geometry = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        return run("grim", &["-g".into(), geometry, path_arg(path)]);
+    }
+    let args: Vec<String> = args.iter().map(|arg| if *arg == "{path}" { path_arg(path) } else { arg.to_string() }).collect();
+    run(program, &args)
+}
+
+/// Region-capture commands in order of preference; `{path}` is the output file.
+#[cfg_attr(any(target_os = "macos", windows), allow(dead_code))]
+fn unix_tools(wayland: bool) -> Vec<(&'static str, &'static [&'static str])> {
+    let mut tools: Vec<(&str, &[&str])> = vec![
+        ("maim", &["-s", "{path}"]),
+        ("scrot", &["-s", "{path}"]),
+        ("gnome-screenshot", &["-a", "-f", "{path}"]),
+        ("import", &["{path}"]),
+    ];
+    if wayland {
+        // X11-only tools see nothing but XWayland windows
+        tools.retain(|(program, _)| *program == "gnome-screenshot");
+        tools.insert(0, ("grim", &["-g", "{geometry}", "{path}"]));
+    }
+    tools
+}
+
+#[cfg_attr(any(target_os = "macos", windows), allow(dead_code))]
+fn installed(program: &str) -> bool {
+    std::env::var_os("PATH").is_some_and(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+}
+
+#[cfg_attr(windows, allow(dead_code))]
+fn path_arg(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+fn run(program: &str, args: &[String]) -> Result<(), XaError> {
+    let status = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .status()
+        .map_err(|e| XaError::Other(format!("cannot run {}: {}", program, e)))?;
+    if !status.success() {
+        return Err(XaError::Usage(format!("Screenshot cancelled ({} exited with {}).", program, status)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefers_grim_on_wayland() {
+        let names = |wayland| unix_tools(wayland).iter().map(|(program, _)| *program).collect::<Vec<_>>();
+        assert_eq!(names(false), ["maim", "scrot", "gnome-screenshot", "import"]);
+        assert_eq!(names(true), ["grim", "gnome-screenshot"]);
+    }
+}
@@ -0,0 +1,149 @@
+//! Web search for `xa ask --search`: the top results of the configured
+//! backend (`[search]` in config.toml) become numbered sources in the
+//! prompt, and the model is asked to cite them as `[n]`.
+//!
+//! DuckDuckGo's Instant Answer API needs no key but only knows topics
+//! with an abstract; SearxNG (an instance with `format=json` enabled) and
+//! Brave return regular web results.
+
+use crate::config::SearchConfig;
+use crate::error::XaError;
+use serde_json::Value;
+
+const DEFAULT_RESULTS: usize = 5;
+
+#[derive(Debug, PartialEq)]
+pub struct SearchResult {
+    pub title: String,
+    pub url: String,
+    pub snippet: String,
+}
+
+/// The top results for `query`.
+pub async fn search(config: &SearchConfig, query: &str) -> Result<Vec<SearchResult>, XaError> {
+    let client = crate::http::client().map_err(XaError::Config)?;
+    let backend = config.backend.as_deref().unwrap_or("duckduckgo");
+    tracing::info!(backend, "searching the web");
+    let request = match backend {
+        "searxng" => {
+            let url = config
+                .url
+                .as_deref()
+                .ok_or_else(|| XaError::Config("search.url is required for the searxng backend".into()))?;
+            client.get(with_query(&format!("{}/search", url), &[("q", query), ("format", "json")])?)
+        }
+        "brave" => {
+            let key = config
+                .api_key
+                .as_deref()
+                .ok_or_else(|| XaError::Config("search.api_key is required for the brave backend".into()))?;
+            client
+                .get(with_query("https://api.search.brave.com/res/v1/web/search", &[("q", query)])?)
+                .header("X-Subscription-Token", key)
+        }
+        "duckduckgo" => client.get(with_query(
+            "https://api.duckduckgo.com/",
+            &[("q", query), ("format", "json"), ("no_html", "1"), ("skip_disambig", "1")],
+        )?),
+        other => return Err(XaError::Config(format!("unknown search backend '{}'", other))),
+    };
+    let response = request.header(reqwest::header::ACCEPT, "application/json").send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(XaError::from_status(status, format!("{} search failed: {}", backend, body.trim())));
+    }
+    let body: Value = response.json().await?;
+    let mut results = parse(backend, &body);
+    results.truncate(config.results.unwrap_or(DEFAULT_RESULTS));
+    Ok(results)
+}
+
+fn with_query(url: &str, params: &[(&str, &str)]) -> Result<reqwest::Url, XaError> {
+    reqwest::Url::parse_with_params(url, params).map_err(|e| XaError::Config(format!("invalid search URL '{}': {}", url, e)))
+}
+
+/// Results out of a backend's JSON response.
+fn parse(backend: &str, body: &Value) -> Vec<SearchResult> {
+    let result = |title: &Value, url: &Value, snippet: &Value| {
+        Some(SearchResult {
+            title: title.as_str()?.trim().to_string(),
+            url: url.as_str().filter(|url| !url.is_empty())?.to_string(),
+            snippet: snippet.as_str().unwrap_or("").trim().to_string(),
+        })
+    };
+    let list = |items: &Value| items.as_array().cloned().unwrap_or_default();
+    match backend {
+        "searxng" => list(&body["results"])
+            .iter()
+            .filter_map(|item| result(&item["title"], &item["url"], &item["content"]))
+            .collect(),
+        "brave" => list(&body["web"]["results"])
+            .iter()
+            .filter_map(|item| result(&item["title"], &item["url"], &item["description"]))
+            .collect(),
+        _ => {
+            let mut results: Vec<SearchResult> =
+                result(&body["Heading"], &body["AbstractURL"], &body["AbstractText"]).into_iter().collect();
+            // Related topics come flat or grouped under a heading
+            let topics = list(&body["RelatedTopics"])
+                .into_iter()
+                .flat_map(|topic| if topic["Topics"].is_array() { list(&topic["Topics"]) } else { vec![topic] });
+            for topic in topics {
+                let text = topic["Text"].as_str().unwrap_or("");
+                let title = Value::from(text.split(" - ").next().unwrap_or(text));
+                results.extend(result(&title, &topic["FirstURL"], &topic["Text"]));
+            }
+            results
+        }
+    }
+}
+
+/// The prompt for `question` grounded in `results`: numbered sources and
+/// the instruction to cite them.
+pub fn grounded_prompt(question: &str, results: &[SearchResult]) -> String {
+    let mut prompt = String::from("Web search results:\n\n");
+    for (i, result) in results.iter().enumerate() {
+        prompt.push_str(&format!("[{}] {}\n{}\n{}\n\n", i + 1, result.title, result.url, result.snippet));
+    }
+    prompt.push_str(
+        "Answer the question below using these results where they help. Cite the results you use \
+         inline as [1], [2], ..., and end with a \"Sources\" list of the cited numbers and URLs. \
+         Say so if the results don't answer it.\n\n",
+    );
+    prompt.push_str(&format!("Question: {}", question));
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_each_backend_and_numbers_sources() {
+        let searx = serde_json::json!({ "results": [
+            { "title": "Rust", "url": "https://rust-lang.org", "content": "A language" },
+            { "title": "No URL", "content": "skipped" },
+        ]});
+        assert_eq!(parse("searxng", &searx).len(), 1);
+
+        let brave = serde_json::json!({ "web": { "results": [
+            { "title": "Tokio", "url": "https://tokio.rs", "description": "Async runtime" },
+        ]}});
+        assert_eq!(parse("brave", &brave)[0].snippet, "Async runtime");
+
+        let ddg = serde_json::json!({
+            "Heading": "Rust", "AbstractURL": "https://en.wikipedia.org/wiki/Rust", "AbstractText": "Rust is...",
+            "RelatedTopics": [
+                { "Text": "Cargo - package manager", "FirstURL": "https://duckduckgo.com/Cargo" },
+                { "Name": "See also", "Topics": [{ "Text": "Ferris", "FirstURL": "https://duckduckgo.com/Ferris" }] },
+            ],
+        });
+        let results = parse("duckduckgo", &ddg);
+        assert_eq!(results.iter().map(|r| r.title.as_str()).collect::<Vec<_>>(), ["Rust", "Cargo", "Ferris"]);
+
+        let prompt = grounded_prompt("what is rust?", &results);
+        assert!(prompt.contains("[2] Cargo\nhttps://duckduckgo.com/Cargo\n"));
+        assert!(prompt.ends_with("Question: what is rust?"));
+    }
+}
@@ -0,0 +1,398 @@
+//! A conversation as a document to share: Markdown, or a standalone HTML
+//! page in the `[theme]` colors. Used by `/export <file>` in the TUI and
+//! `xa sessions export <name>`.
+
+use std::fs;
+use std::path::Path;
+
+use chrono::{Local, TimeZone};
+use crossterm::style::Color;
+
+use super::{Session, StoredMessage};
+use crate::config::ThemeConfig;
+use crate::output::{parse_color, ColorMode};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExportFormat {
+    Markdown,
+    Html,
+}
+
+impl ExportFormat {
+    /// `.html`/`.htm` → HTML, anything else → Markdown.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()).map(str::to_lowercase).as_deref() {
+            Some("html" | "htm") => ExportFormat::Html,
+            _ => ExportFormat::Markdown,
+        }
+    }
+}
+
+/// Write `session` to `path` in the format its extension asks for.
+pub fn write(session: &Session, path: &Path, theme: &ThemeConfig, mode: ColorMode) -> std::io::Result<()> {
+    if let Some(parent) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let document = match ExportFormat::from_path(path) {
+        ExportFormat::Markdown => to_markdown(session),
+        ExportFormat::Html => to_html(session, theme, mode),
+    };
+    fs::write(path, document)
+}
+
+/// One block of the exported conversation. Consecutive messages of one
+/// speaker share a heading; tool calls and their output belong to the
+/// assistant's turn.
+enum Part<'a> {
+    Heading(&'static str),
+    Text(&'a str),
+    ToolCall { name: &'a str, arguments: &'a str },
+    ToolOutput { name: &'a str, output: &'a str },
+}
+
+fn parts(messages: &[StoredMessage]) -> Vec<Part<'_>> {
+    let mut parts = Vec::new();
+    let mut speaker = "";
+    for message in messages {
+        let heading = match message.role.as_str() {
+            "user" => "You",
+            "assistant" | "tool" => "Assistant",
+            // The system prompt is the same in every session
+            _ => continue,
+        };
+        if heading != speaker {
+            parts.push(Part::Heading(heading));
+            speaker = heading;
+        }
+        if message.role == "tool" {
+            let name = messages
+                .iter()
+                .flat_map(|m| m.tool_calls.iter().flatten())
+                .find(|call| Some(&call.id) == message.tool_call_id.as_ref())
+                .map_or("tool", |call| call.name.as_str());
+            parts.push(Part::ToolOutput { name, output: message.content.trim_end() });
+            continue;
+        }
+        if !message.content.trim().is_empty() {
+            parts.push(Part::Text(message.content.trim()));
+        }
+        for call in message.tool_calls.iter().flatten() {
+            parts.push(Part::ToolCall { name: &call.name, arguments: &call.arguments });
+        }
+    }
+    parts
+}
+
+fn local_time(millis: i64) -> String {
+    Local.timestamp_millis_opt(millis).single().map_or_else(String::new, |time| time.format("%Y-%m-%d %H:%M").to_string())
+}
+
+/// The lines under the title: model, when, and how big.
+fn details(session: &Session) -> Vec<(&'static str, String)> {
+    let mut details = vec![
+        ("Model", format!("{} ({})", session.model, session.provider)),
+        ("Started", local_time(session.created)),
+        ("Last message", local_time(session.updated)),
+    ];
+    let usage = session.api_token_usage;
+    if usage.total_tokens > 0 {
+        details.push(("Tokens", format!("{} in, {} out", usage.prompt_tokens, usage.completion_tokens)));
+    }
+    details
+}
+
+/// A code fence longer than any backtick run in `text`.
+fn fence(text: &str) -> String {
+    let longest = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+pub fn to_markdown(session: &Session) -> String {
+    let mut out = format!("# {}\n\n", session.title);
+    for (label, value) in details(session) {
+        out.push_str(&format!("- **{}:** {}\n", label, value));
+    }
+    out.push_str("\n---\n");
+    for part in parts(&session.messages) {
+        match part {
+            Part::Heading(heading) => out.push_str(&format!("\n## {}\n", heading)),
+            Part::Text(text) => out.push_str(&format!("\n{}\n", text)),
+            Part::ToolCall { name, arguments } => {
+                out.push_str(&format!("\n**Tool call:** `{}` {}\n", name, arguments.trim()));
+            }
+            Part::ToolOutput { name, output } => {
+                let fence = fence(output);
+                out.push_str(&format!("\n<details><summary>Output of <code>{}</code></summary>\n\n{}text\n{}\n{}\n\n</details>\n", name, fence, output, fence));
+            }
+        }
+    }
+    out
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// `#rrggbb` for a `[theme]` color, using the xterm palette for numbered
+/// ones.
+fn css_color(value: &str) -> Option<String> {
+    const BASIC: [(u8, u8, u8); 16] = [
+        (0, 0, 0), (205, 0, 0), (0, 205, 0), (205, 205, 0), (0, 0, 238), (205, 0, 205), (0, 205, 205), (229, 229, 229),
+        (127, 127, 127), (255, 0, 0), (0, 255, 0), (255, 255, 0), (92, 92, 255), (255, 0, 255), (0, 255, 255), (255, 255, 255),
+    ];
+    let (r, g, b) = match parse_color(value)? {
+        Color::Rgb { r, g, b } => (r, g, b),
+        Color::AnsiValue(n @ 0..=15) => BASIC[n as usize],
+        Color::AnsiValue(n @ 16..=231) => {
+            let level = |i: u8| if i == 0 { 0 } else { 55 + 40 * i };
+            let n = n - 16;
+            (level(n / 36), level(n / 6 % 6), level(n % 6))
+        }
+        Color::AnsiValue(n) => {
+            let gray = 8 + 10 * (n - 232);
+            (gray, gray, gray)
+        }
+        _ => return None,
+    };
+    Some(format!("#{:02x}{:02x}{:02x}", r, g, b))
+}
+
+/// Page colors: the mode's background and text, and the Markdown colors of
+/// the preset (the one for the mode unless `theme.preset` says otherwise)
+/// with the `[theme]` overrides on top.
+struct Palette {
+    background: &'static str,
+    text: &'static str,
+    muted: &'static str,
+    headers: String,
+    bold: String,
+    italic: String,
+    code: String,
+    quote: String,
+}
+
+impl Palette {
+    fn new(theme: &ThemeConfig, mode: ColorMode) -> Palette {
+        let (background, text, muted) = match mode {
+            ColorMode::Dark => ("#1e1e1e", "#d4d4d4", "#2d2d2d"),
+            ColorMode::Light => ("#ffffff", "#1f1f1f", "#eeeeee"),
+        };
+        // The same numbers as the one-shot Markdown skin: headers, bold,
+        // italic, code, quote
+        const DARK: [&str; 5] = ["214", "33", "36", "35", "244"];
+        const LIGHT: [&str; 5] = ["25", "130", "24", "90", "244"];
+        let preset = match theme.preset.as_deref() {
+            Some("plain") => [text; 5],
+            Some("light") => LIGHT,
+            Some("dark") => DARK,
+            _ if mode == ColorMode::Light => LIGHT,
+            _ => DARK,
+        };
+        let pick = |value: &Option<String>, default: &str| {
+            value.as_deref().and_then(css_color).or_else(|| css_color(default)).unwrap_or_else(|| text.to_string())
+        };
+        Palette {
+            background,
+            text,
+            muted,
+            headers: pick(&theme.headers, preset[0]),
+            bold: pick(&theme.bold, preset[1]),
+            italic: pick(&theme.italic, preset[2]),
+            code: pick(&theme.code, preset[3]),
+            quote: pick(&theme.quote, preset[4]),
+        }
+    }
+
+    fn css(&self) -> String {
+        format!(
+            "body {{ background: {bg}; color: {text}; font: 15px/1.6 system-ui, sans-serif; max-width: 50rem; margin: 2rem auto; padding: 0 1rem; }}\n\
+             h1, h2, h3, h4 {{ color: {headers}; }}\n\
+             h2 {{ border-bottom: 1px solid {muted}; padding-bottom: .2rem; }}\n\
+             strong {{ color: {bold}; }}\n\
+             em {{ color: {italic}; }}\n\
+             code {{ color: {code}; font-family: ui-monospace, monospace; }}\n\
+             pre {{ background: {muted}; padding: .75rem; overflow-x: auto; }}\n\
+             blockquote {{ border-left: 3px solid {quote}; margin-left: 0; padding-left: 1rem; }}\n\
+             .details {{ opacity: .75; }}\n",
+            bg = self.background,
+            text = self.text,
+            muted = self.muted,
+            headers = self.headers,
+            bold = self.bold,
+            italic = self.italic,
+            code = self.code,
+            quote = self.quote,
+        )
+    }
+}
+
+/// Inline Markdown: code spans, then bold and italic outside them.
+fn inline_html(text: &str) -> String {
+    let mut out = String::new();
+    for (i, piece) in text.split('`').enumerate() {
+        if i % 2 == 1 {
+            out.push_str(&format!("<code>{}</code>", escape(piece)));
+            continue;
+        }
+        let mut piece = escape(piece);
+        for (marker, tag) in [("**", "strong"), ("*", "em")] {
+            let runs: Vec<&str> = piece.split(marker).collect();
+            if runs.len() < 3 {
+                continue;
+            }
+            let mut joined = String::new();
+            for (j, run) in runs.iter().enumerate() {
+                // An unpaired last marker stays as it was
+                match j {
+                    0 => {}
+                    _ if j % 2 == 1 && j + 1 == runs.len() => joined.push_str(marker),
+                    _ if j % 2 == 1 => joined.push_str(&format!("<{}>", tag)),
+                    _ => joined.push_str(&format!("</{}>", tag)),
+                }
+                joined.push_str(run);
+            }
+            piece = joined;
+        }
+        out.push_str(&piece);
+    }
+    out
+}
+
+/// Block Markdown: fenced code, headings, quotes, list items and
+/// paragraphs. Anything fancier stays as text.
+fn markdown_html(text: &str) -> String {
+    let mut out = String::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut in_list = false;
+    let mut code: Option<Vec<&str>> = None;
+    let flush = |out: &mut String, paragraph: &mut Vec<&str>| {
+        if !paragraph.is_empty() {
+            out.push_str(&format!("<p>{}</p>\n", paragraph.iter().map(|line| inline_html(line)).collect::<Vec<_>>().join("<br>\n")));
+            paragraph.clear();
+        }
+    };
+    for line in text.lines() {
+        if let Some(block) = code.as_mut() {
+            if line.trim_start().starts_with("```") {
+                out.push_str(&format!("<pre><code>{}</code></pre>\n", escape(&block.join("\n"))));
+                code = None;
+            } else {
+                block.push(line);
+            }
+            continue;
+        }
+        let trimmed = line.trim_start();
+        let item = trimmed.strip_prefix("- ").or_else(|| trimmed.strip_prefix("* "));
+        if in_list && item.is_none() {
+            out.push_str("</ul>\n");
+            in_list = false;
+        }
+        if trimmed.starts_with("```") {
+            flush(&mut out, &mut paragraph);
+            code = Some(Vec::new());
+        } else if let Some(item) = item {
+            flush(&mut out, &mut paragraph);
+            if !in_list {
+                out.push_str("<ul>\n");
+                in_list = true;
+            }
+            out.push_str(&format!("<li>{}</li>\n", inline_html(item)));
+        } else if let Some(quote) = trimmed.strip_prefix('>') {
+            flush(&mut out, &mut paragraph);
+            out.push_str(&format!("<blockquote>{}</blockquote>\n", inline_html(quote.trim_start())));
+        } else if trimmed.starts_with('#') && trimmed.trim_start_matches('#').starts_with(' ') {
+            flush(&mut out, &mut paragraph);
+            // Headings inside a message rank below the speaker's
+            let level = (trimmed.len() - trimmed.trim_start_matches('#').len() + 2).min(6);
+            out.push_str(&format!("<h{0}>{1}</h{0}>\n", level, inline_html(trimmed.trim_start_matches('#').trim())));
+        } else if trimmed.is_empty() {
+            flush(&mut out, &mut paragraph);
+        } else {
+            paragraph.push(line);
+        }
+    }
+    flush(&mut out, &mut paragraph);
+    if in_list {
+        out.push_str("</ul>\n");
+    }
+    if let Some(block) = code {
+        out.push_str(&format!("<pre><code>{}</code></pre>\n", escape(&block.join("\n"))));
+    }
+    out
+}
+
+pub fn to_html(session: &Session, theme: &ThemeConfig, mode: ColorMode) -> String {
+    let title = escape(&session.title);
+    let mut body = format!("<h1>{}</h1>\n<ul class=\"details\">\n", title);
+    for (label, value) in details(session) {
+        body.push_str(&format!("<li><strong>{}:</strong> {}</li>\n", label, escape(&value)));
+    }
+    body.push_str("</ul>\n");
+    for part in parts(&session.messages) {
+        match part {
+            Part::Heading(heading) => body.push_str(&format!("<h2>{}</h2>\n", heading)),
+            Part::Text(text) => body.push_str(&markdown_html(text)),
+            Part::ToolCall { name, arguments } => {
+                body.push_str(&format!("<p><strong>Tool call:</strong> <code>{}</code> <code>{}</code></p>\n", escape(name), escape(arguments.trim())));
+            }
+            Part::ToolOutput { name, output } => {
+                body.push_str(&format!(
+                    "<details><summary>Output of <code>{}</code></summary>\n<pre><code>{}</code></pre>\n</details>\n",
+                    escape(name),
+                    escape(output)
+                ));
+            }
+        }
+    }
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        title,
+        Palette::new(theme, mode).css(),
+        body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::StoredToolCall;
+
+    fn message(role: &str, content: &str) -> StoredMessage {
+        StoredMessage { role: role.into(), content: content.into(), tool_calls: None, tool_call_id: None }
+    }
+
+    #[test]
+    fn exports_markdown_and_themed_html() {
+        let mut session = Session::new("openai", "gpt-4o");
+        session.title = "Standup <notes>".into();
+        let mut call = message("assistant", "");
+        call.tool_calls = Some(vec![StoredToolCall { id: "c1".into(), name: "read".into(), arguments: "{\"path\":\"a.md\"}".into() }]);
+        let mut output = message("tool", "has ``` inside");
+        output.tool_call_id = Some("c1".into());
+        session.messages = vec![
+            message("system", "You are xa"),
+            message("user", "Summarize a.md"),
+            call,
+            output,
+            message("assistant", "## Summary\n\n- **ship** on *Friday*\n- use `x < y`"),
+        ];
+
+        let markdown = to_markdown(&session);
+        assert!(markdown.starts_with("# Standup <notes>\n\n- **Model:** gpt-4o (openai)\n"));
+        assert!(!markdown.contains("You are xa"));
+        assert_eq!(markdown.matches("## Assistant").count(), 1);
+        assert!(markdown.contains("**Tool call:** `read` {\"path\":\"a.md\"}"));
+        assert!(markdown.contains("````text\nhas ``` inside\n````"));
+
+        let theme = ThemeConfig { bold: Some("#ff0000".into()), ..ThemeConfig::default() };
+        let html = to_html(&session, &theme, ColorMode::Light);
+        assert!(html.contains("<title>Standup &lt;notes&gt;</title>"));
+        assert!(html.contains("background: #ffffff"));
+        assert!(html.contains("strong { color: #ff0000; }"));
+        assert!(html.contains("h1, h2, h3, h4 { color: #005faf; }"));
+        assert!(html.contains("<h4>Summary</h4>"));
+        assert!(html.contains("<li><strong>ship</strong> on <em>Friday</em></li>\n<li>use <code>x &lt; y</code></li>"));
+        assert_eq!(ExportFormat::from_path(Path::new("notes.HTML")), ExportFormat::Html);
+        assert_eq!(ExportFormat::from_path(Path::new("notes")), ExportFormat::Markdown);
+    }
+}
@@ -1,14 +1,16 @@
 //! Minimal session persistence for `xa`, inspired by pi_agent_rust's session
 //! model (one file per session + metadata for fast listing) but kept simple:
-//! each session is a single JSON file under `config_dir()/xa/sessions`.
+//! each session is a single JSON file under `xa_dir()/sessions`.
 
 use std::fs;
 use std::collections::BTreeMap;
 use std::path::PathBuf;
 
-use dirs::config_dir;
+use crate::config::xa_dir;
 use serde::{Deserialize, Serialize};
 
+pub mod export;
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct StoredToolCall {
     pub id: String,
@@ -51,6 +53,9 @@ pub struct SessionSummary {
     pub title: String,
     pub model: String,
     pub updated: i64,
+    /// Size of the session file, filled in by [`list_summaries`].
+    #[serde(skip)]
+    pub bytes: u64,
 }
 
 /// Session data used by `xa gain`; message bodies are intentionally omitted.
@@ -64,8 +69,8 @@ pub struct GainSessionRecord {
 }
 
 fn sessions_dir() -> PathBuf {
-    config_dir()
-        .map(|d| d.join("xa").join("sessions"))
+    xa_dir()
+        .map(|d| d.join("sessions"))
         .unwrap_or_else(|| PathBuf::from(".xa/sessions"))
 }
 
@@ -89,8 +94,9 @@ pub fn list_summaries() -> Vec<SessionSummary> {
             let path = entry.path();
             if path.extension().is_some_and(|ext| ext == "json") {
                 if let Ok(file) = fs::File::open(path) {
-                    if let Ok(summary) = serde_json::from_reader(file) {
-                        out.push(summary);
+                    let bytes = file.metadata().map_or(0, |meta| meta.len());
+                    if let Ok(summary) = serde_json::from_reader::<_, SessionSummary>(file) {
+                        out.push(SessionSummary { bytes, ..summary });
                     }
                 }
             }
@@ -130,6 +136,15 @@ pub fn relative_time(timestamp_ms: i64) -> String {
     }
 }
 
+/// Human-readable file size, e.g. `4.2 KB`.
+pub fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..=1_023 => format!("{bytes} B"),
+        1_024..=1_048_575 => format!("{:.1} KB", bytes as f64 / 1_024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
+
 /// Persist a session (creating the directory if needed).
 pub fn save(session: &Session) -> std::io::Result<()> {
     let dir = sessions_dir();
@@ -150,6 +165,95 @@ pub fn load(id: &str) -> Option<Session> {
         })
 }
 
+/// A session by id, id prefix or title (case-insensitive; the newest of
+/// several with that title).
+pub fn find(name: &str) -> Option<Session> {
+    if let Some(session) = load(name) {
+        return Some(session);
+    }
+    let summaries = list_summaries();
+    let matching = summaries
+        .iter()
+        .find(|summary| summary.title.eq_ignore_ascii_case(name))
+        .or_else(|| summaries.iter().find(|summary| summary.id.starts_with(name)))?;
+    load(&matching.id)
+}
+
+/// The file naming the rolling session of `xa ask -c`.
+fn last_ask_path() -> PathBuf {
+    sessions_dir().join("last-ask")
+}
+
+/// The session `xa ask -c` last continued, if it still exists.
+pub fn last_ask() -> Option<Session> {
+    fs::read_to_string(last_ask_path()).ok().and_then(|id| load(id.trim()))
+}
+
+/// Remember `id` as the session the next `xa ask -c` continues.
+pub fn set_last_ask(id: &str) -> std::io::Result<()> {
+    fs::create_dir_all(sessions_dir())?;
+    fs::write(last_ask_path(), id)
+}
+
+/// Titles longer than this are cut, with an ellipsis.
+const MAX_TITLE: usize = 44;
+
+/// `text` on one line and at most [`MAX_TITLE`] characters long.
+fn clip_title(text: &str) -> String {
+    let line = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match line.char_indices().nth(MAX_TITLE) {
+        Some((end, _)) => format!("{}...", line[..end].trim_end()),
+        None => line,
+    }
+}
+
+/// The title a session gets before (or without) a generated one: the first
+/// line of its first question.
+pub fn fallback_title(question: &str) -> String {
+    clip_title(question.lines().find(|line| !line.trim().is_empty()).unwrap_or(""))
+}
+
+/// How much of each turn the title request quotes.
+const MAX_TITLE_CONTEXT: usize = 1500;
+
+fn title_prompt(messages: &[StoredMessage]) -> String {
+    let turns: Vec<String> = messages
+        .iter()
+        .filter(|message| matches!(message.role.as_str(), "user" | "assistant") && !message.content.trim().is_empty())
+        .take(4)
+        .map(|message| {
+            let content: String = message.content.chars().take(MAX_TITLE_CONTEXT).collect();
+            format!("{}: {}", if message.role == "user" { "User" } else { "Assistant" }, content.trim())
+        })
+        .collect();
+    format!(
+        "You name saved conversations so they are easy to find in a list.\n\nRules:\n- Return JSON only.\n- JSON schema: {{\"title\": string}}.\n- title is 3-6 words in the language of the conversation, naming its subject (\"Rust lifetimes in iterators\", not \"Question about code\").\n- No quotes and no final period.\n\nConversation:\n{}\n\nReturn JSON only.",
+        turns.join("\n\n")
+    )
+}
+
+/// The title in the model's answer, cleaned up; `None` when there is none.
+fn parse_title(answer: &str) -> Option<String> {
+    let json = &answer[answer.find('{')?..=answer.rfind('}')?];
+    let title = serde_json::from_str::<serde_json::Value>(json).ok()?["title"].as_str()?.to_string();
+    let title = clip_title(title.trim().trim_matches(|c: char| matches!(c, '"' | '\'' | '`' | '.') || c.is_whitespace()));
+    (!title.is_empty()).then_some(title)
+}
+
+/// A short title for the conversation in `session`, from one extra
+/// non-streaming request; `None` when it fails or has no usable title, so
+/// the caller keeps [`fallback_title`].
+pub async fn generate_title(config: &crate::config::Config, session: &Session) -> Option<String> {
+    let answer = crate::llm::complete(config, &title_prompt(&session.messages)).await;
+    match answer {
+        Ok(completion) => parse_title(&completion.text),
+        Err(error) => {
+            tracing::debug!(error = %error, "session title request failed");
+            None
+        }
+    }
+}
+
 /// Permanently remove a saved session by id.
 pub fn delete(id: &str) -> std::io::Result<()> {
     fs::remove_file(path_for(id))
@@ -257,6 +361,26 @@ impl Session {
     pub fn touch(&mut self) {
         self.updated = chrono::Utc::now().timestamp_millis();
     }
+
+    /// The user and assistant turns so far, in the form one-shot prompts
+    /// carry a conversation: "Previous conversation:" and then `User:` and
+    /// `Assistant:` lines. Empty for a session with no turns yet.
+    pub fn transcript(&self) -> String {
+        let turns: Vec<String> = self
+            .messages
+            .iter()
+            .filter(|message| !message.content.is_empty())
+            .filter_map(|message| match message.role.as_str() {
+                "user" => Some(format!("User: {}\n", message.content)),
+                "assistant" => Some(format!("Assistant: {}\n", message.content)),
+                _ => None,
+            })
+            .collect();
+        if turns.is_empty() {
+            return String::new();
+        }
+        format!("Previous conversation:\n{}\n", turns.concat())
+    }
 }
 
 #[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
@@ -356,6 +480,13 @@ mod tests {
         assert_eq!(session.messages[2].role, "user");
     }
 
+    #[test]
+    fn formats_session_file_sizes() {
+        assert_eq!(format_size(812), "812 B");
+        assert_eq!(format_size(4_300), "4.2 KB");
+        assert_eq!(format_size(3 * 1_048_576 / 2), "1.5 MB");
+    }
+
     #[test]
     fn empty_sessions_are_not_conversations() {
         let mut session = Session::new("test", "test");
@@ -366,6 +497,25 @@ mod tests {
         assert!(session.has_user_message());
     }
 
+    #[test]
+    fn titles_sessions_from_the_model_or_first_question() {
+        assert_eq!(parse_title("```json\n{\"title\": \" \\\"Rust lifetimes in iterators.\\\" \"}\n```").as_deref(), Some("Rust lifetimes in iterators"));
+        assert_eq!(parse_title("{\"title\": \"\"}"), None);
+        assert_eq!(parse_title("Rust lifetimes"), None);
+        assert_eq!(fallback_title("\nhow do I   reverse a linked list in place without allocating?\nthanks"), "how do I reverse a linked list in place with...");
+        let mut session = Session::new("test", "test");
+        session.messages = vec![message("user", "What is 2+2?"), message("tool", "ignored"), message("assistant", "4")];
+        assert!(title_prompt(&session.messages).ends_with("Conversation:\nUser: What is 2+2?\n\nAssistant: 4\n\nReturn JSON only."));
+    }
+
+    #[test]
+    fn transcribes_turns_for_one_shot_prompts() {
+        let mut session = Session::new("test", "test");
+        assert_eq!(session.transcript(), "");
+        session.messages = vec![message("user", "What is 2+2?"), message("tool", "ignored"), message("assistant", "4"), message("user", "Double it")];
+        assert_eq!(session.transcript(), "Previous conversation:\nUser: What is 2+2?\nAssistant: 4\nUser: Double it\n\n");
+    }
+
     #[test]
     fn aggregates_persisted_output_savings() {
         let mut session = Session::new("test", "test");
@@ -0,0 +1,202 @@
+//! Shell integration for `xa fix` and `xa why`. The hook printed by
+//! `xa init <shell>` exports the last command line and its exit status
+//! (`XA_LAST_COMMAND`, `XA_LAST_STATUS`) before each prompt; `xa fix` asks
+//! the model for a corrected command and runs it once confirmed, `xa why`
+//! explains what went wrong.
+//!
+//! The hook cannot see a command's output. `--rerun` runs the command again
+//! to capture it, which is why it is never done without being asked.
+
+use crate::error::XaError;
+use std::process::{ExitStatus, Stdio};
+use std::time::Duration;
+
+/// How long `--rerun` lets the command run.
+const RERUN_TIMEOUT: Duration = Duration::from_secs(10);
+/// Output kept from `--rerun`: the end, where errors usually are.
+const MAX_OUTPUT: usize = 4000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, clap::ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// The snippet `xa init <shell>` prints, to be evaluated from the shell's
+/// startup file. Runs of `xa fix` and `xa why` are not recorded, so they
+/// can be repeated.
+pub fn init_script(shell: Shell) -> &'static str {
+    match shell {
+        Shell::Bash => {
+            r#"# xa shell integration: eval "$(xa init bash)" in ~/.bashrc
+__xa_precmd() {
+    local code=$? cmd
+    cmd=$(HISTTIMEFORMAT= builtin fc -ln -1 2>/dev/null)
+    cmd=${cmd#"${cmd%%[![:space:]]*}"}
+    case $cmd in
+        "xa fix"*|"xa why"*) ;;
+        *) export XA_LAST_COMMAND=$cmd XA_LAST_STATUS=$code ;;
+    esac
+    return $code
+}
+PROMPT_COMMAND="__xa_precmd${PROMPT_COMMAND:+;$PROMPT_COMMAND}"
+"#
+        }
+        Shell::Zsh => {
+            r#"# xa shell integration: eval "$(xa init zsh)" in ~/.zshrc
+__xa_preexec() { __xa_command=$1 }
+__xa_precmd() {
+    local code=$?
+    case $__xa_command in
+        ""|"xa fix"*|"xa why"*) ;;
+        *) export XA_LAST_COMMAND=$__xa_command XA_LAST_STATUS=$code ;;
+    esac
+    __xa_command=
+}
+precmd_functions=(__xa_precmd $precmd_functions)
+preexec_functions+=(__xa_preexec)
+"#
+        }
+        Shell::Fish => {
+            r#"# xa shell integration: xa init fish | source in ~/.config/fish/config.fish
+function __xa_postexec --on-event fish_postexec
+    set -l code $status
+    switch $argv[1]
+        case '' 'xa fix*' 'xa why*'
+        case '*'
+            set -gx XA_LAST_COMMAND $argv[1]
+            set -gx XA_LAST_STATUS $code
+    end
+end
+"#
+        }
+    }
+}
+
+/// The command `xa fix` and `xa why` are about.
+pub struct LastCommand {
+    pub command: String,
+    pub status: i32,
+    /// What it printed, when captured with `--rerun`.
+    pub output: Option<String>,
+}
+
+impl LastCommand {
+    /// The command recorded by the shell hook.
+    pub fn from_env() -> Result<LastCommand, XaError> {
+        let command = std::env::var("XA_LAST_COMMAND").unwrap_or_default();
+        if command.trim().is_empty() {
+            return Err(XaError::Usage(
+                "No previous command recorded. Add the shell hook first, e.g. eval \"$(xa init zsh)\" in ~/.zshrc".into(),
+            ));
+        }
+        let status = std::env::var("XA_LAST_STATUS").ok().and_then(|s| s.trim().parse().ok()).unwrap_or(0);
+        Ok(LastCommand { command: command.trim().to_string(), status, output: None })
+    }
+
+    /// Run the command again to capture its output (stdout and stderr,
+    /// interleaved as far as pipes allow) and exit status.
+    pub async fn rerun(&mut self) -> Result<(), XaError> {
+        let mut child = shell_command(&self.command);
+        child.stdin(Stdio::null()).kill_on_drop(true);
+        let output = tokio::time::timeout(RERUN_TIMEOUT, child.output())
+            .await
+            .map_err(|_| XaError::Other(format!("`{}` was still running after {}s; stopped it", self.command, RERUN_TIMEOUT.as_secs())))?
+            .map_err(|e| XaError::Other(format!("cannot run `{}`: {}", self.command, e)))?;
+        let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+        text.push_str(&String::from_utf8_lossy(&output.stderr));
+        self.status = output.status.code().unwrap_or(self.status);
+        self.output = Some(tail(text.trim_end(), MAX_OUTPUT).to_string());
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        let mut text = format!("Command: {}\nExit status: {}\n", self.command, self.status);
+        if let Some(output) = &self.output {
+            text.push_str(&format!("Output:\n```\n{}\n```\n", output));
+        }
+        text
+    }
+}
+
+/// The question `xa fix` asks.
+pub fn fix_prompt(last: &LastCommand) -> String {
+    format!(
+        "A shell command failed. Reply with the corrected command only, in a single ```sh code block, \
+         with no explanation. If it cannot be fixed, reply with an empty code block.\n\nShell: {}\n{}",
+        shell_name(),
+        last.describe()
+    )
+}
+
+/// The question `xa why` asks.
+pub fn why_prompt(last: &LastCommand) -> String {
+    let outcome = if last.status == 0 { "did not do what I expected" } else { "failed" };
+    format!(
+        "This shell command {}. Explain briefly why, and how to fix it.\n\nShell: {}\n{}",
+        outcome,
+        shell_name(),
+        last.describe()
+    )
+}
+
+/// The command in `xa fix`'s answer, or `None` when the model gave up.
+pub fn parse_fix(answer: &str) -> Option<String> {
+    let command = crate::postprocess::extract_code_block(answer).unwrap_or_else(|| answer.to_string());
+    let command = command.trim().trim_matches('`').trim();
+    (!command.is_empty()).then(|| command.to_string())
+}
+
+/// Run `command` in the user's shell, sharing xa's terminal.
+pub async fn run(command: &str) -> Result<ExitStatus, XaError> {
+    shell_command(command).status().await.map_err(|e| XaError::Other(format!("cannot run `{}`: {}", command, e)))
+}
+
+fn shell_command(command: &str) -> tokio::process::Command {
+    #[cfg(windows)]
+    let mut shell = tokio::process::Command::new("cmd");
+    #[cfg(windows)]
+    shell.args(["/C", command]);
+    #[cfg(not(windows))]
+    let mut shell = tokio::process::Command::new(std::env::var("SHELL").unwrap_or_else(|_| "sh".into()));
+    #[cfg(not(windows))]
+    shell.args(["-c", command]);
+    shell
+}
+
+fn shell_name() -> String {
+    if cfg!(windows) {
+        return "cmd".into();
+    }
+    std::env::var("SHELL")
+        .ok()
+        .and_then(|path| path.rsplit('/').next().map(str::to_string))
+        .unwrap_or_else(|| "sh".into())
+}
+
+/// The last `max` bytes of `text`, cut at a character boundary.
+fn tail(text: &str, max: usize) -> &str {
+    let mut start = text.len().saturating_sub(max);
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    &text[start..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_suggested_command() {
+        assert_eq!(parse_fix("```sh\ngit push -u origin main\n```").as_deref(), Some("git push -u origin main"));
+        assert_eq!(parse_fix("`ls -la`").as_deref(), Some("ls -la"));
+        assert_eq!(parse_fix("```sh\n```"), None);
+        assert_eq!(tail("héllo", 4), "llo");
+
+        let last = LastCommand { command: "gti status".into(), status: 127, output: Some("gti: command not found".into()) };
+        let prompt = fix_prompt(&last);
+        assert!(prompt.contains("Command: gti status\nExit status: 127\nOutput:\n```\ngti: command not found\n```"), "{}", prompt);
+    }
+}
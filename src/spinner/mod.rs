@@ -0,0 +1,72 @@
+//! Activity indicator for one-shot calls that print nothing until the whole
+//! answer is back (`--no-stream`, secret tagging).
+//!
+//! indicatif draws it on stderr from its own ticker thread, so it keeps
+//! turning while the runtime waits on the request, and hides it when stderr
+//! is not a terminal — piped or redirected runs see no escape codes at all.
+
+use indicatif::{ProgressBar, ProgressState, ProgressStyle};
+use std::fmt::Write;
+use std::time::Duration;
+
+/// The last entry is indicatif's "finished" frame, never shown since the
+/// spinner is always cleared.
+const FRAMES: &[&str] = &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏", " "];
+const TEMPLATE: &str = "{spinner:.cyan} {msg}… {secs:.dim}";
+const TICK: Duration = Duration::from_millis(80);
+
+/// Spins until stopped or dropped; either way its line is erased first.
+pub struct Spinner {
+    bar: ProgressBar,
+}
+
+impl Spinner {
+    /// Start spinning next to `message` ("Processing").
+    pub fn start(message: &str) -> Spinner {
+        let bar = ProgressBar::new_spinner();
+        bar.set_style(style());
+        bar.set_message(message.to_string());
+        bar.enable_steady_tick(TICK);
+        Spinner { bar }
+    }
+
+    /// Stop and erase the spinner; output written afterwards starts on a
+    /// clean line.
+    pub fn stop(self) {}
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        self.bar.finish_and_clear();
+    }
+}
+
+fn style() -> ProgressStyle {
+    ProgressStyle::with_template(TEMPLATE)
+        .expect("spinner template is valid")
+        .tick_strings(FRAMES)
+        .with_key("secs", |state: &ProgressState, w: &mut dyn Write| {
+            let _ = w.write_str(&seconds(state.elapsed()));
+        })
+}
+
+/// The elapsed time as shown next to the message: "2.3s".
+fn seconds(elapsed: Duration) -> String {
+    format!("{:.1}s", elapsed.as_secs_f64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stopping_clears_the_spinner_and_shows_elapsed_seconds() {
+        let spinner = Spinner::start("Processing");
+        let bar = spinner.bar.clone();
+        assert_eq!(bar.message(), "Processing");
+        assert!(!bar.is_finished());
+        spinner.stop();
+        assert!(bar.is_finished());
+        assert_eq!(seconds(Duration::from_millis(2300)), "2.3s");
+    }
+}
@@ -1,10 +1,15 @@
 use crate::config::Config;
+use crate::embeddings;
 use crate::llm::process_with_llm;
 use chrono::Utc;
-use dirs::config_dir;
+use crate::error::XaError;
+use crate::config::{lock, safe_write, xa_dir};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 use std::fs;
+use std::path::PathBuf;
+
+pub mod sync;
 
 #[derive(Serialize, Deserialize, Clone, Default)]
 pub struct StoreConfig {
@@ -14,29 +19,61 @@ pub struct StoreConfig {
 #[derive(Serialize, Deserialize, Clone)]
 pub struct StoreEntry {
     pub id: u64,
+    /// What the entry holds; entries from before there were kinds are secrets.
+    #[serde(default)]
+    pub kind: EntryKind,
     pub tag: String,
+    /// What the entry is for, as given when it was added.
     pub note: String,
-    pub secret: String,
+    /// The stored text itself: the secret, note, snippet or URL.
+    #[serde(alias = "secret")]
+    pub value: String,
     pub created_at: String,
+    /// When the value, note or tag last changed; `xa store sync` keeps the
+    /// newer of two versions of an entry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
+}
+
+/// The kinds of store entries. Only secrets are kept from the model and
+/// hidden in listings; the others are shown and printed as they are.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    #[default]
+    Secret,
+    Note,
+    Snippet,
+    Url,
 }
 
-pub async fn list_stores() -> Result<(), Box<dyn std::error::Error>> {
-    let config_dir = config_dir()
-        .ok_or("Could not determine config directory")?
-        .join("xa");
+impl EntryKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            EntryKind::Secret => "secret",
+            EntryKind::Note => "note",
+            EntryKind::Snippet => "snippet",
+            EntryKind::Url => "url",
+        }
+    }
+}
+
+pub async fn list_stores() -> Result<(), XaError> {
+    let config_dir = xa_dir()
+        .ok_or_else(|| XaError::Config("Could not determine config directory".into()))?;
     
     let store_file = config_dir.join("stores.toml");
     
     let store = load_store()?;
     
-    println!("Stored secrets:");
+    println!("Stored entries:");
     println!("Config directory: {:?}", config_dir);
     println!("Store file: {:?}", store_file);
     println!();
     
     if store.entries.is_empty() {
-        println!("No secrets stored yet.");
-        println!("Use 'xa add <secret> <note>' to add a secret.");
+        println!("Nothing stored yet.");
+        println!("Use 'xa add-secret <secret> <note>' to add a secret, or 'xa add --note <text> <note>' (--snippet, --url) for the rest.");
         return Ok(());
     }
     
@@ -44,10 +81,13 @@ pub async fn list_stores() -> Result<(), Box<dyn std::error::Error>> {
     println!();
     
     for entry in &store.entries {
-        println!("[{}]", entry.tag);
+        println!("[{}] {}", entry.tag, entry.kind.name());
         println!("  Note: {}", entry.note);
         println!("  Created: {}", entry.created_at);
-        println!("  Secret: ***hidden***");
+        match entry.kind {
+            EntryKind::Secret => println!("  Secret: ***hidden***"),
+            _ => println!("  Value: {}", entry.value.replace('\n', "\n         ")),
+        }
         println!();
     }
     
@@ -62,21 +102,88 @@ struct TagResponse {
 
 #[derive(Serialize, Deserialize)]
 struct SearchResponse {
+    /// The matching entries, best first.
+    #[serde(default)]
+    ids: Vec<u64>,
+    /// The one-match form of older prompts: `found` and `id`.
+    #[serde(default)]
     found: bool,
     id: Option<u64>,
     reason: Option<String>,
 }
 
-pub async fn add_secret_with_tag(
+impl SearchResponse {
+    /// The ids the model chose, best first, without repeats.
+    fn ranked(self) -> Vec<u64> {
+        let single = self.id.filter(|_| self.found);
+        let mut ids: Vec<u64> = Vec::new();
+        for id in self.ids.into_iter().chain(single) {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+        ids
+    }
+}
+
+/// An entry already in the store that looks like the one being added.
+pub struct Duplicate {
+    pub entry: StoreEntry,
+    /// The value is the same; otherwise the note is close to the new one.
+    pub same_value: bool,
+}
+
+/// What to do with an entry that has a [`Duplicate`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OnDuplicate {
+    /// Give the existing entry the new value and note, keeping its tag.
+    Update,
+    Skip,
+    /// Add the new entry next to the existing one.
+    Add,
+}
+
+/// Notes at least this similar (by characters, ignoring case and spacing)
+/// count as the same note.
+const SIMILAR_NOTE: f32 = 0.8;
+
+fn normalized(note: &str) -> String {
+    note.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+/// The entry that `value` and `note` would duplicate: one with the same
+/// value, or else the one of the same kind with the most similar note.
+/// Compared locally; nothing is sent to the model.
+pub fn find_duplicate(entries: &[StoreEntry], kind: EntryKind, value: &str, note: &str) -> Option<Duplicate> {
+    if let Some(entry) = entries.iter().find(|e| e.value.trim() == value.trim()) {
+        return Some(Duplicate { entry: entry.clone(), same_value: true });
+    }
+    let note = normalized(note);
+    entries
+        .iter()
+        .filter(|e| e.kind == kind)
+        .map(|e| (similar::TextDiff::from_chars(note.as_str(), normalized(&e.note).as_str()).ratio(), e))
+        .filter(|(ratio, _)| *ratio >= SIMILAR_NOTE)
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, entry)| Duplicate { entry: entry.clone(), same_value: false })
+}
+
+/// Store `value` as a `kind` entry described by `note`, under a tag the
+/// model picks from the note (and, except for secrets, the value). When
+/// the store has a [`Duplicate`] of it, `on_duplicate` decides what happens.
+pub async fn add_entry(
     config: &Config,
-    secret: &str,
+    kind: EntryKind,
+    value: &str,
     note: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let secret = secret.trim();
+    on_duplicate: &dyn Fn(&Duplicate) -> OnDuplicate,
+) -> Result<(), XaError> {
+    // Snippets keep their indentation; only blank edges are dropped
+    let value = if kind == EntryKind::Snippet { value.trim_matches('\n').trim_end() } else { value.trim() };
     let note = note.trim();
 
-    if secret.is_empty() {
-        eprintln!("Error: secret cannot be empty.");
+    if value.trim().is_empty() {
+        eprintln!("Error: {} cannot be empty.", kind.name());
         return Ok(());
     }
 
@@ -85,7 +192,31 @@ pub async fn add_secret_with_tag(
         return Ok(());
     }
 
-    let mut store = load_store()?;
+    let store = load_store()?;
+
+    if let Some(duplicate) = find_duplicate(&store.entries, kind, value, note) {
+        match on_duplicate(&duplicate) {
+            OnDuplicate::Skip => {
+                println!("Skipped; [{}] is kept as it is.", duplicate.entry.tag);
+                return Ok(());
+            }
+            OnDuplicate::Update => {
+                let _lock = lock(&store_path()?)?;
+                let mut store = load_store()?;
+                let Some(entry) = store.entries.iter_mut().find(|e| e.id == duplicate.entry.id) else {
+                    return Err(XaError::Other(format!("[{}] was removed while you were asked", duplicate.entry.tag)));
+                };
+                entry.kind = kind;
+                entry.value = value.to_string();
+                entry.note = note.to_string();
+                entry.updated_at = Some(Utc::now().to_rfc3339());
+                save_store(&store)?;
+                println!("Updated {} with tag: {}", kind.name(), duplicate.entry.tag);
+                return Ok(());
+            }
+            OnDuplicate::Add => {}
+        }
+    }
 
     let existing_tags: HashSet<String> = store
         .entries
@@ -93,76 +224,180 @@ pub async fn add_secret_with_tag(
         .map(|e| e.tag.to_lowercase())
         .collect();
 
-    let prompt = build_tag_prompt(note, &existing_tags);
-    let llm_response = process_with_llm(config, &prompt, false).await?;
-    let mut tag = match parse_json::<TagResponse>(&llm_response) {
-        Some(parsed) => parsed.tag,
-        None => fallback_tag(note),
-    };
-
-    tag = sanitize_tag(&tag);
-    if tag.is_empty() {
-        tag = fallback_tag(note);
-    }
+    let mut tag = generate_tag(config, kind, value, note, &existing_tags).await?;
 
+    // The store is read again under the lock: another xa may have added an
+    // entry while the tag was being chosen
+    let _lock = lock(&store_path()?)?;
+    let mut store = load_store()?;
+    let existing_tags: HashSet<String> = store.entries.iter().map(|e| e.tag.to_lowercase()).collect();
     tag = ensure_unique_tag(&tag, &existing_tags);
 
     let entry = StoreEntry {
         id: Utc::now().timestamp_millis() as u64,
+        kind,
         tag: tag.clone(),
         note: note.to_string(),
-        secret: secret.to_string(),
+        value: value.to_string(),
         created_at: Utc::now().to_rfc3339(),
+        updated_at: None,
     };
 
     store.entries.push(entry);
     save_store(&store)?;
 
-    println!("Added secret with tag: {}", tag);
+    println!("Added {} with tag: {}", kind.name(), tag);
     Ok(())
 }
 
-pub async fn search_secret(
+/// At most this many entries answer a search.
+pub const SEARCH_RESULTS: usize = 5;
+
+/// A tag for an entry, chosen by the model from its note (and value,
+/// except for secrets) to differ from `existing_tags`; made from the note
+/// when the answer has none. Not yet checked for uniqueness.
+async fn generate_tag(
+    config: &Config,
+    kind: EntryKind,
+    value: &str,
+    note: &str,
+    existing_tags: &HashSet<String>,
+) -> Result<String, XaError> {
+    let shown = (kind != EntryKind::Secret).then_some(value);
+    let prompt = build_tag_prompt(note, shown, existing_tags);
+    let llm_response = process_with_llm(config, &prompt, false).await?;
+    let tag = match parse_json::<TagResponse>(&llm_response) {
+        Some(parsed) => sanitize_tag(&parsed.tag),
+        None => String::new(),
+    };
+    Ok(if tag.is_empty() { sanitize_tag(&fallback_tag(note)) } else { tag })
+}
+
+/// Give the entry tagged `old` the tag `new`, or without one a tag the
+/// model picks again from its note. Either is made unique among the other
+/// entries' tags.
+pub async fn retag(config: &Config, old: &str, new: Option<&str>) -> Result<(), XaError> {
+    let store = load_store()?;
+    let entry = store
+        .entries
+        .iter()
+        .find(|e| e.tag.eq_ignore_ascii_case(old.trim()))
+        .ok_or_else(|| XaError::Usage(format!("No stored entry is tagged '{}' (see `xa ls stores`)", old.trim())))?;
+    let others = |entries: &[StoreEntry]| -> HashSet<String> {
+        entries.iter().filter(|e| e.id != entry.id).map(|e| e.tag.to_lowercase()).collect()
+    };
+
+    let tag = match new {
+        Some(new) => {
+            let tag = sanitize_tag(new);
+            if tag.is_empty() {
+                return Err(XaError::Usage(format!("'{}' has no letters or digits to make a tag from", new)));
+            }
+            tag
+        }
+        None => {
+            let mut existing = others(&store.entries);
+            // Ask for a different tag than the one it has
+            existing.insert(entry.tag.to_lowercase());
+            generate_tag(config, entry.kind, &entry.value, &entry.note, &existing).await?
+        }
+    };
+
+    // Read again under the lock, as when adding
+    let _lock = lock(&store_path()?)?;
+    let mut fresh = load_store()?;
+    let tag = ensure_unique_tag(&tag, &others(&fresh.entries));
+    let stored = fresh
+        .entries
+        .iter_mut()
+        .find(|e| e.id == entry.id)
+        .ok_or_else(|| XaError::Other(format!("[{}] was removed while it was being retagged", entry.tag)))?;
+    let old = std::mem::replace(&mut stored.tag, tag.clone());
+    stored.updated_at = Some(Utc::now().to_rfc3339());
+    save_store(&fresh)?;
+
+    println!("Retagged [{}] as [{}]", old, tag);
+    Ok(())
+}
+
+/// The stored entries that answer `query`, best first: one when the model
+/// is sure, up to [`SEARCH_RESULTS`] when the query is ambiguous, none when
+/// nothing matches. Secret values are never sent; the model picks them by
+/// tag and note.
+pub async fn search(
     config: &Config,
     query: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<Vec<StoreEntry>, XaError> {
     let query = query.trim();
     if query.is_empty() {
         eprintln!("Error: query cannot be empty.");
-        return Ok(());
+        return Ok(Vec::new());
     }
 
     let store = load_store()?;
     if store.entries.is_empty() {
-        println!("No found such thing.");
-        return Ok(());
+        return Ok(Vec::new());
     }
 
-    let masked_entries = build_masked_entries(&store.entries);
+    let candidates = shortlist(config, query, &store.entries).await;
+    let masked_entries = build_masked_entries(&candidates);
     let prompt = build_search_prompt(query, &masked_entries);
     let llm_response = process_with_llm(config, &prompt, false).await?;
-    let parsed = parse_json::<SearchResponse>(&llm_response);
-
-    if let Some(result) = parsed {
-        if result.found {
-            if let Some(id) = result.id {
-                if let Some(entry) = store.entries.iter().find(|e| e.id == id) {
-                    println!("{}", entry.secret);
-                    return Ok(());
-                }
-            }
-        }
+    let ids = parse_json::<SearchResponse>(&llm_response).map(SearchResponse::ranked).unwrap_or_default();
+
+    Ok(ids
+        .iter()
+        .filter_map(|id| store.entries.iter().find(|e| e.id == *id).cloned())
+        .take(SEARCH_RESULTS)
+        .collect())
+}
+
+/// Entries sent to the model as they are; larger stores are narrowed down
+/// by embedding similarity first.
+const SHORTLIST: usize = 20;
+
+/// The entries whose tag and note (and value, except for secrets) are
+/// closest to `query`, or all of them when there are few or the embeddings
+/// request fails. Secrets are never embedded.
+async fn shortlist(config: &Config, query: &str, entries: &[StoreEntry]) -> Vec<StoreEntry> {
+    if entries.len() <= SHORTLIST {
+        return entries.to_vec();
     }
+    let mut inputs = vec![query.to_string()];
+    inputs.extend(entries.iter().map(|e| match e.kind {
+        EntryKind::Secret => format!("{} {}", e.tag, e.note),
+        _ => format!("{} {} {}", e.tag, e.note, e.value),
+    }));
+    let vectors = match embeddings::embed(config, &config.embedding_model(), &inputs).await {
+        Ok(vectors) => vectors,
+        Err(error) => {
+            tracing::warn!(%error, "embeddings unavailable, searching all entries");
+            return entries.to_vec();
+        }
+    };
+    let mut scored: Vec<(f32, &StoreEntry)> =
+        entries.iter().zip(&vectors[1..]).map(|(e, v)| (embeddings::cosine(&vectors[0], v), e)).collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored.into_iter().take(SHORTLIST).map(|(_, e)| e.clone()).collect()
+}
 
-    println!("No found such thing.");
-    Ok(())
+/// The values of the secret entries, for `--debug` to mask. Empty when
+/// the store is missing or unreadable; unlike the commands that change the
+/// store, this never moves a corrupted file aside.
+pub fn secret_values() -> Vec<String> {
+    let Ok(content) = store_path().and_then(|path| Ok(fs::read_to_string(path)?)) else {
+        return Vec::new();
+    };
+    let store: StoreConfig = toml::from_str(&content).unwrap_or_default();
+    store.entries.into_iter().filter(|entry| entry.kind == EntryKind::Secret).map(|entry| entry.value).collect()
 }
 
-fn load_store() -> Result<StoreConfig, Box<dyn std::error::Error>> {
-    let config_dir = config_dir()
-        .ok_or("Could not determine config directory")?
-        .join("xa");
-    let store_file = config_dir.join("stores.toml");
+fn store_path() -> Result<PathBuf, XaError> {
+    Ok(xa_dir().ok_or_else(|| XaError::Config("Could not determine config directory".into()))?.join("stores.toml"))
+}
+
+fn load_store() -> Result<StoreConfig, XaError> {
+    let store_file = store_path()?;
 
     if !store_file.exists() {
         return Ok(StoreConfig::default());
@@ -183,32 +418,31 @@ fn load_store() -> Result<StoreConfig, Box<dyn std::error::Error>> {
     }
 }
 
-fn save_store(store: &StoreConfig) -> Result<(), Box<dyn std::error::Error>> {
-    let config_dir = config_dir()
-        .ok_or("Could not determine config directory")?
-        .join("xa");
-    fs::create_dir_all(&config_dir)?;
-    let store_file = config_dir.join("stores.toml");
+fn save_store(store: &StoreConfig) -> Result<(), XaError> {
+    let store_file = store_path()?;
     let content = toml::to_string(store)?;
-    fs::write(&store_file, content)?;
+    safe_write(&store_file, content)?;
     Ok(())
 }
 
-fn build_tag_prompt(note: &str, existing_tags: &HashSet<String>) -> String {
+fn build_tag_prompt(note: &str, value: Option<&str>, existing_tags: &HashSet<String>) -> String {
     let mut existing: Vec<String> = existing_tags.iter().cloned().collect();
     existing.sort();
+    let value = value.map(|value| format!("\n\nStored text: {}", value)).unwrap_or_default();
 
     format!(
-        "You generate short, memorable tags for secret notes.\n\nRules:\n- Return JSON only.\n- JSON schema: {{\"tag\": string, \"reason\": string}}.\n- tag must be 2-4 words max, lowercase, use hyphens instead of spaces.\n- tag must not include any sensitive data (only use the note).\n- tag must not duplicate existing tags.\n\nExisting tags: {:?}\n\nNote: {}\n\nReturn JSON only.",
+        "You generate short, memorable tags for stored notes.\n\nRules:\n- Return JSON only.\n- JSON schema: {{\"tag\": string, \"reason\": string}}.\n- tag must be 2-4 words max, lowercase, use hyphens instead of spaces.\n- tag must not include any sensitive data (only use the note).\n- tag must not duplicate existing tags.\n\nExisting tags: {:?}\n\nNote: {}{}\n\nReturn JSON only.",
         existing,
-        note
+        note,
+        value
     )
 }
 
 fn build_search_prompt(query: &str, masked_entries: &[MaskedEntry]) -> String {
     let entries_json = serde_json::to_string_pretty(masked_entries).unwrap_or_else(|_| "[]".to_string());
     format!(
-        "You are a secret locator. Given a user query and a list of entries, find the best matching entry.\n\nRules:\n- Return JSON only.\n- JSON schema: {{\"found\": boolean, \"id\": number|null, \"reason\": string}}.\n- If nothing matches well, set found=false and id=null.\n- Do not invent ids.\n\nEntries (secret is placeholder only):\n{}\n\nQuery: {}\n\nReturn JSON only.",
+        "You are a locator for stored secrets, notes, snippets and URLs. Given a user query and a list of entries, find the entries that match it.\n\nRules:\n- Return JSON only.\n- JSON schema: {{\"ids\": number[], \"reason\": string}}.\n- ids lists the matching entries, best first. Give one id when one entry clearly matches; when the query could mean several, give each of them, at most {}.\n- If nothing matches well, return an empty ids list.\n- Do not invent ids.\n\nEntries (a secret's value is a placeholder only):\n{}\n\nQuery: {}\n\nReturn JSON only.",
+        SEARCH_RESULTS,
         entries_json,
         query
     )
@@ -287,10 +521,12 @@ fn parse_json<T: for<'de> Deserialize<'de>>(input: &str) -> Option<T> {
 #[derive(Serialize)]
 struct MaskedEntry {
     id: u64,
+    kind: EntryKind,
     tag: String,
     note: String,
     created_at: String,
-    secret_placeholder: String,
+    /// The value of a note, snippet or URL; a placeholder for a secret.
+    value: String,
 }
 
 fn build_masked_entries(entries: &[StoreEntry]) -> Vec<MaskedEntry> {
@@ -298,10 +534,67 @@ fn build_masked_entries(entries: &[StoreEntry]) -> Vec<MaskedEntry> {
         .iter()
         .map(|e| MaskedEntry {
             id: e.id,
+            kind: e.kind,
             tag: e.tag.clone(),
             note: e.note.clone(),
             created_at: e.created_at.clone(),
-            secret_placeholder: format!("SECRET_{}", e.id),
+            value: match e.kind {
+                EntryKind::Secret => format!("SECRET_{}", e.id),
+                _ => e.value.chars().take(MAX_SHOWN_VALUE).collect(),
+            },
         })
         .collect()
 }
+
+/// How much of a non-secret value the search prompt quotes.
+const MAX_SHOWN_VALUE: usize = 500;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_old_entries_as_secrets_and_masks_only_secrets() {
+        let old = "[[entries]]\nid = 1\ntag = \"db-password\"\nnote = \"staging db\"\nsecret = \"hunter2\"\ncreated_at = \"2026-01-01T00:00:00Z\"\n";
+        let mut store: StoreConfig = toml::from_str(old).unwrap();
+        assert_eq!((store.entries[0].kind, store.entries[0].value.as_str()), (EntryKind::Secret, "hunter2"));
+
+        store.entries.push(StoreEntry { id: 2, kind: EntryKind::Snippet, value: "kubectl rollout undo deploy/foo".into(), ..store.entries[0].clone() });
+        let saved = toml::to_string(&store).unwrap();
+        assert!(saved.contains("kind = \"snippet\"") && saved.contains("value = \"hunter2\""));
+
+        let masked = serde_json::to_string(&build_masked_entries(&store.entries)).unwrap();
+        assert!(!masked.contains("hunter2") && masked.contains("SECRET_1") && masked.contains("kubectl rollout undo"));
+    }
+
+    #[test]
+    fn ranks_search_answers_in_both_forms() {
+        let ranked = |answer: &str| parse_json::<SearchResponse>(answer).map(SearchResponse::ranked).unwrap_or_default();
+        assert_eq!(ranked("Matches: {\"ids\": [7, 3, 7], \"reason\": \"two tokens\"}"), vec![7, 3]);
+        assert_eq!(ranked("{\"found\": true, \"id\": 4, \"reason\": \"\"}"), vec![4]);
+        assert_eq!(ranked("{\"found\": false, \"id\": 4}"), Vec::<u64>::new());
+        assert_eq!(ranked("{\"ids\": []}"), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn finds_duplicates_by_value_or_similar_note() {
+        let entry = |id, kind, note: &str, value: &str| StoreEntry {
+            id,
+            kind,
+            tag: format!("tag-{}", id),
+            note: note.into(),
+            value: value.into(),
+            created_at: String::new(),
+            updated_at: None,
+        };
+        let entries = vec![entry(1, EntryKind::Secret, "GitHub token", "ghp_abc"), entry(2, EntryKind::Note, "github  Token for CI", "x")];
+
+        let same = find_duplicate(&entries, EntryKind::Note, " ghp_abc", "something else").unwrap();
+        assert!(same.same_value && same.entry.id == 1);
+        let similar = find_duplicate(&entries, EntryKind::Secret, "ghp_new", "Github tokens").unwrap();
+        assert!(!similar.same_value && similar.entry.id == 1);
+        assert_eq!(find_duplicate(&entries, EntryKind::Note, "y", "GitHub Token for CI.").unwrap().entry.id, 2);
+        assert!(find_duplicate(&entries, EntryKind::Secret, "ghp_new", "AWS key").is_none());
+        assert!(find_duplicate(&entries, EntryKind::Url, "ghp_new", "GitHub token").is_none());
+    }
+}
@@ -0,0 +1,198 @@
+//! `xa store sync`: one `stores.toml` for several machines, kept in a git
+//! repository. Each sync fetches the remote's copy, merges it with the
+//! local one entry by entry (by id, the newer version of an entry edited
+//! on both sides winning), writes the result to both and pushes it. The
+//! clone lives in `xa_dir()/store-sync`.
+//!
+//! The store is not encrypted, so the remote gets its secrets in plain
+//! text; sync refuses to run until `store_sync_plaintext` says that is
+//! wanted.
+
+use super::{ensure_unique_tag, load_store, save_store, store_path, StoreConfig, StoreEntry};
+use crate::config::{lock, safe_write, xa_dir, Config};
+use crate::error::XaError;
+use chrono::DateTime;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// What a sync changed on each side.
+#[derive(Debug, Default, PartialEq)]
+pub struct SyncReport {
+    /// Entries that came from the remote, new or newer than the local ones.
+    pub received: usize,
+    /// Entries the remote did not have, or had in an older version.
+    pub sent: usize,
+    /// Entries whose tag was taken by another entry and got a suffix.
+    pub retagged: usize,
+}
+
+/// When `entry` last changed.
+fn changed(entry: &StoreEntry) -> Option<DateTime<chrono::FixedOffset>> {
+    let stamp = entry.updated_at.as_deref().unwrap_or(&entry.created_at);
+    DateTime::parse_from_rfc3339(stamp).ok()
+}
+
+fn same(a: &StoreEntry, b: &StoreEntry) -> bool {
+    (a.kind, &a.tag, &a.note, &a.value) == (b.kind, &b.tag, &b.note, &b.value)
+}
+
+/// `local` and `remote` merged by entry id, in id order. Of two different
+/// versions of one entry the more recently changed wins (the local one on
+/// a tie). Entries added on different machines under the same tag keep
+/// the tag in id order; the later ones get a suffix, the same way on every
+/// machine.
+pub fn merge(local: &StoreConfig, remote: &StoreConfig) -> (StoreConfig, SyncReport) {
+    let mut report = SyncReport::default();
+    let mut merged: BTreeMap<u64, StoreEntry> = local.entries.iter().map(|e| (e.id, e.clone())).collect();
+    for theirs in &remote.entries {
+        match merged.get(&theirs.id) {
+            None => {
+                merged.insert(theirs.id, theirs.clone());
+                report.received += 1;
+            }
+            Some(ours) if !same(ours, theirs) && changed(theirs) > changed(ours) => {
+                merged.insert(theirs.id, theirs.clone());
+                report.received += 1;
+            }
+            Some(_) => {}
+        }
+    }
+    let remote_entries: BTreeMap<u64, &StoreEntry> = remote.entries.iter().map(|e| (e.id, e)).collect();
+
+    let mut tags = HashSet::new();
+    let mut entries = Vec::with_capacity(merged.len());
+    for (_, mut entry) in merged {
+        let tag = ensure_unique_tag(&entry.tag, &tags);
+        if tag != entry.tag {
+            entry.tag = tag;
+            report.retagged += 1;
+        }
+        tags.insert(entry.tag.to_lowercase());
+        if !remote_entries.get(&entry.id).is_some_and(|theirs| same(theirs, &entry)) {
+            report.sent += 1;
+        }
+        entries.push(entry);
+    }
+    (StoreConfig { entries }, report)
+}
+
+/// Run git in `dir` with `args` and return its output.
+fn git(dir: &Path, args: &[&str]) -> Result<String, XaError> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|e| XaError::Other(format!("Could not run git: {}", e)))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(XaError::Other(format!("git {} failed: {}", args[0], stderr.trim())));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Sync `stores.toml` with the git repository at `remote`, once `config`
+/// opts in to pushing it in plain text. The first sync clones it; later
+/// ones pull, merge and push.
+pub fn sync(config: &Config, remote: &str) -> Result<SyncReport, XaError> {
+    if !config.store_sync_plaintext {
+        return Err(XaError::Config(format!(
+            "stores.toml keeps its secrets in plain text, and store sync would push them to {} as they are. \
+             If that remote is private and trusted, allow it with `xa config set store_sync_plaintext true`",
+            remote
+        )));
+    }
+    let dir = xa_dir().ok_or_else(|| XaError::Config("Could not determine config directory".into()))?.join("store-sync");
+    if !dir.join(".git").exists() {
+        let parent = dir.parent().ok_or_else(|| XaError::Config("Could not determine config directory".into()))?;
+        fs::create_dir_all(parent)?;
+        git(parent, &["clone", "--quiet", "--", remote, "store-sync"])?;
+    } else {
+        git(&dir, &["remote", "set-url", "--", "origin", remote])?;
+    }
+
+    git(&dir, &["fetch", "--quiet", "origin"])?;
+    let branch = git(&dir, &["symbolic-ref", "--short", "HEAD"])?.trim().to_string();
+    let upstream = format!("origin/{}", branch);
+    if git(&dir, &["rev-parse", "--verify", "--quiet", &upstream]).is_ok() {
+        git(&dir, &["reset", "--quiet", "--hard", &upstream])?;
+    }
+    // safe_write keeps backups beside stores.toml; they stay on this machine
+    fs::create_dir_all(dir.join(".git/info"))?;
+    fs::write(dir.join(".git/info/exclude"), "stores.toml.bak.*\n")?;
+    let file = dir.join("stores.toml");
+    let remote_store: StoreConfig = match fs::read_to_string(&file) {
+        Ok(content) => toml::from_str(&content).map_err(|e| XaError::Config(format!("Unreadable stores.toml in {}: {}", remote, e)))?,
+        Err(_) => StoreConfig::default(),
+    };
+
+    let report = {
+        let _lock = lock(&store_path()?)?;
+        let local = load_store()?;
+        let (merged, report) = merge(&local, &remote_store);
+        if report.received > 0 || report.retagged > 0 {
+            save_store(&merged)?;
+        }
+        safe_write(&file, toml::to_string(&merged)?)?;
+        report
+    };
+
+    git(&dir, &["add", "stores.toml"])?;
+    if git(&dir, &["diff", "--cached", "--quiet"]).is_err() {
+        let message = format!("Sync stores.toml ({} sent, {} received)", report.sent, report.received);
+        let mut commit = vec!["commit", "--quiet", "-m", message.as_str()];
+        // A machine without a git identity still commits
+        if git(&dir, &["config", "user.email"]).is_err() {
+            commit = [&["-c", "user.name=xa", "-c", "user.email=xa@localhost"][..], &commit[..]].concat();
+        }
+        git(&dir, &commit)?;
+        let refspec = format!("HEAD:{}", branch);
+        git(&dir, &["push", "--quiet", "origin", &refspec])
+            .map_err(|e| XaError::Other(format!("{} (run `xa store sync` again to merge what was pushed meanwhile)", e)))?;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::EntryKind;
+
+    fn entry(id: u64, tag: &str, value: &str, updated_at: Option<&str>) -> StoreEntry {
+        StoreEntry {
+            id,
+            kind: EntryKind::Secret,
+            tag: tag.into(),
+            note: "note".into(),
+            value: value.into(),
+            created_at: "2026-01-01T00:00:00+00:00".into(),
+            updated_at: updated_at.map(String::from),
+        }
+    }
+
+    #[test]
+    fn merges_by_id_keeping_the_newer_version() {
+        let local = StoreConfig {
+            entries: vec![entry(1, "db", "old", None), entry(2, "token", "rotated", Some("2026-03-01T00:00:00+00:00")), entry(4, "api", "a", None)],
+        };
+        let remote = StoreConfig {
+            entries: vec![entry(1, "db", "new", Some("2026-02-01T00:00:00Z")), entry(2, "token", "stale", None), entry(3, "api", "b", None)],
+        };
+        let (merged, report) = merge(&local, &remote);
+        let values: Vec<(&str, &str)> = merged.entries.iter().map(|e| (e.tag.as_str(), e.value.as_str())).collect();
+        assert_eq!(values, [("db", "new"), ("token", "rotated"), ("api", "b"), ("api-2", "a")]);
+        assert_eq!(report, SyncReport { received: 2, sent: 2, retagged: 1 });
+
+        let (again, report) = merge(&merged, &merged);
+        assert_eq!(again.entries.len(), 4);
+        assert_eq!(report, SyncReport::default());
+    }
+
+    #[test]
+    fn refuses_to_push_plain_text_unless_allowed() {
+        let result = sync(&Config::default(), "git@example.com:me/xa-store.git");
+        assert!(matches!(result, Err(XaError::Config(message)) if message.contains("store_sync_plaintext")));
+    }
+}
@@ -0,0 +1,166 @@
+//! `xa subs`: subtitle files (SubRip `.srt` and WebVTT `.vtt`) with only the
+//! cue text transformed. Indices, cue identifiers, timings, the WebVTT
+//! header and its NOTE, STYLE and REGION blocks are written back as they
+//! were; the cue texts go through [`crate::document::transform_each`], so
+//! neighbouring cues share a request and the model sees them in context.
+
+use crate::document::Segment;
+use crate::error::XaError;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+static TIMING: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s*(?:\d+:)?\d{1,2}:\d{2}[,.]\d{3}\s+-->\s+(?:\d+:)?\d{1,2}:\d{2}[,.]\d{3}").expect("valid regex"));
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Kind {
+    Srt,
+    Vtt,
+}
+
+impl Kind {
+    /// The kind of `path`, by extension.
+    pub fn of(path: &Path) -> Option<Kind> {
+        match path.extension().and_then(|ext| ext.to_str()).map(str::to_lowercase).as_deref() {
+            Some("srt") => Some(Kind::Srt),
+            Some("vtt") => Some(Kind::Vtt),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum Block {
+    /// Written back as it is: the WebVTT header, comments, styles.
+    Kept(String),
+    Cue {
+        /// The index or identifier line, and the timing line.
+        head: String,
+        text: String,
+    },
+}
+
+#[derive(Debug)]
+pub struct Subtitles {
+    blocks: Vec<Block>,
+}
+
+impl Subtitles {
+    /// Read a subtitle file's text. Line endings become `\n`.
+    pub fn parse(text: &str, kind: Kind) -> Result<Subtitles, XaError> {
+        let text = text.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+        let mut blocks = Vec::new();
+        let mut lines: Vec<&str> = Vec::new();
+        for line in text.lines().chain([""]) {
+            if !line.trim().is_empty() {
+                lines.push(line);
+                continue;
+            }
+            if lines.is_empty() {
+                continue;
+            }
+            let first = lines[0].trim_start();
+            let metadata = (blocks.is_empty() && first.starts_with("WEBVTT")) || ["NOTE", "STYLE", "REGION"].iter().any(|word| first.starts_with(word));
+            // The timing is the first line, or the second after an index or identifier
+            match lines.iter().take(2).position(|line| TIMING.is_match(line)) {
+                Some(at) if !metadata => blocks.push(Block::Cue { head: lines[..=at].join("\n"), text: lines[at + 1..].join("\n") }),
+                _ => blocks.push(Block::Kept(lines.join("\n"))),
+            }
+            lines.clear();
+        }
+        if kind == Kind::Vtt && !matches!(blocks.first(), Some(Block::Kept(header)) if header.starts_with("WEBVTT")) {
+            return Err(XaError::Usage("not a WebVTT file: it does not start with WEBVTT".into()));
+        }
+        if !blocks.iter().any(|block| matches!(block, Block::Cue { .. })) {
+            return Err(XaError::Usage("no subtitle cues found".into()));
+        }
+        Ok(Subtitles { blocks })
+    }
+
+    /// How many cues have text.
+    pub fn cues(&self) -> usize {
+        self.segments().iter().filter(|segment| segment.prose).count()
+    }
+
+    /// One segment per cue, prose unless the cue is empty.
+    pub fn segments(&self) -> Vec<Segment> {
+        self.blocks
+            .iter()
+            .filter_map(|block| match block {
+                Block::Cue { text, .. } => Some(Segment { text: text.clone(), prose: !text.trim().is_empty() }),
+                Block::Kept(_) => None,
+            })
+            .collect()
+    }
+
+    /// Put new cue texts in, one per segment of [`Subtitles::segments`].
+    /// Blank lines would end a cue early and `-->` would read as a timing,
+    /// so neither is let through; a cue left with no text keeps its own.
+    pub fn set_texts(&mut self, texts: &[String]) {
+        let cues = self.blocks.iter_mut().filter_map(|block| match block {
+            Block::Cue { text, .. } => Some(text),
+            Block::Kept(_) => None,
+        });
+        for (text, new) in cues.zip(texts) {
+            let lines: Vec<String> = new.lines().map(|line| line.trim_end().replace("-->", "→")).filter(|line| !line.trim().is_empty()).collect();
+            if !lines.is_empty() {
+                *text = lines.join("\n");
+            }
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for block in &self.blocks {
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            match block {
+                Block::Kept(text) => out.push_str(text),
+                Block::Cue { head, text } if text.is_empty() => out.push_str(head),
+                Block::Cue { head, text } => {
+                    out.push_str(head);
+                    out.push('\n');
+                    out.push_str(text);
+                }
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Where a result goes when no `-o` is given: next to `path`, tagged with
+/// `tag`, e.g. `movie.srt` to `movie.zh.srt`.
+pub fn output_path(path: &Path, tag: &str) -> PathBuf {
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    let ext = path.extension().map(|ext| ext.to_string_lossy().into_owned()).unwrap_or_default();
+    path.with_file_name(format!("{}.{}.{}", stem, tag, ext))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_indices_and_timings() {
+        let srt = "\u{feff}1\r\n00:00:01,000 --> 00:00:02,500\r\nHello\r\n<i>there</i>\r\n\r\n2\r\n00:00:03,000 --> 00:00:04,000\r\n\r\n3\r\n00:00:05,000 --> 00:00:06,000\r\nBye\r\n";
+        let mut subs = Subtitles::parse(srt, Kind::Srt).unwrap();
+        assert_eq!(subs.cues(), 2);
+        assert_eq!(subs.render(), srt.trim_start_matches('\u{feff}').replace("\r\n", "\n"));
+        subs.set_texts(&["你好\n\n<i>那里</i>".into(), String::new(), "a --> b".into()]);
+        assert_eq!(
+            subs.render(),
+            "1\n00:00:01,000 --> 00:00:02,500\n你好\n<i>那里</i>\n\n2\n00:00:03,000 --> 00:00:04,000\n\n3\n00:00:05,000 --> 00:00:06,000\na → b\n"
+        );
+
+        let vtt = "WEBVTT\n\nNOTE made by hand\n\nintro\n00:01.000 --> 00:02.000 align:start\nHi\n";
+        let subs = Subtitles::parse(vtt, Kind::Vtt).unwrap();
+        assert_eq!(subs.segments(), [Segment { text: "Hi".into(), prose: true }]);
+        assert_eq!(subs.render(), vtt);
+        assert!(Subtitles::parse("intro\n00:01.000 --> 00:02.000\nHi\n", Kind::Vtt).is_err());
+        assert!(Subtitles::parse("just text\n", Kind::Srt).is_err());
+
+        assert_eq!(output_path(Path::new("dir/movie.srt"), "zh"), Path::new("dir/movie.zh.srt"));
+    }
+}
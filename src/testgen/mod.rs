@@ -0,0 +1,137 @@
+//! `xa tests <path>`: unit tests for a source file, written by the model
+//! and saved where the language's tooling looks for them
+//! (`tests/test_<name>.py`, `<name>_test.go`, `<name>.test.ts`, …) or at
+//! `-o`. An existing file is only replaced with `--force`.
+
+use crate::config::Config;
+use crate::error::XaError;
+use crate::explain::Language;
+use std::path::{Component, Path, PathBuf};
+
+/// The framework used when `--framework` is not given.
+pub fn default_framework(language: Language) -> Option<&'static str> {
+    match language {
+        Language::Rust => Some("Rust's built-in test harness"),
+        Language::Python => Some("pytest"),
+        Language::JavaScript => Some("Jest"),
+        Language::TypeScript => Some("Vitest"),
+        Language::Go => Some("Go's testing package"),
+        Language::C | Language::Cpp => Some("GoogleTest"),
+        Language::Java => Some("JUnit 5"),
+        Language::Ruby => Some("RSpec"),
+        Language::Shell => Some("Bats"),
+        Language::Other => None,
+    }
+}
+
+/// Where the tests for `source` go by convention. `None` when there is no
+/// convention for the language; `-o` is needed then.
+pub fn test_path(source: &Path, language: Language, framework: &str) -> Option<PathBuf> {
+    let dir = source.parent().unwrap_or(Path::new(""));
+    let stem = source.file_stem()?.to_str()?;
+    let extension = source.extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+    // the project root for sources under `src/` (or `lib/`), else the file's directory
+    let root = dir
+        .ancestors()
+        .find(|ancestor| ancestor.file_name().is_some_and(|name| name == "src" || name == "lib"))
+        .map_or(dir, |src| src.parent().unwrap_or(Path::new("")));
+    let framework = framework.to_lowercase();
+    let path = match language {
+        Language::Rust => root.join("tests").join(format!("{}.rs", stem)),
+        Language::Python => root.join("tests").join(format!("test_{}.py", stem)),
+        Language::JavaScript | Language::TypeScript => dir.join(format!("{}.test.{}", stem, extension)),
+        Language::Go => dir.join(format!("{}_test.go", stem)),
+        Language::C => root.join("tests").join(format!("test_{}.{}", stem, if framework.contains("googletest") { "cpp" } else { "c" })),
+        Language::Cpp => root.join("tests").join(format!("test_{}.{}", stem, if extension == "h" || extension == "hpp" { "cpp" } else { extension })),
+        Language::Java => {
+            // src/main/java/a/b/Foo.java → src/test/java/a/b/FooTest.java
+            let components: Vec<Component> = dir.components().collect();
+            let main = components.windows(2).position(|pair| pair[0].as_os_str() == "main" && pair[1].as_os_str() == "java");
+            let dir = match main {
+                Some(index) => {
+                    let mut test_dir: PathBuf = components[..index].iter().collect();
+                    test_dir.push("test");
+                    test_dir.extend(&components[index + 1..]);
+                    test_dir
+                }
+                None => dir.to_path_buf(),
+            };
+            dir.join(format!("{}Test.java", stem))
+        }
+        Language::Ruby if framework.contains("minitest") => root.join("test").join(format!("test_{}.rb", stem)),
+        Language::Ruby => root.join("spec").join(format!("{}_spec.rb", stem)),
+        Language::Shell => root.join("test").join(format!("{}.bats", stem)),
+        Language::Other => return None,
+    };
+    Some(path)
+}
+
+fn prompt(source: &str, language: Language, framework: &str, test_path: &Path, text: &str) -> String {
+    format!(
+        "Write unit tests with {} for the {} file {}. They will be saved as {}, so import or include the code under \
+         test relative to that location. Cover the public functions' normal behavior, edge cases and error paths; \
+         keep each test small and named after what it checks. Do not change or repeat the code under test and do \
+         not use network or external services. Reply with the complete test file in a single fenced code block.\n\n\
+         File: {}\n{}",
+        framework,
+        if language == Language::Other { "source" } else { language.name() },
+        source,
+        test_path.display(),
+        source,
+        text
+    )
+}
+
+/// Tests for `source` (whose contents are `text`), as the contents of
+/// `test_path`.
+pub async fn generate(config: &Config, source: &str, text: &str, language: Language, framework: &str, test_path: &Path) -> Result<String, XaError> {
+    let spinner = crate::spinner::Spinner::start("Writing tests");
+    let done = crate::llm::complete(config, &prompt(source, language, framework, test_path, text)).await;
+    spinner.stop();
+    let answer = done?.text;
+    let code = crate::postprocess::extract_code_block(&answer).unwrap_or(answer);
+    if code.trim().is_empty() {
+        return Err(XaError::Other("the answer has no tests".into()));
+    }
+    Ok(format!("{}\n", code.trim_end()))
+}
+
+/// Write `code` to `path`, creating its directory. An existing file is
+/// replaced only when `force` is set.
+pub fn write(path: &Path, code: &str, force: bool) -> Result<(), XaError> {
+    if path.exists() && !force {
+        return Err(XaError::Usage(format!("{} already exists; pass --force to overwrite it", path.display())));
+    }
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).map_err(|e| XaError::Other(format!("cannot create {}: {}", dir.display(), e)))?;
+    }
+    std::fs::write(path, code).map_err(|e| XaError::Other(format!("cannot write {}: {}", path.display(), e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_go_where_the_tooling_looks() {
+        let path = |source: &str, language: Language, framework: &str| test_path(Path::new(source), language, framework).unwrap();
+        assert_eq!(path("src/net/client.rs", Language::Rust, "built-in"), Path::new("tests/client.rs"));
+        assert_eq!(path("pkg/src/util.py", Language::Python, "pytest"), Path::new("pkg/tests/test_util.py"));
+        assert_eq!(path("app/util.py", Language::Python, "pytest"), Path::new("app/tests/test_util.py"));
+        assert_eq!(path("web/api.ts", Language::TypeScript, "Vitest"), Path::new("web/api.test.ts"));
+        assert_eq!(path("server/handler.go", Language::Go, "testing"), Path::new("server/handler_test.go"));
+        assert_eq!(path("src/main/java/com/acme/Parser.java", Language::Java, "JUnit 5"), Path::new("src/test/java/com/acme/ParserTest.java"));
+        assert_eq!(path("lib/xa/cli.rb", Language::Ruby, "RSpec"), Path::new("spec/cli_spec.rb"));
+        assert_eq!(path("lib/xa/cli.rb", Language::Ruby, "minitest"), Path::new("test/test_cli.rb"));
+        assert_eq!(path("src/ring.c", Language::C, "GoogleTest"), Path::new("tests/test_ring.cpp"));
+        assert_eq!(test_path(Path::new("notes.txt"), Language::Other, "x"), None);
+
+        let dir = std::env::temp_dir().join(format!("xa-testgen-test-{}", std::process::id()));
+        let file = dir.join("tests/test_a.py");
+        write(&file, "def test_a(): pass\n", false).unwrap();
+        assert!(write(&file, "", false).unwrap_err().to_string().contains("--force"));
+        write(&file, "def test_b(): pass\n", true).unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "def test_b(): pass\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,62 @@
+//! `--from-tmux[=PANE]`: the text of a tmux pane as the input, so
+//! `xa explain --from-tmux` can read the compiler errors on screen.
+
+use crate::error::XaError;
+use std::process::Command;
+
+/// Lines of scrollback captured above the visible part of the pane.
+const SCROLLBACK: usize = 200;
+
+/// The visible text of `pane` and the scrollback above it. An empty `pane`
+/// means xa's own pane, whose last line (the prompt xa was started from)
+/// is left out.
+pub fn capture(pane: &str) -> Result<String, XaError> {
+    let own = pane.is_empty();
+    let target = if own {
+        std::env::var("TMUX_PANE")
+            .map_err(|_| XaError::Usage("Not inside tmux; name the pane with --from-tmux=<pane>, e.g. --from-tmux=work:1.0".into()))?
+    } else {
+        pane.to_string()
+    };
+    let start = format!("-{}", SCROLLBACK);
+    let output = Command::new("tmux")
+        .args(["capture-pane", "-p", "-J", "-S", &start, "-t", &target])
+        .output()
+        .map_err(|e| XaError::Other(format!("cannot run tmux: {}", e)))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(XaError::Usage(format!("cannot capture tmux pane '{}': {}", target, stderr.trim())));
+    }
+    let text = trim_capture(&String::from_utf8_lossy(&output.stdout), own);
+    if text.trim().is_empty() {
+        return Err(XaError::Usage(format!("tmux pane '{}' is empty.", target)));
+    }
+    Ok(text)
+}
+
+/// Drop the blank lines tmux pads the screen with, and with `drop_prompt`
+/// the last line left.
+fn trim_capture(text: &str, drop_prompt: bool) -> String {
+    let mut lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+    while lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    if drop_prompt {
+        lines.pop();
+    }
+    let start = lines.iter().position(|line| !line.is_empty()).unwrap_or(lines.len());
+    lines[start..].join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trims_the_captured_screen() {
+        let screen = "\n$ cargo build   \nerror[E0425]: cannot find value `x`\n$ xa explain --from-tmux\n\n\n";
+        assert_eq!(trim_capture(screen, true), "$ cargo build\nerror[E0425]: cannot find value `x`");
+        assert_eq!(trim_capture(screen, false), "$ cargo build\nerror[E0425]: cannot find value `x`\n$ xa explain --from-tmux");
+        assert_eq!(trim_capture("\n\n", true), "");
+    }
+}
@@ -24,6 +24,21 @@ pub trait Tool: Send + Sync {
     fn parameters(&self) -> Value;
     /// Execute the tool with parsed arguments; returns text output.
     fn execute(&self, args: Value) -> Result<String, String>;
+    /// Whether `xa ask --tools` asks before running it with `args`: side
+    /// effects, or data leaving the project or the machine.
+    fn needs_confirmation(&self, args: &Value) -> bool {
+        let _ = args;
+        false
+    }
+}
+
+/// Whether `path` is inside the working directory, symlinks resolved. A
+/// path that cannot be resolved is not.
+fn inside_working_dir(path: &str) -> bool {
+    let (Ok(dir), Ok(path)) = (std::env::current_dir().and_then(|dir| dir.canonicalize()), Path::new(path).canonicalize()) else {
+        return false;
+    };
+    path.starts_with(dir)
 }
 
 fn arg_str<'a>(args: &'a Value, key: &str) -> Result<&'a str, String> {
@@ -70,6 +85,9 @@ impl Tool for BashTool {
         let _ = args;
         Err("bash must be run through the async tool runner".into())
     }
+    fn needs_confirmation(&self, _args: &Value) -> bool {
+        true
+    }
 }
 
 const BASH_TIMEOUT: Duration = Duration::from_secs(120);
@@ -204,6 +222,11 @@ impl Tool for ReadTool {
             .map(|s| s.join("\n"))
             .unwrap_or_default())
     }
+    /// Files outside the project (`~/.ssh`, xa's own config) only when the
+    /// user agrees.
+    fn needs_confirmation(&self, args: &Value) -> bool {
+        !arg_str(args, "path").is_ok_and(inside_working_dir)
+    }
 }
 
 // ===========================================================================
@@ -237,6 +260,9 @@ impl Tool for WriteTool {
             .map_err(|e| format!("cannot write {}: {e}", p.display()))?;
         Ok(format!("wrote {} bytes to {}", content.len(), p.display()))
     }
+    fn needs_confirmation(&self, _args: &Value) -> bool {
+        true
+    }
 }
 
 // ===========================================================================
@@ -457,6 +483,40 @@ pub fn unified_diff(path: &str, before: &str, after: &str) -> Option<String> {
     }
 }
 
+// ===========================================================================
+// fetch
+// ===========================================================================
+
+pub struct FetchTool;
+impl Tool for FetchTool {
+    fn name(&self) -> &str { "fetch" }
+    fn description(&self) -> &str {
+        "Download a web page and return its article text (navigation and other page furniture removed)."
+    }
+    fn parameters(&self) -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "url": { "type": "string", "description": "http(s) URL to fetch" }
+            },
+            "required": ["url"]
+        })
+    }
+    fn execute(&self, args: Value) -> Result<String, String> {
+        let _ = args;
+        Err("fetch must be run through the async tool runner".into())
+    }
+    /// The URL may carry whatever the model has read, to anywhere.
+    fn needs_confirmation(&self, _args: &Value) -> bool {
+        true
+    }
+}
+
+/// Tools for `xa ask --tools`: a shell, reading and writing files, and the web.
+pub fn ask_tools() -> Vec<Arc<dyn Tool>> {
+    vec![Arc::new(BashTool), Arc::new(ReadTool), Arc::new(WriteTool), Arc::new(FetchTool)]
+}
+
 /// All built-in tools.
 pub fn all_tools() -> Vec<Arc<dyn Tool>> {
     vec![
@@ -485,6 +545,10 @@ pub async fn call_tool(
     if name == "bash" {
         return run_bash(arg_str(&args, "command")?.to_string(), cancel).await;
     }
+    if name == "fetch" && find_tool(name, tools).is_some() {
+        let article = crate::web::fetch(arg_str(&args, "url")?).await.map_err(|e| e.to_string())?;
+        return Ok(article.input());
+    }
     let tool = find_tool(name, tools)
         .ok_or_else(|| format!("unknown tool: {name}"))?
         .clone();
@@ -0,0 +1,112 @@
+//! `xa transcribe <audio>`: speech to text through the OpenAI-compatible
+//! `audio/transcriptions` endpoint (Whisper, or any server that mimics it).
+//!
+//! The upload is a hand-built `multipart/form-data` body, which saves
+//! enabling reqwest's multipart support for this one request.
+
+use crate::config::Config;
+use crate::error::XaError;
+use std::path::Path;
+use tracing::Instrument;
+
+/// OpenAI's upload limit for one file.
+pub const MAX_AUDIO_BYTES: u64 = 25 * 1024 * 1024;
+
+/// The text spoken in the audio file at `path`. `language` (ISO 639-1,
+/// e.g. `en`) is a hint that improves accuracy and speed.
+pub async fn transcribe(config: &Config, path: &Path, language: Option<&str>) -> Result<String, XaError> {
+    let mime = audio_type(path).ok_or_else(|| {
+        XaError::Usage(format!(
+            "{} is not a supported audio file (mp3, mp4, mpeg, mpga, m4a, wav, webm, ogg or flac)",
+            path.display()
+        ))
+    })?;
+    let size = std::fs::metadata(path).map_err(|e| XaError::Usage(format!("cannot read {}: {}", path.display(), e)))?.len();
+    if size > MAX_AUDIO_BYTES {
+        return Err(XaError::Usage(format!("{} is over the 25 MB upload limit; split it first", path.display())));
+    }
+    let audio = std::fs::read(path).map_err(|e| XaError::Usage(format!("cannot read {}: {}", path.display(), e)))?;
+
+    let model = config.transcription_model();
+    let mut fields = vec![("model", model.as_str()), ("response_format", "text")];
+    if let Some(language) = language {
+        fields.push(("language", language));
+    }
+    let file_name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_else(|| "audio".into());
+    let boundary = format!("xa-{:x}", chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default());
+    let body = multipart_body(&boundary, &fields, &file_name, mime, &audio);
+
+    let client = crate::http::client().map_err(XaError::Config)?;
+    let url = crate::llm::api_url(&config.base_url, "audio/transcriptions");
+    tracing::debug!(url = %url, bytes = audio.len(), "POST");
+    let request = client
+        .post(&url)
+        .header(reqwest::header::CONTENT_TYPE, format!("multipart/form-data; boundary={}", boundary))
+        .body(body);
+    let request = crate::llm::authorize(config, request)?;
+    let response = request.send().instrument(tracing::info_span!("transcription_request", model)).await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        return Err(XaError::from_status(status, response.text().await.unwrap_or_default()));
+    }
+    let text = response.text().await?;
+    // Servers that ignore response_format answer {"text": "..."}
+    match serde_json::from_str::<serde_json::Value>(&text) {
+        Ok(json) if json["text"].is_string() => Ok(json["text"].as_str().unwrap_or_default().trim().to_string()),
+        _ => Ok(text.trim().to_string()),
+    }
+}
+
+/// Upload content type by extension, for the formats Whisper accepts.
+fn audio_type(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "mp3" | "mpga" | "mpeg" => Some("audio/mpeg"),
+        "mp4" => Some("video/mp4"),
+        "m4a" => Some("audio/mp4"),
+        "wav" => Some("audio/wav"),
+        "webm" => Some("audio/webm"),
+        "ogg" | "oga" => Some("audio/ogg"),
+        "flac" => Some("audio/flac"),
+        _ => None,
+    }
+}
+
+/// `fields` as form fields followed by the audio as the `file` part.
+fn multipart_body(boundary: &str, fields: &[(&str, &str)], file_name: &str, mime: &str, audio: &[u8]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(audio.len() + 512);
+    for (name, value) in fields {
+        body.extend_from_slice(
+            format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n", boundary, name, value).as_bytes(),
+        );
+    }
+    let file_name = file_name.replace(['"', '\r', '\n'], "_");
+    body.extend_from_slice(
+        format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\nContent-Type: {}\r\n\r\n",
+            boundary, file_name, mime
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(audio);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_the_multipart_upload() {
+        let body = multipart_body("b", &[("model", "whisper-1")], "talk \"1\".mp3", "audio/mpeg", b"ID3");
+        assert_eq!(
+            String::from_utf8(body).unwrap(),
+            "--b\r\nContent-Disposition: form-data; name=\"model\"\r\n\r\nwhisper-1\r\n\
+             --b\r\nContent-Disposition: form-data; name=\"file\"; filename=\"talk _1_.mp3\"\r\n\
+             Content-Type: audio/mpeg\r\n\r\nID3\r\n--b--\r\n"
+        );
+        assert_eq!(audio_type(Path::new("memo.M4A")), Some("audio/mp4"));
+        assert_eq!(audio_type(Path::new("notes.txt")), None);
+    }
+}
@@ -0,0 +1,205 @@
+//! Opt-in transcript log (`log_transcripts = true`): every one-shot request
+//! body and the full response, one JSON line each, in a file per day under
+//! the state directory (`~/.local/state/xa/transcripts/` on Linux).
+//!
+//! Unlike the run history, nothing is truncated, so these files can grow;
+//! they exist for debugging prompt changes and for audit trails. The API
+//! key and `extra_headers` are never written.
+
+use crate::error::XaError;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::llm::{Completion, Usage};
+use chrono::{DateTime, Local, TimeZone};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+pub struct TranscriptRecord {
+    /// `YYYYMMDD-HHMMSS-xxxx`; the date part names the file it is in.
+    pub id: String,
+    /// Unix seconds.
+    pub time: i64,
+    pub base_url: String,
+    pub model: String,
+    /// The JSON body that was sent.
+    pub request: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+    pub duration_ms: u64,
+}
+
+/// Serialises appends from concurrent requests (`xa batch`).
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+static SEQUENCE: AtomicU32 = AtomicU32::new(0);
+
+/// `$XA_CONFIG_DIR/transcripts` when set, otherwise `<state dir>/xa/transcripts`
+/// (falling back to the local data directory where there is no state dir).
+pub fn transcripts_dir() -> Option<PathBuf> {
+    match std::env::var_os("XA_CONFIG_DIR") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir).join("transcripts")),
+        _ => dirs::state_dir()
+            .or_else(dirs::data_local_dir)
+            .map(|dir| dir.join("xa").join("transcripts")),
+    }
+}
+
+fn new_id(now: &DateTime<Local>) -> String {
+    let salt = now.timestamp_subsec_nanos() ^ std::process::id().rotate_left(16)
+        ^ SEQUENCE.fetch_add(1, Ordering::Relaxed).wrapping_mul(0x9e37_79b9);
+    format!("{}-{:04x}", now.format("%Y%m%d-%H%M%S"), salt & 0xffff)
+}
+
+/// File holding the record with `id`: `YYYY-MM-DD.jsonl`.
+fn file_for_id(id: &str) -> Option<PathBuf> {
+    let date = id.get(..8).filter(|date| date.bytes().all(|b| b.is_ascii_digit()))?;
+    let name = format!("{}-{}-{}.jsonl", &date[..4], &date[4..6], &date[6..]);
+    transcripts_dir().map(|dir| dir.join(name))
+}
+
+/// Append one request/response pair. Failures are reported, never fatal.
+pub fn record(config: &Config, request: &serde_json::Value, outcome: Result<&Completion, &str>, duration: Duration) {
+    let now = Local::now();
+    let record = TranscriptRecord {
+        id: new_id(&now),
+        time: now.timestamp(),
+        base_url: config.base_url.clone(),
+        model: request["model"].as_str().unwrap_or_default().to_string(),
+        request: request.clone(),
+        response: outcome.ok().map(|completion| completion.text.clone()),
+        error: outcome.err().map(str::to_string),
+        usage: outcome.ok().and_then(|completion| completion.usage),
+        duration_ms: duration.as_millis() as u64,
+    };
+    let result = (|| -> io::Result<()> {
+        let path = file_for_id(&record.id).ok_or_else(|| io::Error::other("Could not determine state directory"))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let line = format!("{}\n", serde_json::to_string(&record)?);
+        let _guard = WRITE_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        OpenOptions::new().create(true).append(true).open(path)?.write_all(line.as_bytes())
+    })();
+    if let Err(error) = result {
+        eprintln!("Warning: Could not write transcript: {}", error);
+    }
+}
+
+fn read_file(path: &PathBuf) -> Vec<TranscriptRecord> {
+    fs::read_to_string(path)
+        .map(|content| content.lines().filter_map(|line| serde_json::from_str(line).ok()).collect())
+        .unwrap_or_default()
+}
+
+/// `xa transcript list`: the latest `limit` records, newest first.
+pub fn print_list(limit: usize) -> Result<(), XaError> {
+    let dir = transcripts_dir().ok_or_else(|| XaError::Config("Could not determine state directory".into()))?;
+    let mut files: Vec<PathBuf> = fs::read_dir(&dir)
+        .map(|entries| entries.filter_map(|e| e.ok()).map(|e| e.path()).collect())
+        .unwrap_or_default();
+    files.retain(|path| path.extension().is_some_and(|ext| ext == "jsonl"));
+    files.sort();
+
+    let mut shown = 0;
+    for path in files.iter().rev() {
+        for record in read_file(path).iter().rev() {
+            if shown == limit {
+                return Ok(());
+            }
+            let prompt = record.request["messages"]
+                .as_array()
+                .and_then(|messages| messages.last())
+                .and_then(|message| message["content"].as_str())
+                .unwrap_or_default();
+            let preview: String = prompt.split_whitespace().collect::<Vec<_>>().join(" ");
+            let preview: String = preview.chars().take(70).collect();
+            let failed = if record.error.is_some() { " \x1b[31mfailed\x1b[0m" } else { "" };
+            println!("{}  \x1b[90m{}\x1b[0m{}  {}", record.id, record.model, failed, preview);
+            shown += 1;
+        }
+    }
+    if shown == 0 {
+        println!("No transcripts in {:?}. Enable them with `xa config set log_transcripts true`.", dir);
+    }
+    Ok(())
+}
+
+/// `xa transcript show <id>`: the request messages and the full response.
+pub fn show(id: &str) -> Result<(), XaError> {
+    let path = file_for_id(id).ok_or_else(|| XaError::Usage(format!("'{}' is not a transcript id (see `xa transcript list`)", id)))?;
+    let record = read_file(&path)
+        .into_iter()
+        .find(|record| record.id == id)
+        .ok_or_else(|| XaError::Usage(format!("Transcript '{}' not found", id)))?;
+
+    let time = Local
+        .timestamp_opt(record.time, 0)
+        .single()
+        .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_default();
+    let tokens = record
+        .usage
+        .map(|u| format!(" · {}+{} tokens", u.prompt_tokens, u.completion_tokens))
+        .unwrap_or_default();
+    println!("\x1b[1m{}\x1b[0m", record.id);
+    println!(
+        "\x1b[90m{} · {} · {} · {:.2}s{}\x1b[0m",
+        time,
+        record.base_url,
+        record.model,
+        record.duration_ms as f64 / 1000.0,
+        tokens
+    );
+
+    // Parameters other than the conversation itself (temperature, extra_body…)
+    let mut params = record.request.clone();
+    if let Some(fields) = params.as_object_mut() {
+        for key in ["model", "messages", "stream", "stream_options"] {
+            fields.remove(key);
+        }
+        if !fields.is_empty() {
+            println!("\x1b[90mparams: {}\x1b[0m", params);
+        }
+    }
+
+    for message in record.request["messages"].as_array().into_iter().flatten() {
+        println!("\n\x1b[36m── {} ──\x1b[0m", message["role"].as_str().unwrap_or("message"));
+        println!("{}", message["content"].as_str().unwrap_or_default());
+    }
+    match (&record.response, &record.error) {
+        (_, Some(error)) => {
+            println!("\n\x1b[31m── error ──\x1b[0m");
+            println!("{}", error);
+        }
+        (Some(response), None) => {
+            println!("\n\x1b[32m── response ──\x1b[0m");
+            println!("{}", response);
+        }
+        (None, None) => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_name_their_daily_file() {
+        let now = Local.with_ymd_and_hms(2026, 3, 9, 14, 5, 7).unwrap();
+        let (a, b) = (new_id(&now), new_id(&now));
+        assert!(a.starts_with("20260309-140507-"));
+        assert_ne!(a, b);
+        assert!(file_for_id(&a).unwrap().ends_with("2026-03-09.jsonl"));
+        assert!(file_for_id("latest").is_none());
+    }
+}
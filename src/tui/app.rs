@@ -40,6 +40,7 @@ pub const HELP_TEXT: &str = r#"
 - `/login [name]` — add or update a provider (custom endpoint, key, model)
 - `/models [name]` — switch active provider, or set model on active provider
 - `/clear` — clear the conversation
+- `/export <file>` — write the conversation as Markdown (`.html` for a themed page)
 - `/help` — show this help
 - `/exit` — quit
 
@@ -64,6 +65,8 @@ enum AppEvent {
     Stream(StreamEvent),
     /// Result of an async model-list fetch kicked off by the setup wizard.
     Wizard(Result<Vec<String>, String>),
+    /// A generated title for the session `id`, to replace `fallback`.
+    Title { id: String, fallback: String, title: String },
 }
 
 /// Information printed after leaving the alternate-screen TUI. Keeping this
@@ -304,23 +307,16 @@ impl App {
         }
         self.session.touch();
 
-        // Auto-generate a session title from the first user message once
-        // there are at least 2 user turns (so we know the session is real).
-        if self.session.title == "untitled" {
-            let user_msgs: Vec<&str> = self.cells.iter().filter_map(|c| {
+        // Once the first answer is in, title the session after the first
+        // user message and ask the model for a better title in the background.
+        if self.session.title == "untitled" && self.session.messages.iter().any(|m| m.role == "assistant") {
+            let first = self.cells.iter().find_map(|c| {
                 c.as_any().downcast_ref::<UserCell>().map(|u| u.content.as_str())
-            }).collect();
-            if user_msgs.len() >= 2 {
-                let first = user_msgs[0];
-                let title = first.lines().next().unwrap_or("").trim();
-                let title = if title.len() > 44 {
-                    format!("{}...", &title[..44])
-                } else {
-                    title.to_string()
-                };
-                if !title.is_empty() {
-                    self.session.title = title;
-                }
+            });
+            let title = session::fallback_title(first.unwrap_or(""));
+            if !title.is_empty() {
+                self.session.title = title.clone();
+                self.start_title_generation(title);
             }
         }
 
@@ -490,6 +486,14 @@ impl App {
                 self.active_think = None;
                 self.dirty = true;
             }
+            StreamEvent::Reasoning(_) => {
+                // Shown as the Thinking phase; the reasoning itself stays out
+                // of the transcript, like a `<think>` block
+                if !self.stream_phase.is_terminal() && !self.think_filter.saw_visible {
+                    self.set_stream_phase(StreamPhase::Thinking);
+                }
+                self.dirty = true;
+            }
             StreamEvent::Retrying { attempt, max, reason } => {
                 if !self.stream_phase.is_terminal() {
                     self.set_stream_phase(StreamPhase::Retrying { attempt, max });
@@ -610,6 +614,8 @@ impl App {
                 }
             }
             "/new" => self.new_session(),
+            // The whole rest of the line, so a path may contain spaces
+            "/export" => self.export_session(raw.trim()[cmd.len()..].trim()),
             other => self.system_msg(format!("unknown command: `{other}` (try /help)")),
         }
     }
@@ -1060,6 +1066,25 @@ impl App {
         });
     }
 
+    /// Ask the model for a title for the session, which is titled
+    /// `fallback` meanwhile. The result comes back through
+    /// [`AppEvent::Title`] and is dropped if the title changed since.
+    fn start_title_generation(&mut self, fallback: String) {
+        let tx = self.event_tx.clone();
+        let config = crate::config::Config {
+            base_url: self.provider.endpoint.clone(),
+            api_key: self.provider.api_key.clone(),
+            default_model: Some(self.provider.model.clone()),
+            ..Default::default()
+        };
+        let session = self.session.clone();
+        tokio::spawn(async move {
+            if let Some(title) = session::generate_title(&config, &session).await {
+                let _ = tx.send(AppEvent::Title { id: session.id, fallback, title }).await;
+            }
+        });
+    }
+
     /// Persist the provider the wizard produced and make it active.
     fn finish_wizard(&mut self, provider: agent::Provider) {
         let mut pc = agent::ProvidersConfig::load();
@@ -1096,6 +1121,25 @@ impl App {
         self.system_msg(out);
     }
 
+    /// Write the conversation to `path` as Markdown, or as HTML in the
+    /// `[theme]` colors for a `.html` path.
+    fn export_session(&mut self, path: &str) {
+        if path.is_empty() {
+            self.system_msg("usage: `/export <file.md|file.html>`");
+            return;
+        }
+        if !self.sync_session() {
+            self.system_msg("nothing to export yet — send a message first");
+            return;
+        }
+        let path = std::path::Path::new(path);
+        let colors = crate::config::load_theme_config();
+        match session::export::write(&self.session, path, &colors, theme::t().mode) {
+            Ok(()) => self.system_msg(format!("conversation exported to `{}`", path.display())),
+            Err(e) => self.system_msg(format!("export failed: {e}")),
+        }
+    }
+
     fn new_session(&mut self) {
         self.session = Session::new(&self.provider.name, &self.provider.model);
         self.cells.clear();
@@ -1904,6 +1948,13 @@ fn apply_app_event(
             }
             app.dirty = true;
         }
+        AppEvent::Title { id, fallback, title } => {
+            if app.session.id == id && app.session.title == fallback {
+                app.session.title = title;
+                app.sync_session();
+                app.dirty = true;
+            }
+        }
     }
     Ok(false)
 }
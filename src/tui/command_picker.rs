@@ -0,0 +1,239 @@
+//! Fuzzy command picker for `xa "some text"` (input without a command).
+
+use std::io;
+use std::time::Duration;
+
+use crossterm::{
+    event::{self, Event, KeyCode, KeyModifiers},
+    execute,
+    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Clear, Paragraph},
+    Terminal,
+};
+
+use crate::tui::theme;
+
+/// Open the picker over `(name, description)` pairs and return the chosen
+/// command name. `input` is previewed in the heading.
+pub fn pick_command(commands: &[(String, String)], input: &str) -> io::Result<Option<String>> {
+    let _crash_guard = super::crash::TuiGuard::enter();
+    let result = pick_command_inner(commands, input);
+    if let Err(error) = &result {
+        super::crash::report_error(error);
+    }
+    result
+}
+
+fn pick_command_inner(commands: &[(String, String)], input: &str) -> io::Result<Option<String>> {
+    if commands.is_empty() {
+        println!("No commands available. Add one with `xa add`.");
+        return Ok(None);
+    }
+
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen, crossterm::cursor::Hide)?;
+    terminal::enable_raw_mode()?;
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+    let result = run_picker(&mut terminal, commands, input);
+
+    terminal::disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen, crossterm::cursor::Show)?;
+    terminal.show_cursor()?;
+    result
+}
+
+fn run_picker(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    commands: &[(String, String)],
+    input: &str,
+) -> io::Result<Option<String>> {
+    let matcher = SkimMatcherV2::default();
+    let mut query = String::new();
+    let mut matches = filter(&matcher, commands, &query);
+    let mut selected = 0usize;
+    loop {
+        terminal.draw(|frame| draw(frame, commands, &matches, selected, &query, input))?;
+        if !event::poll(Duration::from_millis(250))? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else { continue };
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('c') if ctrl => return Ok(None),
+            KeyCode::Up => selected = selected.saturating_sub(1),
+            KeyCode::Char('p') if ctrl => selected = selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Tab => selected = (selected + 1).min(matches.len().saturating_sub(1)),
+            KeyCode::Char('n') if ctrl => selected = (selected + 1).min(matches.len().saturating_sub(1)),
+            KeyCode::Enter => {
+                if let Some(&index) = matches.get(selected) {
+                    return Ok(Some(commands[index].0.clone()));
+                }
+            }
+            KeyCode::Esc => return Ok(None),
+            KeyCode::Backspace => {
+                query.pop();
+                matches = filter(&matcher, commands, &query);
+                selected = 0;
+            }
+            KeyCode::Char(c) if !ctrl => {
+                query.push(c);
+                matches = filter(&matcher, commands, &query);
+                selected = 0;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Indices into `commands`, best match first. An empty query keeps every
+/// command in name order.
+fn filter(matcher: &SkimMatcherV2, commands: &[(String, String)], query: &str) -> Vec<usize> {
+    if query.is_empty() {
+        let mut all: Vec<usize> = (0..commands.len()).collect();
+        all.sort_by(|&a, &b| commands[a].0.cmp(&commands[b].0));
+        return all;
+    }
+    let mut scored: Vec<(i64, usize)> = commands
+        .iter()
+        .enumerate()
+        .filter_map(|(index, (name, description))| {
+            // Favour name hits; descriptions only break ties and rescue misses.
+            let by_name = matcher.fuzzy_match(name, query).map(|score| score * 2);
+            let by_description = matcher.fuzzy_match(description, query);
+            by_name.max(by_description).map(|score| (score, index))
+        })
+        .collect();
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| commands[a.1].0.cmp(&commands[b.1].0)));
+    scored.into_iter().map(|(_, index)| index).collect()
+}
+
+fn draw(
+    frame: &mut ratatui::Frame,
+    commands: &[(String, String)],
+    matches: &[usize],
+    selected: usize,
+    query: &str,
+    input: &str,
+) {
+    let area = frame.area();
+    frame.render_widget(Clear, area);
+    frame.render_widget(Block::default().style(Style::default().bg(theme::t().bg)), area);
+    let sections = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Length(2),
+            Constraint::Min(1),
+            Constraint::Length(2),
+        ])
+        .split(area);
+
+    let preview: String = input.split_whitespace().collect::<Vec<_>>().join(" ");
+    let max_preview = sections[0].width.saturating_sub(2) as usize;
+    let preview = if preview.chars().count() > max_preview {
+        let cut: String = preview.chars().take(max_preview.saturating_sub(1)).collect();
+        format!("{cut}…")
+    } else {
+        preview
+    };
+    let heading = Paragraph::new(vec![
+        Line::from(Span::styled(
+            "Run a command",
+            Style::default().fg(theme::t().text).add_modifier(Modifier::BOLD),
+        )),
+        Line::from(Span::styled(preview, Style::default().fg(theme::t().text_dim))),
+    ]);
+    frame.render_widget(heading, sections[0]);
+
+    frame.render_widget(
+        Paragraph::new(Line::from(vec![
+            Span::styled("› ", Style::default().fg(theme::t().accent)),
+            Span::styled(query.to_string(), Style::default().fg(theme::t().text)),
+            Span::styled("▏", Style::default().fg(theme::t().accent)),
+        ]))
+        .style(Style::default().bg(theme::t().field_bg)),
+        sections[1],
+    );
+
+    let rows = sections[2].height.max(1) as usize;
+    let start = selected.saturating_sub(rows.saturating_sub(1));
+    let end = (start + rows).min(matches.len());
+    let width = commands.iter().map(|(name, _)| name.chars().count()).max().unwrap_or(0);
+    let mut lines = Vec::with_capacity(end - start);
+    for (offset, &index) in matches[start..end].iter().enumerate() {
+        let active = start + offset == selected;
+        let (name, description) = &commands[index];
+        let bg = if active { theme::t().select_bg } else { theme::t().bg };
+        let prefix = if active { "›" } else { " " };
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!("{prefix} {name:<width$}  "),
+                Style::default()
+                    .fg(if active { theme::t().accent } else { theme::t().text })
+                    .bg(bg)
+                    .add_modifier(if active { Modifier::BOLD } else { Modifier::empty() }),
+            ),
+            Span::styled(description.clone(), Style::default().fg(theme::t().text_dim).bg(bg)),
+        ]));
+    }
+    if lines.is_empty() {
+        lines.push(Line::from(Span::styled(
+            "  No matching commands",
+            Style::default().fg(theme::t().text_dim),
+        )));
+    }
+    frame.render_widget(Paragraph::new(lines), sections[2]);
+    frame.render_widget(
+        Paragraph::new(Line::from(Span::styled(
+            "type to filter  ·  ↑↓ navigate  ·  Enter run  ·  Esc cancel",
+            Style::default().fg(theme::t().text_dim),
+        ))),
+        sections[3],
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commands() -> Vec<(String, String)> {
+        [
+            ("translate", "Translate text"),
+            ("summarize", "Summarize text"),
+            ("polish", "Polish text for clarity"),
+        ]
+        .iter()
+        .map(|(name, description)| (name.to_string(), description.to_string()))
+        .collect()
+    }
+
+    #[test]
+    fn empty_query_lists_commands_by_name() {
+        let commands = commands();
+        let order: Vec<&str> = filter(&SkimMatcherV2::default(), &commands, "")
+            .into_iter()
+            .map(|index| commands[index].0.as_str())
+            .collect();
+        assert_eq!(order, ["polish", "summarize", "translate"]);
+    }
+
+    #[test]
+    fn query_matches_names_before_descriptions() {
+        let commands = commands();
+        let matches = filter(&SkimMatcherV2::default(), &commands, "sum");
+        assert_eq!(commands[matches[0]].0, "summarize");
+        let matches = filter(&SkimMatcherV2::default(), &commands, "clarity");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(commands[matches[0]].0, "polish");
+    }
+}
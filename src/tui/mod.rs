@@ -30,8 +30,14 @@ mod slash;
 mod render;
 pub mod wizard;
 pub mod resume;
+pub mod command_picker;
 mod app;
 mod crash;
 
 pub use app::run;
 pub use theme::{init_from_preference, ColorMode, ThemePreference};
+
+/// The mode installed by [`init_from_preference`] (dark if none was).
+pub fn color_mode() -> ColorMode {
+    theme::t().mode
+}
@@ -18,6 +18,7 @@ pub const SLASH_COMMANDS: &[SlashCommand] = &[
     SlashCommand { name: "/tools", desc: "list available tools" },
     SlashCommand { name: "/save", desc: "save the current session" },
     SlashCommand { name: "/new", desc: "start a new session" },
+    SlashCommand { name: "/export", desc: "export the conversation (.md or .html)" },
 ];
 
 /// Subsequence fuzzy match: every char of `query` appears in order in `text`.
@@ -15,12 +15,7 @@ use ratatui_markdown::theme::{CodeColors, ThemeConfig};
 
 mod detect;
 
-/// Resolved appearance after preference + detection.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum ColorMode {
-    Dark,
-    Light,
-}
+pub use crate::output::ColorMode;
 
 /// User preference: force a mode, or detect from the terminal.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -348,9 +348,12 @@ impl Wizard {
         self.fetching = false;
         match res {
             Ok(models) if !models.is_empty() => {
+                // Start on the draft's model (the preset default or the
+                // provider's current model) when the endpoint offers it.
+                self.model_idx = models.iter().position(|m| *m == self.draft.model).unwrap_or(0);
                 self.models = models;
-                self.model_idx = 0;
                 self.model_scroll = 0;
+                self.keep_selected_model_visible();
                 self.message = None;
                 self.step = Step::Model;
             }
@@ -441,6 +444,7 @@ impl Wizard {
                         self.draft = Provider {
                             name: p.name.to_string(),
                             endpoint: p.base_url.to_string(),
+                            model: p.default_model.to_string(),
                             ..Default::default()
                         };
                         self.came_from_custom = false;
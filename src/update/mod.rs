@@ -0,0 +1,243 @@
+//! `xa self-update`: replace the running binary with the latest GitHub
+//! release. A release carries one binary per platform, named
+//! `xa-<target>` (`xa-x86_64-unknown-linux-gnu`, `xa-aarch64-apple-darwin`,
+//! `xa-x86_64-pc-windows-msvc.exe`, …), and a `SHA256SUMS` file the
+//! download is checked against before anything is replaced.
+//!
+//! `SHA256SUMS` comes from the same release as the binary, so the check
+//! catches a corrupted or truncated download, not a tampered release:
+//! it proves integrity, not who built the binary. The download is written
+//! beside the executable, to a file only this process created, and
+//! checked again there just before it replaces the executable.
+//!
+//! `XA_RELEASES_URL` points at another releases API (a fork or a mirror).
+
+use crate::error::XaError;
+use crate::output::paint;
+use crate::spinner::Spinner;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::Path;
+
+const LATEST_RELEASE: &str = "https://api.github.com/repos/jinfagang/xa/releases/latest";
+
+/// The release asset listing the binaries' checksums.
+const CHECKSUMS: &str = "SHA256SUMS";
+
+#[derive(Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    #[serde(default)]
+    pub html_url: String,
+    #[serde(default)]
+    assets: Vec<Asset>,
+}
+
+#[derive(Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+/// The Rust target triple of the release binary for this platform.
+pub fn target() -> Option<&'static str> {
+    match (std::env::consts::OS, std::env::consts::ARCH) {
+        ("linux", "x86_64") => Some("x86_64-unknown-linux-gnu"),
+        ("linux", "aarch64") => Some("aarch64-unknown-linux-gnu"),
+        ("macos", "x86_64") => Some("x86_64-apple-darwin"),
+        ("macos", "aarch64") => Some("aarch64-apple-darwin"),
+        ("windows", "x86_64") => Some("x86_64-pc-windows-msvc"),
+        ("windows", "aarch64") => Some("aarch64-pc-windows-msvc"),
+        _ => None,
+    }
+}
+
+fn asset_name(target: &str) -> String {
+    let extension = if target.contains("windows") { ".exe" } else { "" };
+    format!("xa-{}{}", target, extension)
+}
+
+/// The numeric parts of `1.2.3` or `v1.2.3`, and whether it is a
+/// pre-release (`1.3.0-rc.1`).
+fn version_parts(version: &str) -> (Vec<u64>, bool) {
+    let version = version.trim().trim_start_matches('v');
+    let version = version.split('+').next().unwrap_or(version);
+    let (release, pre) = match version.split_once('-') {
+        Some((release, _)) => (release, true),
+        None => (version, false),
+    };
+    (release.split('.').map(|part| part.parse().unwrap_or(0)).collect(), pre)
+}
+
+/// Whether `latest` is a newer version than `current`. A pre-release is
+/// older than the release of the same number.
+pub fn is_newer(current: &str, latest: &str) -> bool {
+    let (mut current, current_pre) = version_parts(current);
+    let (mut latest, latest_pre) = version_parts(latest);
+    let len = current.len().max(latest.len());
+    current.resize(len, 0);
+    latest.resize(len, 0);
+    match latest.cmp(&current) {
+        std::cmp::Ordering::Equal => current_pre && !latest_pre,
+        ordering => ordering.is_gt(),
+    }
+}
+
+/// The checksum listed for `asset` in a `sha256sum`-style file
+/// (`<hex>  <name>`, or `<hex> *<name>` for binary mode).
+fn checksum_for(sums: &str, asset: &str) -> Option<String> {
+    sums.lines().find_map(|line| {
+        let (hash, name) = line.trim().split_once(char::is_whitespace)?;
+        (name.trim().trim_start_matches('*') == asset).then(|| hash.to_lowercase())
+    })
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Write `bytes` to a new file at `path`, never one that already exists,
+/// then check what is on disk, which is what will be installed, against
+/// the `expected` checksum.
+fn stage(path: &Path, bytes: &[u8], expected: &str) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o755);
+    let mut file = options.open(path)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    drop(file);
+    let on_disk = sha256_hex(&std::fs::read(path)?);
+    if on_disk != expected {
+        return Err(std::io::Error::other(format!("{} changed on disk after the download (checksum {})", path.display(), on_disk)));
+    }
+    Ok(())
+}
+
+async fn get(url: &str) -> Result<reqwest::Response, XaError> {
+    let client = crate::http::client().map_err(XaError::Config)?;
+    tracing::info!(url, "fetching release");
+    let response = client
+        .get(url)
+        .header(reqwest::header::USER_AGENT, concat!("xa/", env!("CARGO_PKG_VERSION")))
+        .send()
+        .await?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(XaError::Other(format!("cannot fetch {}: HTTP {}", url, status)));
+    }
+    Ok(response)
+}
+
+/// The latest published release.
+pub async fn latest() -> Result<Release, XaError> {
+    let url = std::env::var("XA_RELEASES_URL").unwrap_or_else(|_| LATEST_RELEASE.to_string());
+    let response = get(&url).await?;
+    response.json().await.map_err(|e| XaError::Other(format!("unexpected answer from {}: {}", url, e)))
+}
+
+/// Download this platform's binary from `release`, check it against the
+/// release's `SHA256SUMS`, and put it in place of the running executable.
+pub async fn install(release: &Release) -> Result<std::path::PathBuf, XaError> {
+    let target = target().ok_or_else(|| {
+        XaError::Usage(format!("No release binary for {}-{}; build xa from source instead.", std::env::consts::ARCH, std::env::consts::OS))
+    })?;
+    let name = asset_name(target);
+    let asset = |name: &str| release.assets.iter().find(|asset| asset.name == name);
+    let binary = asset(&name).ok_or_else(|| XaError::Other(format!("release {} has no {}", release.tag_name, name)))?;
+    let sums = asset(CHECKSUMS)
+        .ok_or_else(|| XaError::Other(format!("release {} has no {}; not installing an unverified binary", release.tag_name, CHECKSUMS)))?;
+
+    let sums = get(&sums.browser_download_url).await?.text().await?;
+    let expected = checksum_for(&sums, &name).ok_or_else(|| XaError::Other(format!("{} does not list {}", CHECKSUMS, name)))?;
+    let bytes = get(&binary.browser_download_url).await?.bytes().await?;
+    let actual = sha256_hex(&bytes);
+    if actual != expected {
+        return Err(XaError::Other(format!("checksum mismatch for {}: expected {}, got {}; nothing was replaced", name, expected, actual)));
+    }
+
+    let exe = std::env::current_exe().map_err(|e| XaError::Other(format!("cannot find the running executable: {}", e)))?;
+    // Beside the executable rather than in the shared temp directory,
+    // where another user could plant or swap the file
+    let dir = exe.parent().ok_or_else(|| XaError::Other(format!("{} has no directory", exe.display())))?;
+    let download = dir.join(format!(".{}.{}.download", name, std::process::id()));
+    let result = stage(&download, &bytes, &expected).and_then(|()| self_replace::self_replace(&download));
+    // A file that was there before is not ours to remove
+    if !result.as_ref().is_err_and(|e| e.kind() == std::io::ErrorKind::AlreadyExists) {
+        let _ = std::fs::remove_file(&download);
+    }
+    result.map_err(|e| match e.kind() {
+        std::io::ErrorKind::PermissionDenied => {
+            XaError::Usage(format!("cannot replace {}: permission denied; run the update with the rights to write there", exe.display()))
+        }
+        _ => XaError::Other(format!("cannot replace {}: {}", exe.display(), e)),
+    })?;
+    Ok(exe)
+}
+
+/// `xa self-update`: compare with the latest release and, unless `check`,
+/// install it.
+pub async fn self_update(check: bool) -> Result<(), XaError> {
+    let current = env!("CARGO_PKG_VERSION");
+    let spinner = Spinner::start("Checking for updates");
+    let release = latest().await;
+    spinner.stop();
+    let release = release?;
+    if !is_newer(current, &release.tag_name) {
+        println!("xa {} is up to date.", current);
+        return Ok(());
+    }
+    println!("xa {} is available (you have {}).", release.tag_name.trim_start_matches('v'), current);
+    if !release.html_url.is_empty() {
+        println!("{}", paint("90", &release.html_url));
+    }
+    if check {
+        return Ok(());
+    }
+    let spinner = Spinner::start(&format!("Downloading {}", release.tag_name));
+    let installed = install(&release).await;
+    spinner.stop();
+    let exe = installed?;
+    eprintln!("{}", paint("32", &format!("✓ updated {} to {}", exe.display(), release.tag_name.trim_start_matches('v'))));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_versions_and_reads_checksums() {
+        assert!(is_newer("0.1.4", "v0.2.0"));
+        assert!(is_newer("0.1.4", "0.1.10"));
+        assert!(!is_newer("0.1.4", "v0.1.4"));
+        assert!(!is_newer("0.2.0", "0.1.9"));
+        assert!(is_newer("0.2.0-rc.1", "0.2.0"));
+        assert!(!is_newer("0.2.0", "0.2.0-rc.1"));
+        assert!(is_newer("1.0", "1.0.1"));
+
+        let sums = "3a7bd3e2360a3d29eea436fcfb7e44c735d117c42d1c1835420b6b9942dd4f1b  xa-x86_64-unknown-linux-gnu\n\
+                    0D1E5F *xa-x86_64-pc-windows-msvc.exe\n";
+        assert_eq!(checksum_for(sums, "xa-x86_64-unknown-linux-gnu").unwrap(), "3a7bd3e2360a3d29eea436fcfb7e44c735d117c42d1c1835420b6b9942dd4f1b");
+        assert_eq!(checksum_for(sums, &asset_name("x86_64-pc-windows-msvc")).unwrap(), "0d1e5f");
+        assert_eq!(checksum_for(sums, "xa-aarch64-apple-darwin"), None);
+        assert_eq!(sha256_hex(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    }
+
+    #[test]
+    fn stages_only_into_a_new_file() {
+        let path = std::env::temp_dir().join(format!("xa-stage-{}", std::process::id()));
+        let abc = sha256_hex(b"abc");
+        let staged = stage(&path, b"abc", &abc);
+        let again = stage(&path, b"abc", &abc);
+        let _ = std::fs::remove_file(&path);
+        let wrong = stage(&path, b"abd", &abc);
+        let _ = std::fs::remove_file(&path);
+
+        assert!(staged.is_ok());
+        assert_eq!(again.unwrap_err().kind(), std::io::ErrorKind::AlreadyExists);
+        assert!(wrong.unwrap_err().to_string().contains("changed on disk"));
+    }
+}
@@ -1,4 +1,6 @@
-pub fn copy_to_clipboard(text: &str) -> Result<(), Box<dyn std::error::Error>> {
+use crate::error::XaError;
+
+pub fn copy_to_clipboard(text: &str) -> Result<(), XaError> {
     #[cfg(target_os = "linux")]
     {
         // On Linux, try to use xclip or xsel
@@ -47,7 +49,7 @@ pub fn copy_to_clipboard(text: &str) -> Result<(), Box<dyn std::error::Error>> {
             eprintln!("  - Fedora/RHEL: sudo dnf install xclip");
             eprintln!("  - Arch: sudo pacman -S xclip");
             eprintln!("  - Or install xsel: sudo apt-get install xsel");
-            return Err("Clipboard utilities not found".into());
+            return Err(XaError::Other("Clipboard utilities not found".into()));
         }
     }
 
@@ -85,4 +87,40 @@ pub fn copy_to_clipboard(text: &str) -> Result<(), Box<dyn std::error::Error>> {
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+/// The text on the system clipboard, read with `xclip`/`xsel` on Linux,
+/// `pbpaste` on macOS and the clipboard API on Windows.
+pub fn read_clipboard() -> Result<String, XaError> {
+    #[cfg(not(target_os = "windows"))]
+    {
+        use std::process::{Command, Stdio};
+
+        #[cfg(target_os = "macos")]
+        let readers: &[(&str, &[&str])] = &[("pbpaste", &[])];
+        #[cfg(not(target_os = "macos"))]
+        let readers: &[(&str, &[&str])] = &[("xclip", &["-selection", "clipboard", "-o"]), ("xsel", &["-bo"])];
+
+        for (program, args) in readers {
+            match Command::new(program).args(*args).stdin(Stdio::null()).stderr(Stdio::piped()).output() {
+                Ok(output) if output.status.success() => return Ok(String::from_utf8_lossy(&output.stdout).into_owned()),
+                Ok(output) => {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    return Err(XaError::Other(format!("{} failed: {}", program, stderr.trim())));
+                }
+                // Not installed: try the next one
+                Err(_) => continue,
+            }
+        }
+        let names: Vec<&str> = readers.iter().map(|(program, _)| *program).collect();
+        Err(XaError::Other(format!("Could not read the clipboard. Install {} to enable it.", names.join(" or "))))
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        use clipboard::ClipboardContext;
+        use clipboard::ClipboardProvider;
+
+        let mut ctx: ClipboardContext = ClipboardProvider::new()?;
+        Ok(ctx.get_contents()?)
+    }
+}
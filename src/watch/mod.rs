@@ -0,0 +1,141 @@
+//! `xa watch <command> -f <file>`: re-run a command whenever a file is
+//! saved, for live-polishing a draft or keeping a translation current.
+//!
+//! The file's directory is watched rather than the file itself, because
+//! many editors save by writing a new file and renaming it over the old
+//! one. Bursts of events are let settle before the file is read, and a save
+//! that leaves the text unchanged does not trigger a run.
+
+use crate::error::XaError;
+use crate::output::paint;
+use chrono::Local;
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::ffi::OsString;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Quiet time after the last event before the file is read.
+const SETTLE: Duration = Duration::from_millis(300);
+
+pub struct FileWatch {
+    path: PathBuf,
+    events: mpsc::UnboundedReceiver<()>,
+    /// The text last handed out; `None` before the first read.
+    last: Option<String>,
+    // Dropping it stops the events
+    _watcher: RecommendedWatcher,
+}
+
+impl FileWatch {
+    /// Start watching the file at `path`, which must exist.
+    pub fn new(path: &Path) -> Result<FileWatch, XaError> {
+        let path = std::fs::canonicalize(path).map_err(|e| XaError::Usage(format!("cannot watch {}: {}", path.display(), e)))?;
+        let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+            return Err(XaError::Usage(format!("cannot watch {}: not a file", path.display())));
+        };
+        let name: OsString = name.to_owned();
+        let (sender, events) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let Ok(event) = event else { return };
+            if !event.kind.is_access() && event.paths.iter().any(|changed| changed.file_name() == Some(name.as_os_str())) {
+                let _ = sender.send(());
+            }
+        })
+        .map_err(|e| XaError::Other(format!("cannot watch {}: {}", path.display(), e)))?;
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(|e| XaError::Other(format!("cannot watch {}: {}", dir.display(), e)))?;
+        Ok(FileWatch { path, events, last: None, _watcher: watcher })
+    }
+
+    /// The file's text: at once the first time, then each time a change
+    /// leaves it different. `None` once the watcher has stopped.
+    pub async fn next(&mut self) -> Option<Result<String, XaError>> {
+        loop {
+            if self.last.is_some() {
+                self.events.recv().await?;
+                // Editors often write in several steps; wait for the last
+                loop {
+                    match tokio::time::timeout(SETTLE, self.events.recv()).await {
+                        Ok(Some(())) => continue,
+                        Ok(None) => return None,
+                        Err(_) => break,
+                    }
+                }
+            }
+            match std::fs::read_to_string(&self.path) {
+                Ok(text) if self.last.as_ref() == Some(&text) => continue,
+                Ok(text) => {
+                    self.last = Some(text.clone());
+                    return Some(Ok(text));
+                }
+                // Removed for a moment while being replaced; the rename brings it back
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound && self.last.is_some() => continue,
+                Err(e) => {
+                    self.last.get_or_insert_with(String::new);
+                    return Some(Err(XaError::Other(format!("cannot read {}: {}", self.path.display(), e))));
+                }
+            }
+        }
+    }
+}
+
+/// Run `command` through `run` on the text of `file` now and after each
+/// change that leaves it different, until the watcher stops. Errors, the
+/// run's or reading the file's, are reported (as JSON with `json_errors`)
+/// and the watch goes on.
+pub async fn rerun<F, Fut>(file: &Path, command: &str, json_errors: bool, mut run: F) -> Result<(), XaError>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = Result<(), XaError>>,
+{
+    let mut watch = FileWatch::new(file)?;
+    eprintln!("{}", paint("90", &format!("Watching {} for changes (Ctrl-C to stop)", file.display())));
+    let mut first = true;
+    while let Some(text) = watch.next().await {
+        let stamp = Local::now().format("%H:%M:%S");
+        let text = match text {
+            Ok(text) if text.trim().is_empty() => {
+                eprintln!("{}", paint("90", &format!("[{}] {} is empty; waiting for text", stamp, file.display())));
+                continue;
+            }
+            Ok(text) => text,
+            Err(e) => {
+                e.report(json_errors);
+                continue;
+            }
+        };
+        let what = if std::mem::take(&mut first) { "read" } else { "changed" };
+        eprintln!("{}", paint("90", &format!("[{}] {} {}; running {}", stamp, file.display(), what, command)));
+        if let Err(e) = run(text.trim_end_matches(['\n', '\r']).to_string()).await {
+            e.report(json_errors);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn reports_changed_text_only() {
+        let dir = std::env::temp_dir().join(format!("xa-watch-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("draft.md");
+        std::fs::write(&path, "first").unwrap();
+
+        let mut watch = FileWatch::new(&path).unwrap();
+        assert_eq!(watch.next().await.unwrap().unwrap(), "first");
+        std::fs::write(&path, "first").unwrap();
+        std::fs::write(dir.join("other.md"), "unrelated").unwrap();
+        // Saved the way editors do: a new file renamed over the old one
+        std::fs::write(dir.join(".draft.md.tmp"), "second").unwrap();
+        std::fs::rename(dir.join(".draft.md.tmp"), &path).unwrap();
+        let next = tokio::time::timeout(Duration::from_secs(5), watch.next()).await;
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(next.unwrap().unwrap().unwrap(), "second");
+    }
+}
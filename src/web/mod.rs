@@ -0,0 +1,211 @@
+//! `xa web <url> [command]`: download a page and pull out the article text
+//! for a prompt command, so nobody has to copy it out of the browser.
+//!
+//! The extraction is a small readability-style pass rather than a full
+//! parser: scripts, styles and page furniture (`nav`, `header`, `footer`,
+//! `aside`, forms) are dropped, the `<article>` or `<main>` element is
+//! preferred over the whole `<body>`, and blocks that are mostly links
+//! (menus, tag clouds, "related" lists) are skipped.
+
+use crate::error::XaError;
+use regex::Regex;
+
+/// Longer articles are cut here; the prompt notes it.
+pub const MAX_ARTICLE_BYTES: usize = 100 * 1024;
+
+/// Elements removed together with their content.
+const BOILERPLATE: &[&str] = &[
+    "script", "style", "noscript", "svg", "template", "iframe", "form", "nav", "header", "footer", "aside", "button",
+];
+
+#[derive(Debug)]
+pub struct Article {
+    pub title: Option<String>,
+    pub text: String,
+}
+
+impl Article {
+    /// The prompt input: the title as a heading, then the text, cut to
+    /// [`MAX_ARTICLE_BYTES`].
+    pub fn input(&self) -> String {
+        let mut input = match &self.title {
+            // Pages usually repeat the title as their first heading
+            Some(title) if !self.text.starts_with(&format!("# {}\n", title)) => format!("# {}\n\n{}", title, self.text),
+            _ => self.text.clone(),
+        };
+        if input.len() > MAX_ARTICLE_BYTES {
+            let mut end = MAX_ARTICLE_BYTES;
+            while !input.is_char_boundary(end) {
+                end -= 1;
+            }
+            input.truncate(end);
+            input.push_str("\n\n[article truncated]");
+        }
+        input
+    }
+
+    pub fn word_count(&self) -> usize {
+        self.text.split_whitespace().count()
+    }
+}
+
+/// A lone `http(s)://` URL, the only input `xa <command> <url>` fetches.
+pub fn is_url(input: &str) -> bool {
+    let input = input.trim();
+    (input.starts_with("https://") || input.starts_with("http://")) && !input.contains(char::is_whitespace)
+}
+
+/// Download `url` and extract its article. Plain-text responses are used
+/// as they are.
+pub async fn fetch(url: &str) -> Result<Article, XaError> {
+    let client = crate::http::client().map_err(XaError::Config)?;
+    tracing::info!(url, "fetching page");
+    let response = client
+        .get(url)
+        .header(reqwest::header::USER_AGENT, concat!("xa/", env!("CARGO_PKG_VERSION")))
+        .header(reqwest::header::ACCEPT, "text/html,text/plain;q=0.9,*/*;q=0.5")
+        .send()
+        .await?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(XaError::Other(format!("cannot fetch {}: HTTP {}", url, status)));
+    }
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("")
+        .to_string();
+    let body = response.text().await?;
+    if content_type.starts_with("text/html") || content_type.contains("xhtml") || content_type.is_empty() {
+        let article = extract(&body);
+        if article.text.is_empty() {
+            return Err(XaError::Other(format!("no article text found at {}", url)));
+        }
+        Ok(article)
+    } else if content_type.starts_with("text/") {
+        Ok(Article { title: None, text: body.trim().to_string() })
+    } else {
+        Err(XaError::Usage(format!("{} is not a web page ({})", url, content_type)))
+    }
+}
+
+/// The title and readable text of an HTML page, as light Markdown
+/// (headings and list items keep their markers).
+pub fn extract(html: &str) -> Article {
+    let mut html = Regex::new(r"(?s)<!--.*?-->").unwrap().replace_all(html, "").into_owned();
+    let title = first_inner(&html, "title").or_else(|| first_inner(&html, "h1")).map(|title| inline_text(&title));
+    for tag in BOILERPLATE {
+        let element = Regex::new(&format!(r"(?is)<{0}\b[^>]*>.*?</{0}\s*>", tag)).unwrap();
+        html = element.replace_all(&html, "").into_owned();
+    }
+    let region = largest_inner(&html, "article")
+        .or_else(|| largest_inner(&html, "main"))
+        .or_else(|| first_inner(&html, "body"))
+        .unwrap_or(html);
+
+    let block = Regex::new(r"(?i)<(p|div|section|li|h[1-6]|pre|blockquote|tr|dd|dt|br)\b[^>]*>").unwrap();
+    let mut blocks = Vec::new();
+    let mut start = 0;
+    let mut kind = String::new();
+    for tag in block.captures_iter(&region) {
+        let whole = tag.get(0).unwrap();
+        push_block(&mut blocks, &kind, &region[start..whole.start()]);
+        kind = tag[1].to_ascii_lowercase();
+        start = whole.end();
+    }
+    push_block(&mut blocks, &kind, &region[start..]);
+    Article { title: title.filter(|title| !title.is_empty()), text: blocks.join("\n\n") }
+}
+
+/// Add the text of one block, unless it is empty or mostly link text.
+fn push_block(blocks: &mut Vec<String>, kind: &str, html: &str) {
+    let text = inline_text(html);
+    if text.is_empty() {
+        return;
+    }
+    let links = Regex::new(r"(?is)<a\b[^>]*>(.*?)</a\s*>").unwrap();
+    let link_chars: usize = links.captures_iter(html).map(|link| inline_text(&link[1]).chars().count()).sum();
+    if link_chars * 2 > text.chars().count() && text.split_whitespace().count() < 30 {
+        return;
+    }
+    blocks.push(match kind {
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => format!("{} {}", "#".repeat(kind[1..].parse().unwrap_or(2)), text),
+        "li" => format!("- {}", text),
+        "blockquote" => format!("> {}", text),
+        _ => text,
+    });
+}
+
+/// Content of the first `<tag>` element.
+fn first_inner(html: &str, tag: &str) -> Option<String> {
+    let element = Regex::new(&format!(r"(?is)<{0}\b[^>]*>(.*?)</{0}\s*>", tag)).unwrap();
+    element.captures(html).map(|element| element[1].to_string())
+}
+
+/// Content of the longest `<tag>` element (pages often have several
+/// `<article>` teasers around the real one).
+fn largest_inner(html: &str, tag: &str) -> Option<String> {
+    let element = Regex::new(&format!(r"(?is)<{0}\b[^>]*>(.*?)</{0}\s*>", tag)).unwrap();
+    element
+        .captures_iter(html)
+        .map(|element| element[1].to_string())
+        .filter(|inner| !inline_text(inner).is_empty())
+        .max_by_key(|inner| inner.len())
+}
+
+/// Tags removed, entities decoded and whitespace collapsed.
+fn inline_text(html: &str) -> String {
+    let text = Regex::new(r"(?s)<[^>]*>").unwrap().replace_all(html, " ");
+    decode_entities(&text).split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn decode_entities(text: &str) -> String {
+    let entity = Regex::new(r"&(#[0-9]+|#[xX][0-9a-fA-F]+|[a-zA-Z]+);").unwrap();
+    entity
+        .replace_all(text, |entity: &regex::Captures| {
+            let name = &entity[1];
+            let decoded = match name {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                "mdash" => Some('—'),
+                "ndash" => Some('–'),
+                "hellip" => Some('…'),
+                "rsquo" | "lsquo" => Some('\''),
+                "rdquo" | "ldquo" => Some('"'),
+                _ => match name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+                    None => name.strip_prefix('#').and_then(|n| n.parse().ok()).and_then(char::from_u32),
+                },
+            };
+            decoded.map(String::from).unwrap_or_else(|| entity[0].to_string())
+        })
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_the_article_and_skips_boilerplate() {
+        let html = r#"<html><head><title>Rust &amp; You</title><script>var x = "<p>no</p>";</script></head>
+            <body><nav><ul><li><a href="/">Home</a></li></ul></nav>
+            <article><p>Short teaser</p></article>
+            <article><h2>Why Rust</h2><p>Memory safety&nbsp;without a <b>garbage</b> collector.</p>
+            <ul><li>Fast</li><li><a href="/more">Read more</a></li></ul><!-- <p>hidden</p> --></article>
+            <footer>© 2026</footer></body></html>"#;
+        let article = extract(html);
+        assert_eq!(article.title.as_deref(), Some("Rust & You"));
+        assert_eq!(article.text, "## Why Rust\n\nMemory safety without a garbage collector.\n\n- Fast");
+        assert!(article.input().starts_with("# Rust & You\n\n## Why Rust"));
+
+        assert!(is_url("https://example.com/a?b=1"));
+        assert!(!is_url("see https://example.com"));
+        assert_eq!(decode_entities("&#233;&#x41;&bogus;"), "éA&bogus;");
+    }
+}